@@ -13,6 +13,22 @@ pub fn block_derive(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut item_struct = parse_macro_input!(item as ItemStruct);
     let name = item_struct.ident.clone();
 
+    // a getter for every field, since they aren't public themselves.
+    let mut getters = Vec::new();
+    if let syn::Fields::Named(ref fields) = item_struct.fields {
+        for field in &fields.named {
+            let ident = &field.ident;
+            let ty = &field.ty;
+            let docs = field.attrs.iter().filter(|f| f.path.is_ident("doc"));
+            getters.push(quote! {
+                #(#docs)*
+                pub fn #ident(&self) -> &#ty {
+                    &self.#ident
+                }
+            });
+        }
+    }
+
     if let syn::Fields::Named(ref mut fields) = item_struct.fields {
         fields.named.push(
             syn::Field::parse_named
@@ -30,9 +46,13 @@ pub fn block_derive(_attr: TokenStream, item: TokenStream) -> TokenStream {
         );
     }
 
-    return quote! {
+    quote! {
         #item_struct
 
+        impl #name {
+            #(#getters)*
+        }
+
         impl Block for #name {
             fn prev(&self) -> Option<String> {
                 self.prev.clone()
@@ -45,5 +65,5 @@ pub fn block_derive(_attr: TokenStream, item: TokenStream) -> TokenStream {
             }
         }
     }
-    .into();
+    .into()
 }
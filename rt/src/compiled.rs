@@ -0,0 +1,151 @@
+//! What transpiled projects need to run on the scheduler.
+//!
+//! Every script and custom block becomes a function that picks up where it
+//! left off, using the pc of its frame. Whenever it has to stop (to yield, to
+//! wait, or to call a custom block) it saves where it is and returns an
+//! [Exit] saying why, and [drive] takes it from there.
+use crate::{
+    poll, value::Val, world::TargetId, Backend, Ctx, Hat, Pending, Status, Thread, WARP_BUDGET,
+};
+
+/// Why a compiled function returned.
+#[derive(Debug, Clone)]
+pub enum Exit {
+    Yield,
+    Wait,
+    /// Calls the function with this index. It runs without screen refresh
+    /// if the bool is set.
+    Call(usize, Vec<Val>, bool),
+    /// Reached the end, or "stop this script".
+    Return,
+    /// The whole script is over: "stop all", or the clone deleted itself.
+    Done,
+}
+
+/// A call of a script or custom block that's in progress.
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    pub func: usize,
+    pub pc: usize,
+    /// Loop counters, which have to survive yields.
+    pub slots: Vec<f64>,
+    pub args: Vec<Val>,
+    pub warp: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Frames {
+    pub frames: Vec<Frame>,
+    /// How many of the frames are running without screen refresh.
+    pub warp: usize,
+}
+
+impl Frames {
+    pub fn new(func: usize) -> Frames {
+        Frames {
+            frames: vec![Frame {
+                func,
+                ..Default::default()
+            }],
+            warp: 0,
+        }
+    }
+}
+
+/// The thread a compiled function is running in.
+pub struct Task<'a> {
+    pub target: TargetId,
+    pub id: u64,
+    pub pending: &'a mut Option<Pending>,
+    pub warp: bool,
+    budget: &'a mut usize,
+}
+
+impl Task<'_> {
+    /// Called at the end of every loop iteration, returns whether to yield.
+    pub fn tick(&mut self) -> bool {
+        if !self.warp {
+            return true;
+        }
+        *self.budget -= 1;
+        *self.budget == 0
+    }
+}
+
+pub type Func = fn(&mut Ctx, &mut Task, &mut Frame) -> Exit;
+
+/// A transpiled project: its hats, and a function for each of them and for
+/// each custom block.
+pub struct Compiled {
+    pub hats: Vec<Hat>,
+    /// What each hat runs, as an index into funcs.
+    pub entries: Vec<usize>,
+    pub funcs: Vec<Func>,
+}
+
+impl Backend for Compiled {
+    type State = Frames;
+
+    fn hats(&self) -> &[Hat] {
+        &self.hats
+    }
+
+    fn start(&self, hat: usize) -> Frames {
+        Frames::new(self.entries[hat])
+    }
+
+    fn step(&self, ctx: &mut Ctx, thread: &mut Thread<Frames>) -> Status {
+        drive(ctx, thread, &self.funcs)
+    }
+}
+
+/// Runs a thread until it yields, waits, or finishes.
+pub fn drive(ctx: &mut Ctx, thread: &mut Thread<Frames>, funcs: &[Func]) -> Status {
+    if !poll(ctx, thread) {
+        return Status::Wait;
+    }
+    let mut budget = WARP_BUDGET;
+    let s = &mut thread.state;
+    loop {
+        let mut frame = match s.frames.pop() {
+            Some(a) => a,
+            None => return Status::Done,
+        };
+        let mut task = Task {
+            target: thread.target,
+            id: thread.id,
+            pending: &mut thread.pending,
+            warp: s.warp > 0,
+            budget: &mut budget,
+        };
+        match funcs[frame.func](ctx, &mut task, &mut frame) {
+            Exit::Yield => {
+                s.frames.push(frame);
+                return Status::Yield;
+            }
+            Exit::Wait => {
+                s.frames.push(frame);
+                return Status::Wait;
+            }
+            Exit::Call(func, args, warp) => {
+                s.frames.push(frame);
+                let warp = warp && s.warp == 0;
+                if warp {
+                    s.warp += 1;
+                }
+                s.frames.push(Frame {
+                    func,
+                    args,
+                    warp,
+                    ..Default::default()
+                });
+            }
+            Exit::Return => {
+                if frame.warp {
+                    s.warp -= 1;
+                }
+            }
+            Exit::Done => return Status::Done,
+        }
+    }
+}
@@ -0,0 +1,563 @@
+//! The runtime for Scratch projects: values, the state of the stage and its
+//! sprites, and the scheduler that runs scripts.
+//!
+//! How scripts actually run is up to a Backend. yase has an interpreter and
+//! a tree walker, and transpiled projects bring their own compiled code.
+//!
+//! The scheduler works the way Scratch's does: every frame, each running
+//! script gets to run until it yields, and that repeats until something
+//! needs to be redrawn.
+use std::{collections::HashSet, sync::Arc};
+
+pub mod compiled;
+pub mod ops;
+mod parallel;
+pub mod value;
+pub mod world;
+
+use value::Val;
+use world::{TargetId, World};
+
+/// How many loop iterations a script running without screen refresh gets
+/// before it has to yield anyway. Scratch gives up after half a second
+/// instead, which would make runs depend on how fast the machine is.
+pub const WARP_BUDGET: usize = 100_000;
+
+/// What starts a script.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HatKind {
+    GreenFlag,
+    /// Key names are lowercase, i.e. "space" or "a".
+    KeyPressed(String),
+    SpriteClicked,
+    StageClicked,
+    BackdropSwitchesTo(String),
+    /// "when timer > value". Loudness is always -1 here so those never fire.
+    TimerGreaterThan(f64),
+    Broadcast(String),
+    CloneStart,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hat {
+    /// Index of the sprite the script belongs to.
+    pub sprite: usize,
+    /// Id of the hat block.
+    pub id: String,
+    pub kind: HatKind,
+}
+
+/// A script that's waiting on something before it can continue.
+#[derive(Debug, Clone)]
+pub enum Pending {
+    Until(f64),
+    /// broadcast and wait, or switch backdrop and wait.
+    Threads(Vec<u64>),
+    Glide {
+        start: f64,
+        secs: f64,
+        from: (f64, f64),
+        to: (f64, f64),
+    },
+    /// say/think for secs, which takes the bubble down afterwards.
+    Bubble {
+        until: f64,
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Ran until the end of a loop (or similar), and wants to keep going.
+    Yield,
+    /// Is waiting on a timer or on other scripts.
+    Wait,
+    Done,
+}
+
+pub struct Thread<S> {
+    pub id: u64,
+    pub target: TargetId,
+    pub hat: usize,
+    pub pending: Option<Pending>,
+    pub state: S,
+}
+
+/// A way of running scripts. Backends are shared with worker threads when
+/// sprites run in parallel.
+pub trait Backend: Sync {
+    /// Whatever the backend needs to remember about a script between steps.
+    type State: Send;
+
+    fn hats(&self) -> &[Hat];
+    fn start(&self, hat: usize) -> Self::State;
+    /// Runs a script until it yields, waits, or finishes.
+    fn step(&self, ctx: &mut Ctx, thread: &mut Thread<Self::State>) -> Status;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub fps: f64,
+    /// How many times every script may run before a frame is forced to end,
+    /// even if nothing needs redrawing. Scratch goes by time instead, which
+    /// would make runs unreproducible.
+    pub ticks_per_frame: usize,
+    pub seed: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            fps: 30.0,
+            ticks_per_frame: 100,
+            seed: 0x5eed,
+        }
+    }
+}
+
+/// The part of the scheduler that scripts can talk to.
+pub struct Ctx {
+    pub world: World,
+    hats: Vec<Hat>,
+    /// Scripts to start once the current one yields: (thread id, hat, target).
+    queue: Vec<(u64, usize, TargetId)>,
+    running: HashSet<u64>,
+    next_id: u64,
+    stop_all: bool,
+    /// (target, thread to spare)
+    stop_others: Vec<(TargetId, u64)>,
+}
+
+impl Ctx {
+    fn start(&mut self, hat: usize, target: TargetId) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.queue.push((id, hat, target));
+        self.running.insert(id);
+        id
+    }
+
+    /// Starts every script with a matching hat, on every target of that hat's
+    /// sprite, topmost first.
+    fn start_hats(&mut self, f: impl Fn(&HatKind) -> bool) -> Vec<u64> {
+        let order = self.execution_order();
+        let mut ids = Vec::new();
+        for target in order {
+            let sprite = self.world.target(target).sprite;
+            for hat in 0..self.hats.len() {
+                if self.hats[hat].sprite == sprite && f(&self.hats[hat].kind) {
+                    ids.push(self.start(hat, target));
+                }
+            }
+        }
+        ids
+    }
+
+    /// Targets from the top layer down, which is the order Scratch starts scripts in.
+    fn execution_order(&self) -> Vec<TargetId> {
+        let mut order: Vec<TargetId> = self.world.alive().collect();
+        order.sort_by_key(|f| std::cmp::Reverse(self.world.target(*f).layer));
+        order
+    }
+
+    pub fn is_running(&self, thread: u64) -> bool {
+        self.running.contains(&thread)
+    }
+
+    /// Returns the scripts that were started.
+    pub fn broadcast(&mut self, name: &Val) -> Vec<u64> {
+        let name = name.to_string();
+        self.start_hats(|f| matches!(f, HatKind::Broadcast(a) if a.eq_ignore_ascii_case(&name)))
+    }
+
+    pub fn switch_backdrop(&mut self, backdrop: &Val) -> Vec<u64> {
+        match self.world.switch_backdrop(backdrop) {
+            Some(name) => self.start_hats(
+                |f| matches!(f, HatKind::BackdropSwitchesTo(a) if a.eq_ignore_ascii_case(&name)),
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn create_clone(&mut self, target: TargetId, of: &Val) {
+        if let Some(clone) = self.world.create_clone(target, of) {
+            let sprite = self.world.target(clone).sprite;
+            for hat in 0..self.hats.len() {
+                if self.hats[hat].sprite == sprite && self.hats[hat].kind == HatKind::CloneStart {
+                    self.start(hat, clone);
+                }
+            }
+        }
+    }
+
+    /// Returns whether the target was a clone (and so is gone now).
+    pub fn delete_clone(&mut self, target: TargetId) -> bool {
+        self.world.delete_clone(target)
+    }
+
+    pub fn stop_all(&mut self) {
+        self.stop_all = true;
+    }
+
+    pub fn stop_others(&mut self, target: TargetId, thread: u64) {
+        self.stop_others.push((target, thread));
+    }
+
+    // The blocks that make a script wait. They return whether it has to.
+
+    pub fn wait(&mut self, pending: &mut Option<Pending>, secs: f64) -> bool {
+        *pending = Some(Pending::Until(self.world.time + secs));
+        true
+    }
+
+    pub fn say_for(
+        &mut self,
+        target: TargetId,
+        pending: &mut Option<Pending>,
+        message: &Val,
+        secs: f64,
+        think: bool,
+    ) -> bool {
+        self.world.say(target, message, think);
+        *pending = Some(Pending::Bubble {
+            until: self.world.time + secs,
+            text: message.to_string(),
+        });
+        true
+    }
+
+    /// Glides to a position, if there's one to glide to.
+    pub fn glide(
+        &mut self,
+        target: TargetId,
+        pending: &mut Option<Pending>,
+        secs: f64,
+        to: Option<(f64, f64)>,
+    ) -> bool {
+        let to = match to {
+            Some(a) => a,
+            None => return false,
+        };
+        let from = self.world.target(target);
+        *pending = Some(Pending::Glide {
+            start: self.world.time,
+            secs,
+            from: (from.x, from.y),
+            to,
+        });
+        true
+    }
+
+    pub fn broadcast_and_wait(&mut self, pending: &mut Option<Pending>, name: &Val) -> bool {
+        let threads = self.broadcast(name);
+        self.wait_for(pending, threads)
+    }
+
+    pub fn switch_backdrop_and_wait(
+        &mut self,
+        pending: &mut Option<Pending>,
+        backdrop: &Val,
+    ) -> bool {
+        let threads = self.switch_backdrop(backdrop);
+        self.wait_for(pending, threads)
+    }
+
+    fn wait_for(&mut self, pending: &mut Option<Pending>, threads: Vec<u64>) -> bool {
+        if threads.is_empty() {
+            return false;
+        }
+        *pending = Some(Pending::Threads(threads));
+        true
+    }
+
+    pub fn play_sound_until_done(
+        &mut self,
+        target: TargetId,
+        pending: &mut Option<Pending>,
+        sound: &Val,
+    ) -> bool {
+        match self.world.sound_duration(target, sound) {
+            Some(secs) => self.wait(pending, secs),
+            None => false,
+        }
+    }
+}
+
+pub struct Runtime<B: Backend> {
+    /// Shared with the worker threads, if there are any.
+    backend: Arc<B>,
+    pub ctx: Ctx,
+    threads: Vec<Thread<B::State>>,
+    options: Options,
+    /// Whether each timer hat's condition held last frame, since they only fire when it becomes true.
+    edges: Vec<bool>,
+    pub frames: u64,
+    /// Sprites whose scripts run on worker threads, if any.
+    parallel: Option<parallel::Parallel<B::State>>,
+}
+
+impl<B: Backend> Runtime<B> {
+    pub fn new(backend: B, world: World, options: Options) -> Runtime<B> {
+        let hats = backend.hats().to_vec();
+        let edges = vec![false; hats.len()];
+        Runtime {
+            backend: Arc::new(backend),
+            ctx: Ctx {
+                world,
+                hats,
+                queue: Vec::new(),
+                running: HashSet::new(),
+                next_id: 0,
+                stop_all: false,
+                stop_others: Vec::new(),
+            },
+            threads: Vec::new(),
+            options,
+            edges,
+            frames: 0,
+            parallel: None,
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.ctx.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.ctx.world
+    }
+
+    pub fn green_flag(&mut self) {
+        self.stop();
+        self.ctx.start_hats(|f| *f == HatKind::GreenFlag);
+        self.flush();
+    }
+
+    pub fn press_key(&mut self, key: &str) {
+        let key = key.to_lowercase();
+        self.ctx.world.keys.insert(key.clone());
+        self.ctx
+            .start_hats(|f| matches!(f, HatKind::KeyPressed(a) if *a == key || a == "any"));
+        self.flush();
+    }
+
+    pub fn release_key(&mut self, key: &str) {
+        self.ctx.world.keys.remove(&key.to_lowercase());
+    }
+
+    /// Clicks on a sprite by name, or on the stage if there's no such sprite.
+    pub fn click(&mut self, sprite: &str) {
+        let hat = match self.ctx.world.find(sprite) {
+            Some(a) if !self.ctx.world.sprites[self.ctx.world.target(a).sprite].is_stage => {
+                HatKind::SpriteClicked
+            }
+            _ => HatKind::StageClicked,
+        };
+        let sprite = sprite.to_string();
+        let world = &self.ctx.world;
+        let targets: Vec<TargetId> = world
+            .alive()
+            .filter(|f| hat == HatKind::StageClicked || world.name(*f) == sprite)
+            .collect();
+        for target in targets {
+            let index = self.ctx.world.target(target).sprite;
+            for i in 0..self.ctx.hats.len() {
+                if self.ctx.hats[i].sprite == index && self.ctx.hats[i].kind == hat {
+                    self.ctx.start(i, target);
+                }
+            }
+        }
+        self.flush();
+    }
+
+    pub fn broadcast(&mut self, name: &str) {
+        self.ctx.broadcast(&Val::from(name));
+        self.flush();
+    }
+
+    /// Stops every script and removes every clone, like the stop sign.
+    pub fn stop(&mut self) {
+        self.threads.clear();
+        self.ctx.queue.clear();
+        self.ctx.running.clear();
+        self.ctx.world.delete_clones();
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.threads.is_empty() && self.ctx.queue.is_empty()
+    }
+
+    /// Runs frames until every script is done, or the limit is hit.
+    /// Returns how many frames ran.
+    pub fn run(&mut self, frames: u64) -> u64 {
+        let mut ran = 0;
+        while ran < frames && !self.is_idle() {
+            self.frame();
+            ran += 1;
+        }
+        ran
+    }
+
+    pub fn frame(&mut self) {
+        self.ctx.world.redraw = false;
+        self.timer_hats();
+        self.flush();
+        for _ in 0..self.options.ticks_per_frame.max(1) {
+            let busy = self.tick();
+            if !busy || self.ctx.world.redraw || self.threads.is_empty() {
+                break;
+            }
+        }
+        self.ctx.world.time += 1.0 / self.options.fps;
+        self.frames += 1;
+    }
+
+    /// Runs every script once. Returns whether any of them did something other than wait.
+    fn tick(&mut self) -> bool {
+        let ran = self.tick_parallel();
+        let mut busy = ran.busy;
+        let mut speech = ran.speech.into_iter().peekable();
+        let mut i = 0;
+        while i < self.threads.len() {
+            let thread = &mut self.threads[i];
+            // what the parallel scripts before this one said goes first.
+            let position = ran.order.get(&thread.id).copied().unwrap_or(usize::MAX);
+            while let Some((_, a)) = speech.next_if(|f| f.0 < position) {
+                self.ctx.world.speech.push(a);
+            }
+            if ran.ran.contains(&thread.id) {
+                i += 1;
+                continue;
+            }
+            let status = match self.ctx.world.is_alive(thread.target) {
+                true => self.backend.step(&mut self.ctx, thread),
+                false => Status::Done,
+            };
+            busy |= status == Status::Yield;
+            if status == Status::Done {
+                self.ctx.running.remove(&thread.id);
+                self.threads.remove(i);
+            } else {
+                i += 1;
+            }
+            if self.ctx.stop_all {
+                self.ctx.stop_all = false;
+                self.stop();
+                self.ctx.world.speech.extend(speech.map(|f| f.1));
+                return false;
+            }
+            i = self.stop_others(i);
+            self.flush();
+        }
+        self.ctx.world.speech.extend(speech.map(|f| f.1));
+        busy
+    }
+
+    /// Handles "stop other scripts in sprite" and deleted clones. Returns
+    /// where the current thread ended up.
+    fn stop_others(&mut self, mut current: usize) -> usize {
+        let stops = std::mem::take(&mut self.ctx.stop_others);
+        let mut j = 0;
+        while j < self.threads.len() {
+            let thread = &self.threads[j];
+            let stopped = !self.ctx.world.is_alive(thread.target)
+                || stops
+                    .iter()
+                    .any(|f| f.0 == thread.target && f.1 != thread.id);
+            if stopped {
+                self.ctx.running.remove(&thread.id);
+                self.threads.remove(j);
+                if j < current {
+                    current -= 1;
+                }
+            } else {
+                j += 1;
+            }
+        }
+        current
+    }
+
+    /// Starts the scripts that were asked for. Scripts that are already
+    /// running on the same target start over, instead of running twice.
+    fn flush(&mut self) {
+        for (id, hat, target) in std::mem::take(&mut self.ctx.queue) {
+            let thread = Thread {
+                id,
+                target,
+                hat,
+                pending: None,
+                state: self.backend.start(hat),
+            };
+            match self
+                .threads
+                .iter_mut()
+                .find(|f| f.hat == hat && f.target == target)
+            {
+                Some(a) => {
+                    self.ctx.running.remove(&a.id);
+                    *a = thread;
+                }
+                None => self.threads.push(thread),
+            }
+        }
+    }
+
+    fn timer_hats(&mut self) {
+        let timer = self.ctx.world.timer();
+        for hat in 0..self.ctx.hats.len() {
+            if let HatKind::TimerGreaterThan(value) = self.ctx.hats[hat].kind {
+                let now = timer > value;
+                if now && !self.edges[hat] {
+                    let sprite = self.ctx.hats[hat].sprite;
+                    let targets: Vec<TargetId> = self
+                        .ctx
+                        .world
+                        .alive()
+                        .filter(|f| self.ctx.world.target(*f).sprite == sprite)
+                        .collect();
+                    for target in targets {
+                        self.ctx.start(hat, target);
+                    }
+                }
+                self.edges[hat] = now;
+            }
+        }
+    }
+}
+
+/// Shared by both backends, since waiting works the same no matter how the
+/// script is run. Returns whether the wait is over.
+pub fn poll(ctx: &mut Ctx, thread: &mut Thread<impl Sized>) -> bool {
+    let done = match &thread.pending {
+        None => return true,
+        Some(Pending::Until(a)) => ctx.world.time >= *a,
+        Some(Pending::Threads(a)) => a.iter().all(|f| !ctx.is_running(*f)),
+        Some(Pending::Glide {
+            start,
+            secs,
+            from,
+            to,
+        }) => {
+            let fraction = match *secs > 0.0 {
+                true => ((ctx.world.time - start) / secs).min(1.0),
+                false => 1.0,
+            };
+            let x = from.0 + (to.0 - from.0) * fraction;
+            let y = from.1 + (to.1 - from.1) * fraction;
+            ctx.world.set_xy(thread.target, x, y);
+            fraction >= 1.0
+        }
+        Some(Pending::Bubble { until, text }) => {
+            if ctx.world.time >= *until {
+                ctx.world.unsay(thread.target, text);
+                true
+            } else {
+                false
+            }
+        }
+    };
+    if done {
+        thread.pending = None;
+    }
+    done
+}
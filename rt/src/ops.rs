@@ -0,0 +1,182 @@
+//! The operator blocks. These are shared by every backend and by the
+//! compiler's constant folding, so they can't disagree with each other.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::value::Val;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Gt,
+    Eq,
+    And,
+    Or,
+    Join,
+    LetterOf,
+    Contains,
+}
+
+pub fn binary(op: BinOp, a: &Val, b: &Val) -> Val {
+    match op {
+        BinOp::Add => Val::Num(a.num() + b.num()),
+        BinOp::Sub => Val::Num(a.num() - b.num()),
+        BinOp::Mul => Val::Num(a.num() * b.num()),
+        BinOp::Div => Val::Num(a.num() / b.num()),
+        BinOp::Mod => Val::Num(modulo(a.num(), b.num())),
+        BinOp::Lt => Val::Bool(a.compare(b).is_lt()),
+        BinOp::Gt => Val::Bool(a.compare(b).is_gt()),
+        BinOp::Eq => Val::Bool(a.equals(b)),
+        BinOp::And => Val::Bool(a.bool() && b.bool()),
+        BinOp::Or => Val::Bool(a.bool() || b.bool()),
+        BinOp::Join => Val::Str(format!("{}{}", a, b).into()),
+        BinOp::LetterOf => letter_of(a.num(), &b.to_string()),
+        BinOp::Contains => Val::Bool(
+            a.to_string()
+                .to_lowercase()
+                .contains(&b.to_string().to_lowercase()),
+        ),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Not,
+    Length,
+    Round,
+    Math(MathOp),
+}
+
+/// The functions of the "abs of" block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathOp {
+    Abs,
+    Floor,
+    Ceiling,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Ln,
+    Log,
+    EPow,
+    TenPow,
+}
+
+/// The options of the "current" block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Year,
+    Month,
+    Date,
+    DayOfWeek,
+    Hour,
+    Minute,
+    Second,
+}
+
+pub fn unary(op: UnOp, a: &Val) -> Val {
+    match op {
+        UnOp::Not => Val::Bool(!a.bool()),
+        UnOp::Length => Val::Num(a.to_string().chars().count() as f64),
+        UnOp::Round => Val::Num((a.num() + 0.5).floor()),
+        UnOp::Math(op) => Val::Num(mathop(op, a.num())),
+    }
+}
+
+/// Scratch's mod takes the sign of the divisor, unlike rust's %.
+pub fn modulo(n: f64, m: f64) -> f64 {
+    let mut result = n % m;
+    if result / m < 0.0 {
+        result += m;
+    }
+    result
+}
+
+/// 1 based, and empty if out of range.
+pub fn letter_of(index: f64, a: &str) -> Val {
+    let index = index.floor();
+    if index < 1.0 {
+        return Val::Str("".into());
+    }
+    match a.chars().nth(index as usize - 1) {
+        Some(a) => Val::Str(a.to_string().into()),
+        None => Val::Str("".into()),
+    }
+}
+
+pub fn mathop(op: MathOp, n: f64) -> f64 {
+    // trig works in degrees, and gets rounded so that sin(180) is 0 and not 1.2e-16.
+    let round = |f: f64| (f * 1e10).round() / 1e10;
+    match op {
+        MathOp::Abs => n.abs(),
+        MathOp::Floor => n.floor(),
+        MathOp::Ceiling => n.ceil(),
+        MathOp::Sqrt => n.sqrt(),
+        MathOp::Sin => round(n.to_radians().sin()),
+        MathOp::Cos => round(n.to_radians().cos()),
+        MathOp::Tan => {
+            let n = n % 360.0;
+            if n == -270.0 || n == 90.0 {
+                f64::INFINITY
+            } else if n == -90.0 || n == 270.0 {
+                f64::NEG_INFINITY
+            } else {
+                round(n.to_radians().tan())
+            }
+        }
+        MathOp::Asin => n.asin().to_degrees(),
+        MathOp::Acos => n.acos().to_degrees(),
+        MathOp::Atan => n.atan().to_degrees(),
+        MathOp::Ln => n.ln(),
+        MathOp::Log => n.log10(),
+        MathOp::EPow => n.exp(),
+        MathOp::TenPow => 10f64.powf(n),
+    }
+}
+
+/// "list contents" joins with spaces, unless every item is a single letter.
+pub fn list_contents(list: &[Val]) -> Val {
+    let items: Vec<String> = list.iter().map(|f| f.to_string()).collect();
+    if items.iter().all(|f| f.chars().count() == 1) {
+        Val::Str(items.concat().into())
+    } else {
+        Val::Str(items.join(" ").into())
+    }
+}
+
+/// "current year" and friends, in UTC.
+pub fn current(unit: TimeUnit) -> f64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|f| f.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // days to a civil date, from Howard Hinnant's algorithm.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let date = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (match unit {
+        TimeUnit::Year => year,
+        TimeUnit::Month => month,
+        TimeUnit::Date => date,
+        // 1970-01-01 was a thursday, and sunday is 1.
+        TimeUnit::DayOfWeek => (days + 4).rem_euclid(7) + 1,
+        TimeUnit::Hour => secs / 3600,
+        TimeUnit::Minute => secs % 3600 / 60,
+        TimeUnit::Second => secs % 60,
+    }) as f64
+}
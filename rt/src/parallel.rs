@@ -0,0 +1,266 @@
+//! Running independent sprites on worker threads.
+//!
+//! A sprite is independent if its scripts only ever touch its own targets,
+//! and nothing else ever looks at them. Working out which sprites those are
+//! is up to whoever knows what the scripts do. Given that, the order their
+//! scripts run in within a tick doesn't matter, except for what they say,
+//! which is put back in the order a serial tick would have said it in.
+//!
+//! Every independent sprite gets a world of its own, kept by the worker it
+//! was given to. The workers live as long as the runtime, and every tick the
+//! sprites' threads and targets are sent over to them and back.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+use crate::{
+    world::{Speech, Target, TargetId},
+    Backend, Ctx, Runtime, Status, Thread,
+};
+
+pub(crate) struct Parallel<S> {
+    /// Which worker each independent sprite is on, and which of its groups
+    /// it is.
+    groups: HashMap<usize, (usize, usize)>,
+    workers: Vec<Worker<S>>,
+}
+
+/// A thread that runs the scripts of the sprites it was given, whenever it
+/// is sent their share of a tick. It stops once its Parallel is dropped.
+struct Worker<S> {
+    jobs: Sender<Job<S>>,
+    done: Receiver<Done<S>>,
+}
+
+/// What the independent part of a tick did.
+#[derive(Default)]
+pub(crate) struct Ran {
+    pub busy: bool,
+    /// Threads that already ran this tick, and are still going.
+    pub ran: HashSet<u64>,
+    /// Where each thread was in the order at the start of the tick.
+    pub order: HashMap<u64, usize>,
+    /// What was said, and by the thread at which position.
+    pub speech: Vec<(usize, Speech)>,
+}
+
+/// A worker's share of a tick: the targets of its sprites, and their
+/// threads by position.
+struct Job<S> {
+    time: f64,
+    /// How many targets there are in all, so ids stay the same.
+    len: usize,
+    /// By group.
+    targets: Vec<(usize, TargetId, Target)>,
+    /// By group and position.
+    threads: Vec<(usize, usize, Thread<S>)>,
+}
+
+/// The targets back, and how each thread did.
+struct Done<S> {
+    targets: Vec<(TargetId, Target)>,
+    redraw: bool,
+    threads: Vec<Outcome<S>>,
+}
+
+/// How a thread did: its position, the thread, where it got to and what it
+/// said.
+type Outcome<S> = (usize, Thread<S>, Status, Vec<Speech>);
+
+impl<B: Backend + Send + 'static> Runtime<B>
+where
+    B::State: 'static,
+{
+    /// Lets these sprites' scripts run on worker threads, up to the given
+    /// number of them. Only give sprites whose scripts touch nothing but
+    /// their own targets and that no other script looks at, or runs won't be
+    /// the same as serial ones anymore.
+    pub fn set_parallel(&mut self, sprites: &[usize], threads: usize) {
+        if sprites.is_empty() {
+            self.parallel = None;
+            return;
+        }
+        let mut world = self.ctx.world.clone();
+        world.targets.clear();
+        world.speech.clear();
+        let count = threads.max(1).min(sprites.len());
+        // every sprite's scripts run in a world of their own.
+        let mut groups: Vec<Vec<Ctx>> = (0..count).map(|_| Vec::new()).collect();
+        let mut groups_of = HashMap::new();
+        for (i, sprite) in sprites.iter().enumerate() {
+            groups_of.insert(*sprite, (i % count, groups[i % count].len()));
+            groups[i % count].push(Ctx {
+                world: world.clone(),
+                hats: Vec::new(),
+                queue: Vec::new(),
+                running: HashSet::new(),
+                next_id: 0,
+                stop_all: false,
+                stop_others: Vec::new(),
+            });
+        }
+        let workers = groups
+            .into_iter()
+            .map(|mut groups| {
+                let (jobs, receive) = channel();
+                let (send, done) = channel();
+                let backend = Arc::clone(&self.backend);
+                std::thread::spawn(move || {
+                    for job in receive {
+                        let done = run(&*backend, &mut groups, job);
+                        if send.send(done).is_err() {
+                            break;
+                        }
+                    }
+                });
+                Worker { jobs, done }
+            })
+            .collect();
+        self.parallel = Some(Parallel {
+            groups: groups_of,
+            workers,
+        });
+    }
+}
+
+impl<B: Backend> Runtime<B> {
+    /// Runs the threads of the independent sprites once, and takes the
+    /// finished ones out.
+    pub(crate) fn tick_parallel(&mut self) -> Ran {
+        let mut ran = Ran::default();
+        let parallel = match &self.parallel {
+            Some(a) => a,
+            None => return ran,
+        };
+        let world = &mut self.ctx.world;
+
+        // which worker and group each thread goes to.
+        let mut sent = Vec::new();
+        for (i, thread) in self.threads.iter().enumerate() {
+            ran.order.insert(thread.id, i);
+            let group = world
+                .targets
+                .get(thread.target)
+                .and_then(|f| f.as_ref())
+                .and_then(|f| parallel.groups.get(&f.sprite));
+            if let Some(group) = group {
+                sent.push((i, *group));
+            }
+        }
+        if sent.is_empty() {
+            return ran;
+        }
+
+        let mut jobs: Vec<Option<Job<B::State>>> = parallel.workers.iter().map(|_| None).collect();
+        let mut threads: Vec<Option<Thread<B::State>>> = std::mem::take(&mut self.threads)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut busy = HashSet::new();
+        for (i, (worker, group)) in sent {
+            let job = jobs[worker].get_or_insert_with(|| Job {
+                time: world.time,
+                len: world.targets.len(),
+                targets: Vec::new(),
+                threads: Vec::new(),
+            });
+            if let Some(thread) = threads[i].take() {
+                job.threads.push((group, i, thread));
+            }
+            busy.insert((worker, group));
+        }
+        // move the targets over.
+        for (id, target) in world.targets.iter_mut().enumerate() {
+            let (worker, group) = match target.as_ref().and_then(|f| parallel.groups.get(&f.sprite))
+            {
+                Some(a) if busy.contains(a) => *a,
+                _ => continue,
+            };
+            if let (Some(job), Some(target)) = (&mut jobs[worker], target.take()) {
+                job.targets.push((group, id, target));
+            }
+        }
+
+        let mut waiting = Vec::new();
+        for (worker, job) in parallel.workers.iter().zip(jobs) {
+            if let Some(job) = job {
+                worker.jobs.send(job).expect("worker thread panicked");
+                waiting.push(worker);
+            }
+        }
+        let mut done = HashSet::new();
+        for worker in waiting {
+            let result = worker.done.recv().expect("worker thread panicked");
+            // and back.
+            for (id, target) in result.targets {
+                world.targets[id] = Some(target);
+            }
+            world.redraw |= result.redraw;
+            for (pos, thread, status, speech) in result.threads {
+                ran.busy |= status == Status::Yield;
+                match status {
+                    Status::Done => {
+                        done.insert(thread.id);
+                    }
+                    _ => {
+                        ran.ran.insert(thread.id);
+                        threads[pos] = Some(thread);
+                    }
+                }
+                ran.speech.extend(speech.into_iter().map(|f| (pos, f)));
+            }
+        }
+        ran.speech.sort_by_key(|f| f.0);
+        self.threads = threads.into_iter().flatten().collect();
+        for id in done {
+            self.ctx.running.remove(&id);
+        }
+        ran
+    }
+}
+
+/// Runs a worker's share of a tick, returning how each thread did and what
+/// it said.
+fn run<B: Backend>(backend: &B, groups: &mut [Ctx], job: Job<B::State>) -> Done<B::State> {
+    for group in groups.iter_mut() {
+        let sub = &mut group.world;
+        sub.targets.resize_with(job.len, || None);
+        sub.time = job.time;
+        sub.redraw = false;
+    }
+    for (group, id, target) in job.targets {
+        groups[group].world.targets[id] = Some(target);
+    }
+
+    let mut threads = Vec::new();
+    for (group, pos, mut thread) in job.threads {
+        let ctx = &mut groups[group];
+        let status = match ctx.world.is_alive(thread.target) {
+            true => backend.step(ctx, &mut thread),
+            false => Status::Done,
+        };
+        let speech = std::mem::take(&mut ctx.world.speech);
+        threads.push((pos, thread, status, speech));
+    }
+
+    let mut targets = Vec::new();
+    let mut redraw = false;
+    for group in groups.iter_mut() {
+        let sub = &mut group.world;
+        for (id, target) in sub.targets.iter_mut().enumerate() {
+            if let Some(a) = target.take() {
+                targets.push((id, a));
+            }
+        }
+        redraw |= sub.redraw;
+    }
+    Done {
+        targets,
+        redraw,
+        threads,
+    }
+}
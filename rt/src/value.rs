@@ -0,0 +1,209 @@
+//! Values as scripts see them, and the (many) rules Scratch has for
+//! turning one kind into another.
+use std::{cmp::Ordering, fmt::Display, sync::Arc};
+
+#[derive(Debug, Clone)]
+pub enum Val {
+    Num(f64),
+    Str(Arc<str>),
+    Bool(bool),
+}
+
+impl Default for Val {
+    fn default() -> Self {
+        Val::Num(0.0)
+    }
+}
+
+impl From<f64> for Val {
+    fn from(a: f64) -> Self {
+        Val::Num(a)
+    }
+}
+
+impl From<bool> for Val {
+    fn from(a: bool) -> Self {
+        Val::Bool(a)
+    }
+}
+
+impl From<&str> for Val {
+    fn from(a: &str) -> Self {
+        Val::Str(a.into())
+    }
+}
+
+impl From<String> for Val {
+    fn from(a: String) -> Self {
+        Val::Str(a.into())
+    }
+}
+
+impl Val {
+    pub fn num(&self) -> f64 {
+        match self {
+            Val::Num(a) if a.is_nan() => 0.0,
+            Val::Num(a) => *a,
+            Val::Bool(a) => *a as u8 as f64,
+            Val::Str(a) => match parse_number(a) {
+                Some(a) if !a.is_nan() => a,
+                _ => 0.0,
+            },
+        }
+    }
+
+    pub fn bool(&self) -> bool {
+        match self {
+            Val::Bool(a) => *a,
+            Val::Num(a) => *a != 0.0 && !a.is_nan(),
+            Val::Str(a) => !(a.is_empty() || &**a == "0" || a.eq_ignore_ascii_case("false")),
+        }
+    }
+
+    /// Whether this would survive being turned into a number, which is what
+    /// decides if comparisons are numeric or not.
+    pub fn is_numeric(&self) -> bool {
+        match self {
+            Val::Num(a) => !a.is_nan(),
+            Val::Bool(_) => true,
+            Val::Str(a) => {
+                !a.trim().is_empty() && parse_number(a).map(|f| !f.is_nan()).unwrap_or(false)
+            }
+        }
+    }
+
+    /// Whether the value is a whole number, the way "pick random" checks it.
+    pub fn is_int(&self) -> bool {
+        match self {
+            Val::Num(a) => a.fract() == 0.0,
+            Val::Bool(_) => true,
+            Val::Str(a) => !a.contains('.'),
+        }
+    }
+
+    /// Compares two values like the comparison operators do: numerically if
+    /// both are numbers, otherwise case-insensitively as text.
+    pub fn compare(&self, other: &Val) -> Ordering {
+        if self.is_numeric() && other.is_numeric() {
+            let (a, b) = (self.num(), other.num());
+            return a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+        }
+        let a = self.to_string().to_lowercase();
+        let b = other.to_string().to_lowercase();
+        a.cmp(&b)
+    }
+
+    pub fn equals(&self, other: &Val) -> bool {
+        self.compare(other) == Ordering::Equal
+    }
+
+    /// The index this value refers to in a list of the given length, 1 based.
+    /// "last", "random" and "any" are understood.
+    pub fn list_index(&self, len: usize, rng: &mut Rng) -> Option<usize> {
+        if let Val::Str(a) = self {
+            match &**a {
+                "last" => return if len > 0 { Some(len) } else { None },
+                "random" | "any" => {
+                    return if len > 0 {
+                        Some(rng.range_int(1, len as i64) as usize)
+                    } else {
+                        None
+                    }
+                }
+                _ => {}
+            }
+        }
+        let index = self.num().floor();
+        if index < 1.0 || index > len as f64 {
+            return None;
+        }
+        Some(index as usize)
+    }
+}
+
+impl Display for Val {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Val::Num(a) => f.write_str(&format_number(*a)),
+            Val::Str(a) => f.write_str(a),
+            Val::Bool(a) => write!(f, "{}", a),
+        }
+    }
+}
+
+impl PartialEq for Val {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+/// Parses a number the way JavaScript's Number() does, which is to say loosely.
+pub fn parse_number(a: &str) -> Option<f64> {
+    let a = a.trim();
+    if a.is_empty() {
+        return Some(0.0);
+    }
+    match a {
+        "Infinity" | "+Infinity" => return Some(f64::INFINITY),
+        "-Infinity" => return Some(f64::NEG_INFINITY),
+        _ => {}
+    }
+    if let Some(hex) = a.strip_prefix("0x").or_else(|| a.strip_prefix("0X")) {
+        return i64::from_str_radix(hex, 16).ok().map(|f| f as f64);
+    }
+    // rust accepts "inf" and "nan", javascript doesn't.
+    if a.chars()
+        .any(|f| f.is_ascii_alphabetic() && f != 'e' && f != 'E')
+    {
+        return None;
+    }
+    a.parse().ok()
+}
+
+/// Formats a number the way JavaScript would, so "1" instead of "1.0".
+pub fn format_number(a: f64) -> String {
+    if a.is_nan() {
+        return String::from("NaN");
+    }
+    if a.is_infinite() {
+        return String::from(if a > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    if a == 0.0 {
+        return String::from("0");
+    }
+    format!("{}", a)
+}
+
+/// A small xorshift generator. Runs need to be reproducible, so we don't take
+/// randomness from the system.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn range_int(&mut self, low: i64, high: i64) -> i64 {
+        let (low, high) = if low > high { (high, low) } else { (low, high) };
+        low + (self.next_f64() * (high - low + 1) as f64).floor() as i64
+    }
+
+    /// "pick random" picks whole numbers if both ends are whole, and
+    /// anything in between otherwise.
+    pub fn pick(&mut self, from: &Val, to: &Val) -> f64 {
+        let (low, high) = (from.num(), to.num());
+        if from.is_int() && to.is_int() {
+            return self.range_int(low as i64, high as i64) as f64;
+        }
+        let (low, high) = if low > high { (high, low) } else { (low, high) };
+        low + self.next_f64() * (high - low)
+    }
+}
@@ -0,0 +1,737 @@
+//! Everything a running project can see and change: the sprites (and their
+//! clones), the stage, the timer and so on. The blocks that touch any of it
+//! are implemented here so that every backend behaves the same way.
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ops,
+    value::{Rng, Val},
+};
+
+/// Index of a target in the world. Clones get new ones, and they aren't reused.
+pub type TargetId = usize;
+
+/// How many clones may exist at once, same as Scratch.
+pub const CLONE_LIMIT: usize = 300;
+
+const STAGE_WIDTH: f64 = 480.0;
+const STAGE_HEIGHT: f64 = 360.0;
+
+/// Where a variable (or list) lives: on the running target, or on the stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Local(usize),
+    Global(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationStyle {
+    AllAround,
+    LeftRight,
+    DontRotate,
+}
+
+/// The starting state of a sprite (or the stage), as saved in the project.
+#[derive(Debug, Clone, Default)]
+pub struct TargetInit {
+    pub name: String,
+    pub is_stage: bool,
+    pub x: f64,
+    pub y: f64,
+    pub direction: f64,
+    pub size: f64,
+    pub visible: bool,
+    pub draggable: bool,
+    pub rotation_style: Option<RotationStyle>,
+    pub costume: usize,
+    pub costumes: Vec<String>,
+    /// Name and length in seconds.
+    pub sounds: Vec<(String, f64)>,
+    pub volume: f64,
+    pub layer: i64,
+    pub variables: Vec<Val>,
+    pub lists: Vec<Vec<Val>>,
+    pub variable_names: Vec<String>,
+    pub list_names: Vec<String>,
+}
+
+/// What sprites have said, in the order they said it. There is no stage to
+/// look at, so this is how a run is observed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Speech {
+    pub time: f64,
+    pub target: String,
+    pub text: String,
+    pub think: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// Which sprite this is (or is a clone of), as an index into World::sprites.
+    pub sprite: usize,
+    pub is_clone: bool,
+    pub x: f64,
+    pub y: f64,
+    pub direction: f64,
+    pub size: f64,
+    pub visible: bool,
+    pub draggable: bool,
+    pub rotation_style: RotationStyle,
+    pub costume: usize,
+    pub volume: f64,
+    pub layer: i64,
+    pub effects: HashMap<Arc<str>, f64>,
+    /// The bubble currently shown, if any.
+    pub bubble: Option<(String, bool)>,
+    pub variables: Vec<Val>,
+    pub lists: Vec<Vec<Val>>,
+}
+
+/// The parts of a sprite that clones share with it.
+#[derive(Debug, Clone)]
+pub struct SpriteInfo {
+    pub name: String,
+    pub is_stage: bool,
+    pub costumes: Vec<String>,
+    pub sounds: Vec<(String, f64)>,
+    pub variable_names: Vec<String>,
+    pub list_names: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct World {
+    pub sprites: Vec<SpriteInfo>,
+    /// Deleted clones leave a hole behind.
+    pub targets: Vec<Option<Target>>,
+    pub stage: TargetId,
+    /// Seconds since the project started. Advanced by the scheduler, not the wall clock.
+    pub time: f64,
+    pub timer_start: f64,
+    pub rng: Rng,
+    pub keys: HashSet<String>,
+    pub mouse: (f64, f64),
+    pub mouse_down: bool,
+    pub answer: String,
+    /// Answers to give to "ask and wait", in order. Once they run out, the answer is empty.
+    pub answers: VecDeque<String>,
+    pub username: String,
+    pub speech: Vec<Speech>,
+    /// Set when something visible changed, which ends the frame for non-warp scripts.
+    pub redraw: bool,
+    clones: usize,
+}
+
+impl Target {
+    fn new(sprite: usize, init: &TargetInit) -> Target {
+        Target {
+            sprite,
+            is_clone: false,
+            x: init.x,
+            y: init.y,
+            direction: init.direction,
+            size: init.size,
+            visible: init.visible,
+            draggable: init.draggable,
+            rotation_style: init.rotation_style.unwrap_or(RotationStyle::AllAround),
+            costume: init.costume,
+            volume: init.volume,
+            layer: init.layer,
+            effects: HashMap::new(),
+            bubble: None,
+            variables: init.variables.clone(),
+            lists: init.lists.clone(),
+        }
+    }
+}
+
+impl World {
+    pub fn new(targets: &[TargetInit], seed: u64) -> World {
+        let sprites = targets
+            .iter()
+            .map(|f| SpriteInfo {
+                name: f.name.clone(),
+                is_stage: f.is_stage,
+                costumes: f.costumes.clone(),
+                sounds: f.sounds.clone(),
+                variable_names: f.variable_names.clone(),
+                list_names: f.list_names.clone(),
+            })
+            .collect();
+        World {
+            sprites,
+            targets: targets
+                .iter()
+                .enumerate()
+                .map(|(i, f)| Some(Target::new(i, f)))
+                .collect(),
+            stage: targets.iter().position(|f| f.is_stage).unwrap_or(0),
+            time: 0.0,
+            timer_start: 0.0,
+            rng: Rng::new(seed),
+            keys: HashSet::new(),
+            mouse: (0.0, 0.0),
+            mouse_down: false,
+            answer: String::new(),
+            answers: VecDeque::new(),
+            username: String::new(),
+            speech: Vec::new(),
+            redraw: false,
+            clones: 0,
+        }
+    }
+
+    pub fn target(&self, t: TargetId) -> &Target {
+        self.targets[t].as_ref().expect("target was deleted")
+    }
+
+    pub fn target_mut(&mut self, t: TargetId) -> &mut Target {
+        self.targets[t].as_mut().expect("target was deleted")
+    }
+
+    /// Which target a slot's variable (or list) lives on, and where.
+    pub fn resolve(&self, t: TargetId, slot: Slot) -> (TargetId, usize) {
+        match slot {
+            Slot::Local(a) => (t, a),
+            Slot::Global(a) => (self.stage, a),
+        }
+    }
+
+    pub fn var(&self, t: TargetId, slot: Slot) -> &Val {
+        let (t, i) = self.resolve(t, slot);
+        &self.target(t).variables[i]
+    }
+
+    pub fn set_var(&mut self, t: TargetId, slot: Slot, val: Val) {
+        let (t, i) = self.resolve(t, slot);
+        self.target_mut(t).variables[i] = val;
+    }
+
+    pub fn change_var(&mut self, t: TargetId, slot: Slot, by: f64) {
+        let (t, i) = self.resolve(t, slot);
+        let var = &mut self.target_mut(t).variables[i];
+        *var = Val::Num(var.num() + by);
+    }
+
+    pub fn is_alive(&self, t: TargetId) -> bool {
+        matches!(self.targets.get(t), Some(Some(_)))
+    }
+
+    pub fn name(&self, t: TargetId) -> &str {
+        &self.sprites[self.target(t).sprite].name
+    }
+
+    /// The original (non clone) target of a sprite, by name.
+    pub fn find(&self, name: &str) -> Option<TargetId> {
+        self.targets.iter().position(|f| match f {
+            Some(a) => !a.is_clone && self.sprites[a.sprite].name == name,
+            None => false,
+        })
+    }
+
+    /// Targets that are still around, in the order they were created.
+    pub fn alive(&self) -> impl Iterator<Item = TargetId> + '_ {
+        self.targets
+            .iter()
+            .enumerate()
+            .filter(|f| f.1.is_some())
+            .map(|f| f.0)
+    }
+
+    // Motion
+
+    pub fn set_xy(&mut self, t: TargetId, x: f64, y: f64) {
+        if self.sprites[self.target(t).sprite].is_stage {
+            return;
+        }
+        let target = self.target_mut(t);
+        // sprites can't leave the stage entirely. Scratch uses their bounds, we
+        // don't know those so keep the center on stage.
+        target.x = x.clamp(-STAGE_WIDTH / 2.0, STAGE_WIDTH / 2.0);
+        target.y = y.clamp(-STAGE_HEIGHT / 2.0, STAGE_HEIGHT / 2.0);
+        if target.visible {
+            self.redraw = true;
+        }
+    }
+
+    pub fn move_steps(&mut self, t: TargetId, steps: f64) {
+        let target = self.target(t);
+        let radians = (90.0 - target.direction).to_radians();
+        let (x, y) = (
+            target.x + steps * radians.cos(),
+            target.y + steps * radians.sin(),
+        );
+        self.set_xy(t, x, y);
+    }
+
+    pub fn set_direction(&mut self, t: TargetId, direction: f64) {
+        if !direction.is_finite() {
+            return;
+        }
+        let target = self.target_mut(t);
+        // wrapped to (-180, 180]
+        let mut direction = (direction + 180.0) % 360.0 - 180.0;
+        if direction <= -180.0 {
+            direction += 360.0;
+        }
+        target.direction = direction;
+        if target.visible {
+            self.redraw = true;
+        }
+    }
+
+    pub fn turn(&mut self, t: TargetId, degrees: f64) {
+        let direction = self.target(t).direction + degrees;
+        self.set_direction(t, direction);
+    }
+
+    /// Where "go to" and "glide to" would send a sprite: a random position,
+    /// the mouse, or another sprite.
+    pub fn position_of(&mut self, option: &Val) -> Option<(f64, f64)> {
+        match option.to_string().as_str() {
+            "_random_" => {
+                let x = (self.rng.next_f64() - 0.5) * STAGE_WIDTH;
+                let y = (self.rng.next_f64() - 0.5) * STAGE_HEIGHT;
+                Some((x.round(), y.round()))
+            }
+            "_mouse_" => Some(self.mouse),
+            name => self.find(name).map(|f| {
+                let target = self.target(f);
+                (target.x, target.y)
+            }),
+        }
+    }
+
+    pub fn goto(&mut self, t: TargetId, option: &Val) {
+        if let Some((x, y)) = self.position_of(option) {
+            self.set_xy(t, x, y);
+        }
+    }
+
+    pub fn point_towards(&mut self, t: TargetId, option: &Val) {
+        let to = match option.to_string().as_str() {
+            "_random_" => {
+                let direction = self.rng.range_int(-180, 180) as f64;
+                return self.set_direction(t, direction);
+            }
+            _ => self.position_of(option),
+        };
+        if let Some((x, y)) = to {
+            let target = self.target(t);
+            let (dx, dy) = (x - target.x, y - target.y);
+            self.set_direction(t, 90.0 - dy.atan2(dx).to_degrees());
+        }
+    }
+
+    pub fn bounce(&mut self, t: TargetId) {
+        let target = self.target(t);
+        let (half_w, half_h) = (STAGE_WIDTH / 2.0, STAGE_HEIGHT / 2.0);
+        let (x, y, direction) = (target.x, target.y, target.direction);
+        let radians = (90.0 - direction).to_radians();
+        let (mut dx, mut dy) = (radians.cos(), radians.sin());
+        if x.abs() >= half_w {
+            dx = -dx.abs() * x.signum();
+        }
+        if y.abs() >= half_h {
+            dy = -dy.abs() * y.signum();
+        }
+        if x.abs() >= half_w || y.abs() >= half_h {
+            self.set_direction(t, 90.0 - dy.atan2(dx).to_degrees());
+            let target = self.target(t);
+            let (x, y) = (
+                target.x.clamp(-half_w + 1.0, half_w - 1.0),
+                target.y.clamp(-half_h + 1.0, half_h - 1.0),
+            );
+            self.set_xy(t, x, y);
+        }
+    }
+
+    pub fn set_rotation_style(&mut self, t: TargetId, style: RotationStyle) {
+        self.target_mut(t).rotation_style = style;
+        self.redraw = true;
+    }
+
+    // Looks
+
+    pub fn say(&mut self, t: TargetId, text: &Val, think: bool) {
+        let text = text.to_string();
+        let bubble = match text.is_empty() {
+            true => None,
+            false => Some((text.clone(), think)),
+        };
+        if bubble.is_some() {
+            self.speech.push(Speech {
+                time: self.time,
+                target: self.name(t).to_string(),
+                text,
+                think,
+            });
+        }
+        self.target_mut(t).bubble = bubble;
+        self.redraw = true;
+    }
+
+    /// Removes a bubble, but only if it's still the one that was put up.
+    pub fn unsay(&mut self, t: TargetId, text: &str) {
+        if !self.is_alive(t) {
+            return;
+        }
+        let target = self.target_mut(t);
+        if target.bubble.as_ref().map(|f| f.0.as_str()) == Some(text) {
+            target.bubble = None;
+            self.redraw = true;
+        }
+    }
+
+    /// Finds a costume by name, or by number if there's no costume with that
+    /// name. Also understands "next costume" and "previous costume".
+    fn costume_index(&mut self, t: TargetId, costume: &Val) -> Option<usize> {
+        let target = self.target(t);
+        let costumes = &self.sprites[target.sprite].costumes;
+        let len = costumes.len();
+        if len == 0 {
+            return None;
+        }
+        if let Val::Num(a) = costume {
+            return Some(wrap_index(*a, len));
+        }
+        let name = costume.to_string();
+        if let Some(a) = costumes.iter().position(|f| *f == name) {
+            return Some(a);
+        }
+        let is_stage = self.sprites[target.sprite].is_stage;
+        match name.as_str() {
+            "next costume" | "next backdrop" => Some((target.costume + 1) % len),
+            "previous costume" | "previous backdrop" => Some((target.costume + len - 1) % len),
+            "random backdrop" if is_stage => {
+                if len < 2 {
+                    return Some(target.costume);
+                }
+                let current = target.costume;
+                let pick = self.rng.range_int(0, len as i64 - 2) as usize;
+                Some(if pick >= current { pick + 1 } else { pick })
+            }
+            _ if costume.is_numeric() && !name.trim().is_empty() => {
+                Some(wrap_index(costume.num(), len))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn switch_costume(&mut self, t: TargetId, costume: &Val) {
+        if let Some(a) = self.costume_index(t, costume) {
+            self.target_mut(t).costume = a;
+            self.redraw = true;
+        }
+    }
+
+    pub fn next_costume(&mut self, t: TargetId) {
+        self.switch_costume(t, &Val::from("next costume"));
+    }
+
+    /// Switches the stage's backdrop, and returns the new backdrop's name so
+    /// that the scheduler can start the scripts waiting for it.
+    pub fn switch_backdrop(&mut self, backdrop: &Val) -> Option<String> {
+        let stage = self.stage;
+        let index = self.costume_index(stage, backdrop)?;
+        self.target_mut(stage).costume = index;
+        self.redraw = true;
+        Some(self.sprites[self.target(stage).sprite].costumes[index].clone())
+    }
+
+    pub fn costume_name(&self, t: TargetId) -> &str {
+        let target = self.target(t);
+        self.sprites[target.sprite]
+            .costumes
+            .get(target.costume)
+            .map(|f| f.as_str())
+            .unwrap_or("")
+    }
+
+    pub fn change_effect(&mut self, t: TargetId, effect: &str, by: f64) {
+        let effect: Arc<str> = effect.to_uppercase().into();
+        let current = self.target(t).effects.get(&effect).copied().unwrap_or(0.0);
+        self.set_effect(t, &effect, current + by);
+    }
+
+    pub fn set_effect(&mut self, t: TargetId, effect: &str, to: f64) {
+        let effect: Arc<str> = effect.to_uppercase().into();
+        let to = match &*effect {
+            "GHOST" => to.clamp(0.0, 100.0),
+            "BRIGHTNESS" => to.clamp(-100.0, 100.0),
+            "PAN" | "PITCH" => to.clamp(-100.0, 100.0),
+            _ => to,
+        };
+        self.target_mut(t).effects.insert(effect, to);
+        self.redraw = true;
+    }
+
+    pub fn clear_effects(&mut self, t: TargetId) {
+        self.target_mut(t).effects.clear();
+        self.redraw = true;
+    }
+
+    pub fn set_size(&mut self, t: TargetId, size: f64) {
+        let target = self.target_mut(t);
+        target.size = size.clamp(5.0, 500.0);
+        if target.visible {
+            self.redraw = true;
+        }
+    }
+
+    pub fn set_visible(&mut self, t: TargetId, visible: bool) {
+        self.target_mut(t).visible = visible;
+        self.redraw = true;
+    }
+
+    pub fn goto_layer(&mut self, t: TargetId, front: bool) {
+        let layers = self.targets.iter().flatten().map(|f| f.layer);
+        let layer = match front {
+            true => layers.max().unwrap_or(0) + 1,
+            // the stage is always at the very back.
+            false => {
+                for target in self.targets.iter_mut().flatten() {
+                    target.layer += 1;
+                }
+                1
+            }
+        };
+        self.target_mut(t).layer = layer;
+        self.redraw = true;
+    }
+
+    pub fn change_layer(&mut self, t: TargetId, by: i64) {
+        let target = self.target_mut(t);
+        target.layer = (target.layer + by).max(1);
+        self.redraw = true;
+    }
+
+    // Sound
+
+    /// How long a sound plays for, found by name or number.
+    pub fn sound_duration(&self, t: TargetId, sound: &Val) -> Option<f64> {
+        let sounds = &self.sprites[self.target(t).sprite].sounds;
+        if sounds.is_empty() {
+            return None;
+        }
+        let name = sound.to_string();
+        match sounds.iter().find(|f| f.0 == name) {
+            Some(a) => Some(a.1),
+            None if sound.is_numeric() => Some(sounds[wrap_index(sound.num(), sounds.len())].1),
+            None => None,
+        }
+    }
+
+    pub fn set_volume(&mut self, t: TargetId, volume: f64) {
+        self.target_mut(t).volume = volume.clamp(0.0, 100.0);
+    }
+
+    // Sensing
+
+    pub fn touching(&self, t: TargetId, what: &Val) -> bool {
+        let target = self.target(t);
+        match what.to_string().as_str() {
+            "_edge_" => target.x.abs() >= STAGE_WIDTH / 2.0 || target.y.abs() >= STAGE_HEIGHT / 2.0,
+            // without costumes we treat every sprite as a point.
+            "_mouse_" => target.x == self.mouse.0 && target.y == self.mouse.1,
+            name => self.targets.iter().flatten().any(|f| {
+                f.visible
+                    && self.sprites[f.sprite].name == name
+                    && f.x == target.x
+                    && f.y == target.y
+            }),
+        }
+    }
+
+    pub fn distance_to(&self, t: TargetId, what: &Val) -> f64 {
+        let to = match what.to_string().as_str() {
+            "_mouse_" => Some(self.mouse),
+            name => self
+                .find(name)
+                .map(|f| (self.target(f).x, self.target(f).y)),
+        };
+        let target = self.target(t);
+        match to {
+            Some((x, y)) => ((x - target.x).powi(2) + (y - target.y).powi(2)).sqrt(),
+            None => 10000.0,
+        }
+    }
+
+    pub fn key_pressed(&self, key: &Val) -> bool {
+        let key = key.to_string().to_lowercase();
+        match key.as_str() {
+            "any" => !self.keys.is_empty(),
+            _ => self.keys.contains(&key),
+        }
+    }
+
+    /// There's nobody to ask, so answers come from World::answers.
+    pub fn ask(&mut self) {
+        self.answer = self.answers.pop_front().unwrap_or_default();
+    }
+
+    pub fn timer(&self) -> f64 {
+        self.time - self.timer_start
+    }
+
+    pub fn reset_timer(&mut self) {
+        self.timer_start = self.time;
+    }
+
+    pub fn days_since_2000(&self) -> f64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|f| f.as_secs_f64())
+            .unwrap_or(0.0);
+        (now - 946684800.0) / 86400.0
+    }
+
+    // Data
+
+    /// Lists (and variables) are addressed by the target that owns them and
+    /// their index in that target.
+    pub fn list(&self, t: TargetId, list: usize) -> &Vec<Val> {
+        &self.target(t).lists[list]
+    }
+
+    fn list_and_rng(&mut self, t: TargetId, list: usize) -> (&mut Vec<Val>, &mut Rng) {
+        let World { targets, rng, .. } = self;
+        let target = targets[t].as_mut().expect("target was deleted");
+        (&mut target.lists[list], rng)
+    }
+
+    pub fn add_to_list(&mut self, t: TargetId, list: usize, item: Val) {
+        self.list_and_rng(t, list).0.push(item);
+    }
+
+    pub fn delete_of_list(&mut self, t: TargetId, list: usize, index: &Val) {
+        let (list, rng) = self.list_and_rng(t, list);
+        if let Val::Str(a) = index {
+            if &**a == "all" {
+                list.clear();
+                return;
+            }
+        }
+        if let Some(a) = index.list_index(list.len(), rng) {
+            list.remove(a - 1);
+        }
+    }
+
+    pub fn delete_all_of_list(&mut self, t: TargetId, list: usize) {
+        self.list_and_rng(t, list).0.clear();
+    }
+
+    pub fn insert_at_list(&mut self, t: TargetId, list: usize, index: &Val, item: Val) {
+        let (list, rng) = self.list_and_rng(t, list);
+        if let Some(a) = index.list_index(list.len() + 1, rng) {
+            list.insert(a - 1, item);
+        }
+    }
+
+    pub fn replace_item_of_list(&mut self, t: TargetId, list: usize, index: &Val, item: Val) {
+        let (list, rng) = self.list_and_rng(t, list);
+        if let Some(a) = index.list_index(list.len(), rng) {
+            list[a - 1] = item;
+        }
+    }
+
+    pub fn item_of_list(&mut self, t: TargetId, list: usize, index: &Val) -> Val {
+        let (list, rng) = self.list_and_rng(t, list);
+        match index.list_index(list.len(), rng) {
+            Some(a) => list[a - 1].clone(),
+            None => Val::from(""),
+        }
+    }
+
+    pub fn list_contains(&self, t: TargetId, list: usize, item: &Val) -> bool {
+        self.list(t, list).iter().any(|f| f.equals(item))
+    }
+
+    pub fn list_contents(&self, t: TargetId, list: usize) -> Val {
+        ops::list_contents(self.list(t, list))
+    }
+
+    // Clones
+
+    /// Clones a target, either the one asking or another sprite by name.
+    pub fn create_clone(&mut self, t: TargetId, of: &Val) -> Option<TargetId> {
+        let parent = match of.to_string().as_str() {
+            "_myself_" => t,
+            name => self.find(name)?,
+        };
+        if self.sprites[self.target(parent).sprite].is_stage || self.clones >= CLONE_LIMIT {
+            return None;
+        }
+        let mut clone = self.target(parent).clone();
+        clone.is_clone = true;
+        clone.bubble = None;
+        // clones go right behind their parent.
+        for target in self.targets.iter_mut().flatten() {
+            if target.layer >= clone.layer {
+                target.layer += 1;
+            }
+        }
+        self.clones += 1;
+        self.targets.push(Some(clone));
+        self.redraw = true;
+        Some(self.targets.len() - 1)
+    }
+
+    pub fn delete_clone(&mut self, t: TargetId) -> bool {
+        match self.targets.get(t) {
+            Some(Some(a)) if a.is_clone => {
+                self.targets[t] = None;
+                self.clones -= 1;
+                self.redraw = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Deletes every clone, for when the project is stopped.
+    pub fn delete_clones(&mut self) {
+        for target in self.targets.iter_mut() {
+            if target.as_ref().map(|f| f.is_clone).unwrap_or(false) {
+                *target = None;
+            }
+        }
+        self.clones = 0;
+    }
+
+    /// Everything that was said, then where every sprite's variables and
+    /// lists ended up, a line each. This is how runs are compared.
+    pub fn transcript(&self) -> String {
+        let mut out = String::new();
+        for speech in &self.speech {
+            let verb = if speech.think { "thinks" } else { "says" };
+            out += &format!(
+                "[{:.2}] {} {}: {}\n",
+                speech.time, speech.target, verb, speech.text
+            );
+        }
+        for target in self.targets.iter().flatten().filter(|f| !f.is_clone) {
+            let sprite = &self.sprites[target.sprite];
+            for (name, val) in sprite.variable_names.iter().zip(&target.variables) {
+                out += &format!("{}: {} = {}\n", sprite.name, name, val);
+            }
+            for (name, list) in sprite.list_names.iter().zip(&target.lists) {
+                let items: Vec<_> = list.iter().map(|f| f.to_string()).collect();
+                out += &format!("{}: {} = [{}]\n", sprite.name, name, items.join(", "));
+            }
+        }
+        out
+    }
+}
+
+/// Costume numbers wrap around, so costume 0 is the last one.
+fn wrap_index(index: f64, len: usize) -> usize {
+    if !index.is_finite() {
+        return 0;
+    }
+    let index = index.round() as i64 - 1;
+    index.rem_euclid(len as i64) as usize
+}
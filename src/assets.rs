@@ -0,0 +1,306 @@
+//! Where costumes and sounds come from.
+//!
+//! Assets are named by their md5ext, the md5 of the file followed by its
+//! format (`83a9787d4cb6f3b7632b4ddfebf74367.wav`), so wherever they come
+//! from they can be checked against their name, and can be kept around
+//! without ever going stale.
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::error::YaseError;
+
+pub trait AssetStore {
+    /// The file, as it's stored. None if it isn't there.
+    fn read(&self, md5ext: &str) -> Result<Option<Vec<u8>>, YaseError>;
+
+    /// Where the store is, for errors.
+    fn location(&self) -> String;
+
+    /// The file, if it's there and is what its name says it is.
+    fn get(&self, md5ext: &str) -> Result<Option<Vec<u8>>, YaseError> {
+        match self.read(md5ext)? {
+            Some(a) => verify(md5ext, &a).map(|_| Some(a)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn verify(md5ext: &str, data: &[u8]) -> Result<(), YaseError> {
+    let md5 = format!("{:x}", md5::compute(data));
+    let expected = md5ext.split('.').next().unwrap_or_default();
+    match md5.eq_ignore_ascii_case(expected) {
+        true => Ok(()),
+        false => Err(YaseError::BadAsset {
+            name: md5ext.to_string(),
+            md5,
+        }),
+    }
+}
+
+/// A directory of files named by their md5ext, like an unzipped .sb3.
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: impl Into<PathBuf>) -> DirStore {
+        DirStore { dir: dir.into() }
+    }
+}
+
+impl AssetStore for DirStore {
+    fn read(&self, md5ext: &str) -> Result<Option<Vec<u8>>, YaseError> {
+        read_file(&self.dir.join(md5ext))
+    }
+
+    fn location(&self) -> String {
+        self.dir.display().to_string()
+    }
+}
+
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, YaseError> {
+    match fs::read(path) {
+        Ok(a) => Ok(Some(a)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(YaseError::Io(format!("{}: {}", path.display(), err))),
+    }
+}
+
+/// The assets in an .sb3 (or .sb2), read into memory all at once.
+pub struct ZipStore {
+    name: String,
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl ZipStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<ZipStore, YaseError> {
+        let path = path.as_ref();
+        let data =
+            fs::read(path).map_err(|err| YaseError::Io(format!("{}: {}", path.display(), err)))?;
+        ZipStore::from_bytes(path.display().to_string(), data)
+    }
+
+    /// The name is only for errors.
+    pub fn from_bytes(name: impl Into<String>, data: Vec<u8>) -> Result<ZipStore, YaseError> {
+        let name = name.into();
+        let io = |err: io::Error| YaseError::Io(format!("{}: {}", name, err));
+        let mut zip = zip::ZipArchive::new(io::Cursor::new(data)).map_err(|f| io(f.into()))?;
+        let mut files = HashMap::new();
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(|f| io(f.into()))?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).map_err(io)?;
+            files.insert(file.name().to_string(), data);
+        }
+        Ok(ZipStore { name, files })
+    }
+
+    /// project.json, which isn't an asset but is in there too.
+    pub fn project_json(&self) -> Option<&[u8]> {
+        self.files.get("project.json").map(Vec::as_slice)
+    }
+}
+
+impl AssetStore for ZipStore {
+    fn read(&self, md5ext: &str) -> Result<Option<Vec<u8>>, YaseError> {
+        Ok(self.files.get(md5ext).cloned())
+    }
+
+    fn location(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// Assets downloaded one by one. The url has `{md5ext}` where the name goes.
+pub struct HttpStore {
+    url: String,
+}
+
+impl HttpStore {
+    pub fn new(url: impl Into<String>) -> HttpStore {
+        HttpStore { url: url.into() }
+    }
+
+    /// Where the Scratch website keeps them.
+    pub fn scratch() -> HttpStore {
+        HttpStore::new("https://assets.scratch.mit.edu/internalapi/asset/{md5ext}/get/")
+    }
+
+    /// Where the Scratch website keeps projects' project.json, by id instead
+    /// of md5ext. What comes from here isn't checked, since ids aren't md5s.
+    pub fn projects() -> HttpStore {
+        HttpStore::new("https://projects.scratch.mit.edu/{md5ext}")
+    }
+}
+
+impl AssetStore for HttpStore {
+    fn read(&self, md5ext: &str) -> Result<Option<Vec<u8>>, YaseError> {
+        let url = self.url.replace("{md5ext}", md5ext);
+        let network = |err: reqwest::Error| YaseError::Network(format!("{}: {}", url, err));
+        let response = reqwest::blocking::get(&url).map_err(network)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status().map_err(network)?;
+        Ok(Some(response.bytes().map_err(network)?.to_vec()))
+    }
+
+    fn location(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// Keeps what another store had in a directory, so it's only ever fetched
+/// once. The directory works as an offline mirror on its own, as a DirStore.
+pub struct CacheStore<S> {
+    dir: PathBuf,
+    inner: S,
+}
+
+impl<S: AssetStore> CacheStore<S> {
+    pub fn new(dir: impl Into<PathBuf>, inner: S) -> CacheStore<S> {
+        CacheStore {
+            dir: dir.into(),
+            inner,
+        }
+    }
+}
+
+impl<S: AssetStore> AssetStore for CacheStore<S> {
+    fn read(&self, md5ext: &str) -> Result<Option<Vec<u8>>, YaseError> {
+        let path = self.dir.join(md5ext);
+        if let Some(a) = read_file(&path)? {
+            return Ok(Some(a));
+        }
+        // only what's been checked goes in, since it's never looked at again.
+        let data = match self.inner.get(md5ext)? {
+            Some(a) => a,
+            None => return Ok(None),
+        };
+        let io = |err: io::Error| YaseError::Io(format!("{}: {}", path.display(), err));
+        fs::create_dir_all(&self.dir).map_err(io)?;
+        // written under another name first, so a half written file is never
+        // taken for the real thing.
+        let part = self.dir.join(format!("{}.part", md5ext));
+        fs::write(&part, &data).map_err(io)?;
+        fs::rename(&part, &path).map_err(io)?;
+        Ok(Some(data))
+    }
+
+    fn location(&self) -> String {
+        format!(
+            "{} (cache of {})",
+            self.dir.display(),
+            self.inner.location()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    fn md5ext(data: &[u8], format: &str) -> String {
+        format!("{:x}.{}", md5::compute(data), format)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("yase-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Serves files by name, for as many requests as it's told to, then
+    /// stops.
+    fn serve(files: HashMap<String, Vec<u8>>, requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/{{md5ext}}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                let path = line.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match files.get(path.trim_start_matches('/')) {
+                    Some(a) => ("200 OK", a.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn checks_md5() {
+        let dir = temp_dir("dir-store");
+        let good = md5ext(b"meow", "wav");
+        let bad = md5ext(b"woof", "wav");
+        fs::write(dir.join(&good), b"meow").unwrap();
+        fs::write(dir.join(&bad), b"meow").unwrap();
+
+        let store = DirStore::new(&dir);
+        assert_eq!(store.get(&good).unwrap(), Some(b"meow".to_vec()));
+        assert!(matches!(
+            store.get(&bad),
+            Err(YaseError::BadAsset { name, .. }) if name == bad
+        ));
+        assert_eq!(store.get(&md5ext(b"purr", "wav")).unwrap(), None);
+    }
+
+    #[test]
+    fn reads_zips() {
+        let name = md5ext(b"<svg/>", "svg");
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        zip.start_file("project.json", options).unwrap();
+        zip.write_all(b"{}").unwrap();
+        zip.start_file(&name, options).unwrap();
+        zip.write_all(b"<svg/>").unwrap();
+        let data = zip.finish().unwrap().into_inner();
+
+        let store = ZipStore::from_bytes("test.sb3", data).unwrap();
+        assert_eq!(store.project_json(), Some(&b"{}"[..]));
+        assert_eq!(store.get(&name).unwrap(), Some(b"<svg/>".to_vec()));
+        assert_eq!(store.get("project.json.png").unwrap(), None);
+    }
+
+    #[test]
+    fn caches_downloads() {
+        let name = md5ext(b"meow", "wav");
+        let missing = md5ext(b"purr", "wav");
+        // one for each fetch, the second time it comes from the cache.
+        let url = serve(HashMap::from([(name.clone(), b"meow".to_vec())]), 2);
+        let dir = temp_dir("cache-store");
+
+        let store = CacheStore::new(&dir, HttpStore::new(url));
+        assert_eq!(store.get(&name).unwrap(), Some(b"meow".to_vec()));
+        assert_eq!(store.get(&missing).unwrap(), None);
+        assert_eq!(store.get(&name).unwrap(), Some(b"meow".to_vec()));
+        assert_eq!(
+            DirStore::new(&dir).get(&name).unwrap(),
+            Some(b"meow".to_vec())
+        );
+        assert!(!dir.join(&missing).exists());
+    }
+}
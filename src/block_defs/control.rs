@@ -1,6 +1,11 @@
+use std::fmt::Display;
+
 use proc::block_derive;
 
-use crate::blocks::{Block, Value};
+use crate::{
+    blocks::{Block, Value},
+    from_fn_from_map,
+};
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -12,11 +17,14 @@ pub struct WaitSeconds {
 #[derive(Debug, Clone)]
 pub struct Repeat {
     pub(crate) units: Option<Value>,
+    pub(crate) substack: Option<Value>,
 }
 
 #[block_derive]
 #[derive(Debug, Clone)]
-pub struct Forever {}
+pub struct Forever {
+    pub(crate) substack: Option<Value>,
+}
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -43,11 +51,26 @@ pub struct WaitUntil {
 #[derive(Debug, Clone)]
 pub struct RepeatUntil {
     pub(crate) condition: Option<Value>,
+    pub(crate) substack: Option<Value>,
+}
+#[derive(Debug, Clone)]
+pub enum StopOption {
+    All,
+    ThisScript,
+    OtherScriptsInSprite,
 }
+from_fn_from_map!(StopOption, {
+    "all" => All,
+    "this script" => ThisScript,
+    "other scripts in sprite" => OtherScriptsInSprite,
+    "other scripts in stage" => OtherScriptsInSprite,
+});
 
 #[block_derive]
 #[derive(Debug, Clone)]
-pub struct StopAll {}
+pub struct StopAll {
+    pub(crate) option: Option<StopOption>,
+}
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -58,25 +81,38 @@ pub enum SpriteOption {
     Sprite(String),
 }
 impl SpriteOption {
-    pub fn from(val: Option<Value>) -> Option<SpriteOption> {
+    pub fn from(val: Option<Value>) -> Result<Option<SpriteOption>, String> {
         match val {
             Some(Value::String(a)) => match a.as_str() {
-                "_myself_" => Some(Self::Myself),
-                _ => Some(Self::Sprite(a)),
+                "_myself_" => Ok(Some(Self::Myself)),
+                _ => Ok(Some(Self::Sprite(a))),
             },
-            _ => {
-                #[cfg(debug_assertions)]
-                panic!("invalid sprite option: {:?}", val);
-                #[cfg(not(debug_assertions))]
-                None
-            }
+            // sprites named like numbers.
+            Some(Value::Number(a)) => Ok(Some(Self::Sprite(a.to_string()))),
+            None => Ok(None),
+            _ => Err(format!("invalid sprite option: {:?}", val)),
+        }
+    }
+}
+impl Display for SpriteOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Myself => f.write_str("_myself_"),
+            Self::Sprite(a) => f.write_str(a),
         }
     }
 }
 
+/// The option is a pointer to a CreateCloneOfMenu, unless someone dropped a reporter in there.
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct CreateCloneOf {
+    pub(crate) of: Option<Value>,
+}
+
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct CreateCloneOfMenu {
     pub(crate) of: Option<SpriteOption>,
 }
 
@@ -1,8 +1,7 @@
 use std::collections::HashMap;
 
 use proc::block_derive;
-use serde::{de::Visitor, Deserialize};
-use serde_derive::Deserialize;
+use serde::de::{self, Visitor};
 use serde_json::Value as SerdeValue;
 
 use crate::blocks::{Block, Value};
@@ -15,40 +14,164 @@ pub struct ProceduresDefinition {
 
 #[block_derive]
 #[derive(Debug, Clone)]
-
-pub struct ProceduresCall {}
+pub struct ProceduresCall {
+    pub(crate) signature: ProcedureSignature,
+    /// The inputs given to the call, in the same order as the signature's arguments.
+    pub(crate) arguments: Vec<Option<Value>>,
+}
 
 /* "mutation":{"tagName":"mutation","proccode":"perlin %n %n","argumentnames":"[\"x\",\"y\"]","argumentids":"[\"input0\",\"input1\"]","argumentdefaults":"[1,1]","warp":true,"children":[] */
 #[block_derive]
 #[derive(Debug, Clone)]
-
 pub struct ProceduresPrototype {
-    pub(crate) mutation: Mutation,
+    pub(crate) signature: ProcedureSignature,
 }
 
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct ProceduresDeclaration {}
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct Mutation {
-    #[serde(rename = "tagName")]
-    pub(crate) tag_name: Option<SerdeValue>,
-    pub(crate) proccode: Option<SerdeValue>,
-    #[serde(rename = "argumentnames")]
-    pub(crate) argument_names: Option<SerdeValue>,
-    #[serde(rename = "argumentids")]
-    pub(crate) argument_ids: Option<SerdeValue>,
-    #[serde(rename = "argumentdefaults")]
-    pub(crate) argument_defaults: Option<SerdeValue>,
+/// The reporters for a custom block's arguments, which only know the name of the argument.
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct ArgumentReporter {
+    pub(crate) name: String,
+    pub(crate) boolean: bool,
+}
+
+/// One piece of a proccode. "perlin %n %n" is a label followed by two number arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProccodeSegment {
+    Label(String),
+    /// %s, a string or number argument.
+    String,
+    /// %n, a number argument. Newer versions of Scratch only emit %s, but old projects still have these.
+    Number,
+    /// %b, a boolean argument.
+    Boolean,
+}
+
+impl ProccodeSegment {
+    pub fn is_argument(&self) -> bool {
+        !matches!(self, Self::Label(_))
+    }
+}
 
+/// The "mutation" of a custom block, which is what actually describes it.
+/// Both the prototype (the definition) and each call carry one, although calls
+/// leave out the argument names and defaults.
+#[derive(Debug, Clone, Default)]
+pub struct ProcedureSignature {
+    pub(crate) proccode: String,
+    pub(crate) segments: Vec<ProccodeSegment>,
+    pub(crate) argument_ids: Vec<String>,
+    pub(crate) argument_names: Vec<String>,
+    pub(crate) argument_defaults: Vec<Value>,
+    /// "run without screen refresh"
     pub(crate) warp: bool,
-    pub(crate) children: Option<SerdeValue>,
+}
+
+impl ProcedureSignature {
+    /// The first label of the block, which is what most people would call its name.
+    pub fn name(&self) -> &str {
+        self.segments
+            .iter()
+            .find_map(|f| match f {
+                ProccodeSegment::Label(a) => Some(a.as_str()),
+                _ => None,
+            })
+            .unwrap_or("")
+    }
+
+    pub fn proccode(&self) -> &str {
+        &self.proccode
+    }
+
+    pub fn segments(&self) -> &Vec<ProccodeSegment> {
+        &self.segments
+    }
+
+    /// The argument segments of the proccode, in order.
+    pub fn argument_types(&self) -> Vec<&ProccodeSegment> {
+        self.segments.iter().filter(|f| f.is_argument()).collect()
+    }
+
+    pub fn argument_ids(&self) -> &Vec<String> {
+        &self.argument_ids
+    }
+
+    pub fn argument_names(&self) -> &Vec<String> {
+        &self.argument_names
+    }
+
+    pub fn argument_defaults(&self) -> &Vec<Value> {
+        &self.argument_defaults
+    }
+
+    pub fn warp(&self) -> bool {
+        self.warp
+    }
+}
+
+/// Splits a proccode into its labels and arguments.
+pub fn parse_proccode(proccode: &str) -> Vec<ProccodeSegment> {
+    let mut segments = Vec::new();
+    let mut label = String::new();
+    let mut chars = proccode.chars().peekable();
+    while let Some(c) = chars.next() {
+        let arg = match (c, chars.peek()) {
+            ('%', Some('s')) => Some(ProccodeSegment::String),
+            ('%', Some('n')) => Some(ProccodeSegment::Number),
+            ('%', Some('b')) => Some(ProccodeSegment::Boolean),
+            _ => None,
+        };
+        match arg {
+            Some(a) => {
+                chars.next();
+                push_label(&mut segments, &mut label);
+                segments.push(a);
+            }
+            None => label.push(c),
+        }
+    }
+    push_label(&mut segments, &mut label);
+    segments
+}
+
+fn push_label(segments: &mut Vec<ProccodeSegment>, label: &mut String) {
+    let trimmed = label.trim();
+    if !trimmed.is_empty() {
+        segments.push(ProccodeSegment::Label(trimmed.to_string()));
+    }
+    label.clear();
+}
+
+/// The argument lists are stored as JSON strings inside of the JSON, i.e. "[\"x\",\"y\"]".
+fn json_list<E: de::Error>(val: Option<&SerdeValue>, what: &str) -> Result<Vec<SerdeValue>, E> {
+    match val {
+        Some(SerdeValue::String(a)) => serde_json::from_str(a)
+            .map_err(|err| de::Error::custom(format!("invalid {} {:?}: {}", what, a, err))),
+        Some(SerdeValue::Array(a)) => Ok(a.clone()),
+        Some(SerdeValue::Null) | None => Ok(Vec::new()),
+        Some(a) => Err(de::Error::custom(format!("invalid {}: {}", what, a))),
+    }
+}
+
+fn json_to_value(val: SerdeValue) -> Value {
+    match val {
+        SerdeValue::Number(a) => match a.as_f64() {
+            Some(a) => Value::Number(a),
+            None => Value::Null,
+        },
+        SerdeValue::String(a) => Value::String(a),
+        SerdeValue::Bool(a) => Value::String(a.to_string()),
+        _ => Value::Null,
+    }
 }
 
 pub struct MutationVisitor;
 impl<'de> Visitor<'de> for MutationVisitor {
-    type Value = Mutation;
+    type Value = ProcedureSignature;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         formatter.write_str("a mutation block")
@@ -59,31 +182,123 @@ impl<'de> Visitor<'de> for MutationVisitor {
         A: serde::de::MapAccess<'de>,
     {
         let mut hashmap: HashMap<String, SerdeValue> = HashMap::new();
-        #[allow(irrefutable_let_patterns)] // we handle breaking ourselves.
-        while let k = map.next_entry() {
-            match k {
-                Ok(a) => match a {
-                    Some(a) => {
-                        hashmap.insert(a.0, a.1);
-                    }
-                    None => {
-                        break;
-                    }
-                },
-                Err(err) => {
-                    return Err(err);
-                }
-            }
+        while let Some(a) = map.next_entry()? {
+            hashmap.insert(a.0, a.1);
         }
 
-        Ok(Mutation {
-            tag_name: hashmap.get("tagName").cloned(),
-            proccode: hashmap.get("proccode").cloned(),
-            argument_names: hashmap.get("argumentnames").cloned(),
-            argument_ids: hashmap.get("argumentids").cloned(),
-            argument_defaults: hashmap.get("argumentdefaults").cloned(),
-            warp: hashmap.get("warp").cloned().unwrap().as_bool().unwrap(),
-            children: hashmap.get("children").cloned(),
+        let proccode = match hashmap.get("proccode") {
+            Some(SerdeValue::String(a)) => a.clone(),
+            _ => return Err(de::Error::custom("mutation has no proccode")),
+        };
+
+        let argument_ids = json_list(hashmap.get("argumentids"), "argument ids")?
+            .into_iter()
+            .map(|f| match f {
+                SerdeValue::String(a) => a,
+                a => a.to_string(),
+            })
+            .collect();
+        let argument_names = json_list(hashmap.get("argumentnames"), "argument names")?
+            .into_iter()
+            .map(|f| match f {
+                SerdeValue::String(a) => a,
+                a => a.to_string(),
+            })
+            .collect();
+        let argument_defaults = json_list(hashmap.get("argumentdefaults"), "argument defaults")?
+            .into_iter()
+            .map(json_to_value)
+            .collect();
+
+        // older projects (and every call block) store this as a string.
+        let warp = match hashmap.get("warp") {
+            Some(SerdeValue::Bool(a)) => *a,
+            Some(SerdeValue::String(a)) => a == "true",
+            Some(SerdeValue::Null) | None => false,
+            Some(a) => return Err(de::Error::custom(format!("invalid warp value: {}", a))),
+        };
+
+        Ok(ProcedureSignature {
+            segments: parse_proccode(&proccode),
+            proccode,
+            argument_ids,
+            argument_names,
+            argument_defaults,
+            warp,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserializer;
+    use serde_json::json;
+    use ProccodeSegment::*;
+
+    fn mutation(val: SerdeValue) -> Result<ProcedureSignature, std::string::String> {
+        val.deserialize_map(MutationVisitor)
+            .map_err(|f| f.to_string())
+    }
+
+    #[test]
+    fn splits_proccodes() {
+        assert_eq!(
+            parse_proccode("move %s steps %n times if %b"),
+            [
+                Label("move".into()),
+                String,
+                Label("steps".into()),
+                Number,
+                Label("times if".into()),
+                Boolean,
+            ]
+        );
+        assert_eq!(parse_proccode("%n%b"), [Number, Boolean]);
+        // only %s, %n and %b are arguments.
+        assert_eq!(parse_proccode("100% %x"), [Label("100% %x".into())]);
+        assert_eq!(parse_proccode("  "), []);
+    }
+
+    #[test]
+    fn reads_mutations() {
+        let signature = mutation(json!({
+            "tagName": "mutation",
+            "proccode": "perlin %n %b",
+            "argumentids": "[\"input0\",\"input1\"]",
+            "argumentnames": "[\"x\",\"is \\\"on\\\"\"]",
+            "argumentdefaults": "[1,\"false\"]",
+            "warp": "true",
+        }))
+        .unwrap();
+        assert_eq!(signature.name(), "perlin");
+        assert_eq!(
+            signature.segments(),
+            &[Label("perlin".into()), Number, Boolean]
+        );
+        assert_eq!(signature.argument_ids(), &["input0", "input1"]);
+        assert_eq!(signature.argument_names(), &["x", "is \"on\""]);
+        assert_eq!(
+            signature.argument_defaults(),
+            &[Value::Number(1.0), Value::String("false".into())]
+        );
+        assert!(signature.warp());
+
+        // calls leave the names and defaults out.
+        let call = mutation(json!({"proccode": "perlin %n %b", "warp": "false"})).unwrap();
+        assert!(!call.warp());
+        assert!(call.argument_names().is_empty());
+        assert!(mutation(json!({"proccode": "a", "warp": true}))
+            .unwrap()
+            .warp());
+
+        assert_eq!(mutation(json!({})).unwrap_err(), "mutation has no proccode");
+        assert_eq!(
+            mutation(json!({"proccode": "a", "warp": 1})).unwrap_err(),
+            "invalid warp value: 1"
+        );
+        assert!(mutation(json!({"proccode": "a", "argumentnames": "[x"}))
+            .unwrap_err()
+            .starts_with("invalid argument names \"[x\": "));
+    }
+}
@@ -1,7 +1,6 @@
 use proc::block_derive;
 
-use crate::blocks::Value;
-use crate::Block;
+use crate::blocks::{Block, Value};
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use proc::block_derive;
 
 use crate::{
@@ -23,7 +25,8 @@ impl Key {
     pub fn from(val: Option<Value>) -> Option<Key> {
         match val {
             Some(a) => match a {
-                Value::Number(_) => todo!(),
+                // number keys end up here, since the value looked like a number.
+                Value::Number(a) => a.to_string().chars().next().map(Self::Alphanumerical),
                 Value::String(a) => match a.as_str() {
                     "up arrow" => Some(Self::UpArrow),
                     "down arrow" => Some(Self::DownArrow),
@@ -31,14 +34,27 @@ impl Key {
                     "left arrow" => Some(Self::LeftArrow),
                     "space" => Some(Self::Space),
                     "any" => Some(Self::Any),
-                    _ => Some(Self::Alphanumerical(a.chars().next().unwrap())),
+                    _ => a.chars().next().map(Self::Alphanumerical),
                 },
-                Value::Null => todo!(),
+                _ => None,
             },
             _ => None,
         }
     }
 }
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LeftArrow => f.write_str("left arrow"),
+            Self::UpArrow => f.write_str("up arrow"),
+            Self::RightArrow => f.write_str("right arrow"),
+            Self::DownArrow => f.write_str("down arrow"),
+            Self::Space => f.write_str("space"),
+            Self::Any => f.write_str("any"),
+            Self::Alphanumerical(a) => write!(f, "{}", a),
+        }
+    }
+}
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -65,8 +81,8 @@ pub enum EventOption {
     Timer,
 }
 from_fn_from_map!(EventOption, {
-    "loudness" => Loudness,
-    "timer" => Timer,
+    "LOUDNESS" => Loudness,
+    "TIMER" => Timer,
 });
 
 #[block_derive]
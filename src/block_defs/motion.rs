@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use proc::block_derive;
 
 use crate::{
@@ -26,15 +28,41 @@ pub struct RotateRight {
 pub enum MovementOption {
     RandomPosition,
     MousePointer,
+    Sprite(String),
+}
+impl MovementOption {
+    pub fn from(val: Option<Value>) -> Option<MovementOption> {
+        match val {
+            Some(Value::String(a)) => match a.as_str() {
+                "_random_" => Some(Self::RandomPosition),
+                "_mouse_" => Some(Self::MousePointer),
+                _ => Some(Self::Sprite(a)),
+            },
+            _ => None,
+        }
+    }
+}
+impl Display for MovementOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RandomPosition => f.write_str("_random_"),
+            Self::MousePointer => f.write_str("_mouse_"),
+            Self::Sprite(a) => f.write_str(a),
+        }
+    }
+}
+
+/// The dropdown that goto, glide and point towards blocks hold.
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct MovementMenu {
+    pub(crate) option: Option<MovementOption>,
 }
-from_fn_from_map!(MovementOption, {
-    "_random_" => RandomPosition,
-    "_mouse_" => MousePointer,
-});
 #[derive(Debug, Clone)]
 pub enum Goto {
     Pos(GotoPos),
     Option(GotoOption),
+    Menu(MovementMenu),
 }
 
 #[block_derive]
@@ -44,20 +72,23 @@ pub struct GotoPos {
     pub(crate) y: Option<Value>,
 }
 
+/// The option is a pointer to a MovementMenu, unless someone dropped a reporter in there.
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct GotoOption {
-    pub(crate) option: Option<MovementOption>,
+    pub(crate) option: Option<Value>,
 }
 #[derive(Debug, Clone)]
 pub enum Glide {
     Pos(GlidePos),
     Option(GlideOption),
+    Menu(MovementMenu),
 }
 
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct GlidePos {
+    pub(crate) secs: Option<Value>,
     pub(crate) x: Option<Value>,
     pub(crate) y: Option<Value>,
 }
@@ -65,13 +96,14 @@ pub struct GlidePos {
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct GlideOption {
-    pub(crate) option: Option<MovementOption>,
+    pub(crate) secs: Option<Value>,
+    pub(crate) option: Option<Value>,
 }
 
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct PointTowardsMenu {
-    pub(crate) option: Option<Point>,
+    pub(crate) option: Option<MovementOption>,
 }
 #[derive(Debug, Clone)]
 pub enum Point {
@@ -82,8 +114,7 @@ pub enum Point {
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct PointDirection {
-    pub(crate) x: Option<Value>,
-    pub(crate) y: Option<Value>,
+    pub(crate) direction: Option<Value>,
 }
 
 #[block_derive]
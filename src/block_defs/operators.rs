@@ -1,6 +1,9 @@
 use proc::block_derive;
 
-use crate::blocks::{Block, Value};
+use crate::{
+    blocks::{Block, Value},
+    from_fn_from_map,
+};
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -118,8 +121,44 @@ pub struct Round {
     pub(crate) a: Option<Value>,
 }
 
-#[block_derive]
-#[derive(Debug, Clone)]
-pub struct Absolute {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathOperator {
+    Abs,
+    Floor,
+    Ceiling,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Ln,
+    Log,
+    EPow,
+    TenPow,
+}
+from_fn_from_map!(MathOperator, {
+    "abs" => Abs,
+    "floor" => Floor,
+    "ceiling" => Ceiling,
+    "sqrt" => Sqrt,
+    "sin" => Sin,
+    "cos" => Cos,
+    "tan" => Tan,
+    "asin" => Asin,
+    "acos" => Acos,
+    "atan" => Atan,
+    "ln" => Ln,
+    "log" => Log,
+    "e ^" => EPow,
+    "10 ^" => TenPow,
+});
+
+/// "abs of", "floor of", "sqrt of" and friends.
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct MathOp {
+    pub(crate) operator: Option<MathOperator>,
     pub(crate) a: Option<Value>,
 }
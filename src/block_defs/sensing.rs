@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use proc::block_derive;
 
 use crate::{
@@ -25,6 +27,17 @@ impl SensingOption {
         }
     }
 }
+impl Display for SensingOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MousePointer => f.write_str("_mouse_"),
+            Self::Edge => f.write_str("_edge_"),
+            Self::Sprite(Value::String(a)) => f.write_str(a),
+            Self::Sprite(Value::Number(a)) => write!(f, "{}", a),
+            Self::Sprite(_) => Ok(()),
+        }
+    }
+}
 
 #[block_derive]
 #[derive(Debug, Clone)]
@@ -54,9 +67,66 @@ pub struct ColorTouchingColor {
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct DistanceTo {
+    pub(crate) to: Option<Value>,
+}
+
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct DistanceToMenu {
     pub(crate) to: Option<SensingOption>,
 }
 
+/// What `of` asks about: the stage or a sprite.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OfObject {
+    Stage,
+    Sprite(String),
+}
+impl OfObject {
+    pub fn from(val: Option<Value>) -> Result<Option<OfObject>, String> {
+        match val {
+            Some(Value::String(a)) => match a.as_str() {
+                "_stage_" => Ok(Some(Self::Stage)),
+                _ => Ok(Some(Self::Sprite(a))),
+            },
+            // sprites named like numbers.
+            Some(Value::Number(a)) => Ok(Some(Self::Sprite(a.to_string()))),
+            None => Ok(None),
+            _ => Err(format!("invalid sprite option: {:?}", val)),
+        }
+    }
+}
+impl Display for OfObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stage => f.write_str("_stage_"),
+            Self::Sprite(a) => f.write_str(a),
+        }
+    }
+}
+
+/// A property of a sprite or the stage, such as its x position, its backdrop
+/// number, or one of its own variables by name. The object is a pointer to an
+/// OfObjectMenu, unless someone dropped a reporter in there.
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct Of {
+    pub(crate) property: Option<String>,
+    pub(crate) object: Option<Value>,
+}
+
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct OfObjectMenu {
+    pub(crate) object: Option<OfObject>,
+}
+
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct AskAndWait {
+    pub(crate) question: Option<Value>,
+}
+
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct Answer {}
@@ -64,6 +134,12 @@ pub struct Answer {}
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct KeyPressed {
+    pub(crate) key: Option<Value>,
+}
+
+#[block_derive]
+#[derive(Debug, Clone)]
+pub struct KeyOptions {
     pub(crate) key: Option<Key>,
 }
 
@@ -129,13 +205,13 @@ pub enum CurrentTimeOption {
     Second,
 }
 from_fn_from_map!(CurrentTimeOption, {
-    "year" => Year,
-    "month" => Month,
-    "date" => Date,
-    "day of week" => DayOfWeek,
-    "hour" => Hour,
-    "minute" => Minute,
-    "second" => Second,
+    "YEAR" => Year,
+    "MONTH" => Month,
+    "DATE" => Date,
+    "DAYOFWEEK" => DayOfWeek,
+    "HOUR" => Hour,
+    "MINUTE" => Minute,
+    "SECOND" => Second,
 });
 
 #[block_derive]
@@ -1,13 +1,13 @@
-/// simple file containing a bunch of pub constants.
-/// there's so many that having these in blocks.rs would clog it up.
+//! simple file containing a bunch of pub constants.
+//! there's so many that having these in blocks.rs would clog it up.
 
 pub const MOTION_MOVE: &str = "motion_movesteps";
 pub const MOTION_GOTO_XY: &str = "motion_gotoxy";
 pub const MOTION_GOTO: &str = "motion_goto";
 pub const MOTION_GOTO_MENU: &str = "motion_goto_menu";
 
-pub const MOTION_TURN_LEFT: &str = "motion_turnright";
-pub const MOTION_TURN_RIGHT: &str = "motion_turnleft";
+pub const MOTION_TURN_LEFT: &str = "motion_turnleft";
+pub const MOTION_TURN_RIGHT: &str = "motion_turnright";
 pub const MOTION_POINT_MENU: &str = "motion_pointtowards_menu";
 pub const MOTION_POINT_DIRECTION: &str = "motion_pointindirection";
 pub const MOTION_POINT_TOWARDS: &str = "motion_pointtowards";
@@ -91,6 +91,7 @@ pub const CONTROL_IF: &str = "control_if";
 pub const CONTROL_IF_ELSE: &str = "control_if_else";
 pub const CONTROL_STOP: &str = "control_stop";
 pub const CONTROL_CREATE_CLONE_OF: &str = "control_create_clone_of";
+pub const CONTROL_CREATE_CLONE_OF_MENU: &str = "control_create_clone_of_menu";
 pub const CONTROL_DELETE_THIS_CLONE: &str = "control_delete_this_clone";
 pub const CONTROL_GET_COUNTER: &str = "control_get_counter";
 pub const CONTROL_INCREMENT_COUNTER: &str = "control_incr_counter";
@@ -103,14 +104,17 @@ pub const SENSING_TOUCHING_OBJECT: &str = "sensing_touchingobject";
 pub const SENSING_TOUCHING_COLOR: &str = "sensing_touchingcolor";
 pub const SENSING_COLOR_IS_TOUCHING_COLOR: &str = "sensing_coloristouchingcolor";
 pub const SENSING_DISTANCE_TO: &str = "sensing_distanceto";
+pub const SENSING_DISTANCE_TO_MENU: &str = "sensing_distancetomenu";
 pub const SENSING_TIMER: &str = "sensing_timer";
 pub const SENSING_RESET_TIMER: &str = "sensing_resettimer";
 pub const SENSING_OF: &str = "sensing_of";
+pub const SENSING_OF_OBJECT_MENU: &str = "sensing_of_object_menu";
 pub const SENSING_MOUSE_X: &str = "sensing_mousex";
 pub const SENSING_MOUSE_Y: &str = "sensing_mousey";
 pub const SENSING_SET_DRAG_MODE: &str = "sensing_setdragmode";
 pub const SENSING_MOUSE_DOWN: &str = "sensing_mousedown";
 pub const SENSING_KEY_PRESSED: &str = "sensing_keypressed";
+pub const SENSING_KEY_OPTIONS: &str = "sensing_keyoptions";
 pub const SENSING_CURRENT: &str = "sensing_current";
 pub const SENSING_DAYS_SINCE_2000: &str = "sensing_dayssince2000";
 pub const SENSING_LOUDNESS: &str = "sensing_loudness";
@@ -156,8 +160,12 @@ pub const DATA_LENGTH_OF_LIST: &str = "data_lengthoflist";
 pub const DATA_LIST_CONTAINS_ITEM: &str = "data_listcontainsitem";
 pub const DATA_HIDE_LIST: &str = "data_hidelist";
 pub const DATA_SHOW_LIST: &str = "data_showlist";
+pub const DATA_LIST_INDEX_ALL: &str = "data_listindexall";
+pub const DATA_LIST_INDEX_RANDOM: &str = "data_listindexrandom";
 
 pub const PROCEDURES_DEFINITION: &str = "procedures_definition";
 pub const PROCEDURES_CALL: &str = "procedures_call";
 pub const PROCEDURES_PROTOTYPE: &str = "procedures_prototype";
 pub const PROCEDURES_DECLARATION: &str = "procedures_declaration";
+pub const ARGUMENT_REPORTER_STRING_NUMBER: &str = "argument_reporter_string_number";
+pub const ARGUMENT_REPORTER_BOOLEAN: &str = "argument_reporter_boolean";
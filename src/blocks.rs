@@ -13,20 +13,21 @@
 use lazy_static::lazy_static;
 use proc::block_derive;
 use regex::Regex;
-use serde::de::{value::StringDeserializer, Visitor};
+use serde::de::Visitor;
 #[allow(dead_code)]
 use serde::{de, Deserialize, Deserializer};
 use serde_json::{Map, Value as SerdeValue};
 use std::{
+    cell::RefCell,
     collections::HashMap,
-    fmt::{Debug, Formatter, Pointer},
+    fmt::{Debug, Formatter},
 };
 
 pub use crate::block_defs::{
     control::*, custom::*, data::*, events::*, look::*, motion::*, operators::*, sensing::*,
     sound::*,
 };
-use crate::block_names::*;
+use crate::{block_names::*, error::BlockError};
 use std::fmt::Display;
 
 // Any block that has a prev/next field.
@@ -38,22 +39,45 @@ pub trait Block {
 
 impl Display for dyn Block {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        struct Debugged<'a>(&'a dyn Block);
+        impl Debug for Debugged<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                self.0.debug_fmt(f)
+            }
+        }
         // :3
-        let mut lol: String = String::new();
-        let mut f2 = Formatter::new(&mut lol);
-        self.debug_fmt(&mut f2)?;
-
-        lol = format!("WHAT{}", lol);
+        let lol = format!("WHAT{:?}", Debugged(self));
 
         f.write_str(lol.as_str())
     }
 }
 
-/// Either a number or a String, the latter signifying a pointer to another block.
+/// What as_block gives for the few variants that don't have a block.
+#[derive(Debug)]
+struct Unlinked;
+
+impl Block for Unlinked {
+    fn prev(&self) -> Option<String> {
+        None
+    }
+    fn next(&self) -> Option<String> {
+        None
+    }
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Either a number or a String, the latter signifying a pointer to another block
+/// if there's a block with that id, or a literal otherwise.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Number(f64),
     String(String),
+    /// A variable reporter dropped directly into an input, by name.
+    Variable(String),
+    /// Same as above but for lists.
+    List(String),
     Null,
 }
 
@@ -131,6 +155,7 @@ pub enum BlockType {
     StopAll(StopAll),
     WhenIStartAsAClone(WhenIStartAsAClone),
     CreateCloneOf(CreateCloneOf),
+    CreateCloneOfMenu(CreateCloneOfMenu),
     DeleteClone(DeleteClone),
     // Sensing blocks
     Touching(Touching),
@@ -138,8 +163,13 @@ pub enum BlockType {
     TouchingColor(TouchingColor),
     ColorTouchingColor(ColorTouchingColor),
     DistanceTo(DistanceTo),
+    DistanceToMenu(DistanceToMenu),
+    Of(Of),
+    OfObjectMenu(OfObjectMenu),
+    AskAndWait(AskAndWait),
     Answer(Answer),
     KeyPressed(KeyPressed),
+    KeyOptions(KeyOptions),
     MouseDown(MouseDown),
     MouseX(MouseX),
     MouseY(MouseY),
@@ -170,7 +200,7 @@ pub enum BlockType {
     Contains(Contains),
     Modulo(Modulo),
     Round(Round),
-    Absolute(Absolute),
+    MathOp(MathOp),
     SoundEffectsMenu(SoundEffectsMenu),
     SoundSoundsMenu(SoundSoundsMenu),
     PointTowardsMenu(PointTowardsMenu),
@@ -198,47 +228,541 @@ pub enum BlockType {
     ProceduresDeclaration(ProceduresDeclaration),
     ProceduresDefinition(ProceduresDefinition),
     ProceduresPrototype(ProceduresPrototype),
+    ArgumentReporter(ArgumentReporter),
 
     /// some opcodes are straight up unused or redundant and should be labelled as such.
     UnusedOpcode(UnusedOpcode),
-    /// some aren't implemented, but in release mode we need to ignore them.
+    /// stands in for blocks that couldn't be read, when loading leniently.
     InvalidOpcode(InvalidOpcode),
     /// some aren't connected to anything.
     Stray,
 }
 
-lazy_static! {
-    static ref DUP_REGEX: Regex = Regex::new("(.*?)\\((.*?) \\{(.*?)\\}\\)").unwrap();
-    static ref SOME_REGEX: Regex = Regex::new("(Some|String)\\((.*?)\\)").unwrap();
-    static ref NUM_REGEX: Regex = Regex::new("Number\\((.*?)\\)").unwrap();
-    static ref PREV_REGEX: Regex = Regex::new("prev: (.*?)(,|\\s})").unwrap();
-    static ref NEXT_REGEX: Regex = Regex::new("next: (.*?)(,|\\s})").unwrap();
+impl BlockType {
+    /// The block behind this variant, for getting at its prev/next.
+    /// Menus without any block data (and strays) are never linked to
+    /// anything, so they get a block with no prev or next.
+    pub fn as_block(&self) -> &dyn Block {
+        match self {
+            BlockType::Move(a) => a,
+            BlockType::RotateLeft(a) => a,
+            BlockType::RotateRight(a) => a,
+            BlockType::Goto(Goto::Pos(a)) => a,
+            BlockType::Goto(Goto::Option(a)) => a,
+            BlockType::Goto(Goto::Menu(a)) => a,
+            BlockType::Glide(Glide::Pos(a)) => a,
+            BlockType::Glide(Glide::Option(a)) => a,
+            BlockType::Glide(Glide::Menu(a)) => a,
+            BlockType::Point(Point::Direction(a)) => a,
+            BlockType::Point(Point::Towards(a)) => a,
+            BlockType::ChangeX(a) => a,
+            BlockType::SetX(a) => a,
+            BlockType::ChangeY(a) => a,
+            BlockType::SetY(a) => a,
+            BlockType::IfOnEdgeBounce(a) => a,
+            BlockType::SetRotationStyle(a) => a,
+            BlockType::XPosition(a) => a,
+            BlockType::YPosition(a) => a,
+            BlockType::Direction(a) => a,
+            BlockType::Say(a) => a,
+            BlockType::SayForever(a) => a,
+            BlockType::Think(a) => a,
+            BlockType::ThinkForever(a) => a,
+            BlockType::SwitchCostume(a) => a,
+            BlockType::NextCostume(a) => a,
+            BlockType::SwitchBackdrop(a) => a,
+            BlockType::SwitchBackdropAndWait(a) => a,
+            BlockType::NextBackdrop(a) => a,
+            BlockType::ChangeSize(a) => a,
+            BlockType::SetSize(a) => a,
+            BlockType::ClearGraphicEffects(a) => a,
+            BlockType::ShowSprite(a) => a,
+            BlockType::HideSprite(a) => a,
+            BlockType::HideAllSprites(a) => a,
+            BlockType::GotoLayer(a) => a,
+            BlockType::ChangeLayer(a) => a,
+            BlockType::Costume(Costume::ByNumber(a)) => a,
+            BlockType::Costume(Costume::ByName(a)) => a,
+            BlockType::Costume(Costume::WithName(_)) => &Unlinked,
+            BlockType::Backdrop(Backdrop::ByNumber(a)) => a,
+            BlockType::Backdrop(Backdrop::ByName(a)) => a,
+            BlockType::Backdrop(Backdrop::WithName(_)) => &Unlinked,
+            BlockType::Size(a) => a,
+            BlockType::PlaySound(a) => a,
+            BlockType::PlaySoundUntilDone(a) => a,
+            BlockType::StartSound(a) => a,
+            BlockType::StopAllSounds(a) => a,
+            BlockType::ChangeEffectBy(a) => a,
+            BlockType::SetEffectTo(a) => a,
+            BlockType::ClearSoundEffects(a) => a,
+            BlockType::ChangeVolumeBy(a) => a,
+            BlockType::SetVolumeTo(a) => a,
+            BlockType::Volume(a) => a,
+            BlockType::WhenGreenFlagClicked(a) => a,
+            BlockType::WhenKeyPressed(a) => a,
+            BlockType::WhenSpriteClicked(a) => a,
+            BlockType::WhenStageClicked(a) => a,
+            BlockType::WhenBackdropSwitchesTo(a) => a,
+            BlockType::WhenOptionGreaterThen(a) => a,
+            BlockType::WhenIRecieveBroadcast(a) => a,
+            BlockType::Broadcast(a) => a,
+            BlockType::BroadcastAndWait(a) => a,
+            BlockType::WaitSeconds(a) => a,
+            BlockType::Repeat(a) => a,
+            BlockType::Forever(a) => a,
+            BlockType::IfThen(a) => a,
+            BlockType::IfThenElse(a) => a,
+            BlockType::WaitUntil(a) => a,
+            BlockType::RepeatUntil(a) => a,
+            BlockType::StopAll(a) => a,
+            BlockType::WhenIStartAsAClone(a) => a,
+            BlockType::CreateCloneOf(a) => a,
+            BlockType::CreateCloneOfMenu(a) => a,
+            BlockType::DeleteClone(a) => a,
+            BlockType::Touching(a) => a,
+            BlockType::TouchingMenu(a) => a,
+            BlockType::TouchingColor(a) => a,
+            BlockType::ColorTouchingColor(a) => a,
+            BlockType::DistanceTo(a) => a,
+            BlockType::DistanceToMenu(a) => a,
+            BlockType::Of(a) => a,
+            BlockType::OfObjectMenu(a) => a,
+            BlockType::AskAndWait(a) => a,
+            BlockType::Answer(a) => a,
+            BlockType::KeyPressed(a) => a,
+            BlockType::KeyOptions(a) => a,
+            BlockType::MouseDown(a) => a,
+            BlockType::MouseX(a) => a,
+            BlockType::MouseY(a) => a,
+            BlockType::DraggableOption(_) => &Unlinked,
+            BlockType::SetDragMode(a) => a,
+            BlockType::Loudness(a) => a,
+            BlockType::Timer(a) => a,
+            BlockType::ResetTimer(a) => a,
+            BlockType::BackdropOf(a) => a,
+            BlockType::CurrentTime(a) => a,
+            BlockType::DaysSince2000(a) => a,
+            BlockType::Username(a) => a,
+            BlockType::Add(a) => a,
+            BlockType::Sub(a) => a,
+            BlockType::Mul(a) => a,
+            BlockType::Divide(a) => a,
+            BlockType::PickRandom(a) => a,
+            BlockType::GreaterThen(a) => a,
+            BlockType::LesserThen(a) => a,
+            BlockType::EqualTo(a) => a,
+            BlockType::And(a) => a,
+            BlockType::Or(a) => a,
+            BlockType::Not(a) => a,
+            BlockType::Join(a) => a,
+            BlockType::LetterOf(a) => a,
+            BlockType::LengthOf(a) => a,
+            BlockType::Contains(a) => a,
+            BlockType::Modulo(a) => a,
+            BlockType::Round(a) => a,
+            BlockType::MathOp(a) => a,
+            BlockType::SoundEffectsMenu(a) => a,
+            BlockType::SoundSoundsMenu(a) => a,
+            BlockType::PointTowardsMenu(a) => a,
+            BlockType::DataGetVariable(a) => a,
+            BlockType::DataSetVariableTo(a) => a,
+            BlockType::DataChangeVariableBy(a) => a,
+            BlockType::DataShowVariable(a) => a,
+            BlockType::DataHideVariable(a) => a,
+            BlockType::DataListContents(a) => a,
+            BlockType::DataListIndexAll(a) => a,
+            BlockType::DataListIndexAllRandom(a) => a,
+            BlockType::DataAddToList(a) => a,
+            BlockType::DataDeleteOfList(a) => a,
+            BlockType::DataDeleteAllOfList(a) => a,
+            BlockType::DataInsertAtList(a) => a,
+            BlockType::DataReplaceItemOfList(a) => a,
+            BlockType::DataItemOfList(a) => a,
+            BlockType::DataLengthOfList(a) => a,
+            BlockType::DataListContainsItem(a) => a,
+            BlockType::ShowList(a) => a,
+            BlockType::HideList(a) => a,
+            BlockType::ProceduresCall(a) => a,
+            BlockType::ProceduresDeclaration(a) => a,
+            BlockType::ProceduresDefinition(a) => a,
+            BlockType::ProceduresPrototype(a) => a,
+            BlockType::ArgumentReporter(a) => a,
+            BlockType::UnusedOpcode(a) => a,
+            BlockType::InvalidOpcode(a) => a,
+            BlockType::Stray => &Unlinked,
+        }
+    }
+
+    /// The id of the block after this one, if there is one.
+    pub fn next(&self) -> Option<String> {
+        self.as_block().next()
+    }
+
+    /// Every input that's set, by the name it has in project.json. Substacks
+    /// are inputs too. Fields aren't, and neither is a definition's
+    /// prototype, which is a part of the definition rather than something in it.
+    pub fn inputs(&self) -> Vec<(&str, &Value)> {
+        let inputs: Vec<(&str, Option<&Value>)> = match self {
+            BlockType::Move(a) => vec![("STEPS", a.steps.as_ref())],
+            BlockType::RotateLeft(a) => vec![("DEGREES", a.degrees.as_ref())],
+            BlockType::RotateRight(a) => vec![("DEGREES", a.degrees.as_ref())],
+            BlockType::Goto(Goto::Pos(a)) => vec![("X", a.x.as_ref()), ("Y", a.y.as_ref())],
+            BlockType::Goto(Goto::Option(a)) => vec![("TO", a.option.as_ref())],
+            BlockType::Glide(Glide::Pos(a)) => vec![
+                ("SECS", a.secs.as_ref()),
+                ("X", a.x.as_ref()),
+                ("Y", a.y.as_ref()),
+            ],
+            BlockType::Glide(Glide::Option(a)) => {
+                vec![("SECS", a.secs.as_ref()), ("TO", a.option.as_ref())]
+            }
+            BlockType::Point(Point::Direction(a)) => vec![("DIRECTION", a.direction.as_ref())],
+            BlockType::Point(Point::Towards(a)) => vec![("TOWARDS", a.option.as_ref())],
+            BlockType::ChangeX(a) => vec![("DX", a.x.as_ref())],
+            BlockType::SetX(a) => vec![("X", a.x.as_ref())],
+            BlockType::ChangeY(a) => vec![("DY", a.y.as_ref())],
+            BlockType::SetY(a) => vec![("Y", a.y.as_ref())],
+            BlockType::Say(a) => {
+                vec![("MESSAGE", a.message.as_ref()), ("SECS", a.secs.as_ref())]
+            }
+            BlockType::Think(a) => {
+                vec![("MESSAGE", a.message.as_ref()), ("SECS", a.secs.as_ref())]
+            }
+            BlockType::SayForever(a) => {
+                vec![("MESSAGE", a.message.as_ref())]
+            }
+            BlockType::ThinkForever(a) => {
+                vec![("MESSAGE", a.message.as_ref())]
+            }
+            BlockType::SwitchCostume(a) => vec![("COSTUME", a.costume.as_ref())],
+            BlockType::SwitchBackdrop(a) => vec![("BACKDROP", a.backdrop.as_ref())],
+            BlockType::SwitchBackdropAndWait(a) => vec![("BACKDROP", a.backdrop.as_ref())],
+            BlockType::ChangeSize(a) => vec![("CHANGE", a.units.as_ref())],
+            BlockType::SetSize(a) => vec![("SIZE", a.percentage.as_ref())],
+            BlockType::ChangeLayer(a) => vec![("NUM", a.by.as_ref())],
+            BlockType::PlaySound(a) => vec![("SOUND_MENU", a.sound.as_ref())],
+            BlockType::PlaySoundUntilDone(a) => vec![("SOUND_MENU", a.sound.as_ref())],
+            BlockType::ChangeEffectBy(a) => {
+                let name = match crate::serialize::is_sound_effect(&a.effect) {
+                    true => "VALUE",
+                    false => "CHANGE",
+                };
+                vec![(name, a.units.as_ref())]
+            }
+            BlockType::SetEffectTo(a) => vec![("VALUE", a.percentage.as_ref())],
+            BlockType::ChangeVolumeBy(a) => vec![("VOLUME", a.units.as_ref())],
+            BlockType::SetVolumeTo(a) => vec![("VOLUME", a.percentage.as_ref())],
+            BlockType::WhenOptionGreaterThen(a) => vec![("VALUE", a.by.as_ref())],
+            BlockType::Broadcast(a) => vec![("BROADCAST_INPUT", a.broadcast.as_ref())],
+            BlockType::BroadcastAndWait(a) => vec![("BROADCAST_INPUT", a.broadcast.as_ref())],
+            BlockType::WaitSeconds(a) => vec![("DURATION", a.seconds.as_ref())],
+            BlockType::Repeat(a) => {
+                vec![
+                    ("TIMES", a.units.as_ref()),
+                    ("SUBSTACK", a.substack.as_ref()),
+                ]
+            }
+            BlockType::Forever(a) => vec![("SUBSTACK", a.substack.as_ref())],
+            BlockType::IfThen(a) => vec![
+                ("CONDITION", a.condition.as_ref()),
+                ("SUBSTACK", a.then.as_ref()),
+            ],
+            BlockType::IfThenElse(a) => vec![
+                ("CONDITION", a.condition.as_ref()),
+                ("SUBSTACK", a.then.as_ref()),
+                ("SUBSTACK2", a.otherwise.as_ref()),
+            ],
+            BlockType::WaitUntil(a) => vec![("CONDITION", a.condition.as_ref())],
+            BlockType::RepeatUntil(a) => vec![
+                ("CONDITION", a.condition.as_ref()),
+                ("SUBSTACK", a.substack.as_ref()),
+            ],
+            BlockType::CreateCloneOf(a) => vec![("CLONE_OPTION", a.of.as_ref())],
+            BlockType::Touching(a) => vec![("TOUCHINGOBJECTMENU", a.touching.as_ref())],
+            BlockType::TouchingColor(a) => vec![("COLOR", a.color.as_ref())],
+            BlockType::ColorTouchingColor(a) => {
+                vec![("COLOR", a.color1.as_ref()), ("COLOR2", a.color2.as_ref())]
+            }
+            BlockType::DistanceTo(a) => vec![("DISTANCETOMENU", a.to.as_ref())],
+            BlockType::Of(a) => vec![("OBJECT", a.object.as_ref())],
+            BlockType::AskAndWait(a) => vec![("QUESTION", a.question.as_ref())],
+            BlockType::KeyPressed(a) => vec![("KEY_OPTION", a.key.as_ref())],
+            BlockType::Add(a) => {
+                vec![("NUM1", a.a.as_ref()), ("NUM2", a.b.as_ref())]
+            }
+            BlockType::Sub(a) => {
+                vec![("NUM1", a.a.as_ref()), ("NUM2", a.b.as_ref())]
+            }
+            BlockType::Mul(a) => {
+                vec![("NUM1", a.a.as_ref()), ("NUM2", a.b.as_ref())]
+            }
+            BlockType::Divide(a) => {
+                vec![("NUM1", a.a.as_ref()), ("NUM2", a.b.as_ref())]
+            }
+            BlockType::Modulo(a) => vec![("NUM1", a.a.as_ref()), ("NUM2", a.b.as_ref())],
+            BlockType::PickRandom(a) => vec![("FROM", a.min.as_ref()), ("TO", a.max.as_ref())],
+            BlockType::GreaterThen(a) => {
+                vec![("OPERAND1", a.a.as_ref()), ("OPERAND2", a.b.as_ref())]
+            }
+            BlockType::LesserThen(a) => {
+                vec![("OPERAND1", a.a.as_ref()), ("OPERAND2", a.b.as_ref())]
+            }
+            BlockType::EqualTo(a) => {
+                vec![("OPERAND1", a.a.as_ref()), ("OPERAND2", a.b.as_ref())]
+            }
+            BlockType::And(a) => {
+                vec![("OPERAND1", a.a.as_ref()), ("OPERAND2", a.b.as_ref())]
+            }
+            BlockType::Or(a) => {
+                vec![("OPERAND1", a.a.as_ref()), ("OPERAND2", a.b.as_ref())]
+            }
+            BlockType::Not(a) => vec![("OPERAND", a.a.as_ref())],
+            BlockType::Join(a) => {
+                vec![("STRING1", a.a.as_ref()), ("STRING2", a.b.as_ref())]
+            }
+            BlockType::Contains(a) => {
+                vec![("STRING1", a.a.as_ref()), ("STRING2", a.b.as_ref())]
+            }
+            BlockType::LetterOf(a) => {
+                vec![("LETTER", a.index.as_ref()), ("STRING", a.a.as_ref())]
+            }
+            BlockType::LengthOf(a) => vec![("STRING", a.a.as_ref())],
+            BlockType::Round(a) => vec![("NUM", a.a.as_ref())],
+            BlockType::MathOp(a) => vec![("NUM", a.a.as_ref())],
+            BlockType::DataSetVariableTo(a) => vec![("VALUE", Some(&a.value))],
+            BlockType::DataChangeVariableBy(a) => vec![("VALUE", Some(&a.value))],
+            BlockType::DataAddToList(a) => vec![("ITEM", Some(&a.item))],
+            BlockType::DataDeleteOfList(a) => vec![("INDEX", Some(&a.item))],
+            BlockType::DataInsertAtList(a) => {
+                vec![("ITEM", Some(&a.item)), ("INDEX", Some(&a.index))]
+            }
+            BlockType::DataReplaceItemOfList(a) => {
+                vec![("INDEX", Some(&a.index)), ("ITEM", Some(&a.item))]
+            }
+            BlockType::DataItemOfList(a) => vec![("INDEX", Some(&a.index))],
+            BlockType::DataListContainsItem(a) => vec![("ITEM", Some(&a.input))],
+            BlockType::ProceduresCall(a) => a
+                .signature
+                .argument_ids()
+                .iter()
+                .zip(&a.arguments)
+                .map(|(id, f)| (id.as_str(), f.as_ref()))
+                .collect(),
+            _ => vec![],
+        };
+        inputs
+            .into_iter()
+            .filter_map(|(name, f)| f.map(|f| (name, f)))
+            .collect()
+    }
+
+    /// Whether this block starts a script.
+    pub fn is_hat(&self) -> bool {
+        matches!(
+            self,
+            BlockType::WhenGreenFlagClicked(_)
+                | BlockType::WhenKeyPressed(_)
+                | BlockType::WhenSpriteClicked(_)
+                | BlockType::WhenStageClicked(_)
+                | BlockType::WhenBackdropSwitchesTo(_)
+                | BlockType::WhenOptionGreaterThen(_)
+                | BlockType::WhenIRecieveBroadcast(_)
+                | BlockType::WhenIStartAsAClone(_)
+                | BlockType::ProceduresDefinition(_)
+        )
+    }
+
+    /// Which part of the palette this block is from. Menus, prototypes and
+    /// opcodes that aren't really blocks aren't from any.
+    pub fn category(&self) -> Option<Category> {
+        use BlockType as B;
+        let category = match self {
+            B::Goto(Goto::Menu(_))
+            | B::Glide(Glide::Menu(_))
+            | B::PointTowardsMenu(_)
+            | B::Costume(Costume::WithName(_))
+            | B::Backdrop(Backdrop::WithName(_))
+            | B::SoundEffectsMenu(_)
+            | B::SoundSoundsMenu(_)
+            | B::CreateCloneOfMenu(_)
+            | B::TouchingMenu(_)
+            | B::DistanceToMenu(_)
+            | B::OfObjectMenu(_)
+            | B::KeyOptions(_)
+            | B::DraggableOption(_)
+            | B::DataListIndexAll(_)
+            | B::DataListIndexAllRandom(_)
+            | B::ProceduresPrototype(_)
+            | B::ProceduresDeclaration(_)
+            | B::UnusedOpcode(_)
+            | B::InvalidOpcode(_) => return None,
+            B::Move(_)
+            | B::RotateLeft(_)
+            | B::RotateRight(_)
+            | B::Goto(_)
+            | B::Glide(_)
+            | B::Point(_)
+            | B::ChangeX(_)
+            | B::SetX(_)
+            | B::ChangeY(_)
+            | B::SetY(_)
+            | B::IfOnEdgeBounce(_)
+            | B::SetRotationStyle(_)
+            | B::XPosition(_)
+            | B::YPosition(_)
+            | B::Direction(_) => Category::Motion,
+            B::Say(_)
+            | B::SayForever(_)
+            | B::Think(_)
+            | B::ThinkForever(_)
+            | B::SwitchCostume(_)
+            | B::NextCostume(_)
+            | B::SwitchBackdrop(_)
+            | B::SwitchBackdropAndWait(_)
+            | B::NextBackdrop(_)
+            | B::ChangeSize(_)
+            | B::SetSize(_)
+            | B::ClearGraphicEffects(_)
+            | B::ShowSprite(_)
+            | B::HideSprite(_)
+            | B::HideAllSprites(_)
+            | B::GotoLayer(_)
+            | B::ChangeLayer(_)
+            | B::Costume(_)
+            | B::Backdrop(_)
+            | B::Size(_) => Category::Looks,
+            B::PlaySound(_)
+            | B::PlaySoundUntilDone(_)
+            | B::StartSound(_)
+            | B::StopAllSounds(_)
+            | B::ChangeEffectBy(_)
+            | B::SetEffectTo(_)
+            | B::ClearSoundEffects(_)
+            | B::ChangeVolumeBy(_)
+            | B::SetVolumeTo(_)
+            | B::Volume(_) => Category::Sound,
+            B::WhenGreenFlagClicked(_)
+            | B::WhenKeyPressed(_)
+            | B::WhenSpriteClicked(_)
+            | B::WhenStageClicked(_)
+            | B::WhenBackdropSwitchesTo(_)
+            | B::WhenOptionGreaterThen(_)
+            | B::WhenIRecieveBroadcast(_)
+            | B::Broadcast(_)
+            | B::BroadcastAndWait(_) => Category::Events,
+            B::WaitSeconds(_)
+            | B::Repeat(_)
+            | B::Forever(_)
+            | B::IfThen(_)
+            | B::IfThenElse(_)
+            | B::WaitUntil(_)
+            | B::RepeatUntil(_)
+            | B::StopAll(_)
+            | B::WhenIStartAsAClone(_)
+            | B::CreateCloneOf(_)
+            | B::DeleteClone(_) => Category::Control,
+            B::Touching(_)
+            | B::TouchingColor(_)
+            | B::ColorTouchingColor(_)
+            | B::DistanceTo(_)
+            | B::Of(_)
+            | B::AskAndWait(_)
+            | B::Answer(_)
+            | B::KeyPressed(_)
+            | B::MouseDown(_)
+            | B::MouseX(_)
+            | B::MouseY(_)
+            | B::SetDragMode(_)
+            | B::Loudness(_)
+            | B::Timer(_)
+            | B::ResetTimer(_)
+            | B::BackdropOf(_)
+            | B::CurrentTime(_)
+            | B::DaysSince2000(_)
+            | B::Username(_) => Category::Sensing,
+            B::Add(_)
+            | B::Sub(_)
+            | B::Mul(_)
+            | B::Divide(_)
+            | B::PickRandom(_)
+            | B::GreaterThen(_)
+            | B::LesserThen(_)
+            | B::EqualTo(_)
+            | B::And(_)
+            | B::Or(_)
+            | B::Not(_)
+            | B::Join(_)
+            | B::LetterOf(_)
+            | B::LengthOf(_)
+            | B::Contains(_)
+            | B::Modulo(_)
+            | B::Round(_)
+            | B::MathOp(_) => Category::Operators,
+            // a stray is a variable or list reporter on its own.
+            B::DataGetVariable(_)
+            | B::DataSetVariableTo(_)
+            | B::DataChangeVariableBy(_)
+            | B::DataShowVariable(_)
+            | B::DataHideVariable(_)
+            | B::DataListContents(_)
+            | B::DataAddToList(_)
+            | B::DataDeleteOfList(_)
+            | B::DataDeleteAllOfList(_)
+            | B::DataInsertAtList(_)
+            | B::DataReplaceItemOfList(_)
+            | B::DataItemOfList(_)
+            | B::DataLengthOfList(_)
+            | B::DataListContainsItem(_)
+            | B::ShowList(_)
+            | B::HideList(_)
+            | B::Stray => Category::Data,
+            B::ProceduresCall(_) | B::ProceduresDefinition(_) | B::ArgumentReporter(_) => {
+                Category::Procedures
+            }
+        };
+        Some(category)
+    }
 }
 
-/// We syphen the information from BlockType's debug trait and remove what we don't want, because due to the size of the enum, doing this is quicker then doing it properly.
-impl Display for BlockType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        // :3
-        let mut lol: String = String::new();
-        let mut f2 = Formatter::new(&mut lol);
-        Debug::fmt(self, &mut f2)?;
-
-        lol = DUP_REGEX.replace_all(&lol, "$1 {\"$3\"}").to_string();
-        lol = SOME_REGEX.replace_all(&lol, "$2").to_string();
-        lol = NUM_REGEX
-            .replace_all(&lol, "$2")
-            .to_string()
-            .replace("\"", "");
-
-        let mut lol2 = NEXT_REGEX.replace_all(&lol, "}").to_string();
-        lol2 = PREV_REGEX.replace_all(&lol2, "").to_string();
-
-        if !lol2.contains("{  }") {
-            lol = lol2;
-        }
-        lol = lol.replace("  ", "");
+/// The parts of the palette, the same as the modules in `block_defs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Motion,
+    Looks,
+    Sound,
+    Events,
+    Control,
+    Sensing,
+    Operators,
+    Data,
+    Procedures,
+}
 
-        f.write_str(lol.as_str())
+impl Category {
+    pub const ALL: [Category; 9] = [
+        Category::Motion,
+        Category::Looks,
+        Category::Sound,
+        Category::Events,
+        Category::Control,
+        Category::Sensing,
+        Category::Operators,
+        Category::Data,
+        Category::Procedures,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Motion => "motion",
+            Category::Looks => "looks",
+            Category::Sound => "sound",
+            Category::Events => "events",
+            Category::Control => "control",
+            Category::Sensing => "sensing",
+            Category::Operators => "operators",
+            Category::Data => "data",
+            Category::Procedures => "procedures",
+        }
     }
 }
 
@@ -247,40 +771,47 @@ impl Display for BlockType {
 macro_rules! from_fn_from_map {
     ($structname:ty, {$($name:tt => $result:ident,)*}) => {
         impl $structname {
-            pub fn from(val: Option<Value>) -> Option<$structname> {
+            pub fn from(val: Option<Value>) -> Result<Option<$structname>, String> {
                 match val {
                     Some(a) => {
                         match a {
                             Value::String(a) => match a.as_str() {
                                 $(
-                                    $name => Some(Self::$result),
+                                    $name => Ok(Some(Self::$result)),
                                 )*
-                                _ => {
-                                    #[cfg(debug_assertions)]
-                                    panic!("invalid $structname: {}",a);
-                                    #[cfg(not(debug_assertions))]
-                                    None
-                                },
-                            },
-                            _ => {
-                                #[cfg(debug_assertions)]
-                                panic!("invalid type given for $structname, expected string");
-                                #[cfg(not(debug_assertions))]
-                                None
+                                _ => Err(format!("invalid {}: {}", stringify!($structname), a)),
                             },
+                            a => Err(format!(
+                                "invalid type given for {}, expected string: {:?}",
+                                stringify!($structname),
+                                a
+                            )),
                         }
                     }
-                    None => None,
+                    None => Ok(None),
                 }
             }
         }
+
+        // back to what project.json has. When several names give the same
+        // variant, the first one is used.
+        impl std::fmt::Display for $structname {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                $(
+                    if matches!(self, Self::$result) {
+                        return f.write_str($name);
+                    }
+                )*
+                Ok(())
+            }
+        }
     }
 }
 
 #[block_derive]
 #[derive(Debug, Clone)]
 pub struct UnusedOpcode {
-    name: String,
+    pub(crate) name: String,
 }
 
 #[block_derive]
@@ -302,313 +833,298 @@ impl<'de> Visitor<'de> for BlockType {
     }
 }
 
-enum HashOrVec {
-    Hash(HashMap<String, SerdeValue>),
-    Vec(Vec<SerdeValue>),
-}
-struct HashOrVecVisitor;
-impl<'de> Visitor<'de> for HashOrVecVisitor {
-    type Value = HashOrVec;
-
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a map or a sequence")
-    }
-
-    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-    where
-        A: de::MapAccess<'de>,
-    {
-        let mut hashmap: HashMap<String, SerdeValue> = HashMap::new();
-        #[allow(irrefutable_let_patterns)] // we handle breaking ourselves.
-        while let k = map.next_entry() {
-            match k {
-                Ok(a) => match a {
-                    Some(a) => {
-                        hashmap.insert(a.0, a.1);
-                    }
-                    None => {
-                        break;
-                    }
-                },
-                Err(err) => {
-                    return Err(err);
+/// Reads the value of an input (which is at index 1) or a field (at index 0).
+fn idk(f: &SerdeValue, id: usize) -> Result<Value, String> {
+    let bl = match f.get(id) {
+        Some(SerdeValue::Array(a)) => {
+            // variables and lists dropped straight into an input are stored as [12 or 13, name, id]
+            match (a.first().and_then(|f| f.as_u64()), a.get(1)) {
+                (Some(12), Some(SerdeValue::String(name))) => {
+                    return Ok(Value::Variable(name.clone()))
                 }
+                (Some(13), Some(SerdeValue::String(name))) => return Ok(Value::List(name.clone())),
+                _ => {}
+            }
+            match a.get(1) {
+                Some(a) => a,
+                None => return Err(format!("no value in {}", f)),
             }
         }
-        Ok(HashOrVec::Hash(hashmap))
-    }
-
-    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-    where
-        A: de::SeqAccess<'de>,
-    {
-        let mut vec: Vec<SerdeValue> = Vec::new();
-        #[allow(irrefutable_let_patterns)] // we handle breaking ourselves.
-        while let a = seq.next_element() {
-            match a {
-                Ok(a) => match a {
-                    Some(a) => vec.push(a),
-                    None => {
-                        break;
-                    }
-                },
-                Err(err) => {
-                    return Err(err);
+        Some(a) => a,
+        None => return Err(format!("no value in {}", f)),
+    };
+    match bl {
+        SerdeValue::Null => Ok(Value::Null),
+        SerdeValue::Number(a) => Ok(Value::Number(a.as_f64().unwrap_or(0.0))),
+        SerdeValue::String(st) => {
+            if st.is_empty() {
+                return Ok(Value::Null);
+            }
+            if NUMBERS_ONLY_REGEX.is_match(st) {
+                Ok(Value::String(st.to_string()))
+            } else {
+                match st.parse() {
+                    Ok(a) => Ok(Value::Number(a)),
+                    Err(_) => Err(format!("could not format {} into a number", st)),
                 }
             }
         }
-        Ok(HashOrVec::Vec(vec))
+        a => Err(format!("expected a number or a string, got {}", a)),
     }
 }
 
-fn idk<'a>(
-    f: (&'a String, &'a SerdeValue),
+/// Reads the inputs, fields or params of a block. Empty ones are left out.
+fn entries(
+    hash: &Map<String, SerdeValue>,
+    key: &str,
     id: usize,
-) -> Result<(&'a String, Value), serde_json::Error> {
-    let b = f.1.get(id);
-    let block = match b {
-        Some(a) => {
-            let bl;
-            if a.is_array() {
-                bl = a.as_array().unwrap().get(1).unwrap();
-            } else {
-                bl = a
-            };
-            if bl.is_null() {
-                Ok((f.0, Value::Null))
-            } else if bl.is_u64() {
-                Ok((f.0, Value::Number(bl.as_u64().unwrap() as f64)))
-            } else if bl.is_i64() {
-                Ok((f.0, Value::Number(bl.as_i64().unwrap() as f64)))
-            } else if bl.is_f64() {
-                Ok((f.0, Value::Number(bl.as_f64().unwrap())))
-            } else {
-                let st = bl.as_str().unwrap();
-                if st == "" {
-                    return Ok((f.0, Value::Null));
-                }
-                if NUMBERS_ONLY_REGEX.is_match(st) {
-                    Ok((f.0, Value::String(st.to_string())))
-                } else {
-                    match st.parse() {
-                        Ok(a) => Ok((f.0, Value::Number(a))),
-                        Err(_) => {
-                            return Err(format!("could not format {} into a number", st))
-                                .map_err(de::Error::custom)
-                        }
-                    }
-                }
-            }
-        }
-        None => {
-            return Err(format!("what")).map_err(de::Error::custom);
-        } // we should never reach this
+) -> Result<HashMap<String, Value>, BlockError> {
+    let map = match hash.get(key) {
+        Some(SerdeValue::Object(a)) => a,
+        None => return Ok(HashMap::new()),
+        Some(_) => return Err(BlockError::new(key, "expected an object")),
     };
-    block
+    let mut entries = HashMap::new();
+    for (name, value) in map {
+        let value = idk(value, id).map_err(|f| BlockError::new(format!("{}.{}", key, name), f))?;
+        if value != Value::Null {
+            entries.insert(name.clone(), value);
+        }
+    }
+    Ok(entries)
 }
 
 /// A representation of what we'd expect blocks to have.
-struct RawBlock {
+struct RawBlock<'a> {
     opcode: String,
     next: Option<String>,
     parent: Option<String>,
     inputs: HashMap<String, Value>,
     fields: HashMap<String, Value>,
     params: HashMap<String, Value>,
-    hash: HashMap<String, SerdeValue>,
+    hash: &'a Map<String, SerdeValue>,
 }
 
-impl<'de> Visitor<'de> for RawBlock {
-    type Value = SerdeValue;
+impl<'a> RawBlock<'a> {
+    fn new(hash: &'a Map<String, SerdeValue>) -> Result<RawBlock<'a>, BlockError> {
+        let opcode = match hash.get("opcode") {
+            Some(SerdeValue::String(a)) => a.clone(),
+            Some(a) => return Err(BlockError::new("opcode", format!("not a string: {}", a))),
+            None => return Err(BlockError::new("", "no opcode")),
+        };
+        //
+        // Each block has three parts.
+        // The first value is always a number and signifies whether there's a "shadow".
+        // - 1 (SameBlockShadow): unobscured "shadow": the second value is a block
+        // - 2 (BlockNoShadow): no shadow: the second value is a reference to a block
+        // - 3 (DiffBlockShadow): obscured shadow: the second value is a reference to a block and the third is a "shadow"
+        // a "shadow" is something that's only important to the visual editor; its the value that the user dragged a block over.
+        // we don't care about this.
+        let inputs = entries(hash, "inputs", 1)?;
+        let fields = entries(hash, "fields", 0)?;
+        let params = entries(hash, "params", 0)?;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(formatter, "a valid value that the serde package can handle")
+        let parent = match hash.get("parent") {
+            Some(SerdeValue::String(a)) => Some(a.clone()),
+            _ => None,
+        };
+        let next = match hash.get("next") {
+            Some(SerdeValue::String(a)) => Some(a.clone()),
+            _ => None,
+        };
+
+        Ok(RawBlock {
+            opcode,
+            next,
+            parent,
+            inputs,
+            fields,
+            params,
+            hash,
+        })
     }
 }
 
-impl<'de> Deserialize<'de> for RawBlock {
-    fn deserialize<D>(d: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let hash = match d.deserialize_any(HashOrVecVisitor) {
-            Ok(a) => match a {
-                HashOrVec::Hash(a) => a,
-                HashOrVec::Vec(a) => {
-                    let mut count = 0;
-                    a.into_iter()
-                        .map(|f| {
-                            count += 1;
-                            (count.to_string(), f)
-                        })
-                        .collect()
-                }
-            },
-            Err(err) => {
-                return Err(err);
-            }
-        };
-        match hash.get_key_value("opcode") {
-            Some(a) => {
-                let name: &String = match a.1 {
-                    SerdeValue::String(a) => a,
-                    _ => {
-                        return Err(format!("not a string: {}", a.1)).map_err(de::Error::custom);
-                    }
-                };
-                //
-                // Each block has three parts.
-                // The first value is always a number and signifies whether there's a "shadow".
-                // - 1 (SameBlockShadow): unobscured "shadow": the second value is a block
-                // - 2 (BlockNoShadow): no shadow: the second value is a reference to a block
-                // - 3 (DiffBlockShadow): obscured shadow: the second value is a reference to a block and the third is a "shadow"
-                // a "shadow" is something that's only important to the visual editor; its the value that the user dragged a block over.
-                // we don't care about this.
-
-                // inputs is never null only empty
-                let (_, inputs) = hash.get_key_value("inputs").unwrap();
-                let inputs = inputs.as_object().unwrap();
-
-                // same with fields
-                let (_, fields) = hash.get_key_value("fields").unwrap();
-                let fields = fields.as_object().unwrap();
-
-                let params;
-                if let Some((_, a)) = hash.get_key_value("params") {
-                    params = Some(a.as_object().unwrap());
-                } else {
-                    params = None;
-                }
+fn mutation(hash: &Map<String, SerdeValue>) -> Result<ProcedureSignature, BlockError> {
+    match hash.get("mutation") {
+        Some(a) => a
+            .deserialize_map(MutationVisitor)
+            .map_err(|f| BlockError::new("mutation", f.to_string())),
+        None => Err(BlockError::new("mutation", "custom block has no mutation")),
+    }
+}
 
-                let inputs: HashMap<String, Value> = inputs
-                    .into_iter()
-                    .map(|f| idk(f, 1))
-                    .map(|f| f.unwrap())
-                    .filter(|f| f.1 != Value::Null)
-                    .map(|f| (f.0.clone(), f.1))
-                    .collect();
+/// Where problems go when loading leniently. Strict loads have nowhere to
+/// put them, and fail instead.
+type Warnings = Option<Vec<BlockError>>;
 
-                let fields: HashMap<String, Value> = fields
-                    .into_iter()
-                    .map(|f| idk(f, 0))
-                    .map(|f| f.unwrap())
-                    .filter(|f| f.1 != Value::Null)
-                    .map(|f| (f.0.clone(), f.1))
-                    .collect();
-
-                let params = match params {
-                    Some(a) => a
-                        .into_iter()
-                        .map(|f| idk(f, 0))
-                        .map(|f| f.unwrap())
-                        .filter(|f| f.1 != Value::Null)
-                        .map(|f| (f.0.clone(), f.1))
-                        .collect(),
+/// Reads a dropdown, which has to hold one of the values Scratch knows about.
+/// When lenient, one that doesn't is left empty.
+fn menu<T>(
+    fields: &HashMap<String, Value>,
+    name: &str,
+    from: fn(Option<Value>) -> Result<Option<T>, String>,
+    warnings: &mut Warnings,
+) -> Result<Option<T>, BlockError> {
+    match from(fields.get(name).cloned()) {
+        Ok(a) => Ok(a),
+        Err(err) => {
+            let err = BlockError::new(format!("fields.{}", name), err);
+            match warnings {
+                Some(a) => {
+                    a.push(err);
+                    Ok(None)
+                }
+                None => Err(err),
+            }
+        }
+    }
+}
 
-                    None => HashMap::new(),
-                };
+/// For opcodes that are known, but that yase doesn't handle (yet).
+fn unsupported(opcode: &str) -> BlockError {
+    BlockError::new("opcode", format!("{} isn't supported", opcode))
+}
 
-                let prev = match hash.get_key_value("parent") {
-                    Some(a) => Some(a.to_owned().1.to_string()),
-                    None => None,
-                };
-                let next = match hash.get_key_value("next") {
-                    Some(a) => Some(a.to_owned().1.to_string()),
-                    None => None,
-                };
+impl BlockType {
+    /// Reads a block as it's stored in project.json.
+    pub fn from_json(json: &SerdeValue) -> Result<BlockType, BlockError> {
+        BlockType::read(json, &mut None)
+    }
 
-                Ok(RawBlock {
-                    opcode: name.clone(),
-                    next: next.clone(),
-                    parent: prev.clone(),
-                    inputs,
-                    fields,
-                    params: params,
-                    hash,
-                })
-            }
-            None => {
-                return Err("no opcode").map_err(de::Error::custom);
+    /// Same as from_json, but never fails: blocks that can't be read become
+    /// an InvalidOpcode, and whatever was wrong with them is given back.
+    pub fn from_json_lenient(json: &SerdeValue) -> (BlockType, Vec<BlockError>) {
+        let mut warnings = Some(Vec::new());
+        let block = BlockType::read(json, &mut warnings);
+        let mut warnings = warnings.unwrap_or_default();
+        match block {
+            Ok(a) => (a, warnings),
+            Err(err) => {
+                warnings.push(err);
+                let get = |name: &str| json.get(name).and_then(|f| f.as_str()).map(String::from);
+                let block = BlockType::InvalidOpcode(InvalidOpcode {
+                    name: get("opcode").unwrap_or_default(),
+                    prev: get("parent"),
+                    next: get("next"),
+                });
+                (block, warnings)
             }
         }
     }
+
+    fn read(json: &SerdeValue, warnings: &mut Warnings) -> Result<BlockType, BlockError> {
+        match json {
+            SerdeValue::Object(a) => RawBlock::new(a)?.block(warnings),
+            // variables and lists lying around on their own are stored as arrays.
+            SerdeValue::Array(_) => Ok(BlockType::Stray),
+            a => Err(BlockError::new("", format!("expected a block, got {}", a))),
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for BlockType {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
-        let raw = RawBlock::deserialize(d)?;
-
-        //let name = raw.name;
-        let inputs = raw.inputs;
-        let fields = raw.fields;
-        let params = raw.params;
-
-        let val1 = inputs.get("0").cloned();
-        let val2 = inputs.get("1").cloned();
-        let val3 = inputs.get("2").cloned();
-        let val4 = inputs.get("3").cloned();
-        let val5 = inputs.get("4").cloned();
-        let field1 = fields.get("0").cloned();
-        let field2 = fields.get("1").cloned();
-        let field3 = fields.get("2").cloned();
-        let field4 = fields.get("3").cloned();
-        let field5 = fields.get("4").cloned();
-
-        let prev = raw.parent;
-        let next = raw.next;
-
-        let block_type: Result<BlockType, <D as Deserializer>::Error> = match raw.opcode.as_str() {
+        let json = SerdeValue::deserialize(d)?;
+        BlockType::from_json(&json).map_err(de::Error::custom)
+    }
+}
+
+impl RawBlock<'_> {
+    fn block(self, warnings: &mut Warnings) -> Result<BlockType, BlockError> {
+        let opcode = self.opcode;
+        let inputs = self.inputs;
+        let fields = self.fields;
+        let params = self.params;
+
+        // inputs with a shadow are there even when they're empty, and only
+        // boolean slots and substacks are left out.
+        let missing = RefCell::new(Vec::new());
+        let input = |name: &str| {
+            if self
+                .hash
+                .get("inputs")
+                .is_none_or(|f| f.get(name).is_none())
+            {
+                missing.borrow_mut().push(name.to_string());
+            }
+            inputs.get(name).cloned()
+        };
+        let optional = |name: &str| inputs.get(name).cloned();
+        let field = |name: &str| fields.get(name).cloned();
+        // variables and lists are always named in a field.
+        let required = |name: &str| {
+            fields
+                .get(name)
+                .cloned()
+                .ok_or_else(|| BlockError::new(format!("fields.{}", name), "missing"))
+        };
+
+        let prev = self.parent;
+        let next = self.next;
+
+        let block = match opcode.as_str() {
             MOTION_MOVE => Ok(BlockType::Move(Move {
-                steps: val1,
+                steps: input("STEPS"),
                 prev,
                 next,
             })),
             MOTION_GOTO_XY => Ok(BlockType::Goto(Goto::Pos(GotoPos {
-                x: val1,
-                y: val2,
+                x: input("X"),
+                y: input("Y"),
+                prev,
+                next,
+            }))),
+            MOTION_GOTO => Ok(BlockType::Goto(Goto::Option(GotoOption {
+                option: input("TO"),
                 prev,
                 next,
             }))),
-            MOTION_GOTO_MENU => Ok(BlockType::Goto(Goto::Option(GotoOption {
-                option: MovementOption::from(val1),
+            MOTION_GOTO_MENU => Ok(BlockType::Goto(Goto::Menu(MovementMenu {
+                option: MovementOption::from(field("TO")),
                 prev,
                 next,
             }))),
             MOTION_TURN_LEFT => Ok(BlockType::RotateLeft(RotateLeft {
-                degrees: val1,
+                degrees: input("DEGREES"),
                 prev,
                 next,
             })),
             MOTION_TURN_RIGHT => Ok(BlockType::RotateRight(RotateRight {
-                degrees: val1,
+                degrees: input("DEGREES"),
                 prev,
                 next,
             })),
-            // Unused: Mouse. It's always mouse.
-            MOTION_POINT_MENU => Ok(BlockType::Point(Point::Towards(PointOption {
-                option: Some(Value::String(String::from("_mouse_"))),
+            MOTION_POINT_MENU => Ok(BlockType::PointTowardsMenu(PointTowardsMenu {
+                option: MovementOption::from(field("TOWARDS")),
                 prev,
                 next,
-            }))),
+            })),
             MOTION_POINT_DIRECTION => Ok(BlockType::Point(Point::Direction(PointDirection {
-                x: val1,
-                y: val2,
+                direction: input("DIRECTION"),
                 prev,
                 next,
             }))),
             MOTION_POINT_TOWARDS => Ok(BlockType::Point(Point::Towards(PointOption {
-                option: val1,
+                option: input("TOWARDS"),
                 prev,
                 next,
             }))),
             MOTION_GLIDE_SECONDS_TO_XY => Ok(BlockType::Glide(Glide::Pos(GlidePos {
-                x: val1,
-                y: val2,
+                secs: input("SECS"),
+                x: input("X"),
+                y: input("Y"),
                 prev,
                 next,
             }))),
-            MOTION_GLIDE_TO_MENU => Ok(BlockType::Glide(Glide::Option(GlideOption {
-                option: MovementOption::from(field1),
+            MOTION_GLIDE_TO => Ok(BlockType::Glide(Glide::Option(GlideOption {
+                secs: input("SECS"),
+                option: input("TO"),
+                prev,
+                next,
+            }))),
+            MOTION_GLIDE_TO_MENU => Ok(BlockType::Glide(Glide::Menu(MovementMenu {
+                option: MovementOption::from(field("TO")),
                 prev,
                 next,
             }))),
@@ -616,27 +1132,27 @@ impl<'de> Deserialize<'de> for BlockType {
                 Ok(BlockType::IfOnEdgeBounce(IfOnEdgeBounce { prev, next }))
             }
             MOTION_SET_ROTATION_STYLE => Ok(BlockType::SetRotationStyle(SetRotationStyle {
-                style: RotationStyle::from(val1),
+                style: menu(&fields, "STYLE", RotationStyle::from, warnings)?,
                 prev,
                 next,
             })),
             MOTION_CHANGE_X_BY => Ok(BlockType::ChangeX(ChangeX {
-                x: val1,
+                x: input("DX"),
                 prev,
                 next,
             })),
             MOTION_SET_X => Ok(BlockType::SetX(SetX {
-                x: val1,
+                x: input("X"),
                 prev,
                 next,
             })),
             MOTION_CHANGE_Y_BY => Ok(BlockType::ChangeY(ChangeY {
-                y: val1,
+                y: input("DY"),
                 prev,
                 next,
             })),
             MOTION_SET_Y => Ok(BlockType::SetY(SetY {
-                y: val1,
+                y: input("Y"),
                 prev,
                 next,
             })),
@@ -644,40 +1160,30 @@ impl<'de> Deserialize<'de> for BlockType {
             MOTION_YPOSITION => Ok(BlockType::YPosition(YPosition { prev, next })),
             MOTION_DIRECTION => Ok(BlockType::Direction(Direction { prev, next })),
             // presumed unused
-            MOTION_SCROLL_RIGHT => {
-                todo!()
-            }
-            MOTION_SCROLL_UP => {
-                todo!()
-            }
-            MOTION_ALIGN_SCENE => {
-                todo!()
-            }
-            MOTION_XSCROLL => {
-                todo!()
-            }
-            MOTION_YSCROLL => {
-                todo!()
-            }
+            MOTION_SCROLL_RIGHT => Err(unsupported(&opcode)),
+            MOTION_SCROLL_UP => Err(unsupported(&opcode)),
+            MOTION_ALIGN_SCENE => Err(unsupported(&opcode)),
+            MOTION_XSCROLL => Err(unsupported(&opcode)),
+            MOTION_YSCROLL => Err(unsupported(&opcode)),
             LOOKS_SAY => Ok(BlockType::SayForever(SayForever {
-                message: val1,
+                message: input("MESSAGE"),
                 prev,
                 next,
             })),
             LOOKS_SAY_FOR_SECS => Ok(BlockType::Say(Say {
-                message: val1,
-                secs: val2,
+                message: input("MESSAGE"),
+                secs: input("SECS"),
                 prev,
                 next,
             })),
             LOOKS_THINK => Ok(BlockType::ThinkForever(ThinkForever {
-                message: val1,
+                message: input("MESSAGE"),
                 prev,
                 next,
             })),
             LOOKS_THINK_FOR_SECS => Ok(BlockType::Think(Think {
-                message: val1,
-                secs: val2,
+                message: input("MESSAGE"),
+                secs: input("SECS"),
                 prev,
                 next,
             })),
@@ -685,18 +1191,18 @@ impl<'de> Deserialize<'de> for BlockType {
             LOOKS_HIDE => Ok(BlockType::HideSprite(HideSprite { prev, next })),
             LOOKS_HIDE_ALL_SPRITES => Ok(BlockType::HideAllSprites(HideAllSprites { prev, next })),
             LOOKS_SWITCH_COSTUME_TO => Ok(BlockType::SwitchCostume(SwitchCostume {
-                costume: val1,
+                costume: input("COSTUME"),
                 prev,
                 next,
             })),
             LOOKS_SWITCH_BACKDROP_TO => Ok(BlockType::SwitchBackdrop(SwitchBackdrop {
-                backdrop: val1,
+                backdrop: input("BACKDROP"),
                 prev,
                 next,
             })),
             LOOKS_SWITCH_BACKDROP_TO_AND_WAIT => {
                 Ok(BlockType::SwitchBackdropAndWait(SwitchBackdropAndWait {
-                    backdrop: val1,
+                    backdrop: input("BACKDROP"),
                     prev,
                     next,
                 }))
@@ -704,14 +1210,14 @@ impl<'de> Deserialize<'de> for BlockType {
             LOOKS_NEXT_COSTUME => Ok(BlockType::NextCostume(NextCostume { prev, next })),
             LOOKS_NEXT_BACKDROP => Ok(BlockType::NextBackdrop(NextBackdrop { prev, next })),
             LOOKS_CHANGE_EFFECT_BY => Ok(BlockType::ChangeEffectBy(ChangeEffectBy {
-                effect: val1,
-                units: val2,
+                effect: field("EFFECT"),
+                units: input("CHANGE"),
                 prev,
                 next,
             })),
             LOOKS_SET_EFFECT_TO => Ok(BlockType::SetEffectTo(SetEffectTo {
-                effect: val1,
-                percentage: val2,
+                effect: field("EFFECT"),
+                percentage: input("VALUE"),
                 prev,
                 next,
             })),
@@ -722,35 +1228,31 @@ impl<'de> Deserialize<'de> for BlockType {
                 }))
             }
             LOOKS_CHANGE_SIZE_BY => Ok(BlockType::ChangeSize(ChangeSize {
-                units: val1,
+                units: input("CHANGE"),
                 prev,
                 next,
             })),
             LOOKS_SET_SIZE_TO => Ok(BlockType::SetSize(SetSize {
-                percentage: val1,
+                percentage: input("SIZE"),
                 prev,
                 next,
             })),
-            LOOKS_CHANGE_STRETCH_BY => {
-                todo!()
-            }
-            LOOKS_SET_STRETCH_TO => {
-                todo!()
-            }
+            LOOKS_CHANGE_STRETCH_BY => Err(unsupported(&opcode)),
+            LOOKS_SET_STRETCH_TO => Err(unsupported(&opcode)),
             LOOKS_GOTO_FRONT_BACK => Ok(BlockType::GotoLayer(GotoLayer {
-                option: LayerOption::from(val1),
+                option: menu(&fields, "FRONT_BACK", LayerOption::from, warnings)?,
                 prev,
                 next,
             })),
             LOOKS_GO_FORWARD_BACKWARD_LAYERS => Ok(BlockType::ChangeLayer(ChangeLayer {
-                direction: LayerDirection::from(val1),
-                by: val2,
+                direction: menu(&fields, "FORWARD_BACKWARD", LayerDirection::from, warnings)?,
+                by: input("NUM"),
                 prev,
                 next,
             })),
             LOOKS_SIZE => Ok(BlockType::Size(Size { prev, next })),
-            LOOKS_COSTUME => Ok(BlockType::Costume(Costume::WithName(field1))),
-            LOOKS_COSTUME_NUMBER_NAME => match field1 {
+            LOOKS_COSTUME => Ok(BlockType::Costume(Costume::WithName(field("COSTUME")))),
+            LOOKS_COSTUME_NUMBER_NAME => match field("NUMBER_NAME") {
                 Some(Value::String(a)) => match a.as_str() {
                     "number" => Ok(BlockType::Costume(Costume::ByNumber(CostumeByNumber {
                         prev,
@@ -760,18 +1262,18 @@ impl<'de> Deserialize<'de> for BlockType {
                         prev,
                         next,
                     }))),
-                    _ => {
-                        return Err(format!("invalid option given for costume number/name"))
-                            .map_err(de::Error::custom);
-                    }
+                    _ => Err(BlockError::new(
+                        "fields.NUMBER_NAME",
+                        format!("invalid option given for costume number/name: {}", a),
+                    )),
                 },
-                _ => {
-                    return Err(format!("no option given for costume number/name"))
-                        .map_err(de::Error::custom);
-                }
+                _ => Err(BlockError::new(
+                    "fields.NUMBER_NAME",
+                    "no option given for costume number/name",
+                )),
             },
-            LOOKS_BACKDROP => Ok(BlockType::Backdrop(Backdrop::WithName(field1))),
-            LOOKS_BACKDROP_NUMBER_NAME => match field1 {
+            LOOKS_BACKDROP => Ok(BlockType::Backdrop(Backdrop::WithName(field("BACKDROP")))),
+            LOOKS_BACKDROP_NUMBER_NAME => match field("NUMBER_NAME") {
                 Some(Value::String(a)) => match a.as_str() {
                     "number" => Ok(BlockType::Backdrop(Backdrop::ByNumber(BackdropByNumber {
                         prev,
@@ -781,36 +1283,36 @@ impl<'de> Deserialize<'de> for BlockType {
                         prev,
                         next,
                     }))),
-                    _ => {
-                        return Err(format!("invalid option given for backdrop number/name"))
-                            .map_err(de::Error::custom);
-                    }
+                    _ => Err(BlockError::new(
+                        "fields.NUMBER_NAME",
+                        format!("invalid option given for backdrop number/name: {}", a),
+                    )),
                 },
-                _ => {
-                    return Err(format!("no option given for backdrop number/name"))
-                        .map_err(de::Error::custom);
-                }
+                _ => Err(BlockError::new(
+                    "fields.NUMBER_NAME",
+                    "no option given for backdrop number/name",
+                )),
             },
             SOUND_PLAY => Ok(BlockType::PlaySound(PlaySound {
-                sound: val1,
+                sound: input("SOUND_MENU"),
                 prev,
                 next,
             })),
             SOUND_PLAY_UNTIL_DONE => Ok(BlockType::PlaySoundUntilDone(PlaySoundUntilDone {
-                sound: val1,
+                sound: input("SOUND_MENU"),
                 prev,
                 next,
             })),
             SOUND_STOP_ALL_SOUNDS => Ok(BlockType::StopAllSounds(StopAllSounds { prev, next })),
             SOUND_SET_EFFECT_TO => Ok(BlockType::SetEffectTo(SetEffectTo {
-                effect: val1,
-                percentage: val2,
+                effect: field("EFFECT"),
+                percentage: input("VALUE"),
                 prev,
                 next,
             })),
             SOUND_CHANGE_EFFECT_BY => Ok(BlockType::ChangeEffectBy(ChangeEffectBy {
-                effect: val1,
-                units: val2,
+                effect: field("EFFECT"),
+                units: input("VALUE"),
                 prev,
                 next,
             })),
@@ -819,33 +1321,31 @@ impl<'de> Deserialize<'de> for BlockType {
                 next,
             })),
             SOUND_SET_VOLUME_TO => Ok(BlockType::SetVolumeTo(SetVolumeTo {
-                percentage: val1,
+                percentage: input("VOLUME"),
                 prev,
                 next,
             })),
             SOUND_CHANGE_VOLUME_BY => Ok(BlockType::ChangeVolumeBy(ChangeVolumeBy {
-                units: val1,
+                units: input("VOLUME"),
                 prev,
                 next,
             })),
             SOUND_VOLUME => Ok(BlockType::Volume(Volume { prev, next })),
-            EVENT_WHEN_TOUCHING_OBJECT => {
-                todo!()
-            }
+            EVENT_WHEN_TOUCHING_OBJECT => Err(unsupported(&opcode)),
             EVENT_BROADCAST => Ok(BlockType::Broadcast(Broadcast {
-                broadcast: val1,
+                broadcast: input("BROADCAST_INPUT"),
                 prev,
                 next,
             })),
             EVENT_BROADCAST_AND_WAIT => Ok(BlockType::BroadcastAndWait(BroadcastAndWait {
-                broadcast: val1,
+                broadcast: input("BROADCAST_INPUT"),
                 prev,
                 next,
             })),
             EVENT_WHEN_GREATER_THAN => {
                 Ok(BlockType::WhenOptionGreaterThen(WhenOptionGreaterThen {
-                    option: EventOption::from(val1),
-                    by: val2,
+                    option: menu(&fields, "WHENGREATERTHANMENU", EventOption::from, warnings)?,
+                    by: input("VALUE"),
                     prev,
                     next,
                 }))
@@ -855,7 +1355,7 @@ impl<'de> Deserialize<'de> for BlockType {
                 next,
             })),
             EVENT_WHEN_KEY_PRESSED => Ok(BlockType::WhenKeyPressed(WhenKeyPressed {
-                key: Key::from(val1),
+                key: Key::from(field("KEY_OPTION")),
                 prev,
                 next,
             })),
@@ -868,68 +1368,87 @@ impl<'de> Deserialize<'de> for BlockType {
             }
             EVENT_WHEN_BACKDROP_SWITCHESTO => {
                 Ok(BlockType::WhenBackdropSwitchesTo(WhenBackdropSwitchesTo {
-                    backdrop: val1,
+                    backdrop: field("BACKDROP"),
                     prev,
                     next,
                 }))
             }
             EVENT_WHEN_BROADCAST_RECEIVED => {
                 Ok(BlockType::WhenIRecieveBroadcast(WhenIRecieveBroadcast {
-                    broadcast: field1,
+                    broadcast: field("BROADCAST_OPTION"),
                     prev,
                     next,
                 }))
             }
 
             CONTROL_REPEAT => Ok(BlockType::Repeat(Repeat {
-                units: val1,
+                units: input("TIMES"),
+                substack: optional("SUBSTACK"),
                 prev,
                 next,
             })),
             CONTROL_REPEAT_UNTIL => Ok(BlockType::RepeatUntil(RepeatUntil {
-                condition: val1,
+                condition: optional("CONDITION"),
+                substack: optional("SUBSTACK"),
                 prev,
                 next,
             })),
             CONTROL_WHILE => Ok(BlockType::RepeatUntil(RepeatUntil {
-                condition: val1,
+                condition: optional("CONDITION"),
+                substack: optional("SUBSTACK"),
+                prev,
+                next,
+            })),
+            // i don't see this in the scratch part picker what?
+            CONTROL_FOR_EACH => Err(unsupported(&opcode)),
+            CONTROL_FOREVER => Ok(BlockType::Forever(Forever {
+                substack: optional("SUBSTACK"),
                 prev,
                 next,
             })),
-            CONTROL_FOR_EACH => {
-                todo!() // i don't see this in the scratch part picker what?
-            }
-            CONTROL_FOREVER => Ok(BlockType::Forever(Forever { prev, next })),
             CONTROL_WAIT => Ok(BlockType::WaitSeconds(WaitSeconds {
-                seconds: val1,
+                seconds: input("DURATION"),
                 prev,
                 next,
             })),
             CONTROL_WAIT_UNTIL => Ok(BlockType::WaitUntil(WaitUntil {
-                condition: val1,
+                condition: optional("CONDITION"),
                 prev,
                 next,
             })),
             CONTROL_IF => Ok(BlockType::IfThen(IfThen {
-                condition: val1,
-                then: val2,
+                condition: optional("CONDITION"),
+                then: optional("SUBSTACK"),
                 prev,
                 next,
             })),
             CONTROL_IF_ELSE => Ok(BlockType::IfThenElse(IfThenElse {
-                condition: val1,
-                then: val2,
-                otherwise: val3,
+                condition: optional("CONDITION"),
+                then: optional("SUBSTACK"),
+                otherwise: optional("SUBSTACK2"),
+                prev,
+                next,
+            })),
+            CONTROL_STOP => Ok(BlockType::StopAll(StopAll {
+                option: menu(&fields, "STOP_OPTION", StopOption::from, warnings)?,
                 prev,
                 next,
             })),
-            CONTROL_STOP => Ok(BlockType::StopAll(StopAll { prev, next })),
             CONTROL_CREATE_CLONE_OF => Ok(BlockType::CreateCloneOf(CreateCloneOf {
-                of: SpriteOption::from(val1),
+                of: input("CLONE_OPTION"),
+                prev,
+                next,
+            })),
+            CONTROL_CREATE_CLONE_OF_MENU => Ok(BlockType::CreateCloneOfMenu(CreateCloneOfMenu {
+                of: menu(&fields, "CLONE_OPTION", SpriteOption::from, warnings)?,
                 prev,
                 next,
             })),
             CONTROL_DELETE_THIS_CLONE => Ok(BlockType::DeleteClone(DeleteClone { prev, next })),
+            CONTROL_START_AS_CLONE => Ok(BlockType::WhenIStartAsAClone(WhenIStartAsAClone {
+                prev,
+                next,
+            })),
             /*CONTROL_GET_COUNTER => {
                 todo!()
             }
@@ -941,333 +1460,384 @@ impl<'de> Deserialize<'de> for BlockType {
             }
             CONTROL_ALL_AT_ONCE => {
                 todo!()
-            }
-            CONTROL_START_AS_CLONE => {
-                todo!()
             }*/
             SENSING_TOUCHING_OBJECT_MENU => Ok(BlockType::TouchingMenu(TouchingMenu {
-                touching: SensingOption::from(field1),
+                touching: SensingOption::from(field("TOUCHINGOBJECTMENU")),
                 prev,
                 next,
             })),
             SENSING_TOUCHING_OBJECT => Ok(BlockType::Touching(Touching {
-                touching: val1,
+                touching: input("TOUCHINGOBJECTMENU"),
                 prev,
                 next,
             })),
             SENSING_TOUCHING_COLOR => Ok(BlockType::TouchingColor(TouchingColor {
-                color: val1,
+                color: input("COLOR"),
                 prev,
                 next,
             })),
             SENSING_COLOR_IS_TOUCHING_COLOR => {
                 Ok(BlockType::ColorTouchingColor(ColorTouchingColor {
-                    color1: val1,
-                    color2: val2,
+                    color1: input("COLOR"),
+                    color2: input("COLOR2"),
                     prev,
                     next,
                 }))
             }
             SENSING_DISTANCE_TO => Ok(BlockType::DistanceTo(DistanceTo {
-                to: SensingOption::from(val1),
+                to: input("DISTANCETOMENU"),
+                prev,
+                next,
+            })),
+            SENSING_DISTANCE_TO_MENU => Ok(BlockType::DistanceToMenu(DistanceToMenu {
+                to: SensingOption::from(field("DISTANCETOMENU")),
                 prev,
                 next,
             })),
             SENSING_TIMER => Ok(BlockType::Timer(Timer { prev, next })),
             SENSING_RESET_TIMER => Ok(BlockType::ResetTimer(ResetTimer { prev, next })),
-            // uses fields.
-            SENSING_OF => {
-                todo!()
-            }
+            SENSING_OF => Ok(BlockType::Of(Of {
+                property: field("PROPERTY").map(|f| match f {
+                    Value::Number(a) => a.to_string(),
+                    Value::String(a) | Value::Variable(a) | Value::List(a) => a,
+                    Value::Null => String::new(),
+                }),
+                object: input("OBJECT"),
+                prev,
+                next,
+            })),
+            SENSING_OF_OBJECT_MENU => Ok(BlockType::OfObjectMenu(OfObjectMenu {
+                object: menu(&fields, "OBJECT", OfObject::from, warnings)?,
+                prev,
+                next,
+            })),
             SENSING_MOUSE_X => Ok(BlockType::MouseX(MouseX { prev, next })),
             SENSING_MOUSE_Y => Ok(BlockType::MouseY(MouseY { prev, next })),
             SENSING_SET_DRAG_MODE => Ok(BlockType::SetDragMode(SetDragMode {
-                option: DraggableOption::from(val1),
+                option: menu(&fields, "DRAG_MODE", DraggableOption::from, warnings)?,
                 prev,
                 next,
             })),
             SENSING_MOUSE_DOWN => Ok(BlockType::MouseDown(MouseDown { prev, next })),
             SENSING_KEY_PRESSED => Ok(BlockType::KeyPressed(KeyPressed {
-                key: Key::from(val1),
+                key: input("KEY_OPTION"),
+                prev,
+                next,
+            })),
+            SENSING_KEY_OPTIONS => Ok(BlockType::KeyOptions(KeyOptions {
+                key: Key::from(field("KEY_OPTION")),
                 prev,
                 next,
             })),
             SENSING_CURRENT => Ok(BlockType::CurrentTime(CurrentTime {
-                option: CurrentTimeOption::from(val1),
+                option: menu(&fields, "CURRENTMENU", CurrentTimeOption::from, warnings)?,
                 prev,
                 next,
             })),
             SENSING_DAYS_SINCE_2000 => Ok(BlockType::DaysSince2000(DaysSince2000 { prev, next })),
             SENSING_LOUDNESS => Ok(BlockType::Loudness(Loudness { prev, next })),
-            SENSING_LOUD => {
-                todo!() // What?
-            }
-            SENSING_ASK_AND_WAIT => {
-                todo!()
-            }
+            // What?
+            SENSING_LOUD => Err(unsupported(&opcode)),
+            SENSING_ASK_AND_WAIT => Ok(BlockType::AskAndWait(AskAndWait {
+                question: input("QUESTION"),
+                prev,
+                next,
+            })),
             SENSING_ANSWER => Ok(BlockType::Answer(Answer { prev, next })),
             SENSING_USERNAME => Ok(BlockType::Username(Username { prev, next })),
-            SENSING_USER_ID => {
-                todo!()
-            }
+            SENSING_USER_ID => Err(unsupported(&opcode)),
 
             OPERATOR_ADD => Ok(BlockType::Add(Add {
-                a: val1,
-                b: val2,
+                a: input("NUM1"),
+                b: input("NUM2"),
                 prev,
                 next,
             })),
             OPERATOR_SUBTRACT => Ok(BlockType::Sub(Sub {
-                a: val1,
-                b: val2,
+                a: input("NUM1"),
+                b: input("NUM2"),
                 prev,
                 next,
             })),
             OPERATOR_MULTIPLY => Ok(BlockType::Mul(Mul {
-                a: val1,
-                b: val2,
+                a: input("NUM1"),
+                b: input("NUM2"),
                 prev,
                 next,
             })),
             OPERATOR_DIVIDE => Ok(BlockType::Divide(Divide {
-                a: val1,
-                b: val2,
+                a: input("NUM1"),
+                b: input("NUM2"),
                 prev,
                 next,
             })),
             OPERATOR_LESSER_THEN => Ok(BlockType::LesserThen(LesserThen {
-                a: val1,
-                b: val2,
+                a: input("OPERAND1"),
+                b: input("OPERAND2"),
                 prev,
                 next,
             })),
             OPERATOR_EQUALS => Ok(BlockType::EqualTo(EqualTo {
-                a: val1,
-                b: val2,
+                a: input("OPERAND1"),
+                b: input("OPERAND2"),
                 prev,
                 next,
             })),
             OPERATOR_GREATER_THEN => Ok(BlockType::GreaterThen(GreaterThen {
-                a: val1,
-                b: val2,
+                a: input("OPERAND1"),
+                b: input("OPERAND2"),
                 prev,
                 next,
             })),
             OPERATOR_AND => Ok(BlockType::And(And {
-                a: val1,
-                b: val2,
+                a: optional("OPERAND1"),
+                b: optional("OPERAND2"),
                 prev,
                 next,
             })),
             OPERATOR_OR => Ok(BlockType::Or(Or {
-                a: val1,
-                b: val2,
+                a: optional("OPERAND1"),
+                b: optional("OPERAND2"),
                 prev,
                 next,
             })),
             OPERATOR_NOT => Ok(BlockType::Not(Not {
-                a: val1,
+                a: optional("OPERAND"),
                 prev,
                 next,
             })),
             OPERATOR_RANDOM => Ok(BlockType::PickRandom(PickRandom {
-                min: val1,
-                max: val2,
+                min: input("FROM"),
+                max: input("TO"),
                 prev,
                 next,
             })),
             OPERATOR_JOIN => Ok(BlockType::Join(Join {
-                a: val1,
-                b: val2,
+                a: input("STRING1"),
+                b: input("STRING2"),
                 prev,
                 next,
             })),
             OPERATOR_LETTER_OF => Ok(BlockType::LetterOf(LetterOf {
-                index: val1,
-                a: val2,
+                index: input("LETTER"),
+                a: input("STRING"),
                 prev,
                 next,
             })),
             OPERATOR_LENGTH => Ok(BlockType::LengthOf(LengthOf {
-                a: val1,
+                a: input("STRING"),
                 prev,
                 next,
             })),
             OPERATOR_CONTAINS => Ok(BlockType::Contains(Contains {
-                a: val1,
-                b: val2,
+                a: input("STRING1"),
+                b: input("STRING2"),
                 prev,
                 next,
             })),
             OPERATOR_MOD => Ok(BlockType::Modulo(Modulo {
-                a: val1,
-                b: val2,
+                a: input("NUM1"),
+                b: input("NUM2"),
                 prev,
                 next,
             })),
             OPERATOR_ROUND => Ok(BlockType::Round(Round {
-                a: val1,
+                a: input("NUM"),
                 prev,
                 next,
             })),
-            OPERATOR_MATHOP => Ok(BlockType::Absolute(Absolute {
-                a: val1,
+            OPERATOR_MATHOP => Ok(BlockType::MathOp(MathOp {
+                operator: menu(&fields, "OPERATOR", MathOperator::from, warnings)?,
+                a: input("NUM"),
                 prev,
                 next,
             })),
             SOUND_SOUNDS_MENU => Ok(BlockType::SoundSoundsMenu(SoundSoundsMenu {
-                option: val1,
+                option: field("SOUND_MENU"),
                 prev,
                 next,
             })),
 
             SOUND_EFFECTS_MENU => Ok(BlockType::SoundEffectsMenu(SoundEffectsMenu {
-                option: SoundEffect::from(val1),
+                option: menu(&fields, "EFFECT", SoundEffect::from, warnings)?,
                 prev,
                 next,
             })),
             DATA_VARIABLE => Ok(BlockType::DataGetVariable(DataGetVariable {
-                variable: params.get("VARIABLE").unwrap().clone(),
+                // monitors keep the name in params instead.
+                variable: match fields.get("VARIABLE").or(params.get("VARIABLE")) {
+                    Some(a) => a.clone(),
+                    None => return Err(BlockError::new("fields.VARIABLE", "missing")),
+                },
                 prev,
                 next,
             })),
 
             DATA_SET_VARIABLE_TO => Ok(BlockType::DataSetVariableTo(DataSetVariableTo {
-                variable: fields.get("VARIABLE").unwrap().clone(),
-                value: inputs.get("VALUE").unwrap().clone(),
+                variable: required("VARIABLE")?,
+                value: input("VALUE").unwrap_or(Value::Null),
                 prev,
                 next,
             })),
 
             DATA_CHANGE_VARIABLE_BY => Ok(BlockType::DataChangeVariableBy(DataChangeVariableBy {
-                variable: fields.get("VARIABLE").unwrap().clone(),
-                value: inputs.get("VALUE").unwrap().clone(),
+                variable: required("VARIABLE")?,
+                value: input("VALUE").unwrap_or(Value::Null),
                 prev,
                 next,
             })),
 
             DATA_HIDE_VARIABLE => Ok(BlockType::DataHideVariable(DataHideVariable {
-                variable: fields.get("VARIABLE").unwrap().clone(),
+                variable: required("VARIABLE")?,
                 prev,
                 next,
             })),
 
             DATA_SHOW_VARIABLE => Ok(BlockType::DataShowVariable(DataShowVariable {
-                variable: fields.get("VARIABLE").unwrap().clone(),
+                variable: required("VARIABLE")?,
                 prev,
                 next,
             })),
 
             DATA_LIST_COTNENTS => Ok(BlockType::DataListContents(DataListContents {
-                variable: fields.get("VARIABLE").unwrap().clone(),
+                variable: required("LIST")?,
                 prev,
                 next,
             })),
 
             DATA_ADD_TO_LIST => Ok(BlockType::DataAddToList(DataAddToList {
-                item: inputs.get("ITEM").unwrap().clone(),
-                list: fields.get("LIST").unwrap().clone(),
+                item: input("ITEM").unwrap_or(Value::Null),
+                list: required("LIST")?,
                 prev,
                 next,
             })),
 
             DATA_DELETE_OF_LIST => Ok(BlockType::DataDeleteOfList(DataDeleteOfList {
-                item: inputs.get("INDEX").unwrap().clone(),
-                list: fields.get("LIST").unwrap().clone(),
+                item: input("INDEX").unwrap_or(Value::Null),
+                list: required("LIST")?,
                 prev,
                 next,
             })),
 
             DATA_DELETE_ALL_OF_LIST => Ok(BlockType::DataDeleteAllOfList(DataDeleteAllOfList {
-                list: fields.get("LIST").unwrap().clone(),
+                list: required("LIST")?,
                 prev,
                 next,
             })),
 
             DATA_INSERT_AT_LIST => Ok(BlockType::DataInsertAtList(DataInsertAtList {
-                item: inputs.get("ITEM").unwrap().clone(),
-                list: fields.get("LIST").unwrap().clone(),
-                index: inputs.get("INDEX").unwrap().clone(),
+                item: input("ITEM").unwrap_or(Value::Null),
+                list: required("LIST")?,
+                index: input("INDEX").unwrap_or(Value::Null),
                 prev,
                 next,
             })),
 
             DATA_REPLACE_ITEM_OF_LIST => {
                 Ok(BlockType::DataReplaceItemOfList(DataReplaceItemOfList {
-                    item: inputs.get("ITEM").unwrap().clone(),
-                    list: fields.get("LIST").unwrap().clone(),
-                    index: inputs.get("INDEX").unwrap().clone(),
+                    item: input("ITEM").unwrap_or(Value::Null),
+                    list: required("LIST")?,
+                    index: input("INDEX").unwrap_or(Value::Null),
                     prev,
                     next,
                 }))
             }
 
             DATA_ITEM_OF_LIST => Ok(BlockType::DataItemOfList(DataItemOfList {
-                list: fields.get("LIST").unwrap().clone(),
-                index: inputs.get("INDEX").unwrap().clone(),
+                list: required("LIST")?,
+                index: input("INDEX").unwrap_or(Value::Null),
                 prev,
                 next,
             })),
 
             DATA_LENGTH_OF_LIST => Ok(BlockType::DataLengthOfList(DataLengthOfList {
-                list: fields.get("LIST").unwrap().clone(),
+                list: required("LIST")?,
+                prev,
+                next,
+            })),
+
+            DATA_SHOW_LIST => Ok(BlockType::ShowList(DataShowList {
+                list: required("LIST")?,
+                prev,
+                next,
+            })),
+
+            DATA_HIDE_LIST => Ok(BlockType::HideList(DataHideList {
+                list: required("LIST")?,
                 prev,
                 next,
             })),
 
             DATA_LIST_CONTAINS_ITEM => Ok(BlockType::DataListContainsItem(DataListContainsItem {
-                input: inputs.get("ITEM").unwrap().clone(),
-                list: fields.get("LIST").unwrap().clone(),
+                input: input("ITEM").unwrap_or(Value::Null),
+                list: required("LIST")?,
                 prev,
                 next,
             })),
 
-            PROCEDURES_CALL => Ok(BlockType::ProceduresCall(ProceduresCall { prev, next })),
+            PROCEDURES_CALL => {
+                let signature = mutation(self.hash)?;
+                let arguments = signature
+                    .argument_ids
+                    .iter()
+                    .map(|f| inputs.get(f).cloned())
+                    .collect();
+                Ok(BlockType::ProceduresCall(ProceduresCall {
+                    signature,
+                    arguments,
+                    prev,
+                    next,
+                }))
+            }
             PROCEDURES_DECLARATION => Ok(BlockType::ProceduresDeclaration(ProceduresDeclaration {
                 prev,
                 next,
             })),
             PROCEDURES_DEFINITION => Ok(BlockType::ProceduresDefinition(ProceduresDefinition {
-                block: match inputs.get("custom_block").unwrap() {
-                    Value::Number(_) => todo!(),
-                    Value::String(a) => a.clone(),
-                    Value::Null => todo!(),
+                block: match inputs.get("custom_block") {
+                    Some(Value::String(a)) => a.clone(),
+                    _ => return Err(BlockError::new("inputs.custom_block", "missing prototype")),
                 },
                 prev,
                 next,
             })),
             PROCEDURES_PROTOTYPE => Ok(BlockType::ProceduresPrototype(ProceduresPrototype {
-                mutation: raw
-                    .hash
-                    .get("mutation")
-                    .unwrap()
-                    .deserialize_map(MutationVisitor)
-                    .unwrap(),
+                signature: mutation(self.hash)?,
                 prev,
                 next,
             })),
-
-            // unused opcodes
-            MOTION_GOTO | SOUNDS_BEATS_MENU | MOTION_GLIDE_TO => {
-                Ok(BlockType::UnusedOpcode(UnusedOpcode {
-                    name: raw.opcode.to_string(),
+            ARGUMENT_REPORTER_STRING_NUMBER | ARGUMENT_REPORTER_BOOLEAN => {
+                Ok(BlockType::ArgumentReporter(ArgumentReporter {
+                    name: match field("VALUE") {
+                        Some(Value::String(a)) => a,
+                        Some(Value::Number(a)) => a.to_string(),
+                        _ => String::new(),
+                    },
+                    boolean: opcode == ARGUMENT_REPORTER_BOOLEAN,
                     prev,
                     next,
                 }))
             }
 
-            _ => {
-                #[cfg(debug_assertions)]
-                return Err(format!("invalid opcode {}", raw.opcode)).map_err(de::Error::custom);
-                #[cfg(not(debug_assertions))]
-                Ok(BlockType::InvalidOpcode(InvalidOpcode {
-                    name: raw.opcode.to_string(),
-                    prev,
-                    next,
-                }))
+            // unused opcodes
+            SOUNDS_BEATS_MENU => Ok(BlockType::UnusedOpcode(UnusedOpcode {
+                name: opcode.to_string(),
+                prev,
+                next,
+            })),
+
+            _ => Err(BlockError::new(
+                "opcode",
+                format!("unknown opcode {}", opcode),
+            )),
+        }?;
+        for name in missing.into_inner() {
+            let err = BlockError::new(format!("inputs.{}", name), "missing");
+            match warnings {
+                Some(a) => a.push(err),
+                None => return Err(err),
             }
-        };
-        let block_type = block_type;
-        block_type
+        }
+        Ok(block)
     }
 }
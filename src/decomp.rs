@@ -1,28 +1,62 @@
 #![allow(dead_code)]
 
-use serde::{Deserialize, Deserializer};
-use serde_derive::Deserialize;
-use serde_json::Value;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 /// This module contains the structure of a Scratch project and
 /// the functions for interacting with it.
-use std::{collections::HashMap, fs::read_to_string};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{read_to_string, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
 
-use crate::blocks;
+use crate::{
+    assets::{AssetStore, DirStore, HttpStore},
+    blocks::{self, BlockType},
+    error::{BlockError, Warning, YaseError},
+    sb2,
+    serialize::{blocks_json, Context},
+};
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct Project {
-    #[serde(rename = "targets")]
-    #[serde(default)]
     sprites: Vec<Sprite>,
-    #[serde(default)]
     extensions: Vec<String>,
+    /// Kept as they are, since nothing here uses them but renames.
+    pub(crate) monitors: Vec<Value>,
+    meta: Option<Value>,
+    /// Anything else project.json had.
+    extra: Map<String, Value>,
 
-    #[serde(default)]
     cur: usize,
+
+    /// The directory the costumes and sounds are in.
+    assets: Option<PathBuf>,
+
+    /// What a lenient load got past.
+    warnings: Vec<Warning>,
+}
+
+/// How to load a project.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOptions {
+    /// Fail on the first block that can't be read. Otherwise it's replaced by
+    /// an InvalidOpcode (or a bad dropdown or missing input is left empty),
+    /// and a warning is kept instead.
+    pub strict: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions { strict: true }
+    }
 }
 
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct Sprite {
+    #[serde(rename = "isStage")]
     #[serde(default)]
     pub is_stage: bool,
     #[serde(default)]
@@ -35,114 +69,649 @@ pub struct Sprite {
     pub broadcasts: HashMap<String, String>,
     #[serde(default)]
     pub blocks: HashMap<String, blocks::BlockType>,
+    #[serde(rename = "currentCostume")]
     #[serde(default)]
-    pub current_costume: f32,
+    pub current_costume: f64,
     #[serde(default)]
     pub costumes: Vec<Costume>,
     #[serde(default)]
     pub sounds: Vec<Sound>,
     #[serde(default)]
-    pub volume: f32,
+    pub volume: f64,
+    #[serde(rename = "layerOrder")]
+    #[serde(default)]
+    pub layer_order: f64,
     #[serde(default)]
-    pub layer_order: f32,
+    pub tempo: f64,
+    #[serde(rename = "videoTransparency")]
     #[serde(default)]
-    pub tempo: f32,
+    pub video_transparency: f64,
+    #[serde(rename = "videoState")]
     #[serde(default)]
-    pub video_transparency: f32,
+    pub video_state: Option<String>,
+    #[serde(rename = "textToSpeechLanguage")]
     #[serde(default)]
-    pub video_state: f32,
+    pub tts_language: Option<String>,
+    #[serde(rename = "x")]
     #[serde(default)]
-    pub tts_language: String,
+    pub position_x: f64,
+    #[serde(rename = "y")]
     #[serde(default)]
-    pub position_x: f32,
+    pub position_y: f64,
     #[serde(default)]
-    pub position_y: f32,
+    pub visible: bool,
     #[serde(default)]
-    pub size: f32,
+    pub size: f64,
     #[serde(default)]
-    pub direction: f32,
+    pub direction: f64,
     #[serde(default)]
     pub draggable: bool,
+    #[serde(rename = "rotationStyle")]
     #[serde(default)]
     pub rotation_style: String,
+    #[serde(default)]
+    pub comments: Map<String, Value>,
+    /// Anything else the target had.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+
+    /// What project.json had for each block, for everything the blocks
+    /// themselves don't keep (shadows, positions, comments). Blocks that are
+    /// still the same are written back exactly as they were.
+    #[serde(skip)]
+    pub(crate) originals: HashMap<String, Value>,
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct Costume {
     #[serde(rename = "assetId")]
     #[serde(default)]
     asset_id: String,
     #[serde(default)]
-    name: String,
+    pub(crate) name: String,
+    /// Only bitmaps have this.
+    #[serde(rename = "bitmapResolution")]
     #[serde(default)]
-    bitmap_resolution: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_number")]
+    bitmap_resolution: Option<f64>,
+    #[serde(rename = "md5ext")]
     #[serde(default)]
     md5: String,
+    #[serde(rename = "dataFormat")]
     #[serde(default)]
     data_format: String,
+    #[serde(rename = "rotationCenterX")]
     #[serde(default)]
-    rotation_center_x: String,
+    #[serde(serialize_with = "serialize_number")]
+    rotation_center_x: f64,
+    #[serde(rename = "rotationCenterY")]
     #[serde(default)]
-    rotation_center_y: String,
+    #[serde(serialize_with = "serialize_number")]
+    rotation_center_y: f64,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct Sound {
     #[serde(rename = "assetId")]
     asset_id: String,
-    name: String,
+    pub(crate) name: String,
     #[serde(rename = "dataFormat")]
     data_format: String,
-    rate: f32,
+    #[serde(serialize_with = "serialize_number")]
+    rate: f64,
     #[serde(rename = "sampleCount")]
-    sample_count: f32,
+    #[serde(serialize_with = "serialize_number")]
+    sample_count: f64,
     #[serde(rename = "md5ext")]
     md5: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Variable {
-    name: String,
+    pub(crate) name: String,
     value: Value,
+    /// Cloud variables have a `true` after the value.
+    rest: Vec<Value>,
 }
 
 impl Project {
-    pub fn new(id: Option<i32>) -> Result<Project, String> {
-        let json: String = match id {
-            Some(a) => {
-                match reqwest::blocking::get(format!("https://projects.scratch.mit.edu/{}", a)) {
-                    Ok(a) => match a.text() {
-                        Ok(a) => a,
-                        Err(err) => {
-                            return Err(format!("error reading recieved json to string: {}", err));
-                        }
-                    },
-                    Err(err) => {
-                        return Err(format!("error getting json: {}", err));
-                    }
+    /// Downloads a project from the Scratch website.
+    pub fn new(id: i32) -> Result<Project, YaseError> {
+        Project::load(id, LoadOptions::default())
+    }
+
+    pub fn load(id: i32, options: LoadOptions) -> Result<Project, YaseError> {
+        Project::load_from(&HttpStore::projects(), id, options)
+    }
+
+    /// A project by its id, from wherever the store keeps them, like a local
+    /// mirror or a DirStore of project.json files named by id. Only the
+    /// project.json is read, not the assets.
+    pub fn load_from(
+        store: &impl AssetStore,
+        id: i32,
+        options: LoadOptions,
+    ) -> Result<Project, YaseError> {
+        let json = match store.read(&id.to_string())? {
+            Some(a) => a,
+            None => {
+                let message = format!("{}: no project {}", store.location(), id);
+                return Err(YaseError::Network(message));
+            }
+        };
+        let json = String::from_utf8(json).map_err(|err| YaseError::Json {
+            path: String::new(),
+            message: err.to_string(),
+        })?;
+        Project::from_json_str(&json, options)
+    }
+
+    /// Blocks are read one by one instead of with the rest, so that errors can
+    /// tell which block they're about.
+    pub fn from_json_str(json: &str, options: LoadOptions) -> Result<Project, YaseError> {
+        match serde_json::from_str(json) {
+            Ok(a) => Project::read(a, options),
+            Err(err) => Err(YaseError::Json {
+                path: String::new(),
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    pub fn from_json_reader(json: impl Read, options: LoadOptions) -> Result<Project, YaseError> {
+        match serde_json::from_reader(json) {
+            Ok(a) => Project::read(a, options),
+            Err(err) if err.is_io() => Err(YaseError::Io(err.to_string())),
+            Err(err) => Err(YaseError::Json {
+                path: String::new(),
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    /// An .sb3 that's already in memory. Only project.json is read, the
+    /// costumes and sounds can be had with a ZipStore.
+    pub fn from_sb3_bytes(data: &[u8], options: LoadOptions) -> Result<Project, YaseError> {
+        let json = project_json(data, "sb3")?;
+        Project::from_json_str(&json, options)
+    }
+
+    /// A Scratch 2 project, as an .sb2 or just its project.json. Its
+    /// blocks are read the same as everything else's, once they're turned
+    /// into Scratch 3 ones.
+    pub fn load_sb2(path: impl AsRef<Path>, options: LoadOptions) -> Result<Project, YaseError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let data =
+            std::fs::read(path).map_err(|err| YaseError::Io(format!("{}: {}", name, err)))?;
+        let json = match data.starts_with(b"PK") {
+            true => project_json(&data, &name)?,
+            false => String::from_utf8(data).map_err(|err| YaseError::Json {
+                path: String::new(),
+                message: err.to_string(),
+            })?,
+        };
+        Project::from_sb2(&json, options)
+    }
+
+    pub fn from_sb2(json: &str, options: LoadOptions) -> Result<Project, YaseError> {
+        let json: Value = serde_json::from_str(json).map_err(|err| YaseError::Json {
+            path: String::new(),
+            message: err.to_string(),
+        })?;
+        Project::read(sb2::convert(&json)?, options)
+    }
+
+    /// An unzipped project: project.json, with its costumes and sounds next
+    /// to it named by their md5ext. Every one of them has to be there, and
+    /// be what its name says it is.
+    pub fn from_dir(path: impl AsRef<Path>, options: LoadOptions) -> Result<Project, YaseError> {
+        let path = path.as_ref();
+        let file = path.join("project.json");
+        let json = read_to_string(&file)
+            .map_err(|err| YaseError::Io(format!("{}: {}", file.display(), err)))?;
+        let mut project = Project::from_json_str(&json, options)?;
+        project.fetch_assets(&DirStore::new(path))?;
+        project.assets = Some(path.to_path_buf());
+        Ok(project)
+    }
+
+    fn read(json: Value, options: LoadOptions) -> Result<Project, YaseError> {
+        let invalid = |path: String, message: String| YaseError::Json { path, message };
+        let mut json = match json {
+            Value::Object(a) => a,
+            _ => return Err(invalid(String::new(), "expected an object".to_string())),
+        };
+
+        let mut project = Project::default();
+        if let Some(a) = json.remove("extensions") {
+            project.extensions = Vec::deserialize(a)
+                .map_err(|f| invalid("extensions".to_string(), f.to_string()))?;
+        }
+        if let Some(a) = json.remove("monitors") {
+            project.monitors =
+                Vec::deserialize(a).map_err(|f| invalid("monitors".to_string(), f.to_string()))?;
+        }
+        project.meta = json.remove("meta");
+        let targets = match json.remove("targets") {
+            Some(Value::Array(a)) => a,
+            Some(_) => {
+                return Err(invalid(
+                    "targets".to_string(),
+                    "expected an array".to_string(),
+                ))
+            }
+            None => Vec::new(),
+        };
+        project.extra = json;
+        for (i, mut target) in targets.into_iter().enumerate() {
+            let blocks = target.as_object_mut().and_then(|f| f.remove("blocks"));
+            let mut sprite = Sprite::deserialize(&target)
+                .map_err(|f| invalid(format!("targets[{}]", i), f.to_string()))?;
+            let blocks = match blocks {
+                Some(Value::Object(a)) => a,
+                None => Map::new(),
+                Some(_) => {
+                    return Err(invalid(
+                        format!("targets[{}].blocks", i),
+                        "expected an object".to_string(),
+                    ))
                 }
+            };
+            for (id, block) in blocks {
+                let warn = |err: BlockError| {
+                    let mut path = format!("targets[{}].blocks[{:?}]", i, id);
+                    if !err.path.is_empty() {
+                        path = format!("{}.{}", path, err.path);
+                    }
+                    Warning {
+                        sprite: sprite.name.clone(),
+                        id: id.clone(),
+                        opcode: block
+                            .get("opcode")
+                            .and_then(|f| f.as_str())
+                            .unwrap_or_default()
+                            .to_string(),
+                        path,
+                        message: err.message,
+                    }
+                };
+                let parsed = match options.strict {
+                    true => BlockType::from_json(&block).map_err(warn)?,
+                    false => {
+                        let (parsed, warnings) = BlockType::from_json_lenient(&block);
+                        project.warnings.extend(warnings.into_iter().map(warn));
+                        parsed
+                    }
+                };
+                sprite.blocks.insert(id.clone(), parsed);
+                sprite.originals.insert(id, block);
             }
-            None => match read_to_string("./test.json") {
-                Ok(a) => a,
-                Err(err) => {
-                    return Err(format!("error reading default file: {}", err));
+            for (id, at, to) in loops(&sprite.blocks) {
+                let opcode = sprite.originals[&id]["opcode"].as_str().unwrap_or_default();
+                let warning = Warning {
+                    sprite: sprite.name.clone(),
+                    opcode: opcode.to_string(),
+                    path: format!("targets[{}].blocks[{:?}].{}", i, id, at),
+                    message: format!("loops back to {}", to),
+                    id,
+                };
+                match options.strict {
+                    true => return Err(warning.into()),
+                    false => project.warnings.push(warning),
                 }
-            },
+            }
+            project.sprites.push(sprite);
+        }
+        Ok(project)
+    }
+
+    /// What couldn't be read, if the project was loaded leniently.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn extensions(&self) -> &Vec<String> {
+        &self.extensions
+    }
+
+    pub fn sprites(&self) -> &Vec<Sprite> {
+        &self.sprites
+    }
+
+    pub fn sprites_mut(&mut self) -> &mut Vec<Sprite> {
+        &mut self.sprites
+    }
+
+    /// A broadcast's name. Blocks sometimes give it by id instead, which is
+    /// looked up in every sprite's broadcasts.
+    pub fn broadcast_name(&self, broadcast: &str) -> String {
+        self.sprites
+            .iter()
+            .find_map(|f| f.broadcasts.get(broadcast))
+            .cloned()
+            .unwrap_or_else(|| broadcast.to_string())
+    }
+
+    /// Which sprite a variable or list belongs to, by index: the one using it
+    /// if it has one by that name, otherwise the stage if that has one. Ones
+    /// that are nowhere are made by whoever uses them.
+    pub fn owner(&self, sprite: usize, name: &str, list: bool) -> usize {
+        let has = |f: &Sprite| match list {
+            true => f
+                .lists
+                .keys()
+                .any(|id| f.list(id).is_some_and(|f| f.0 == name)),
+            false => f.variables.values().any(|f| f.name() == name),
         };
+        if has(&self.sprites[sprite]) {
+            return sprite;
+        }
+        match self.sprites.iter().position(|f| f.is_stage && has(f)) {
+            Some(a) => a,
+            None => sprite,
+        }
+    }
+
+    /// The project as project.json has it.
+    pub fn to_json(&self) -> Value {
+        let stage = self.sprites.iter().find(|f| f.is_stage);
+        let mut json = self.extra.clone();
+        json.insert(
+            "targets".to_string(),
+            self.sprites.iter().map(|f| f.to_json(stage)).collect(),
+        );
+        json.insert("monitors".to_string(), json!(self.monitors));
+        json.insert("extensions".to_string(), json!(self.extensions));
+        let meta = self.meta.clone().unwrap_or_else(|| {
+            json!({
+                "semver": "3.0.0",
+                "vm": "0.2.0",
+                "agent": concat!("yase/", env!("CARGO_PKG_VERSION")),
+            })
+        });
+        json.insert("meta".to_string(), meta);
+        Value::Object(json)
+    }
+
+    /// The md5ext of every costume and sound.
+    fn asset_names(&self) -> BTreeSet<String> {
+        self.sprites
+            .iter()
+            .flat_map(|f| {
+                f.costumes
+                    .iter()
+                    .map(Costume::md5ext)
+                    .chain(f.sounds.iter().map(Sound::md5ext))
+            })
+            .collect()
+    }
+
+    /// Every costume and sound, by md5ext. If any can't be found, the error
+    /// lists all of them.
+    pub fn fetch_assets(
+        &self,
+        store: &dyn AssetStore,
+    ) -> Result<BTreeMap<String, Vec<u8>>, YaseError> {
+        let mut assets = BTreeMap::new();
+        let mut missing = Vec::new();
+        for name in self.asset_names() {
+            match store.get(&name)? {
+                Some(a) => {
+                    assets.insert(name, a);
+                }
+                None => missing.push(name),
+            }
+        }
+        match missing.is_empty() {
+            true => Ok(assets),
+            false => Err(YaseError::MissingAssets {
+                dir: store.location(),
+                assets: missing,
+            }),
+        }
+    }
+
+    /// Where the costumes and sounds are, if the project was loaded from a
+    /// directory.
+    pub fn assets(&self) -> Option<&Path> {
+        self.assets.as_deref()
+    }
+
+    /// Writes the project out as an .sb3, with its costumes and sounds taken
+    /// from a store. Nothing is written if any of them are missing or aren't
+    /// what their name says they are.
+    pub fn save_sb3(
+        &self,
+        path: impl AsRef<Path>,
+        store: &dyn AssetStore,
+    ) -> Result<(), YaseError> {
+        let assets = self.fetch_assets(store)?;
 
-        // base project
-        let project: Project = match serde_json::from_str(&json) {
-            Ok(a) => a,
-            Err(err) => {
-                return Err(format!("error unmarshalling json to project: {}", err));
+        let path = path.as_ref();
+        let io = |err: std::io::Error| YaseError::Io(format!("{}: {}", path.display(), err));
+        let mut zip = zip::ZipWriter::new(File::create(path).map_err(io)?);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let json = serde_json::to_vec(self).map_err(|f| io(f.into()))?;
+        zip.start_file("project.json", options)
+            .map_err(|f| io(f.into()))?;
+        zip.write_all(&json).map_err(io)?;
+        for (name, data) in assets {
+            zip.start_file(name, options).map_err(|f| io(f.into()))?;
+            zip.write_all(&data).map_err(io)?;
+        }
+        zip.finish().map_err(|f| io(f.into()))?;
+        Ok(())
+    }
+}
+
+impl Serialize for Project {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(s)
+    }
+}
+
+/// The same as a strict Project::from_json_str.
+impl<'de> Deserialize<'de> for Project {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let json = Value::deserialize(d)?;
+        Project::read(json, LoadOptions::default()).map_err(de::Error::custom)
+    }
+}
+
+/// Blocks that lead back to themselves, through their next block, their
+/// inputs or their substacks: the block, where in it, and the block it leads
+/// back to. Nothing the editor saves does that, but project.json can say so.
+fn loops(blocks: &HashMap<String, BlockType>) -> Vec<(String, String, String)> {
+    // inputs first, then the next block, the same order they're walked in.
+    let leads_to = |id: &str| -> Vec<(String, &String)> {
+        let block = &blocks[id];
+        let mut out: Vec<_> = block
+            .inputs()
+            .into_iter()
+            .filter_map(|(name, val)| match val {
+                blocks::Value::String(a) => blocks
+                    .get_key_value(a)
+                    .map(|f| (format!("inputs.{}", name), f.0)),
+                _ => None,
+            })
+            .collect();
+        if let Some((to, _)) = block.next().and_then(|f| blocks.get_key_value(&f)) {
+            out.push(("next".to_string(), to));
+        }
+        out
+    };
+    let mut ids: Vec<&String> = blocks.keys().collect();
+    ids.sort();
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut on_path: HashSet<&str> = HashSet::new();
+    let mut loops = Vec::new();
+    for id in ids {
+        if done.contains(id.as_str()) {
+            continue;
+        }
+        on_path.insert(id);
+        let mut path = vec![(id, leads_to(id), 0)];
+        while let Some((from, out, i)) = path.last_mut() {
+            let from: &String = from;
+            match out.get(*i).cloned() {
+                Some((at, to)) => {
+                    *i += 1;
+                    if on_path.contains(to.as_str()) {
+                        loops.push((from.clone(), at, to.clone()));
+                    } else if done.insert(to) {
+                        on_path.insert(to);
+                        path.push((to, leads_to(to), 0));
+                    }
+                }
+                None => {
+                    on_path.remove(from.as_str());
+                    done.insert(from);
+                    path.pop();
+                }
             }
+        }
+    }
+    loops
+}
+
+/// project.json out of a zipped project. The name is only for errors.
+fn project_json(data: &[u8], name: &str) -> Result<String, YaseError> {
+    let io = |err: std::io::Error| YaseError::Io(format!("{}: {}", name, err));
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data)).map_err(|f| io(f.into()))?;
+    let mut json = String::new();
+    zip.by_name("project.json")
+        .map_err(|f| io(f.into()))?
+        .read_to_string(&mut json)
+        .map_err(io)?;
+    Ok(json)
+}
+
+impl Sprite {
+    /// Lists are stored as [name, [items...]], keyed by id.
+    pub fn list(&self, id: &str) -> Option<(&str, &Vec<Value>)> {
+        let list = self.lists.get(id)?;
+        match (list.first(), list.get(1)) {
+            (Some(Value::String(name)), Some(Value::Array(items))) => Some((name, items)),
+            _ => None,
+        }
+    }
+
+    /// The sprite as project.json has it. Variables, lists and broadcasts are
+    /// looked up in the stage too, which is needed to get their ids right.
+    pub fn to_json(&self, stage: Option<&Sprite>) -> Value {
+        let mut json = json!({
+            "isStage": self.is_stage,
+            "name": self.name,
+            "variables": self.variables,
+            "lists": self.lists,
+            "broadcasts": self.broadcasts,
+            "blocks": blocks_json(&Context::new(self, stage)),
+            "comments": self.comments,
+            "currentCostume": number(self.current_costume),
+            "costumes": self.costumes,
+            "sounds": self.sounds,
+            "volume": number(self.volume),
+            "layerOrder": number(self.layer_order),
+        });
+        let rest = match self.is_stage {
+            true => json!({
+                "tempo": number(self.tempo),
+                "videoTransparency": number(self.video_transparency),
+                "videoState": self.video_state.as_deref().unwrap_or("on"),
+                "textToSpeechLanguage": self.tts_language,
+            }),
+            false => json!({
+                "visible": self.visible,
+                "x": number(self.position_x),
+                "y": number(self.position_y),
+                "size": number(self.size),
+                "direction": number(self.direction),
+                "draggable": self.draggable,
+                "rotationStyle": self.rotation_style,
+            }),
         };
+        if let (Value::Object(json), Value::Object(rest)) = (&mut json, rest) {
+            json.extend(rest);
+            for (key, value) in &self.extra {
+                json.entry(key).or_insert_with(|| value.clone());
+            }
+        }
+        json
+    }
+}
 
-        Ok(project)
+/// A number the way JavaScript writes it, so whole ones don't get a `.0`.
+fn number(val: f64) -> Value {
+    match val.fract() == 0.0 && val.abs() < 1e15 {
+        true => json!(val as i64),
+        false => json!(val),
     }
+}
 
-    pub fn extensions(&self) -> &Vec<String> {
-        return &self.extensions;
+fn serialize_number<S: Serializer>(val: &f64, s: S) -> Result<S::Ok, S::Error> {
+    number(*val).serialize(s)
+}
+
+fn serialize_optional_number<S: Serializer>(val: &Option<f64>, s: S) -> Result<S::Ok, S::Error> {
+    val.map(number).serialize(s)
+}
+
+impl Serialize for Sprite {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.to_json(None).serialize(s)
+    }
+}
+
+impl Costume {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of its file, in an .sb3 or on the asset server.
+    pub fn md5ext(&self) -> String {
+        match self.md5.is_empty() {
+            true => format!("{}.{}", self.asset_id, self.data_format),
+            false => self.md5.clone(),
+        }
+    }
+}
+
+impl Sound {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of its file, in an .sb3 or on the asset server.
+    pub fn md5ext(&self) -> String {
+        match self.md5.is_empty() {
+            true => format!("{}.{}", self.asset_id, self.data_format),
+            false => self.md5.clone(),
+        }
+    }
+
+    /// How long the sound plays for, in seconds.
+    pub fn duration(&self) -> f64 {
+        if self.rate == 0.0 {
+            return 0.0;
+        }
+        self.sample_count / self.rate
+    }
+}
+
+impl Variable {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
     }
 }
 
@@ -150,27 +719,305 @@ impl Iterator for Project {
     type Item = Sprite;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let o = match self.sprites.get(self.cur) {
-            Some(a) => Some(a.clone()),
-            None => None,
-        };
+        let o = self.sprites.get(self.cur).cloned();
         self.cur += 1;
         o
     }
 }
 
+impl Serialize for Variable {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut json = vec![json!(self.name), self.value.clone()];
+        json.extend(self.rest.iter().cloned());
+        json.serialize(s)
+    }
+}
+
 impl<'de> Deserialize<'de> for Variable {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
         let v: Vec<Value> = Vec::deserialize(d)?;
         let mut vi = v.into_iter();
-        let name = vi.next();
-        let value = vi.next();
-        let fuck: Variable = Variable {
-            // doing this without unwrap isn't possible because rust
-            // complains about referencing a which falls out of scope
-            name: name.unwrap().to_string().replace("\"", ""),
-            value: value.unwrap(),
+        let name = match vi.next() {
+            Some(a) => a.to_string().replace("\"", ""),
+            None => return Err(de::Error::custom("variable has no name")),
         };
-        return Ok(fuck);
+        let value = vi.next().unwrap_or(Value::Null);
+        Ok(Variable {
+            name,
+            value,
+            rest: vi.collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scratchblocks,
+        test_util::{check_blocks, test_project},
+    };
+
+    /// A project with just a stage, with these blocks.
+    fn load(blocks: Value, strict: bool) -> Result<Project, YaseError> {
+        let json = json!({"targets": [{"isStage": true, "name": "Stage", "blocks": blocks}]});
+        Project::from_json_str(&json.to_string(), LoadOptions { strict })
+    }
+
+    #[test]
+    fn loads_by_id() {
+        let dir = std::env::temp_dir().join(format!("yase-by-id-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("42"), test_project().to_json().to_string()).unwrap();
+        let store = DirStore::new(&dir);
+        let project = Project::load_from(&store, 42, LoadOptions::default()).unwrap();
+        assert_eq!(project.to_json(), test_project().to_json());
+        assert!(matches!(
+            Project::load_from(&store, 7, LoadOptions::default()),
+            Err(YaseError::Network(a)) if a.ends_with("no project 7")
+        ));
+    }
+
+    #[test]
+    fn needs_every_asset_in_a_dir() {
+        let dir = std::env::temp_dir().join(format!("yase-from-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let svg = "<svg/>";
+        let md5 = format!("{:x}", md5::compute(svg));
+        let md5ext = format!("{}.svg", md5);
+        let json = json!({"targets": [{
+            "isStage": true,
+            "name": "Stage",
+            "costumes": [{"name": "backdrop1", "assetId": md5, "md5ext": md5ext, "dataFormat": "svg"}],
+            // a block that only a lenient load gets past.
+            "blocks": {"a": {"opcode": "data_setvariableto", "inputs": {}, "fields": {}}},
+        }]});
+        std::fs::write(dir.join("project.json"), json.to_string()).unwrap();
+
+        let lenient = LoadOptions { strict: false };
+        assert_eq!(
+            Project::from_dir(&dir, lenient).unwrap_err(),
+            YaseError::MissingAssets {
+                dir: dir.display().to_string(),
+                assets: vec![md5ext.clone()],
+            }
+        );
+        std::fs::write(dir.join(&md5ext), svg).unwrap();
+        assert!(matches!(
+            Project::from_dir(&dir, LoadOptions::default()),
+            Err(YaseError::Block { .. })
+        ));
+        let project = Project::from_dir(&dir, lenient).unwrap();
+        assert_eq!(project.assets(), Some(dir.as_path()));
+        assert_eq!(project.warnings().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn says_which_block_is_wrong() {
+        let blocks = json!({"a": {"opcode": "data_setvariableto", "inputs": {}, "fields": {}}});
+        assert_eq!(
+            load(blocks.clone(), true).unwrap_err(),
+            YaseError::Block {
+                sprite: "Stage".to_string(),
+                id: "a".to_string(),
+                opcode: "data_setvariableto".to_string(),
+                path: "targets[0].blocks[\"a\"].fields.VARIABLE".to_string(),
+                message: "missing".to_string(),
+            }
+        );
+        let project = load(blocks, false).unwrap();
+        assert!(matches!(
+            project.sprites()[0].blocks["a"],
+            BlockType::InvalidOpcode(_)
+        ));
+        let warnings: Vec<String> = project.warnings().iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            warnings,
+            ["block a (data_setvariableto) in Stage at targets[0].blocks[\"a\"].fields.VARIABLE: missing"]
+        );
+    }
+
+    #[test]
+    fn warns_about_missing_inputs() {
+        let blocks = json!({
+            "a": {"opcode": "motion_movesteps", "inputs": {}, "fields": {}},
+            "b": {"opcode": "looks_setsizeto", "inputs": {}, "fields": {}},
+            // empty boolean slots and substacks aren't saved at all.
+            "c": {"opcode": "control_if", "inputs": {}, "fields": {}},
+        });
+        let project = load(blocks.clone(), false).unwrap();
+        let mut warnings: Vec<String> = project.warnings().iter().map(|f| f.to_string()).collect();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            [
+                "block a (motion_movesteps) in Stage at targets[0].blocks[\"a\"].inputs.STEPS: missing",
+                "block b (looks_setsizeto) in Stage at targets[0].blocks[\"b\"].inputs.SIZE: missing",
+            ]
+        );
+        assert!(matches!(
+            project.sprites()[0].blocks["a"],
+            BlockType::Move(_)
+        ));
+        let err = load(json!({"a": blocks["a"]}), true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid block a (motion_movesteps) in Stage at targets[0].blocks[\"a\"].inputs.STEPS: missing"
+        );
+    }
+
+    #[test]
+    fn stops_at_loops() {
+        let blocks = json!({
+            "a": {"opcode": "event_whenflagclicked", "next": "b", "parent": null,
+                "inputs": {}, "fields": {}, "topLevel": true, "x": 0, "y": 0},
+            "b": {"opcode": "motion_ifonedgebounce", "next": "c", "parent": "a",
+                "inputs": {}, "fields": {}},
+            "c": {"opcode": "looks_nextcostume", "next": "b", "parent": "b",
+                "inputs": {}, "fields": {}},
+        });
+        assert_eq!(
+            load(blocks.clone(), true).unwrap_err().to_string(),
+            "invalid block c (looks_nextcostume) in Stage at targets[0].blocks[\"c\"].next: loops back to b"
+        );
+        let project = load(blocks, false).unwrap();
+        assert_eq!(project.warnings().len(), 1);
+        let linked = crate::script::Linked::new(&project.sprites()[0]);
+        assert_eq!(linked.stack(Some("a")).count(), 3);
+        crate::runtime::ir::Program::compile(&project);
+        assert_eq!(crate::diff::diff(&project, &project), []);
+
+        // a forever inside itself, and an add that adds itself.
+        let blocks = json!({
+            "a": {"opcode": "event_whenflagclicked", "next": "b", "parent": null,
+                "inputs": {}, "fields": {}, "topLevel": true, "x": 0, "y": 0},
+            "b": {"opcode": "control_forever", "next": "c", "parent": "a",
+                "inputs": {"SUBSTACK": [2, "b"]}, "fields": {}},
+            "c": {"opcode": "looks_say", "next": null, "parent": "b",
+                "inputs": {"MESSAGE": [3, "d", [10, "hi"]]}, "fields": {}},
+            "d": {"opcode": "operator_add", "next": null, "parent": "c",
+                "inputs": {"NUM1": [3, "d", [4, "1"]], "NUM2": [3, "d", [4, "2"]]}, "fields": {}},
+        });
+        assert_eq!(
+            load(blocks.clone(), true).unwrap_err().to_string(),
+            "invalid block b (control_forever) in Stage at targets[0].blocks[\"b\"].inputs.SUBSTACK: loops back to b"
+        );
+        let project = load(blocks, false).unwrap();
+        let warnings: Vec<&str> = project.warnings().iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "targets[0].blocks[\"b\"].inputs.SUBSTACK",
+                "targets[0].blocks[\"d\"].inputs.NUM1",
+                "targets[0].blocks[\"d\"].inputs.NUM2",
+            ]
+        );
+        let program = crate::runtime::ir::Program::compile(&project);
+        crate::runtime::types::Types::infer(&project, &program);
+    }
+
+    /// All of these used to panic.
+    #[test]
+    fn explains_what_is_wrong() {
+        let cases = [
+            (
+                json!({"opcode": "motion_movesteps", "inputs": {"STEPS": 5}, "fields": {}}),
+                "(motion_movesteps) in Stage at targets[0].blocks[\"a\"].inputs.STEPS: no value in 5",
+            ),
+            (
+                json!({"opcode": "motion_movesteps", "inputs": [], "fields": {}}),
+                "(motion_movesteps) in Stage at targets[0].blocks[\"a\"].inputs: expected an object",
+            ),
+            (
+                json!({"opcode": "procedures_call", "inputs": {}, "fields": {}}),
+                "(procedures_call) in Stage at targets[0].blocks[\"a\"].mutation: custom block has no mutation",
+            ),
+            (
+                json!({"opcode": "control_create_clone_of_menu", "inputs": {},
+                    "fields": {"CLONE_OPTION": [true, null]}}),
+                "(control_create_clone_of_menu) in Stage at targets[0].blocks[\"a\"].fields.CLONE_OPTION: \
+                 expected a number or a string, got true",
+            ),
+            (
+                json!({"opcode": "data_changevariableby", "inputs": {}, "fields": {}}),
+                "(data_changevariableby) in Stage at targets[0].blocks[\"a\"].fields.VARIABLE: missing",
+            ),
+            (
+                json!({"inputs": {}, "fields": {}}),
+                "() in Stage at targets[0].blocks[\"a\"]: no opcode",
+            ),
+            (
+                json!(5),
+                "() in Stage at targets[0].blocks[\"a\"]: expected a block, got 5",
+            ),
+        ];
+        for (block, message) in cases {
+            let err = load(json!({ "a": block }), true).unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid block a {}", message));
+        }
+
+        let cases = [
+            (
+                "{",
+                "invalid project: EOF while parsing an object at line 1 column 1",
+            ),
+            ("[]", "invalid project: expected an object"),
+            (
+                r#"{"targets": 5}"#,
+                "invalid project at targets: expected an array",
+            ),
+            (
+                r#"{"targets": [{"blocks": 5}]}"#,
+                "invalid project at targets[0].blocks: expected an object",
+            ),
+            (
+                r#"{"targets": [{"name": 5}]}"#,
+                "invalid project at targets[0]: invalid type: integer `5`, expected a string",
+            ),
+        ];
+        for (json, message) in cases {
+            let err = Project::from_json_str(json, LoadOptions::default()).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn reads_of() {
+        let blocks = json!({
+            "a": {"opcode": "sensing_of", "parent": null, "next": null, "topLevel": true,
+                "x": 0, "y": 0, "shadow": false,
+                "inputs": {"OBJECT": [1, "b"]}, "fields": {"PROPERTY": ["backdrop #", null]}},
+            "b": {"opcode": "sensing_of_object_menu", "parent": "a", "next": null,
+                "topLevel": false, "shadow": true,
+                "inputs": {}, "fields": {"OBJECT": ["_stage_", null]}},
+        });
+        let mut project = load(blocks, true).unwrap();
+        let stage = &mut project.sprites_mut()[0];
+        assert!(
+            matches!(&stage.blocks["a"], BlockType::Of(a) if a.property.as_deref() == Some("backdrop #"))
+        );
+        assert!(matches!(
+            &stage.blocks["b"],
+            BlockType::OfObjectMenu(blocks::OfObjectMenu {
+                object: Some(blocks::OfObject::Stage),
+                ..
+            })
+        ));
+        assert_eq!(
+            scratchblocks::script(stage, "a"),
+            "([backdrop # v] of (Stage v))\n"
+        );
+
+        stage.blocks.clear();
+        stage.originals.clear();
+        scratchblocks::add(stage, "say ([x position v] of (Sprite1 v))").unwrap();
+        let json = project.to_json();
+        check_blocks(&json["targets"][0]);
+        let again = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        assert_eq!(
+            scratchblocks::sprite(&again.sprites()[0]).trim_end(),
+            "say ([x position v] of (Sprite1 v))"
+        );
     }
 }
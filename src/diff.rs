@@ -0,0 +1,480 @@
+//! What changed between two versions of a project.
+//!
+//! project.json moves everything around every time it's saved, so this
+//! compares what a person would look at instead. Sprites are matched by
+//! name. Scripts are matched by the id of their top block, then by being
+//! the same, then by having the same hat, and are shown as scratchblocks.
+//! Within a script that's still there, blocks are matched by id, and each
+//! one that changed is shown on its own.
+//!
+//! Variables, lists, costumes and sounds are matched by id, then by name, so
+//! renaming one doesn't look like removing it and adding another. Costumes
+//! and sounds go by their file, and have changed if it has.
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::{
+    decomp::{Project, Sprite},
+    scratchblocks,
+    visit::Walker,
+};
+
+/// What a difference is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Part {
+    Sprite,
+    Script,
+    /// A block inside a script that's in both.
+    Block,
+    Variable,
+    List,
+    Costume,
+    Sound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+    /// From the name before to the one after. If its value changed too,
+    /// that's another difference.
+    Renamed,
+}
+
+/// One thing that's different.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    pub sprite: String,
+    pub part: Part,
+    pub change: Change,
+    /// The hat of the script, or the one the block is in, or the name of
+    /// everything else.
+    pub name: String,
+    /// The id of the script's top block, or of the block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<String>,
+    /// Scratchblocks for scripts and blocks, JSON for values, and the md5ext
+    /// of costumes and sounds. Sprites don't have one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// Everything that's different in `after`, sprite by sprite as `after` has
+/// them, with sprites that were removed at the end.
+pub fn diff(before: &Project, after: &Project) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let empty = Sprite::default();
+    for sprite in after.sprites() {
+        let old = before.sprites().iter().find(|f| f.name == sprite.name);
+        if old.is_none() {
+            differences.push(Difference::sprite(sprite, Change::Added));
+        }
+        diff_sprite(old.unwrap_or(&empty), sprite, &mut differences);
+    }
+    for sprite in before.sprites() {
+        if !after.sprites().iter().any(|f| f.name == sprite.name) {
+            differences.push(Difference::sprite(sprite, Change::Removed));
+            diff_sprite(sprite, &empty, &mut differences);
+        }
+    }
+    differences
+}
+
+impl Difference {
+    fn sprite(sprite: &Sprite, change: Change) -> Difference {
+        Difference {
+            sprite: sprite.name.clone(),
+            part: Part::Sprite,
+            change,
+            name: sprite.name.clone(),
+            block: None,
+            before: None,
+            after: None,
+        }
+    }
+}
+
+/// A sprite that was added or removed is compared with an empty one.
+fn diff_sprite(a: &Sprite, b: &Sprite, out: &mut Vec<Difference>) {
+    let sprite = match b.name.is_empty() {
+        true => &a.name,
+        false => &b.name,
+    };
+    let push = |out: &mut Vec<Difference>, part, name: &str, block, before, after| {
+        let change = match (&before, &after) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            _ => Change::Changed,
+        };
+        out.push(Difference {
+            sprite: sprite.clone(),
+            part,
+            change,
+            name: name.to_string(),
+            block,
+            before,
+            after,
+        });
+    };
+
+    let (old, new) = (scripts(a), scripts(b));
+    for (i, j) in match_scripts(&old, &new) {
+        let (before, after) = (i.map(|i| &old[i]), j.map(|j| &new[j]));
+        let (id, hat) = match (before, after) {
+            (_, Some((id, text))) | (Some((id, text)), None) => {
+                (id.to_string(), text.lines().next().unwrap_or_default())
+            }
+            (None, None) => continue,
+        };
+        if let (Some(before), Some(after)) = (before, after) {
+            if before.1 == after.1 {
+                continue;
+            }
+            let (old, new) = (
+                scratchblocks::blocks(a, before.0),
+                scratchblocks::blocks(b, after.0),
+            );
+            let start = out.len();
+            let find = |lines: &[(&str, String)], id: &str| {
+                lines.iter().find(|f| f.0 == id).map(|f| f.1.clone())
+            };
+            for (id, line) in &new {
+                let before = find(&old, id);
+                if before.as_ref() != Some(line) {
+                    let block = Some(id.to_string());
+                    push(out, Part::Block, hat, block, before, Some(line.clone()));
+                }
+            }
+            for (id, line) in &old {
+                if find(&new, id).is_none() {
+                    let block = Some(id.to_string());
+                    push(out, Part::Block, hat, block, Some(line.clone()), None);
+                }
+            }
+            // otherwise the same blocks were only moved around.
+            if out.len() > start {
+                continue;
+            }
+        }
+        let before = before.map(|f| f.1.clone());
+        let after = after.map(|f| f.1.clone());
+        push(out, Part::Script, hat, Some(id), before, after);
+    }
+
+    let variables = |f: &Sprite| -> Vec<Item> {
+        let mut variables: Vec<_> = f
+            .variables
+            .iter()
+            .map(|(id, f)| (id.clone(), f.name().to_string(), f.value().to_string()))
+            .collect();
+        variables.sort_by(|a, b| a.1.cmp(&b.1));
+        variables
+    };
+    let lists = |f: &Sprite| -> Vec<Item> {
+        let mut lists: Vec<_> = f
+            .lists
+            .keys()
+            .filter_map(|id| Some((id, f.list(id)?)))
+            .map(|(id, (name, items))| {
+                let items = Value::from(items.clone()).to_string();
+                (id.clone(), name.to_string(), items)
+            })
+            .collect();
+        lists.sort_by(|a, b| a.1.cmp(&b.1));
+        lists
+    };
+    let costumes = |f: &Sprite| -> Vec<Item> {
+        f.costumes
+            .iter()
+            .map(|f| (f.md5ext(), f.name().to_string(), f.md5ext()))
+            .collect()
+    };
+    let sounds = |f: &Sprite| -> Vec<Item> {
+        f.sounds
+            .iter()
+            .map(|f| (f.md5ext(), f.name().to_string(), f.md5ext()))
+            .collect()
+    };
+    for (part, values) in [
+        (Part::Variable, variables as fn(&Sprite) -> Vec<Item>),
+        (Part::List, lists),
+        (Part::Costume, costumes),
+        (Part::Sound, sounds),
+    ] {
+        let (old, new) = (values(a), values(b));
+        // the same id, then the same name.
+        let same = |rule: usize, a: &Item, b: &Item| match rule {
+            0 => a.0 == b.0,
+            _ => a.1 == b.1,
+        };
+        for pair in pair(&old, &new, 2, same) {
+            let (before, after) = match pair {
+                (Some(i), Some(j)) => (&old[i], &new[j]),
+                (None, Some(j)) => {
+                    let (_, name, value) = &new[j];
+                    push(out, part, name, None, None, Some(value.clone()));
+                    continue;
+                }
+                (Some(i), None) => {
+                    let (_, name, value) = &old[i];
+                    push(out, part, name, None, Some(value.clone()), None);
+                    continue;
+                }
+                (None, None) => continue,
+            };
+            if before.1 != after.1 {
+                out.push(Difference {
+                    sprite: sprite.clone(),
+                    part,
+                    change: Change::Renamed,
+                    name: after.1.clone(),
+                    block: None,
+                    before: Some(before.1.clone()),
+                    after: Some(after.1.clone()),
+                });
+            }
+            if before.2 != after.2 {
+                let values = (Some(before.2.clone()), Some(after.2.clone()));
+                push(out, part, &after.1, None, values.0, values.1);
+            }
+        }
+    }
+}
+
+/// A variable, list, costume or sound, as its id, its name and its value.
+type Item = (String, String, String);
+
+/// A top level stack, as its top block's id and its scratchblocks.
+type Script<'a> = (&'a str, String);
+
+fn scripts(sprite: &Sprite) -> Vec<Script<'_>> {
+    Walker::new(&sprite.blocks)
+        .tops()
+        .into_iter()
+        .map(|f| (f, scratchblocks::script(sprite, f)))
+        .collect()
+}
+
+/// Which script before is which after, by the id of their top block, then
+/// by being the same, then by having the same hat.
+fn match_scripts(before: &[Script], after: &[Script]) -> Vec<(Option<usize>, Option<usize>)> {
+    let same = |rule: usize, a: &Script, b: &Script| match rule {
+        0 => a.0 == b.0,
+        1 => a.1 == b.1,
+        _ => a.1.lines().next() == b.1.lines().next(),
+    };
+    pair(before, after, 3, same)
+}
+
+/// Which thing before is which after, by index, in the order they're in
+/// after, with the ones that are gone at the end. Each rule is tried in turn
+/// on what's left unmatched by the ones before it.
+fn pair<T>(
+    before: &[T],
+    after: &[T],
+    rules: usize,
+    same: impl Fn(usize, &T, &T) -> bool,
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut matched: Vec<Option<usize>> = vec![None; after.len()];
+    let mut taken = vec![false; before.len()];
+    for rule in 0..rules {
+        for (j, script) in after.iter().enumerate() {
+            if matched[j].is_some() {
+                continue;
+            }
+            let found = (0..before.len()).find(|&i| !taken[i] && same(rule, &before[i], script));
+            if let Some(i) = found {
+                matched[j] = Some(i);
+                taken[i] = true;
+            }
+        }
+    }
+    let mut pairs: Vec<_> = matched
+        .into_iter()
+        .zip((0..after.len()).map(Some))
+        .collect();
+    pairs.extend(
+        (0..before.len())
+            .filter(|&i| !taken[i])
+            .map(|i| (Some(i), None)),
+    );
+    pairs
+}
+
+/// Which lines of a script were taken out (`-`) and put in (`+`), with the
+/// ones that stayed in between.
+fn lines<'a>(before: &'a str, after: &'a str) -> Vec<(char, &'a str)> {
+    let (a, b): (Vec<&str>, Vec<&str>) = (before.lines().collect(), after.lines().collect());
+    // the longest run of lines they have in common, from the end.
+    let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = match a[i] == b[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push((' ', a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < a.len() && (j == b.len() || common[i + 1][j] >= common[i][j + 1]) {
+            out.push(('-', a[i]));
+            i += 1;
+        } else {
+            out.push(('+', b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// A line saying what changed, then for scripts every line of it, marked
+/// with what happened to it.
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let change = match self.change {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+            Change::Renamed => "renamed",
+        };
+        let part = match self.part {
+            Part::Sprite => "sprite",
+            Part::Script => "script",
+            Part::Block => "block in",
+            Part::Variable => "variable",
+            Part::List => "list",
+            Part::Costume => "costume",
+            Part::Sound => "sound",
+        };
+        write!(f, "{}: {} {} \"{}\"", self.sprite, change, part, self.name)?;
+        if let Some(a) = &self.block {
+            write!(f, " ({})", a)?;
+        }
+        let (before, after) = (
+            self.before.as_deref().unwrap_or_default(),
+            self.after.as_deref().unwrap_or_default(),
+        );
+        match (self.part, self.change) {
+            (Part::Sprite, _) => Ok(()),
+            (Part::Script, _) => {
+                for (mark, line) in lines(before, after) {
+                    write!(f, "\n{} {}", mark, line)?;
+                }
+                Ok(())
+            }
+            (_, Change::Added) => write!(f, ": {}", after),
+            (_, Change::Removed) => write!(f, ": {}", before),
+            (_, Change::Changed) | (_, Change::Renamed) => write!(f, ": {} -> {}", before, after),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decomp::LoadOptions, scratchblocks, test_util::test_project};
+
+    #[test]
+    fn compares_projects() {
+        let before = test_project();
+        let json = before.to_json().to_string();
+        let same = Project::from_json_str(&json, LoadOptions::default()).unwrap();
+        assert_eq!(diff(&before, &same), []);
+
+        let mut json = before.to_json();
+        let variables = json["targets"][0]["variables"].as_object_mut().unwrap();
+        let width = variables.values_mut().find(|f| f[0] == "width").unwrap();
+        width[1] = serde_json::json!(20);
+        json["targets"][1]["costumes"]
+            .as_array_mut()
+            .unwrap()
+            .remove(0);
+        let mut after = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        let sprite = &mut after.sprites_mut()[1];
+        let name = sprite.name.clone();
+        scratchblocks::add(sprite, "when this sprite clicked\nsay [hi]").unwrap();
+
+        let differences = diff(&before, &after);
+        let found: Vec<(Part, Change, &str)> = differences
+            .iter()
+            .map(|f| (f.part, f.change, f.name.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Part::Variable, Change::Changed, "width"),
+                (Part::Script, Change::Added, "when this sprite clicked"),
+                (Part::Costume, Change::Removed, "Ice"),
+            ]
+        );
+        assert_eq!(
+            differences[0].to_string(),
+            "Stage: changed variable \"width\": \"17\" -> 20"
+        );
+        let script = differences[1].to_string();
+        assert!(script.starts_with(&format!("{}: added script", name)));
+        assert!(script.ends_with("\n+ when this sprite clicked\n+ say [hi]"));
+    }
+
+    #[test]
+    fn matches_by_id() {
+        let json = serde_json::json!({"targets": [{"isStage": true, "name": "Stage",
+        "variables": {"a-id": ["speed", 3], "b-id": ["score", 0]},
+        "lists": {"c-id": ["names", []]},
+        "costumes": [{"assetId": "abc", "name": "blank", "md5ext": "abc.svg",
+            "dataFormat": "svg"}],
+        "blocks": {
+            "hat": {"opcode": "event_whenflagclicked", "next": "say", "parent": null,
+                "inputs": {}, "fields": {}, "topLevel": true, "x": 0, "y": 0},
+            "say": {"opcode": "looks_say", "next": "move", "parent": "hat",
+                "inputs": {"MESSAGE": [1, [10, "hi"]]}, "fields": {}},
+            "move": {"opcode": "motion_movesteps", "next": null, "parent": "say",
+                "inputs": {"STEPS": [1, [4, "10"]]}, "fields": {}},
+        }}]});
+        let before = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        let mut json = json;
+        let stage = &mut json["targets"][0];
+        stage["variables"]["a-id"] = serde_json::json!(["velocity", 4]);
+        stage["lists"]["c-id"][0] = serde_json::json!("people");
+        stage["costumes"][0]["name"] = serde_json::json!("empty");
+        stage["blocks"]["say"]["inputs"]["MESSAGE"] = serde_json::json!([1, [10, "bye"]]);
+        stage["blocks"]["say"]["next"] = serde_json::json!(null);
+        stage["blocks"].as_object_mut().unwrap().remove("move");
+        let after = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+
+        let differences: Vec<String> = diff(&before, &after)
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            differences,
+            [
+                "Stage: changed block in \"when flag clicked\" (say): say [hi] -> say [bye]",
+                "Stage: removed block in \"when flag clicked\" (move): move (10) steps",
+                "Stage: renamed variable \"velocity\": speed -> velocity",
+                "Stage: changed variable \"velocity\": 3 -> 4",
+                "Stage: renamed list \"people\": names -> people",
+                "Stage: renamed costume \"empty\": blank -> empty",
+            ]
+        );
+    }
+
+    #[test]
+    fn marks_lines() {
+        let changed = lines("a\nb\nc", "a\nx\nc\nd");
+        assert_eq!(
+            changed,
+            [(' ', "a"), ('-', "b"), ('+', "x"), (' ', "c"), ('+', "d")]
+        );
+    }
+}
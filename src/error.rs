@@ -0,0 +1,128 @@
+//! Everything that can go wrong while loading a project.
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum YaseError {
+    /// The project file couldn't be read.
+    Io(String),
+    /// The project couldn't be downloaded.
+    Network(String),
+    /// Costumes or sounds that aren't where they should be, by md5ext.
+    MissingAssets { dir: String, assets: Vec<String> },
+    /// A costume or sound that isn't what its name says.
+    BadAsset { name: String, md5: String },
+    /// Not JSON, or not shaped like a project. The path is empty if it's
+    /// the whole file.
+    Json { path: String, message: String },
+    /// A block that couldn't be made sense of.
+    Block {
+        sprite: String,
+        id: String,
+        opcode: String,
+        /// Where in project.json, i.e. `targets[1].blocks["a"].inputs.VALUE`.
+        path: String,
+        message: String,
+    },
+    /// Scratchblocks text that couldn't be read, by line from 1.
+    Scratchblocks { line: usize, message: String },
+    /// A rename that can't be done: the old name isn't there, or the new one
+    /// is already taken.
+    Rename(String),
+}
+
+impl Display for YaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            YaseError::Io(a) => write!(f, "error reading project: {}", a),
+            YaseError::Network(a) => write!(f, "error downloading project: {}", a),
+            YaseError::MissingAssets { dir, assets } => {
+                write!(f, "missing assets in {}: {}", dir, assets.join(", "))
+            }
+            YaseError::BadAsset { name, md5 } => {
+                write!(f, "asset {} has the wrong md5 ({})", name, md5)
+            }
+            YaseError::Json { path, message } if path.is_empty() => {
+                write!(f, "invalid project: {}", message)
+            }
+            YaseError::Json { path, message } => {
+                write!(f, "invalid project at {}: {}", path, message)
+            }
+            YaseError::Block {
+                sprite,
+                id,
+                opcode,
+                path,
+                message,
+            } => write!(
+                f,
+                "invalid block {} ({}) in {} at {}: {}",
+                id, opcode, sprite, path, message
+            ),
+            YaseError::Scratchblocks { line, message } => {
+                write!(f, "invalid scratchblocks at line {}: {}", line, message)
+            }
+            YaseError::Rename(a) => write!(f, "can't rename: {}", a),
+        }
+    }
+}
+
+impl std::error::Error for YaseError {}
+
+impl From<Warning> for YaseError {
+    fn from(a: Warning) -> YaseError {
+        YaseError::Block {
+            sprite: a.sprite,
+            id: a.id,
+            opcode: a.opcode,
+            path: a.path,
+            message: a.message,
+        }
+    }
+}
+
+/// Something wrong with a block that a lenient load got past. Says where,
+/// the same way YaseError::Block does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub sprite: String,
+    pub id: String,
+    pub opcode: String,
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "block {} ({}) in {} at {}: {}",
+            self.id, self.opcode, self.sprite, self.path, self.message
+        )
+    }
+}
+
+/// What went wrong inside a block, before it's known which block it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockError {
+    /// Where in the block, i.e. `inputs.VALUE`.
+    pub path: String,
+    pub message: String,
+}
+
+impl BlockError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> BlockError {
+        BlockError {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.is_empty() {
+            true => f.write_str(&self.message),
+            false => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! How a project's scripts hang together, as a Graphviz graph.
+//!
+//! Each sprite is a cluster with a node for every block in its stacks, and an
+//! edge from each block to the one under it and into its substacks. Reporters
+//! are only part of the label of the block they're in. Broadcasts point at
+//! every hat that receives them, in any sprite, whatever their case, and
+//! custom block calls at their definition. Render it with `dot -Tsvg`.
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    blocks::{BlockType, Value},
+    decomp::{Project, Sprite},
+    runtime::ir::name_of,
+    scratchblocks::Printer,
+    script::Input,
+    visit::Walker,
+};
+
+/// The whole project as a DOT digraph.
+pub fn dot(project: &Project) -> String {
+    let mut out = String::from("digraph project {\n  node [shape=box, fontname=\"Helvetica\"];\n");
+    let mut graph = Graph::default();
+    for (i, sprite) in project.sprites().iter().enumerate() {
+        graph.sprite(i, sprite, project, &mut out);
+    }
+    for (name, from) in &graph.sends {
+        for to in graph
+            .receivers
+            .get(&name.to_lowercase())
+            .into_iter()
+            .flatten()
+        {
+            out.push_str(&format!(
+                "  {} -> {} [style=dashed, color=blue, label={}];\n",
+                from,
+                to,
+                quote(name)
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// What's needed for the edges that go between sprites.
+#[derive(Default)]
+struct Graph {
+    /// Nodes of the hats that receive each broadcast, by its name in lower
+    /// case.
+    receivers: HashMap<String, Vec<String>>,
+    /// Every broadcast block, with the name it sends.
+    sends: Vec<(String, String)>,
+}
+
+impl Graph {
+    fn sprite(&mut self, index: usize, sprite: &Sprite, project: &Project, out: &mut String) {
+        let printer = Printer::new(sprite);
+        let node = |id: &str| quote(&format!("{}/{}", index, id));
+        out.push_str(&format!("  subgraph \"cluster_{}\" {{\n", index));
+        out.push_str(&format!("    label={};\n", quote(&sprite.name)));
+
+        let mut stacks: Vec<(Option<String>, &str, &str)> = Walker::new(&sprite.blocks)
+            .tops()
+            .into_iter()
+            .rev()
+            .map(|f| (None, "", f))
+            .collect();
+        // a block in its own substack only gets an edge back to itself.
+        let mut drawn = HashSet::new();
+        while let Some((parent, label, first)) = stacks.pop() {
+            let mut prev = parent.map(|f| (f, label));
+            for (id, block) in printer.linked.stack(Some(first)) {
+                let again = !drawn.insert(id);
+                let shape = match block.is_hat() {
+                    true => ", shape=house",
+                    false => "",
+                };
+                if !again {
+                    out.push_str(&format!(
+                        "    {} [label={}{}];\n",
+                        node(id),
+                        quote(&printer.block(block)),
+                        shape
+                    ));
+                }
+                match prev {
+                    Some((from, "")) => out.push_str(&format!("    {} -> {};\n", from, node(id))),
+                    Some((from, label)) => out.push_str(&format!(
+                        "    {} -> {} [label={}];\n",
+                        from,
+                        node(id),
+                        quote(label)
+                    )),
+                    None => {}
+                }
+                if again {
+                    break;
+                }
+                prev = Some((node(id), ""));
+
+                let substacks: &[(&Option<Value>, &str)] = match block {
+                    BlockType::Repeat(a) => &[(&a.substack, "do")],
+                    BlockType::Forever(a) => &[(&a.substack, "do")],
+                    BlockType::RepeatUntil(a) => &[(&a.substack, "do")],
+                    BlockType::IfThen(a) => &[(&a.then, "then")],
+                    BlockType::IfThenElse(a) => &[(&a.then, "then"), (&a.otherwise, "else")],
+                    _ => &[],
+                };
+                for (val, label) in substacks.iter().rev() {
+                    if let Input::Block(first, _) = printer.linked.input(val) {
+                        stacks.push((Some(node(id)), label, first));
+                    }
+                }
+
+                match block {
+                    BlockType::WhenIRecieveBroadcast(a) => {
+                        if let Some(name) = &a.broadcast {
+                            let name = project.broadcast_name(&name_of(name)).to_lowercase();
+                            self.receivers.entry(name).or_default().push(node(id));
+                        }
+                    }
+                    BlockType::Broadcast(a) => self.send(&printer, &a.broadcast, node(id), project),
+                    BlockType::BroadcastAndWait(a) => {
+                        self.send(&printer, &a.broadcast, node(id), project)
+                    }
+                    BlockType::ProceduresCall(a) => {
+                        let proccode = a.signature.proccode();
+                        if let Some(procedure) = printer.linked.procedure(proccode) {
+                            out.push_str(&format!(
+                                "    {} -> {} [style=dotted, color=darkgreen];\n",
+                                node(id),
+                                node(procedure.id)
+                            ));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        out.push_str("  }\n");
+    }
+
+    /// Broadcasts of a name that's worked out while running can't be
+    /// followed, so they're left out.
+    fn send(&mut self, printer: &Printer, val: &Option<Value>, from: String, project: &Project) {
+        if let Input::Literal(a) = printer.linked.input(val) {
+            self.sends.push((project.broadcast_name(&name_of(a)), from));
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decomp::LoadOptions, scratchblocks, test_util::test_project};
+
+    #[test]
+    fn ignores_case_and_stops_at_loops() {
+        let mut project = test_project();
+        let sprites = project.sprites_mut();
+        scratchblocks::add(&mut sprites[0], "when flag clicked\nbroadcast (Go v)").unwrap();
+        scratchblocks::add(&mut sprites[1], "when I receive [go v]\nsay [hi]").unwrap();
+        let graph = dot(&project);
+        let edge = graph
+            .lines()
+            .find(|f| f.ends_with("[style=dashed, color=blue, label=\"Go\"];"))
+            .unwrap();
+        assert!(edge.trim().starts_with("\"0/sb-"), "{}", edge);
+        assert!(edge.contains(" -> \"1/sb-"), "{}", edge);
+
+        let json = serde_json::json!({"targets": [{"isStage": true, "name": "Stage", "blocks": {
+            "a": {"opcode": "event_whenflagclicked", "next": "b", "parent": null,
+                "inputs": {}, "fields": {}, "topLevel": true, "x": 0, "y": 0},
+            "b": {"opcode": "control_forever", "next": null, "parent": "a",
+                "inputs": {"SUBSTACK": [2, "b"]}, "fields": {}},
+        }}]});
+        let options = LoadOptions { strict: false };
+        let project = Project::from_json_str(&json.to_string(), options).unwrap();
+        let dot = dot(&project);
+        assert_eq!(dot.matches("[label=").count(), 3, "{}", dot);
+        assert!(
+            dot.contains("    \"0/b\" -> \"0/b\" [label=\"do\"];\n"),
+            "{}",
+            dot
+        );
+    }
+
+    #[test]
+    fn links_broadcasts_and_calls() {
+        let dot = dot(&test_project());
+        assert!(dot.starts_with("digraph project {\n"));
+        assert!(dot.contains("label=\"when flag clicked\", shape=house]"));
+        assert!(dot.contains("[label=\"do\"];"));
+        assert!(dot.contains("[style=dashed, color=blue, label=\"Display\"];"));
+        assert!(dot.contains("[style=dotted, color=darkgreen];"));
+        // every edge is between nodes that are there.
+        for line in dot.lines().filter(|f| f.contains(" -> ")) {
+            let (from, rest) = line.trim().split_once(" -> ").unwrap();
+            let to = rest.split(' ').next().unwrap().trim_end_matches(';');
+            for node in [from, to] {
+                assert!(dot.contains(&format!("    {} [label=", node)), "{}", line);
+            }
+        }
+    }
+}
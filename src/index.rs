@@ -0,0 +1,397 @@
+//! Who uses what, across every sprite.
+//!
+//! Before renaming or deleting a variable, a costume or a whole sprite, it's
+//! worth knowing which blocks still mention it. Only names that are there
+//! before the project runs count: a costume switched to by a joined string
+//! could be any of them, and isn't in here.
+use serde_derive::Serialize;
+
+use crate::{
+    blocks::{
+        Backdrop, BlockType, Costume, CreateCloneOfMenu, DistanceToMenu, Glide, Goto, MovementMenu,
+        MovementOption, OfObject, OfObjectMenu, Point, PointTowardsMenu, SensingOption,
+        SoundSoundsMenu, SpriteOption, TouchingMenu, Value,
+    },
+    decomp::{Project, Sprite},
+    runtime::ir::name_of,
+    script::{Input, Linked},
+};
+
+/// What a name is the name of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Variable,
+    List,
+    Broadcast,
+    Costume,
+    Backdrop,
+    Sound,
+    Sprite,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 7] = [
+        Kind::Variable,
+        Kind::List,
+        Kind::Broadcast,
+        Kind::Costume,
+        Kind::Backdrop,
+        Kind::Sound,
+        Kind::Sprite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Variable => "variable",
+            Kind::List => "list",
+            Kind::Broadcast => "broadcast",
+            Kind::Costume => "costume",
+            Kind::Backdrop => "backdrop",
+            Kind::Sound => "sound",
+            Kind::Sprite => "sprite",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+/// What a block does with the thing it names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Usage {
+    Read,
+    Write,
+    /// Showing or hiding a variable or list's monitor.
+    Show,
+    Send,
+    Receive,
+    /// Switching to or playing a costume, backdrop or sound, or going to,
+    /// pointing towards or cloning a sprite.
+    Name,
+    /// Touching, distance to, or `of` a sprite.
+    Sense,
+}
+
+/// One block mentioning one thing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reference {
+    pub kind: Kind,
+    pub name: String,
+    /// The sprite the variable, list, costume or sound belongs to.
+    /// Broadcasts and sprites don't belong to one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// The sprite the block is in.
+    pub sprite: String,
+    pub block: String,
+    pub usage: Usage,
+}
+
+impl std::fmt::Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let usage = match self.usage {
+            Usage::Read => "reads",
+            Usage::Write => "writes",
+            Usage::Show => "shows",
+            Usage::Send => "sends",
+            Usage::Receive => "receives",
+            Usage::Name => "names",
+            Usage::Sense => "senses",
+        };
+        write!(
+            f,
+            "{} ({}): {} {} \"{}\"",
+            self.sprite,
+            self.block,
+            usage,
+            self.kind.name(),
+            self.name
+        )?;
+        match &self.owner {
+            Some(a) if *a != self.sprite => write!(f, " of {}", a),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Every reference in a project, ordered by what's referenced.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Index {
+    pub references: Vec<Reference>,
+}
+
+impl Index {
+    pub fn new(project: &Project) -> Index {
+        let mut index = Index::default();
+        for (i, sprite) in project.sprites().iter().enumerate() {
+            index.sprite(project, i, sprite);
+        }
+        index.references.sort_by(|a, b| {
+            (a.kind, &a.name, &a.owner, &a.sprite, &a.block)
+                .cmp(&(b.kind, &b.name, &b.owner, &b.sprite, &b.block))
+        });
+        index
+    }
+
+    /// Everything that references a name, in any sprite.
+    pub fn find(&self, kind: Kind, name: &str) -> Vec<&Reference> {
+        self.references
+            .iter()
+            .filter(|f| f.kind == kind && f.name == name)
+            .collect()
+    }
+
+    /// Every name of a kind that's referenced, once each.
+    pub fn names(&self, kind: Kind) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .references
+            .iter()
+            .filter(|f| f.kind == kind)
+            .map(|f| f.name.as_str())
+            .collect();
+        names.dedup();
+        names
+    }
+
+    fn sprite(&mut self, project: &Project, index: usize, sprite: &Sprite) {
+        let linked = Linked::new(sprite);
+        let sprites = project.sprites();
+        let stage = sprites.iter().find(|f| f.is_stage).unwrap_or(sprite);
+        let mut ids: Vec<&String> = sprite.blocks.keys().collect();
+        ids.sort();
+        for id in ids {
+            let mut mentions: Vec<_> = mentions(&linked, project, &sprite.blocks[id])
+                .into_iter()
+                .map(|(kind, name, usage)| (kind, name, usage, None))
+                .collect();
+            if let Some((sensed, property)) = sensing_of(&linked, &sprite.blocks[id]) {
+                let sensed = sprites.iter().find(|f| match &sensed {
+                    OfObject::Stage => f.is_stage,
+                    OfObject::Sprite(a) => f.name == *a,
+                });
+                if let Some(sensed) = sensed {
+                    mentions.push((Kind::Sprite, sensed.name.clone(), Usage::Sense, None));
+                    // `of` can give a sprite's own variable.
+                    if let Some(property) = property {
+                        let owner = Some(sensed.name.clone());
+                        mentions.push((Kind::Variable, property, Usage::Read, owner));
+                    }
+                }
+            }
+            for (kind, name, usage, owner) in mentions {
+                if name.is_empty() {
+                    continue;
+                }
+                let owner = match (kind, owner) {
+                    (_, Some(a)) => Some(a),
+                    (Kind::Variable | Kind::List, None) => {
+                        let owner = project.owner(index, &name, kind == Kind::List);
+                        Some(sprites[owner].name.clone())
+                    }
+                    (Kind::Costume | Kind::Sound, None) => Some(sprite.name.clone()),
+                    (Kind::Backdrop, None) => Some(stage.name.clone()),
+                    (Kind::Broadcast | Kind::Sprite, None) => None,
+                };
+                self.references.push(Reference {
+                    kind,
+                    name,
+                    owner,
+                    sprite: sprite.name.clone(),
+                    block: id.clone(),
+                    usage,
+                });
+            }
+        }
+    }
+}
+
+/// What a block names, and what it does with each.
+fn mentions(linked: &Linked, project: &Project, block: &BlockType) -> Vec<(Kind, String, Usage)> {
+    let (variable, list) = (Kind::Variable, Kind::List);
+    let menu = |val| named(linked, val).unwrap_or_default();
+    let mut mentions = match block {
+        BlockType::DataGetVariable(a) => vec![(variable, name_of(&a.variable), Usage::Read)],
+        BlockType::DataSetVariableTo(a) => vec![(variable, name_of(&a.variable), Usage::Write)],
+        BlockType::DataChangeVariableBy(a) => vec![(variable, name_of(&a.variable), Usage::Write)],
+        BlockType::DataShowVariable(a) => vec![(variable, name_of(&a.variable), Usage::Show)],
+        BlockType::DataHideVariable(a) => vec![(variable, name_of(&a.variable), Usage::Show)],
+        BlockType::DataListContents(a) => vec![(list, name_of(&a.variable), Usage::Read)],
+        BlockType::DataAddToList(a) => vec![(list, name_of(&a.list), Usage::Write)],
+        BlockType::DataDeleteOfList(a) => vec![(list, name_of(&a.list), Usage::Write)],
+        BlockType::DataDeleteAllOfList(a) => vec![(list, name_of(&a.list), Usage::Write)],
+        BlockType::DataInsertAtList(a) => vec![(list, name_of(&a.list), Usage::Write)],
+        BlockType::DataReplaceItemOfList(a) => vec![(list, name_of(&a.list), Usage::Write)],
+        BlockType::DataItemOfList(a) => vec![(list, name_of(&a.list), Usage::Read)],
+        BlockType::DataLengthOfList(a) => vec![(list, name_of(&a.list), Usage::Read)],
+        BlockType::DataListContainsItem(a) => vec![(list, name_of(&a.list), Usage::Read)],
+        BlockType::ShowList(a) => vec![(list, name_of(&a.list), Usage::Show)],
+        BlockType::HideList(a) => vec![(list, name_of(&a.list), Usage::Show)],
+
+        BlockType::Broadcast(a) => vec![(Kind::Broadcast, menu(&a.broadcast), Usage::Send)],
+        BlockType::BroadcastAndWait(a) => vec![(Kind::Broadcast, menu(&a.broadcast), Usage::Send)],
+        BlockType::WhenIRecieveBroadcast(a) => {
+            vec![(Kind::Broadcast, menu(&a.broadcast), Usage::Receive)]
+        }
+        BlockType::SwitchCostume(a) => vec![(Kind::Costume, menu(&a.costume), Usage::Name)],
+        BlockType::SwitchBackdrop(a) => vec![(Kind::Backdrop, menu(&a.backdrop), Usage::Name)],
+        BlockType::SwitchBackdropAndWait(a) => {
+            vec![(Kind::Backdrop, menu(&a.backdrop), Usage::Name)]
+        }
+        BlockType::WhenBackdropSwitchesTo(a) => {
+            vec![(Kind::Backdrop, menu(&a.backdrop), Usage::Name)]
+        }
+        BlockType::PlaySound(a) => vec![(Kind::Sound, menu(&a.sound), Usage::Name)],
+        BlockType::PlaySoundUntilDone(a) => vec![(Kind::Sound, menu(&a.sound), Usage::Name)],
+        BlockType::StartSound(a) => vec![(Kind::Sound, a.sound.clone(), Usage::Name)],
+        BlockType::Goto(Goto::Option(a)) => vec![(Kind::Sprite, menu(&a.option), Usage::Name)],
+        BlockType::Glide(Glide::Option(a)) => vec![(Kind::Sprite, menu(&a.option), Usage::Name)],
+        BlockType::Point(Point::Towards(a)) => vec![(Kind::Sprite, menu(&a.option), Usage::Name)],
+        BlockType::CreateCloneOf(a) => vec![(Kind::Sprite, menu(&a.of), Usage::Name)],
+        BlockType::Touching(a) => vec![(Kind::Sprite, menu(&a.touching), Usage::Sense)],
+        BlockType::DistanceTo(a) => vec![(Kind::Sprite, menu(&a.to), Usage::Sense)],
+        _ => vec![],
+    };
+    for (kind, name, _) in &mut mentions {
+        match kind {
+            Kind::Broadcast => *name = project.broadcast_name(name),
+            // the mouse pointer, the edge and such.
+            Kind::Sprite if name.starts_with('_') && name.ends_with('_') => name.clear(),
+            _ => {}
+        }
+    }
+    for (_, input) in block.inputs() {
+        match input {
+            Value::Variable(a) => mentions.push((variable, a.clone(), Usage::Read)),
+            Value::List(a) => mentions.push((list, a.clone(), Usage::Read)),
+            _ => {}
+        }
+    }
+    mentions
+}
+
+/// The name in a dropdown, or typed straight into the input.
+fn named(linked: &Linked, val: &Option<Value>) -> Option<String> {
+    let block = match linked.input(val) {
+        Input::Literal(a) => return Some(name_of(a)),
+        Input::Block(_, a) => a,
+        _ => return None,
+    };
+    match block {
+        BlockType::Costume(Costume::WithName(Some(a)))
+        | BlockType::Backdrop(Backdrop::WithName(Some(a)))
+        | BlockType::SoundSoundsMenu(SoundSoundsMenu {
+            option: Some(a), ..
+        }) => Some(name_of(a)),
+        BlockType::TouchingMenu(TouchingMenu {
+            touching: Some(SensingOption::Sprite(a)),
+            ..
+        })
+        | BlockType::DistanceToMenu(DistanceToMenu {
+            to: Some(SensingOption::Sprite(a)),
+            ..
+        }) => Some(name_of(a)),
+        BlockType::Goto(Goto::Menu(MovementMenu {
+            option: Some(MovementOption::Sprite(a)),
+            ..
+        }))
+        | BlockType::Glide(Glide::Menu(MovementMenu {
+            option: Some(MovementOption::Sprite(a)),
+            ..
+        }))
+        | BlockType::PointTowardsMenu(PointTowardsMenu {
+            option: Some(MovementOption::Sprite(a)),
+            ..
+        })
+        | BlockType::CreateCloneOfMenu(CreateCloneOfMenu {
+            of: Some(SpriteOption::Sprite(a)),
+            ..
+        }) => Some(a.clone()),
+        _ => None,
+    }
+}
+
+/// Which sprite an `of` block asks about, and the variable it asks for if
+/// it isn't one of the built in properties.
+fn sensing_of(linked: &Linked, block: &BlockType) -> Option<(OfObject, Option<String>)> {
+    let of = match block {
+        BlockType::Of(a) => a,
+        _ => return None,
+    };
+    let sensed = match linked.input(&of.object) {
+        Input::Block(
+            _,
+            BlockType::OfObjectMenu(OfObjectMenu {
+                object: Some(a), ..
+            }),
+        ) => a.clone(),
+        _ => return None,
+    };
+    let property = of.property.as_deref().unwrap_or_default();
+    let builtin = [
+        "x position",
+        "y position",
+        "direction",
+        "costume #",
+        "costume name",
+        "size",
+        "volume",
+        "backdrop #",
+        "backdrop name",
+    ];
+    let variable = match builtin.contains(&property) || property.is_empty() {
+        true => None,
+        false => Some(property.to_string()),
+    };
+    Some((sensed, variable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scratchblocks, test_util::test_project};
+
+    #[test]
+    fn finds_references() {
+        let mut project = test_project();
+        let stage = project.sprites()[0].name.clone();
+        let sprite = &mut project.sprites_mut()[1];
+        let name = sprite.name.clone();
+        let text = "when flag clicked\n\
+            switch costume to (costume2 v)\n\
+            start sound (Pop v)\n\
+            if <touching (Other v) ?> then\n\
+            set [secret v] to (distance to (Other v))\n\
+            broadcast (go v)\n\
+            say ([Height v] of (Stage v))\n\
+            end";
+        scratchblocks::add(sprite, text).unwrap();
+
+        let index = Index::new(&project);
+        let found = |kind, name| -> Vec<(Usage, Option<&str>)> {
+            index
+                .find(kind, name)
+                .into_iter()
+                .filter(|f| f.block.starts_with("sb-"))
+                .map(|f| (f.usage, f.owner.as_deref()))
+                .collect()
+        };
+        let own = Some(name.as_str());
+        assert_eq!(found(Kind::Costume, "costume2"), [(Usage::Name, own)]);
+        assert_eq!(found(Kind::Sound, "Pop"), [(Usage::Name, own)]);
+        assert_eq!(found(Kind::Broadcast, "go"), [(Usage::Send, None)]);
+        assert_eq!(found(Kind::Variable, "secret"), [(Usage::Write, own)]);
+        assert_eq!(
+            found(Kind::Sprite, "Other"),
+            [(Usage::Sense, None), (Usage::Sense, None)]
+        );
+        assert_eq!(found(Kind::Sprite, &stage), [(Usage::Sense, None)]);
+        assert_eq!(
+            found(Kind::Variable, "Height"),
+            [(Usage::Read, Some(stage.as_str()))]
+        );
+        assert!(index.names(Kind::Variable).contains(&"amplitude1"));
+    }
+}
@@ -0,0 +1,34 @@
+//! **Y**et **A**nother **S**cratch **E**mulator.
+//!
+//! Loading a project gives a [`Project`], a list of [`Sprite`]s whose blocks
+//! are [`BlockType`]s that point at each other by id. [`Linked`] follows those
+//! ids for you, and [`Runtime`] runs the whole thing.
+pub mod assets;
+pub(crate) mod block_defs;
+pub mod block_names;
+pub mod blocks;
+pub mod decomp;
+pub mod diff;
+pub mod error;
+pub mod graph;
+pub mod index;
+pub mod lint;
+pub mod merge;
+pub mod optimize;
+pub mod rename;
+pub mod runtime;
+pub mod sb2;
+pub mod scratchblocks;
+pub mod script;
+pub mod serialize;
+pub mod stats;
+#[cfg(test)]
+mod test_util;
+pub mod transpile;
+pub mod visit;
+
+pub use blocks::BlockType;
+pub use decomp::{LoadOptions, Project, Sprite};
+pub use error::YaseError;
+pub use runtime::Runtime;
+pub use script::Linked;
@@ -0,0 +1,337 @@
+//! Things in a project that are probably mistakes.
+//!
+//! None of these stop a project from running, Scratch runs them just fine.
+//! They're what works by accident or not at all: messages nobody listens to,
+//! blocks that can never run, a sprite's variable hiding the stage's.
+use std::collections::{BTreeMap, HashSet};
+
+use serde_derive::Serialize;
+
+use crate::{
+    blocks::{BlockType, Value},
+    decomp::{Project, Sprite},
+    runtime::ir::name_of,
+    script::{Input, Linked},
+    visit::Walker,
+};
+
+/// One thing that looks wrong.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lint {
+    /// What kind of mistake it is, like `unused-custom-block`.
+    pub rule: &'static str,
+    pub sprite: String,
+    /// The block it's about, if it's about one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.block {
+            Some(a) => write!(
+                f,
+                "{} ({}): {} [{}]",
+                self.sprite, a, self.message, self.rule
+            ),
+            None => write!(f, "{}: {} [{}]", self.sprite, self.message, self.rule),
+        }
+    }
+}
+
+/// Everything that looks wrong, sprite by sprite, with what's about more
+/// than one sprite (broadcasts and variables) at the end.
+pub fn lint(project: &Project) -> Vec<Lint> {
+    let sprites = project.sprites();
+    let mut linter = Linter {
+        project,
+        sprites,
+        lints: Vec::new(),
+        sent: BTreeMap::new(),
+        received: BTreeMap::new(),
+        computed: false,
+        set: HashSet::new(),
+        read: BTreeMap::new(),
+    };
+    for (i, sprite) in sprites.iter().enumerate() {
+        linter.sprite(i, sprite);
+    }
+    linter.finish()
+}
+
+struct Linter<'a> {
+    project: &'a Project,
+    sprites: &'a [Sprite],
+    lints: Vec<Lint>,
+    /// Blocks that broadcast each message, as (sprite, block, the message
+    /// as it's written there). Messages are matched whatever their case.
+    sent: BTreeMap<String, Vec<(usize, &'a str, String)>>,
+    /// Hats that receive each message.
+    received: BTreeMap<String, Vec<(usize, &'a str, String)>>,
+    /// Whether any broadcast's message is only known while running, which
+    /// could be any of them.
+    computed: bool,
+    /// Variables something sets, as (sprite it belongs to, name).
+    set: HashSet<(usize, String)>,
+    /// The first block that reads each variable.
+    read: BTreeMap<(usize, String), (usize, &'a str)>,
+}
+
+impl<'a> Linter<'a> {
+    fn push(&mut self, rule: &'static str, sprite: usize, block: Option<&str>, message: String) {
+        self.lints.push(Lint {
+            rule,
+            sprite: self.sprites[sprite].name.clone(),
+            block: block.map(str::to_string),
+            message,
+        });
+    }
+
+    fn sprite(&mut self, index: usize, sprite: &'a Sprite) {
+        // a value saved with the project, other than the 0 a new variable
+        // starts at, is as good as setting it.
+        for variable in sprite.variables.values() {
+            let value = variable.value();
+            let unset = value.as_f64() == Some(0.0) || value == "" || value == "0";
+            if !unset {
+                self.set.insert((index, variable.name().to_string()));
+            }
+        }
+
+        let linked = Linked::new(sprite);
+        let mut ids: Vec<&'a String> = sprite.blocks.keys().collect();
+        ids.sort();
+        let mut called = HashSet::new();
+        for id in ids {
+            let block = &sprite.blocks[id];
+            match block {
+                BlockType::Broadcast(a) => self.send(&linked, index, id, &a.broadcast),
+                BlockType::BroadcastAndWait(a) => self.send(&linked, index, id, &a.broadcast),
+                BlockType::WhenIRecieveBroadcast(a) => {
+                    if let Some(a) = &a.broadcast {
+                        let name = self.project.broadcast_name(&name_of(a));
+                        let entry = self.received.entry(name.to_lowercase());
+                        entry.or_default().push((index, id, name));
+                    }
+                }
+                BlockType::DataSetVariableTo(a) => {
+                    let name = name_of(&a.variable);
+                    self.set
+                        .insert((self.project.owner(index, &name, false), name));
+                }
+                BlockType::DataChangeVariableBy(a) => {
+                    let name = name_of(&a.variable);
+                    self.set
+                        .insert((self.project.owner(index, &name, false), name));
+                }
+                BlockType::DataGetVariable(a) => self.read(index, id, &name_of(&a.variable)),
+                BlockType::ProceduresCall(a) => {
+                    called.insert(a.signature.proccode());
+                }
+                BlockType::Forever(_) => {
+                    let after = linked.stack(block.as_block().next().as_deref()).count();
+                    if after > 0 {
+                        let message = format!("{} blocks after a forever never run", after);
+                        self.push("unreachable-after-forever", index, Some(id), message);
+                    }
+                }
+                _ => {}
+            }
+            for (_, input) in block.inputs() {
+                if let Value::Variable(a) = input {
+                    self.read(index, id, a);
+                }
+            }
+        }
+
+        for top in Walker::new(&sprite.blocks).tops() {
+            let block = &sprite.blocks[top];
+            if block.is_hat() {
+                continue;
+            }
+            let message = "blocks that aren't under a hat never run".to_string();
+            self.push("stray-blocks", index, Some(top), message);
+        }
+
+        let mut procedures: Vec<_> = linked.procedures.iter().collect();
+        procedures.sort_by_key(|f| f.1.id);
+        for (proccode, procedure) in procedures {
+            if !called.contains(proccode) {
+                let message = format!(
+                    "custom block \"{}\" is never used",
+                    procedure.signature.name()
+                );
+                self.push("unused-custom-block", index, Some(procedure.id), message);
+            }
+        }
+
+        let stage = match self.sprites.iter().find(|f| f.is_stage) {
+            Some(a) if !sprite.is_stage => a,
+            _ => return,
+        };
+        let mut names: Vec<(&str, &str)> = Vec::new();
+        names.extend(sprite.variables.values().map(|f| ("variable", f.name())));
+        names.extend(
+            sprite
+                .lists
+                .keys()
+                .filter_map(|f| Some(("list", sprite.list(f)?.0))),
+        );
+        names.sort();
+        for (kind, name) in names {
+            let global = match kind {
+                "variable" => stage.variables.values().any(|f| f.name() == name),
+                _ => stage
+                    .lists
+                    .keys()
+                    .any(|f| stage.list(f).is_some_and(|f| f.0 == name)),
+            };
+            if global {
+                let message = format!(
+                    "{} \"{}\" hides the stage's {} of the same name",
+                    kind, name, kind
+                );
+                self.push("name-collision", index, None, message);
+            }
+        }
+    }
+
+    fn send(&mut self, linked: &Linked<'a>, sprite: usize, id: &'a str, val: &'a Option<Value>) {
+        match linked.input(val) {
+            Input::Literal(a) => {
+                let name = self.project.broadcast_name(&name_of(a));
+                let entry = self.sent.entry(name.to_lowercase());
+                entry.or_default().push((sprite, id, name));
+            }
+            _ => self.computed = true,
+        }
+    }
+
+    fn read(&mut self, sprite: usize, id: &'a str, name: &str) {
+        let key = (self.project.owner(sprite, name, false), name.to_string());
+        self.read.entry(key).or_insert((sprite, id));
+    }
+
+    fn finish(mut self) -> Vec<Lint> {
+        let sent = std::mem::take(&mut self.sent);
+        for (key, blocks) in &sent {
+            if self.received.contains_key(key) {
+                continue;
+            }
+            for (sprite, id, name) in blocks {
+                let message = format!("nothing receives \"{}\"", name);
+                self.push("unreceived-broadcast", *sprite, Some(id), message);
+            }
+        }
+        // a computed message could be any of them.
+        let received = match self.computed {
+            true => BTreeMap::new(),
+            false => std::mem::take(&mut self.received),
+        };
+        for (key, hats) in &received {
+            if sent.contains_key(key) {
+                continue;
+            }
+            for (sprite, id, name) in hats {
+                let message = format!("nothing broadcasts \"{}\"", name);
+                self.push("unsent-broadcast", *sprite, Some(id), message);
+            }
+        }
+
+        let read = std::mem::take(&mut self.read);
+        for ((owner, name), (sprite, id)) in read {
+            if !self.set.contains(&(owner, name.clone())) {
+                let message = format!("variable \"{}\" is used but never set", name);
+                self.push("unset-variable", sprite, Some(id), message);
+            }
+        }
+        self.lints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scratchblocks, test_util::test_project};
+
+    #[test]
+    fn finds_mistakes() {
+        let mut project = test_project();
+        let before = lint(&project);
+        let sprite = &mut project.sprites_mut()[1];
+        let text = "when flag clicked\n\
+            broadcast (nobody v)\n\
+            forever\n\
+            end\n\
+            say (never set)\n\
+            \n\
+            when I receive [nothing v]\n\
+            \n\
+            define unused\n\
+            \n\
+            move (1) steps";
+        scratchblocks::add(sprite, text).unwrap();
+        let lints = lint(&project);
+        let mut rules: Vec<&str> = lints
+            .iter()
+            .filter(|f| !before.contains(f))
+            .map(|f| f.rule)
+            .collect();
+        rules.sort();
+        assert_eq!(
+            rules,
+            [
+                "stray-blocks",
+                "unreachable-after-forever",
+                "unreceived-broadcast",
+                "unsent-broadcast",
+                "unset-variable",
+                "unused-custom-block"
+            ]
+        );
+    }
+
+    #[test]
+    fn knows_what_isnt_a_mistake() {
+        let json = serde_json::json!({"targets": [
+            {"isStage": true, "name": "Stage",
+                "variables": {"a-id": ["speed", 3], "b-id": ["score", 0]}},
+            {"isStage": false, "name": "Cat", "variables": {"c-id": ["score", 0]}},
+        ]});
+        let options = crate::decomp::LoadOptions::default();
+        let mut project =
+            crate::decomp::Project::from_json_str(&json.to_string(), options).unwrap();
+        let text = "when flag clicked\n\
+            broadcast (Go v)\n\
+            move (speed) steps\n\
+            forever\n\
+            end\n\
+            say [hi]\n\
+            say [bye]\n\
+            \n\
+            when I receive [go v]\n\
+            say [went]";
+        scratchblocks::add(&mut project.sprites_mut()[1], text).unwrap();
+        let lints: Vec<String> = lint(&project).iter().map(|f| f.to_string()).collect();
+        let forever = project.sprites()[1]
+            .blocks
+            .iter()
+            .find(|f| matches!(f.1, BlockType::Forever(_)))
+            .unwrap()
+            .0;
+        // speed is saved as 3, and go is received as well as sent.
+        assert_eq!(
+            lints,
+            [
+                format!(
+                    "Cat ({}): 2 blocks after a forever never run [unreachable-after-forever]",
+                    forever
+                ),
+                "Cat: variable \"score\" hides the stage's variable of the same name \
+                [name-collision]"
+                    .to_string()
+            ]
+        );
+    }
+}
@@ -1,217 +1,265 @@
-#![feature(fmt_internals)]
+use std::{error::Error, time::Instant};
 
-use std::{collections::HashMap, error::Error, fmt::Debug};
+use yase::{
+    decomp, diff, error, graph, index, lint, merge, optimize, rename, runtime, scratchblocks,
+    stats, transpile,
+};
 
-use blocks::{Block, BlockType, WhenGreenFlagClicked};
+use runtime::{
+    ir::Program, treewalk::TreeWalker, types::Types, vm::Vm, world::World, Backend, Options,
+    Runtime,
+};
 
-use crate::blocks::BlockType::*;
+/// The commands, which come first. Without one the scripts are printed.
+const COMMANDS: [&str; 11] = [
+    "diff",
+    "merge",
+    "run",
+    "bench",
+    "types",
+    "graph",
+    "lint",
+    "refs",
+    "rename",
+    "stats",
+    "transpile",
+];
 
-pub(crate) mod block_defs;
-pub mod block_names;
-pub mod blocks;
-pub mod decomp;
+fn main() {
+    if let Err(err) = yase() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let project = decomp::Project::new(None)?;
+fn yase() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // runs sprites that keep to themselves on worker threads.
+    let parallel = args.iter().any(|f| f == "--parallel");
+    // skips over blocks that can't be read, instead of giving up.
+    let lenient = args.iter().any(|f| f == "--lenient");
+    // lints, stats, references and diffs as JSON, for whatever reads them next.
+    let json = args.iter().any(|f| f == "--json");
+    // simplifies the scripts before anything else happens to them.
+    let optimizing = args.iter().any(|f| f == "--optimize");
+    // where a transpiled crate gets yase_rt from, a path or a version.
+    let rt = args
+        .iter()
+        .position(|f| f == "--rt")
+        .filter(|f| f + 1 < args.len());
+    let rt = rt.map(|f| args.drain(f..f + 2).next_back().unwrap());
+    args.retain(|f| f != "--parallel" && f != "--lenient" && f != "--json" && f != "--optimize");
+    let options = decomp::LoadOptions { strict: !lenient };
 
-    for sprite in project {
-        println!("{}", sprite.name);
+    // compares or merges projects, instead of loading test.json.
+    match args.first().map(|f| f.as_str()) {
+        Some("diff") => {
+            let (before, after) = match (args.get(1), args.get(2)) {
+                (Some(a), Some(b)) => (open(a, options)?, open(b, options)?),
+                _ => return Err("diff needs the project before and the one after".into()),
+            };
+            let differences = diff::diff(&before, &after);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&differences)?),
+                false => differences.iter().for_each(|f| println!("{}", f)),
+            }
+            return Ok(());
+        }
+        Some("merge") => {
+            // the project both started from, then ours and theirs. Prints the
+            // merged project.json.
+            let projects = match (args.get(1), args.get(2), args.get(3)) {
+                (Some(a), Some(b), Some(c)) => [a, b, c].map(|f| open(f, options)),
+                _ => return Err("merge needs the base project, ours and theirs".into()),
+            };
+            let [base, ours, theirs] = projects;
+            let (merged, conflicts) = merge::merge(&base?, &ours?, &theirs?)?;
+            for conflict in &conflicts {
+                eprintln!("conflict: {}", conflict);
+            }
+            println!("{}", merged.to_json());
+            return Ok(());
+        }
+        _ => {}
+    }
+    // a project to load instead of test.json, anywhere after the command,
+    // or on its own.
+    let skip = match args.first() {
+        Some(a) if COMMANDS.contains(&a.as_str()) => 1,
+        _ => 0,
+    };
+    let path = args
+        .iter()
+        .skip(skip)
+        .position(|f| [".sb3", ".sb2", ".json"].iter().any(|g| f.ends_with(g)));
+    let path = path.map(|f| args.remove(f + skip));
 
-        let blocks = sprite.blocks;
-        for block in &blocks {
-            match block.1 {
-                WhenGreenFlagClicked(a) => {
-                    println!("main:");
-                    follow_main_block(&blocks, a);
-                }
-                WhenKeyPressed(a) => {
-                    println!("when {:?} pressed:", a.key);
-                    follow_main_block(&blocks, a);
-                }
-                WhenSpriteClicked(a) => {
-                    println!("when sprite clicked:");
-                    follow_main_block(&blocks, a);
-                }
-                WhenStageClicked(a) => {
-                    println!("when stage clicked:");
-                    follow_main_block(&blocks, a);
-                }
-                WhenBackdropSwitchesTo(a) => {
-                    println!("when backdrop switched to {:?}:", a.backdrop);
-                    follow_main_block(&blocks, a);
-                }
-                WhenOptionGreaterThen(a) => {
-                    println!("when {:?} greater then {:?}", a.option, a.by);
-                    follow_main_block(&blocks, a);
-                }
-                WhenIRecieveBroadcast(a) => {
-                    println!("when i recieve {:?}:", a.broadcast);
-                    follow_main_block(&blocks, a);
-                }
-                Broadcast(a) => {
-                    println!("broadcast {:?}:", a.broadcast);
-                    follow_main_block(&blocks, a);
-                }
-                BroadcastAndWait(a) => {
-                    println!("broadcast+wait {:?}:", a.broadcast);
-                    follow_main_block(&blocks, a);
-                }
-                ProceduresDefinition(a) => {
-                    if let Some(ProceduresPrototype(b)) = blocks.get(&a.block.replace("\"", "")) {
-                        // @TODO: DEAR MOTHER OF GOD
-                        println!(
-                            "procedure: {:?}",
-                            b.mutation
-                                .proccode
-                                .as_ref()
-                                .unwrap()
-                                .as_str()
-                                .unwrap()
-                                .to_string()
-                                .split(" ")
-                                .map(|f| f.to_string())
-                                .collect::<Vec<String>>()
-                                .get(0)
-                        );
-                        follow_main_block(&blocks, a);
-                    }
-                }
-                _ => {}
+    let mut project = open(path.as_deref().unwrap_or("./test.json"), options)?;
+    for warning in project.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    if optimizing {
+        eprintln!("optimized: {}", optimize::optimize(&mut project));
+    }
+    // how many frames to run for, 30 of them make a second.
+    let frames = args.get(1).and_then(|f| f.parse().ok()).unwrap_or(300);
+    match args.first().map(|f| f.as_str()) {
+        Some("run") => {
+            run(&project, frames, parallel);
+            return Ok(());
+        }
+        Some("bench") => {
+            bench(&project, frames);
+            return Ok(());
+        }
+        Some("types") => {
+            let program = Program::compile(&project);
+            print!("{}", Types::infer(&project, &program).report(&program));
+            return Ok(());
+        }
+        Some("graph") => {
+            print!("{}", graph::dot(&project));
+            return Ok(());
+        }
+        Some("lint") => {
+            let lints = lint::lint(&project);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&lints)?),
+                false => lints.iter().for_each(|f| println!("{}", f)),
+            }
+            return Ok(());
+        }
+        Some("refs") => {
+            // optionally only one kind of thing, and one name of it.
+            let kind = match args.get(1) {
+                Some(a) => Some(index::Kind::from_name(a).ok_or(format!("unknown kind: {}", a))?),
+                None => None,
+            };
+            let name = args.get(2);
+            let index = index::Index::new(&project);
+            let found: Vec<&index::Reference> = index
+                .references
+                .iter()
+                .filter(|f| kind.is_none_or(|kind| f.kind == kind))
+                .filter(|f| name.is_none_or(|name| f.name == *name))
+                .collect();
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&found)?),
+                false => found.iter().for_each(|f| println!("{}", f)),
+            }
+            return Ok(());
+        }
+        Some("rename") => {
+            // then the old name, the new one, and whose it is if it's not the
+            // stage's. Prints the renamed project.json.
+            let [kind, old, new] = [1, 2, 3].map(|f| args.get(f).cloned().unwrap_or_default());
+            let kind = index::Kind::from_name(&kind).ok_or(format!("unknown kind: {}", kind))?;
+            let sprite = args.get(4).map(|f| f.as_str());
+            let renamed = rename::rename(&mut project, kind, sprite, &old, &new)?;
+            eprintln!("renamed {} blocks", renamed);
+            println!("{}", project.to_json());
+            return Ok(());
+        }
+        Some("stats") => {
+            let stats = stats::Stats::new(&project);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&stats)?),
+                false => print!("{}", stats),
             }
+            return Ok(());
+        }
+        Some("transpile") => {
+            let out = args.get(1).map(|f| f.as_str()).unwrap_or("transpiled");
+            let rt = match rt {
+                Some(a) if a.starts_with(|f: char| f.is_ascii_digit()) => transpile::Rt::Version(a),
+                Some(a) => transpile::Rt::Path(std::fs::canonicalize(a)?),
+                None => transpile::Rt::default(),
+            };
+            transpile::transpile(&project, std::path::Path::new(out), &rt)?;
+            println!("wrote {}, run it with cargo run --release", out);
+            return Ok(());
         }
+        _ => {}
+    }
+
+    for sprite in project {
+        println!("{}:", sprite.name);
+        println!("{}", scratchblocks::sprite(&sprite));
     }
 
     Ok(())
 }
 
-fn follow_main_block(blocks: &HashMap<String, BlockType>, b: &dyn Block) {
-    if let Some(m) = &b.next() {
-        if let Some(next) = blocks.get(&m.replace("\"", "")) {
-            println!("\t{}", next);
-            match next {
-                Move(a) => follow_main_block(blocks, a),
-                RotateLeft(a) => follow_main_block(blocks, a),
-                RotateRight(a) => follow_main_block(blocks, a),
-                Goto(a) => match a {
-                    blocks::Goto::Pos(b) => follow_main_block(blocks, b),
-                    blocks::Goto::Option(b) => follow_main_block(blocks, b),
-                },
-                Glide(a) => match a {
-                    blocks::Glide::Pos(b) => follow_main_block(blocks, b),
-                    blocks::Glide::Option(b) => follow_main_block(blocks, b),
-                },
-                Point(a) => match a {
-                    blocks::Point::Direction(b) => follow_main_block(blocks, b),
-                    blocks::Point::Towards(b) => follow_main_block(blocks, b),
-                },
-                ChangeX(a) => follow_main_block(blocks, a),
-                SetX(a) => follow_main_block(blocks, a),
-                ChangeY(a) => follow_main_block(blocks, a),
-                SetY(a) => follow_main_block(blocks, a),
-                IfOnEdgeBounce(a) => follow_main_block(blocks, a),
-                SetRotationStyle(a) => follow_main_block(blocks, a),
-                XPosition(a) => follow_main_block(blocks, a),
-                YPosition(a) => follow_main_block(blocks, a),
-                Direction(a) => follow_main_block(blocks, a),
-                Say(a) => follow_main_block(blocks, a),
-                SayForever(a) => follow_main_block(blocks, a),
-                Think(a) => follow_main_block(blocks, a),
-                ThinkForever(a) => follow_main_block(blocks, a),
-                SwitchCostume(a) => follow_main_block(blocks, a),
-                NextCostume(a) => follow_main_block(blocks, a),
-                SwitchBackdrop(a) => follow_main_block(blocks, a),
-                SwitchBackdropAndWait(a) => follow_main_block(blocks, a),
-                NextBackdrop(a) => follow_main_block(blocks, a),
-                ChangeSize(a) => follow_main_block(blocks, a),
-                SetSize(a) => follow_main_block(blocks, a),
-                ClearGraphicEffects(a) => follow_main_block(blocks, a),
-                ShowSprite(a) => follow_main_block(blocks, a),
-                HideSprite(a) => follow_main_block(blocks, a),
-                HideAllSprites(a) => follow_main_block(blocks, a),
-                GotoLayer(a) => follow_main_block(blocks, a),
-                ChangeLayer(a) => follow_main_block(blocks, a),
-                Costume(a) => {}
-                Backdrop(a) => {}
-                Size(a) => follow_main_block(blocks, a),
-                PlaySound(a) => follow_main_block(blocks, a),
-                PlaySoundUntilDone(a) => follow_main_block(blocks, a),
-                StartSound(a) => follow_main_block(blocks, a),
-                StopAllSounds(a) => follow_main_block(blocks, a),
-                ChangeEffectBy(a) => follow_main_block(blocks, a),
-                SetEffectTo(a) => follow_main_block(blocks, a),
-                ClearSoundEffects(a) => follow_main_block(blocks, a),
-                ChangeVolumeBy(a) => follow_main_block(blocks, a),
-                SetVolumeTo(a) => follow_main_block(blocks, a),
-                Volume(a) => follow_main_block(blocks, a),
-                BlockType::WhenGreenFlagClicked(a) => follow_main_block(blocks, a),
-                WhenKeyPressed(a) => follow_main_block(blocks, a),
-                WhenSpriteClicked(a) => follow_main_block(blocks, a),
-                WhenStageClicked(a) => follow_main_block(blocks, a),
-                WhenBackdropSwitchesTo(a) => follow_main_block(blocks, a),
-                WhenOptionGreaterThen(a) => follow_main_block(blocks, a),
-                WhenIRecieveBroadcast(a) => follow_main_block(blocks, a),
-                Broadcast(a) => follow_main_block(blocks, a),
-                BroadcastAndWait(a) => follow_main_block(blocks, a),
-                WaitSeconds(a) => follow_main_block(blocks, a),
-                Repeat(a) => follow_main_block(blocks, a),
-                Forever(a) => follow_main_block(blocks, a),
-                IfThen(a) => follow_main_block(blocks, a),
-                IfThenElse(a) => follow_main_block(blocks, a),
-                WaitUntil(a) => follow_main_block(blocks, a),
-                RepeatUntil(a) => follow_main_block(blocks, a),
-                StopAll(a) => follow_main_block(blocks, a),
-                WhenIStartAsAClone(a) => follow_main_block(blocks, a),
-                CreateCloneOf(a) => follow_main_block(blocks, a),
-                DeleteClone(a) => follow_main_block(blocks, a),
-                Touching(a) => follow_main_block(blocks, a),
-                TouchingMenu(a) => follow_main_block(blocks, a),
-                TouchingColor(a) => follow_main_block(blocks, a),
-                ColorTouchingColor(a) => follow_main_block(blocks, a),
-                DistanceTo(a) => follow_main_block(blocks, a),
-                Answer(a) => follow_main_block(blocks, a),
-                KeyPressed(a) => follow_main_block(blocks, a),
-                MouseDown(a) => follow_main_block(blocks, a),
-                MouseX(a) => follow_main_block(blocks, a),
-                MouseY(a) => follow_main_block(blocks, a),
+/// An .sb3, an .sb2, or a project.json.
+fn open(path: &str, options: decomp::LoadOptions) -> Result<decomp::Project, error::YaseError> {
+    let io = |err: std::io::Error| error::YaseError::Io(format!("{}: {}", path, err));
+    match path.rsplit_once('.').map(|f| f.1) {
+        Some("sb3") => decomp::Project::from_sb3_bytes(&std::fs::read(path).map_err(io)?, options),
+        Some("sb2") => decomp::Project::load_sb2(path, options),
+        _ => decomp::Project::from_json_str(&std::fs::read_to_string(path).map_err(io)?, options),
+    }
+}
 
-                SetDragMode(a) => follow_main_block(blocks, a),
-                Loudness(a) => follow_main_block(blocks, a),
-                Timer(a) => follow_main_block(blocks, a),
-                ResetTimer(a) => follow_main_block(blocks, a),
-                BackdropOf(a) => follow_main_block(blocks, a),
-                CurrentTime(a) => follow_main_block(blocks, a),
-                DaysSince2000(a) => follow_main_block(blocks, a),
-                Username(a) => follow_main_block(blocks, a),
-                Add(a) => follow_main_block(blocks, a),
-                Sub(a) => follow_main_block(blocks, a),
-                Mul(a) => follow_main_block(blocks, a),
-                Divide(a) => follow_main_block(blocks, a),
-                PickRandom(a) => follow_main_block(blocks, a),
-                GreaterThen(a) => follow_main_block(blocks, a),
-                LesserThen(a) => follow_main_block(blocks, a),
-                EqualTo(a) => follow_main_block(blocks, a),
-                And(a) => follow_main_block(blocks, a),
-                Or(a) => follow_main_block(blocks, a),
-                Not(a) => follow_main_block(blocks, a),
-                Join(a) => follow_main_block(blocks, a),
-                LetterOf(a) => follow_main_block(blocks, a),
-                LengthOf(a) => follow_main_block(blocks, a),
-                Contains(a) => follow_main_block(blocks, a),
-                Modulo(a) => follow_main_block(blocks, a),
-                Round(a) => follow_main_block(blocks, a),
-                Absolute(a) => follow_main_block(blocks, a),
-                SoundEffectsMenu(a) => follow_main_block(blocks, a),
-                SoundSoundsMenu(a) => follow_main_block(blocks, a),
-                PointTowardsMenu(a) => follow_main_block(blocks, a),
-                UnusedOpcode(a) => follow_main_block(blocks, a),
-                InvalidOpcode(a) => follow_main_block(blocks, a),
-                DraggableOption(_) => {}
-                ProceduresCall(a) => follow_main_block(blocks, a),
-                ProceduresDeclaration(a) => follow_main_block(blocks, a),
-                ProceduresDefinition(a) => follow_main_block(blocks, a),
-                ProceduresPrototype(a) => follow_main_block(blocks, a),
-                _ => {}
-            };
-        }
+/// Clicks the green flag and prints whatever the sprites say.
+fn run(project: &decomp::Project, frames: u64, parallel: bool) {
+    let program = Program::compile(project);
+    let sprites = runtime::parallel::isolated(&program);
+    let world = World::new(&program.targets, Options::default().seed);
+    let mut runtime = Runtime::new(Vm::new(program), world, Options::default());
+    if parallel {
+        let threads = std::thread::available_parallelism().map_or(1, |f| f.get());
+        runtime.set_parallel(&sprites, threads);
     }
+    runtime.green_flag();
+    let ran = runtime.run(frames);
+    print!("{}", runtime.world().transcript());
+    println!("ran for {} frames", ran);
+}
+
+fn run_with<B: Backend>(backend: B, program: &Program, frames: u64) -> (World, f64) {
+    let world = World::new(&program.targets, Options::default().seed);
+    let mut runtime = Runtime::new(backend, world, Options::default());
+    let start = Instant::now();
+    runtime.green_flag();
+    runtime.run(frames);
+    (runtime.world().clone(), start.elapsed().as_secs_f64())
+}
+
+/// Runs the project with both the compiled and the tree walking backends,
+/// and compares how long they took and whether they ended up in the same place.
+fn bench(project: &decomp::Project, frames: u64) {
+    let start = Instant::now();
+    let program = Program::compile(project);
+    let compile = start.elapsed().as_secs_f64();
+    println!(
+        "compiled {} instructions in {:.3}ms",
+        program.code.len(),
+        compile * 1000.0
+    );
+
+    let (walked, walk_time) = run_with(TreeWalker::new(project, &program), &program, frames);
+    println!("tree walking: {:.3}ms", walk_time * 1000.0);
+    let (compiled, vm_time) = run_with(Vm::new(program.clone()), &program, frames);
+    println!("bytecode:     {:.3}ms", vm_time * 1000.0);
+    println!("speedup:      {:.2}x", walk_time / vm_time);
+
+    let same = walked.speech == compiled.speech
+        && walked.targets.len() == compiled.targets.len()
+        && walked
+            .targets
+            .iter()
+            .zip(&compiled.targets)
+            .all(|f| match f {
+                (Some(a), Some(b)) => {
+                    a.x == b.x
+                        && a.y == b.y
+                        && a.direction == b.direction
+                        && a.costume == b.costume
+                        && a.variables == b.variables
+                        && a.lists == b.lists
+                }
+                (None, None) => true,
+                _ => false,
+            });
+    println!("results {}", if same { "match" } else { "DIFFER" });
 }
@@ -0,0 +1,394 @@
+//! Putting together two projects that were both changed from the same one.
+//!
+//! It's done on project.json. Sprites are matched by name, scripts by the id
+//! of their top block, and variables, lists, broadcasts and comments by id;
+//! everything else about a sprite is matched by its key. Whatever only one
+//! side changed is taken from that side. Where both sides changed the same
+//! thing differently it's a conflict, and ours is kept, or whichever side
+//! still has it if the other deleted it. Moving a script around isn't
+//! changing it, so tidying one side up doesn't conflict with the other.
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    decomp::{LoadOptions, Project},
+    error::YaseError,
+};
+
+/// Something both sides changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// None for what's about the whole project, like monitors.
+    pub sprite: Option<String>,
+    /// What it is, like `script a1 (event_whenflagclicked)` or `costumes`.
+    pub part: String,
+    /// Whether one side deleted it instead of changing it.
+    pub deleted: bool,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(a) = &self.sprite {
+            write!(f, "{}: ", a)?;
+        }
+        match self.deleted {
+            true => write!(
+                f,
+                "{} was changed on one side and deleted on the other, kept it",
+                self.part
+            ),
+            false => write!(f, "{} was changed on both sides, kept ours", self.part),
+        }
+    }
+}
+
+/// The merged project, and what couldn't be merged cleanly.
+pub fn merge(
+    base: &Project,
+    ours: &Project,
+    theirs: &Project,
+) -> Result<(Project, Vec<Conflict>), YaseError> {
+    let (base, ours, theirs) = (base.to_json(), ours.to_json(), theirs.to_json());
+    let mut merger = Merger::default();
+    let mut json = ours.as_object().cloned().unwrap_or_default();
+
+    let targets = |f: &Value| -> Vec<(String, Value)> {
+        let targets = f["targets"].as_array().into_iter().flatten();
+        targets
+            .map(|f| {
+                (
+                    f["name"].as_str().unwrap_or_default().to_string(),
+                    f.clone(),
+                )
+            })
+            .collect()
+    };
+    let (base_targets, our_targets, their_targets) =
+        (targets(&base), targets(&ours), targets(&theirs));
+    let find = |targets: &[(String, Value)], name: &str| {
+        targets.iter().find(|f| f.0 == name).map(|f| f.1.clone())
+    };
+    // ours first, in the order ours has them.
+    let mut names: Vec<&String> = Vec::new();
+    for (name, _) in our_targets
+        .iter()
+        .chain(&their_targets)
+        .chain(&base_targets)
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    let mut merged = Vec::new();
+    for name in names {
+        merger.sprite = Some(name.clone());
+        let target = merger.target(
+            find(&base_targets, name).as_ref(),
+            find(&our_targets, name).as_ref(),
+            find(&their_targets, name).as_ref(),
+        );
+        merged.extend(target);
+    }
+    json.insert("targets".to_string(), Value::Array(merged));
+
+    merger.sprite = None;
+    // monitors are matched by id, like variables, and stay in ours' order.
+    let monitors = |f: &Value| -> Vec<(String, Value)> {
+        let monitors = f["monitors"].as_array().into_iter().flatten();
+        monitors
+            .map(|f| (f["id"].as_str().unwrap_or_default().to_string(), f.clone()))
+            .collect()
+    };
+    let [b, o, t] = [&base, &ours, &theirs].map(monitors);
+    let mut order: Vec<&String> = Vec::new();
+    for (id, _) in o.iter().chain(&t) {
+        if !order.contains(&id) {
+            order.push(id);
+        }
+    }
+    let [b, o, t] = [&b, &o, &t].map(|f| Value::Object(f.iter().cloned().collect()));
+    let merged = merger.entries("monitors", Some(&b), Some(&o), Some(&t));
+    let monitors = order.into_iter().filter_map(|f| merged.get(f).cloned());
+    json.insert("monitors".to_string(), Value::Array(monitors.collect()));
+    let key = "extensions";
+    if let Some(a) = merger.pick(
+        || key.to_string(),
+        base.get(key),
+        ours.get(key),
+        theirs.get(key),
+    ) {
+        json.insert(key.to_string(), a);
+    }
+    let json = Value::Object(json).to_string();
+    let project = Project::from_json_str(&json, LoadOptions::default())?;
+    Ok((project, merger.conflicts))
+}
+
+/// Whichever side changed it, or Err with what to keep if both did.
+fn choose<'a, T: PartialEq>(
+    base: Option<&'a T>,
+    ours: Option<&'a T>,
+    theirs: Option<&'a T>,
+) -> Result<Option<&'a T>, Option<&'a T>> {
+    match (ours == theirs || theirs == base, ours == base) {
+        (true, _) => Ok(ours),
+        (false, true) => Ok(theirs),
+        (false, false) => Err(ours.or(theirs)),
+    }
+}
+
+#[derive(Default)]
+struct Merger {
+    /// The sprite being merged.
+    sprite: Option<String>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+    fn pick<T: PartialEq + Clone>(
+        &mut self,
+        part: impl FnOnce() -> String,
+        base: Option<&T>,
+        ours: Option<&T>,
+        theirs: Option<&T>,
+    ) -> Option<T> {
+        match choose(base, ours, theirs) {
+            Ok(a) => a.cloned(),
+            Err(a) => {
+                self.conflicts.push(Conflict {
+                    sprite: self.sprite.clone(),
+                    part: part(),
+                    deleted: ours.is_none() || theirs.is_none(),
+                });
+                a.cloned()
+            }
+        }
+    }
+
+    /// A sprite, key by key if both sides still have it.
+    fn target(
+        &mut self,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Option<Value> {
+        let (ours, theirs) = match (
+            ours.and_then(Value::as_object),
+            theirs.and_then(Value::as_object),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return self.pick(|| "sprite".to_string(), base, ours, theirs),
+        };
+        let base = base.and_then(Value::as_object);
+        let get = |key: &str| base.and_then(|f| f.get(key)).cloned();
+        let mut json = Map::new();
+        for key in keys(Some(ours), Some(theirs)) {
+            let (b, o, t) = (get(&key), ours.get(&key), theirs.get(&key));
+            let value = match key.as_str() {
+                "blocks" => Some(Value::Object(self.blocks(b.as_ref(), o, t))),
+                "variables" | "lists" | "broadcasts" | "comments" => {
+                    Some(Value::Object(self.entries(&key, b.as_ref(), o, t)))
+                }
+                _ => self.pick(|| key.clone(), b.as_ref(), o, t),
+            };
+            if let Some(a) = value {
+                json.insert(key, a);
+            }
+        }
+        Some(Value::Object(json))
+    }
+
+    /// Variables, lists, broadcasts, comments or monitors, one by one.
+    fn entries(
+        &mut self,
+        key: &str,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Map<String, Value> {
+        let [base, ours, theirs] = [base, ours, theirs].map(|f| f.and_then(Value::as_object));
+        let mut json = Map::new();
+        for id in keys(ours, theirs).into_iter().chain(keys(base, None)) {
+            if json.contains_key(&id) {
+                continue;
+            }
+            let [b, o, t] = [base, ours, theirs].map(|f| f.and_then(|f| f.get(&id)));
+            let part = || {
+                let entry = o.or(t).or(b);
+                // variables and lists are [name, value], broadcasts just a name.
+                let name = entry.and_then(|f| f.get(0).unwrap_or(f).as_str());
+                let kind = key.trim_end_matches('s');
+                match name {
+                    Some(a) => format!("{} \"{}\"", kind, a),
+                    None => format!("{} {}", kind, id),
+                }
+            };
+            if let Some(a) = self.pick(part, b, o, t) {
+                json.insert(id.clone(), a);
+            }
+        }
+        json
+    }
+
+    /// The blocks of a sprite, script by script.
+    fn blocks(
+        &mut self,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+    ) -> Map<String, Value> {
+        let [base, ours, theirs] = [base, ours, theirs].map(scripts);
+        let mut tops: Vec<&String> = ours
+            .keys()
+            .chain(theirs.keys())
+            .chain(base.keys())
+            .collect();
+        tops.sort();
+        tops.dedup();
+        let mut json = Map::new();
+        for top in tops {
+            let [b, o, t] = [&base, &ours, &theirs].map(|f| f.get(top));
+            // where it is doesn't count.
+            let [b, o, t] = [b, o, t].map(|f| f.map(|f| placed(f, top, None).0));
+            let part = || {
+                let block = o.as_ref().or(t.as_ref()).or(b.as_ref()).map(|f| &f[top]);
+                let opcode = block
+                    .and_then(|f| f["opcode"].as_str())
+                    .unwrap_or("variable");
+                format!("script {} ({})", top, opcode)
+            };
+            let script = match self.pick(part, b.as_ref(), o.as_ref(), t.as_ref()) {
+                Some(a) => a,
+                None => continue,
+            };
+            // but it goes where whichever side moved it put it.
+            let [b, o, t] =
+                [&base, &ours, &theirs].map(|f| f.get(top).map(|f| placed(f, top, None).1));
+            let at = match choose(b.as_ref(), o.as_ref(), t.as_ref()) {
+                Ok(a) | Err(a) => a.cloned().flatten(),
+            };
+            for (id, block) in placed(&script, top, at).0 {
+                if json.contains_key(&id) {
+                    // a block both sides moved to different scripts.
+                    self.conflicts.push(Conflict {
+                        sprite: self.sprite.clone(),
+                        part: format!("block {}", id),
+                        deleted: false,
+                    });
+                    continue;
+                }
+                json.insert(id, block);
+            }
+        }
+        json
+    }
+}
+
+/// Every key either has, in order.
+fn keys(a: Option<&Map<String, Value>>, b: Option<&Map<String, Value>>) -> Vec<String> {
+    let mut keys: Vec<String> = a.into_iter().flat_map(|f| f.keys().cloned()).collect();
+    for key in b.into_iter().flat_map(|f| f.keys()) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    keys
+}
+
+/// A sprite's blocks, grouped by the top block of the script they're in.
+fn scripts(blocks: Option<&Value>) -> BTreeMap<String, Map<String, Value>> {
+    let blocks = match blocks.and_then(Value::as_object) {
+        Some(a) => a,
+        None => return BTreeMap::new(),
+    };
+    let mut scripts: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+    for (id, block) in blocks {
+        let mut top = id;
+        // up to as many steps as there are blocks, in case of a loop.
+        for _ in 0..blocks.len() {
+            match blocks[top]["parent"].as_str() {
+                Some(a) if blocks.contains_key(a) => top = blocks.get_key_value(a).unwrap().0,
+                _ => break,
+            }
+        }
+        scripts
+            .entry(top.clone())
+            .or_default()
+            .insert(id.clone(), block.clone());
+    }
+    scripts
+}
+
+/// The script without where its top block is, and where that was. Puts it
+/// somewhere else if given where.
+fn placed(
+    script: &Map<String, Value>,
+    top: &str,
+    at: Option<(Value, Value)>,
+) -> (Map<String, Value>, Option<(Value, Value)>) {
+    let mut script = script.clone();
+    let block = match script.get_mut(top).and_then(Value::as_object_mut) {
+        Some(a) => a,
+        None => return (script, None),
+    };
+    let was = match (block.remove("x"), block.remove("y")) {
+        (Some(x), Some(y)) => Some((x, y)),
+        _ => None,
+    };
+    if let Some((x, y)) = at {
+        block.insert("x".to_string(), x);
+        block.insert("y".to_string(), y);
+    }
+    (script, was)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index::Kind, rename::rename, scratchblocks};
+
+    fn names(project: &Project) -> Vec<&str> {
+        project.sprites()[0]
+            .variables
+            .values()
+            .map(|f| f.name())
+            .collect()
+    }
+
+    #[test]
+    fn merges_both_sides() {
+        let base = crate::test_util::test_project();
+        let mut ours = base.clone();
+        let sprite = &mut ours.sprites_mut()[1];
+        scratchblocks::add(sprite, "when this sprite clicked\nsay [hi]").unwrap();
+        rename(&mut ours, Kind::Variable, None, "width", "breadth").unwrap();
+
+        let mut json = base.to_json();
+        json["targets"][1]["costumes"]
+            .as_array_mut()
+            .unwrap()
+            .remove(0);
+        let mut theirs = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        rename(&mut theirs, Kind::Variable, None, "Height", "tall").unwrap();
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs).unwrap();
+        // both renamed variables in the flag script.
+        assert!(!conflicts.is_empty());
+        for conflict in &conflicts {
+            assert!(conflict.part.starts_with("script "), "{}", conflict);
+            assert!(!conflict.deleted);
+        }
+        let names = names(&merged);
+        assert!(names.contains(&"breadth") && names.contains(&"tall"));
+        let sprite = &merged.sprites()[1];
+        assert_eq!(sprite.costumes.len(), base.sprites()[1].costumes.len() - 1);
+        assert!(scratchblocks::sprite(sprite).contains("when this sprite clicked\nsay [hi]"));
+
+        // with nothing to merge, it's whichever side changed.
+        let (merged, conflicts) = merge(&base, &ours, &base).unwrap();
+        assert_eq!((merged.to_json(), conflicts), (ours.to_json(), vec![]));
+        let (merged, conflicts) = merge(&base, &base, &theirs).unwrap();
+        assert_eq!((merged.to_json(), conflicts), (theirs.to_json(), vec![]));
+    }
+}
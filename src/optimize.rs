@@ -0,0 +1,601 @@
+//! Making scripts smaller without changing what they do.
+//!
+//! Stray and unreachable blocks are taken out, small custom blocks are put
+//! in place of their calls, operators on two literals become their result,
+//! and blocks that do nothing are dropped. What comes out is a project like
+//! any other, so it runs and saves the same way.
+//!
+//! Each pass decides what to change by looking at the blocks through
+//! [`Linked`], then makes the change to the blocks as project.json has them,
+//! where every block has the same shape, and reads back the ones that changed.
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Map, Value as SerdeValue};
+
+use crate::{
+    block_names::*,
+    blocks::{BlockType, Value},
+    decomp::{Project, Sprite},
+    runtime::{
+        ir::literal,
+        ops::{self, BinOp},
+        value::Val,
+    },
+    script::{Input, Linked, Procedure},
+    serialize::{self, blocks_json, Context},
+    visit::{BlockVisitor, Walker},
+};
+
+/// Custom blocks with at most this many blocks in them, reporters and all,
+/// are inlined.
+const TINY: usize = 5;
+
+/// How much was changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    /// Blocks that were stray, unreachable or did nothing, and the
+    /// definitions of custom blocks that are no longer called.
+    pub removed: usize,
+    pub folded: usize,
+    pub inlined: usize,
+}
+
+impl std::fmt::Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} blocks removed, {} expressions folded, {} calls inlined",
+            self.removed, self.folded, self.inlined
+        )
+    }
+}
+
+/// Every sprite of the project.
+pub fn optimize(project: &mut Project) -> Changes {
+    let mut changes = Changes::default();
+    for sprite in project.sprites_mut() {
+        changes.add(optimize_sprite(sprite));
+    }
+    changes
+}
+
+pub fn optimize_sprite(sprite: &mut Sprite) -> Changes {
+    let mut changes = Changes::default();
+    unreachable(sprite, &mut changes);
+    inline(sprite, &mut changes);
+    // folding one operator can make a literal for the one it's in, and
+    // taking a block out can leave an if with nothing inside.
+    while simplify(sprite, &mut changes) {}
+    changes
+}
+
+impl Changes {
+    fn add(&mut self, other: Changes) {
+        self.removed += other.removed;
+        self.folded += other.folded;
+        self.inlined += other.inlined;
+    }
+}
+
+/// Blocks sorted by id, so the same project always changes the same way.
+fn sorted(sprite: &Sprite) -> Vec<(&str, &BlockType)> {
+    let mut blocks: Vec<_> = sprite.blocks.iter().map(|f| (f.0.as_str(), f.1)).collect();
+    blocks.sort_by_key(|f| f.0);
+    blocks
+}
+
+/// Stacks without a hat and whatever comes after a forever.
+fn unreachable(sprite: &mut Sprite, changes: &mut Changes) {
+    let mut edit = Edit::new(sprite);
+    for top in Walker::new(&sprite.blocks).tops() {
+        if !sprite.blocks[top].is_hat() {
+            changes.removed += edit.delete_stack(top);
+        }
+    }
+    for (id, block) in sorted(sprite) {
+        if let (BlockType::Forever(_), Some(next)) = (block, block.as_block().next()) {
+            edit.blocks[id]["next"] = SerdeValue::Null;
+            changes.removed += edit.delete_stack(&next);
+        }
+    }
+    edit.apply(sprite);
+}
+
+/// Calls of tiny custom blocks whose arguments are all literals. Blocks that
+/// aren't called any more after that go too.
+fn inline(sprite: &mut Sprite, changes: &mut Changes) {
+    let linked = Linked::new(sprite);
+    let mut edit = Edit::new(sprite);
+    let mut inlined = HashSet::new();
+    let mut kept = HashSet::new();
+    for (id, block) in sorted(sprite) {
+        let call = match block {
+            BlockType::ProceduresCall(a) => a,
+            _ => continue,
+        };
+        let proccode = call.signature.proccode();
+        let procedure = match linked.procedure(proccode) {
+            Some(a) if is_tiny(&linked, a) => a,
+            _ => {
+                kept.insert(proccode);
+                continue;
+            }
+        };
+        let given: HashMap<&str, &Option<Value>> = call
+            .signature
+            .argument_ids()
+            .iter()
+            .map(String::as_str)
+            .zip(&call.arguments)
+            .collect();
+        let arguments: Option<HashMap<&str, Option<&Value>>> = procedure
+            .signature
+            .argument_ids()
+            .iter()
+            .zip(procedure.signature.argument_names())
+            .map(
+                |(id, name)| match given.get(id.as_str()).map(|f| linked.input(f)) {
+                    Some(Input::Literal(a)) => Some((name.as_str(), Some(a))),
+                    Some(Input::Empty) | None => Some((name.as_str(), None)),
+                    Some(_) => None,
+                },
+            )
+            .collect();
+        let arguments = match arguments {
+            Some(a) => a,
+            None => {
+                kept.insert(proccode);
+                continue;
+            }
+        };
+        edit.inline(id, procedure.id, &arguments);
+        inlined.insert(proccode);
+        changes.inlined += 1;
+    }
+    let mut unused: Vec<&str> = inlined.difference(&kept).copied().collect();
+    unused.sort();
+    for proccode in unused {
+        changes.removed += edit.delete_stack(linked.procedures[proccode].id);
+    }
+    edit.apply(sprite);
+}
+
+/// Not run without screen refresh, since inlining would undo that, and
+/// without calls or stops, which would mean something else in the caller.
+fn is_tiny(linked: &Linked, procedure: &Procedure) -> bool {
+    struct Tiny<'a> {
+        names: &'a [String],
+        count: usize,
+        fits: bool,
+    }
+    impl<'a> BlockVisitor<'a> for Tiny<'_> {
+        fn enter(&mut self, _: &'a str, block: &'a BlockType, _: usize) -> bool {
+            self.count += 1;
+            self.fits &= match block {
+                BlockType::ProceduresCall(_) | BlockType::StopAll(_) => false,
+                BlockType::ArgumentReporter(a) => self.names.contains(&a.name),
+                _ => true,
+            };
+            self.fits
+        }
+    }
+
+    let body = match linked.sprite.blocks.get(procedure.id) {
+        Some(a) => a.as_block().next(),
+        None => return false,
+    };
+    let mut tiny = Tiny {
+        names: procedure.signature.argument_names(),
+        count: 0,
+        fits: !procedure.signature.warp(),
+    };
+    if let Some(first) = body {
+        Walker::new(&linked.sprite.blocks).walk_stack(&first, &mut tiny, 0);
+    }
+    tiny.fits && tiny.count <= TINY
+}
+
+/// One round of folding operators, and taking out moves and turns by 0 and
+/// ifs with nothing in them. Whether anything changed.
+fn simplify(sprite: &mut Sprite, changes: &mut Changes) -> bool {
+    let linked = Linked::new(sprite);
+    let mut edit = Edit::new(sprite);
+    let before = changes.clone();
+    let value = |val| match linked.input(val) {
+        Input::Literal(a) => Some(literal(a)),
+        Input::Empty => Some(Val::Str("".into())),
+        _ => None,
+    };
+    let is_zero = |val| value(val).is_some_and(|f: Val| f.num() == 0.0);
+    let is_empty = |val| matches!(linked.input(val), Input::Empty);
+    for (id, block) in sorted(sprite) {
+        // in something that was taken out already.
+        if !edit.blocks.contains_key(id) {
+            continue;
+        }
+        let operands = match block {
+            BlockType::Add(a) => Some((&a.a, &a.b, BinOp::Add)),
+            BlockType::Sub(a) => Some((&a.a, &a.b, BinOp::Sub)),
+            BlockType::Mul(a) => Some((&a.a, &a.b, BinOp::Mul)),
+            BlockType::Divide(a) => Some((&a.a, &a.b, BinOp::Div)),
+            BlockType::Modulo(a) => Some((&a.a, &a.b, BinOp::Mod)),
+            BlockType::Join(a) => Some((&a.a, &a.b, BinOp::Join)),
+            _ => None,
+        };
+        if let Some((a, b, op)) = operands {
+            let val = match (value(a), value(b)) {
+                (Some(a), Some(b)) => ops::binary(op, &a, &b),
+                _ => continue,
+            };
+            // infinity and NaN don't read back as what they were.
+            let finite = !matches!(val, Val::Num(a) if !a.is_finite());
+            if finite && edit.fold(id, &val) {
+                changes.folded += 1;
+            }
+            continue;
+        }
+
+        let useless = match block {
+            BlockType::ChangeX(a) => is_zero(&a.x),
+            BlockType::ChangeY(a) => is_zero(&a.y),
+            BlockType::RotateLeft(a) => is_zero(&a.degrees),
+            BlockType::RotateRight(a) => is_zero(&a.degrees),
+            BlockType::IfThen(a) => is_empty(&a.then),
+            BlockType::IfThenElse(a) => is_empty(&a.then) && is_empty(&a.otherwise),
+            _ => false,
+        };
+        if useless {
+            edit.unlink(id);
+            changes.removed += edit.delete(id);
+        }
+    }
+    edit.apply(sprite);
+    *changes != before
+}
+
+/// A sprite's blocks as project.json has them, being changed.
+struct Edit {
+    blocks: Map<String, SerdeValue>,
+    before: Map<String, SerdeValue>,
+    /// For ids of new blocks.
+    count: usize,
+}
+
+impl Edit {
+    fn new(sprite: &Sprite) -> Edit {
+        let blocks = blocks_json(&Context::new(sprite, None));
+        Edit {
+            before: blocks.clone(),
+            blocks,
+            count: 0,
+        }
+    }
+
+    /// Reads back every block that changed. If any can't be, which would be
+    /// a mistake in here, the sprite is left as it was.
+    fn apply(self, sprite: &mut Sprite) {
+        let mut changed = Vec::new();
+        for (id, json) in &self.blocks {
+            if self.before.get(id) == Some(json) {
+                continue;
+            }
+            match BlockType::from_json(json) {
+                Ok(a) => changed.push((id.clone(), a)),
+                Err(_) => return,
+            }
+        }
+        let gone = |id: &String| self.before.contains_key(id) && !self.blocks.contains_key(id);
+        sprite.blocks.retain(|id, _| !gone(id));
+        sprite.blocks.extend(changed);
+    }
+
+    fn parent(&self, id: &str) -> Option<String> {
+        self.blocks[id]["parent"].as_str().map(String::from)
+    }
+
+    fn next(&self, id: &str) -> Option<String> {
+        self.blocks[id]["next"].as_str().map(String::from)
+    }
+
+    /// Blocks in the inputs of this one, and in substacks the rest of the
+    /// stack after them too.
+    fn children(&self, id: &str) -> Vec<String> {
+        let inputs = self.blocks[id]["inputs"].as_object().into_iter().flatten();
+        let mut children: Vec<String> = Vec::new();
+        for first in inputs.filter_map(|f| f.1.get(1)?.as_str()) {
+            let mut cur = Some(first.to_string());
+            while let Some(id) = cur.filter(|f| self.blocks.contains_key(f)) {
+                // in case of a loop.
+                if children.contains(&id) {
+                    break;
+                }
+                cur = self.next(&id);
+                children.push(id);
+            }
+        }
+        children
+    }
+
+    /// The block and everything in its inputs and substacks, but not what's
+    /// under it. How many blocks that was.
+    fn delete(&mut self, id: &str) -> usize {
+        if !self.blocks.contains_key(id) {
+            return 0;
+        }
+        let children = self.children(id);
+        self.blocks.remove(id);
+        1 + children.iter().map(|f| self.delete(f)).sum::<usize>()
+    }
+
+    /// The block and everything under it.
+    fn delete_stack(&mut self, first: &str) -> usize {
+        let mut count = 0;
+        let mut cur = Some(first.to_string());
+        while let Some(id) = cur.filter(|f| self.blocks.contains_key(f)) {
+            cur = self.next(&id);
+            count += self.delete(&id);
+        }
+        count
+    }
+
+    /// Makes what points at `old` from `parent`, its next or one of its
+    /// inputs, point at `new` instead.
+    fn repoint(&mut self, parent: &str, old: &str, new: Option<&str>) {
+        let block = &mut self.blocks[parent];
+        if block["next"] == json!(old) {
+            block["next"] = json!(new);
+            return;
+        }
+        let inputs = match block["inputs"].as_object_mut() {
+            Some(a) => a,
+            None => return,
+        };
+        let name = inputs.iter().find(|f| f.1.get(1) == Some(&json!(old)));
+        match (name.map(|f| f.0.clone()), new) {
+            (Some(name), Some(new)) => inputs[&name][1] = json!(new),
+            (Some(name), None) => {
+                inputs.remove(&name);
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Takes a block out of its stack, and closes the gap.
+    fn unlink(&mut self, id: &str) {
+        let (parent, next) = (self.parent(id), self.next(id));
+        match &parent {
+            Some(a) => self.repoint(a, id, next.as_deref()),
+            None => {
+                if let Some(next) = &next {
+                    let block = self.blocks[id].clone();
+                    for key in ["topLevel", "x", "y"] {
+                        self.blocks[next][key] = block[key].clone();
+                    }
+                }
+            }
+        }
+        if let Some(next) = &next {
+            self.blocks[next]["parent"] = json!(parent);
+        }
+        self.blocks[id]["next"] = SerdeValue::Null;
+    }
+
+    /// Puts the result of an operator in the input it's in. Whether there
+    /// was an input to put it in, which there isn't for ones that are on
+    /// their own or in a menu.
+    fn fold(&mut self, id: &str, val: &Val) -> bool {
+        let parent = match self.parent(id) {
+            Some(a) => a,
+            None => return false,
+        };
+        let inputs = self.blocks[&parent]["inputs"].as_object_mut();
+        let input = inputs
+            .into_iter()
+            .flatten()
+            .map(|f| f.1)
+            .find(|f| f.get(1) == Some(&json!(id)) && f[0] == json!(3));
+        let code = match input.as_ref().and_then(|f| f[2].get(0)) {
+            Some(a) => a.clone(),
+            None => return false,
+        };
+        *input.unwrap() = json!([1, [code, val.to_string()]]);
+        self.delete(id);
+        true
+    }
+
+    fn new_id(&mut self) -> String {
+        loop {
+            self.count += 1;
+            let id = format!("opt-{}", self.count);
+            if !self.blocks.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// Puts a copy of a definition's blocks where the call is, with the
+    /// arguments given by name. Arguments that aren't given are empty.
+    fn inline(&mut self, call: &str, definition: &str, arguments: &HashMap<&str, Option<&Value>>) {
+        let mut body = Vec::new();
+        let mut stack = self.next(definition);
+        // in case of a loop.
+        while let Some(id) = stack.filter(|f| !body.contains(f)) {
+            stack = self.next(&id);
+            body.push(id);
+        }
+        let (first, last) = match (body.first(), body.last()) {
+            (Some(a), Some(b)) => (a.clone(), b.clone()),
+            _ => {
+                self.unlink(call);
+                self.delete(call);
+                return;
+            }
+        };
+
+        // what's in the inputs comes along too.
+        let mut copied = body.clone();
+        let mut i = 0;
+        while i < copied.len() {
+            copied.extend(self.children(&copied[i]));
+            i += 1;
+        }
+        let is_argument = |f: &SerdeValue| {
+            f["opcode"] == json!(ARGUMENT_REPORTER_STRING_NUMBER)
+                || f["opcode"] == json!(ARGUMENT_REPORTER_BOOLEAN)
+        };
+        copied.retain(|f| !is_argument(&self.blocks[f]));
+        let ids: HashMap<String, String> =
+            copied.iter().map(|f| (f.clone(), self.new_id())).collect();
+
+        for old in &copied {
+            let mut block = self.blocks[old].clone();
+            for key in ["parent", "next"] {
+                if let Some(a) = block[key].as_str().and_then(|f| ids.get(f)) {
+                    block[key] = json!(a);
+                }
+            }
+            let mut inputs = match block["inputs"].as_object() {
+                Some(a) => a.clone(),
+                None => Map::new(),
+            };
+            for (name, input) in inputs.clone() {
+                let child = match input.get(1).and_then(|f| f.as_str()) {
+                    Some(a) => a,
+                    None => continue,
+                };
+                if let Some(a) = ids.get(child) {
+                    inputs[&name][1] = json!(a);
+                    continue;
+                }
+                let reporter = match self.blocks.get(child) {
+                    Some(a) if is_argument(a) => a,
+                    _ => continue,
+                };
+                let given = reporter["fields"]["VALUE"][0]
+                    .as_str()
+                    .and_then(|f| *arguments.get(f)?);
+                let code = input.get(2).and_then(|f| f.get(0)).cloned();
+                match (given, code) {
+                    (Some(a), Some(code)) => {
+                        inputs[&name] = json!([1, [code, serialize::text(a)]]);
+                    }
+                    (None, Some(code)) => inputs[&name] = json!([1, [code, ""]]),
+                    _ => {
+                        inputs.remove(&name);
+                    }
+                }
+            }
+            if block.get("inputs").is_some() {
+                block["inputs"] = SerdeValue::Object(inputs);
+            }
+            self.blocks.insert(ids[old].clone(), block);
+        }
+
+        let parent = self.parent(call);
+        let next = self.next(call);
+        let (first, last) = (&ids[&first], &ids[&last]);
+        self.blocks[first]["parent"] = json!(parent);
+        self.blocks[last]["next"] = json!(next);
+        if let Some(parent) = &parent {
+            self.repoint(parent, call, Some(first));
+        }
+        if let Some(next) = &next {
+            self.blocks[next]["parent"] = json!(last);
+        }
+        self.blocks[call]["next"] = SerdeValue::Null;
+        self.delete(call);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scratchblocks,
+        test_util::{check_blocks, test_project, transcript},
+    };
+
+    #[test]
+    fn keeps_what_projects_do() {
+        let mut project = test_project();
+        let text = "when flag clicked\n\
+            change y by ((1) - (1))\n\
+            say (join [x is ] ((2) * (x position)))\n\
+            shout [hey] (3)\n\
+            \n\
+            define shout (what) (times)\n\
+            say (join (what) ((times) + (1)))";
+        scratchblocks::add(&mut project.sprites_mut()[1], text).unwrap();
+        let before = transcript(&project);
+        assert!(before.contains("hey4"));
+        let changes = optimize(&mut project);
+        assert_eq!((changes.inlined, changes.folded), (1, 3), "{}", changes);
+        assert_eq!(transcript(&project), before);
+        // and saves like anything else.
+        let reloaded: Project = serde_json::from_value(project.to_json()).unwrap();
+        assert_eq!(transcript(&reloaded), before);
+    }
+
+    #[test]
+    fn simplifies_scripts() {
+        let mut project = test_project();
+        let sprite = &mut project.sprites_mut()[1];
+        sprite.blocks.clear();
+        let text = "when flag clicked\n\
+            change x by (0)\n\
+            if <mouse down?> then\n\
+            turn right (0) degrees\n\
+            end\n\
+            say ((1) + ((2) * (3)))\n\
+            greet [you]\n\
+            forever\n\
+            end\n\
+            say [never]\n\
+            \n\
+            define greet (who)\n\
+            say (join [hi ] (who))\n\
+            \n\
+            move (10) steps";
+        scratchblocks::add(sprite, text).unwrap();
+        let changes = optimize_sprite(sprite);
+        assert_eq!(changes.inlined, 1);
+        assert_eq!(changes.folded, 3);
+        assert_eq!(
+            scratchblocks::sprite(sprite).trim_end(),
+            "when flag clicked\nsay [7]\nsay [hi you]\nforever\nend"
+        );
+    }
+
+    #[test]
+    fn takes_whole_substacks() {
+        let mut project = test_project();
+        let sprite = &mut project.sprites_mut()[1];
+        sprite.blocks.clear();
+        let text = "when flag clicked\n\
+            hop\n\
+            \n\
+            define hop\n\
+            if <mouse down?> then\n\
+            change y by (5)\n\
+            change y by (-5)\n\
+            end\n\
+            \n\
+            repeat (10)\n\
+            move (1) steps\n\
+            turn right (15) degrees\n\
+            say [orphan]\n\
+            end";
+        scratchblocks::add(sprite, text).unwrap();
+        let changes = optimize_sprite(sprite);
+        // the repeat and its three blocks, and the definition with its
+        // prototype, if, condition and two blocks.
+        assert_eq!((changes.removed, changes.inlined), (10, 1), "{}", changes);
+        assert_eq!(
+            scratchblocks::sprite(sprite).trim_end(),
+            "when flag clicked\nif <mouse down?> then\n  change y by (5)\n  change y by (-5)\nend"
+        );
+        let json = project.to_json();
+        check_blocks(&json["targets"][1]);
+        assert_eq!(json["targets"][1]["blocks"].as_object().unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,437 @@
+//! Giving a variable, list, broadcast, sprite, costume or sound a new name,
+//! everywhere it's used.
+//!
+//! The blocks to change are the ones an [`Index`] finds, so they're the same
+//! ones `refs` lists. They're changed as project.json has them, dropdowns and
+//! ids and all, then read back, and whatever couldn't be read when the
+//! project was loaded is changed in what project.json had. `of` names a
+//! variable by name alone, so it's changed on the block itself. Monitors
+//! follow along, and the result saves like any other project.
+use std::collections::BTreeSet;
+
+use serde_json::{json, Value as SerdeValue};
+
+use crate::{
+    blocks::BlockType,
+    decomp::{Project, Sprite},
+    error::YaseError,
+    index::{Index, Kind},
+    serialize::{blocks_json, Context},
+};
+
+/// Renames a thing and every block that mentions it, and says how many
+/// blocks changed. `sprite` is the one a variable, list, costume or sound
+/// belongs to, the stage if it's None, where costumes are backdrops.
+/// Broadcasts and sprites don't belong to one, so it's ignored for them.
+pub fn rename(
+    project: &mut Project,
+    kind: Kind,
+    sprite: Option<&str>,
+    old: &str,
+    new: &str,
+) -> Result<usize, YaseError> {
+    let sprites = project.sprites();
+    let owner = match kind {
+        Kind::Broadcast | Kind::Sprite => None,
+        Kind::Backdrop => Some(stage(sprites)?),
+        _ => match sprite {
+            Some(a) => Some(
+                sprites
+                    .iter()
+                    .position(|f| f.name == a)
+                    .ok_or_else(|| YaseError::Rename(format!("there's no sprite called {}", a)))?,
+            ),
+            None => Some(stage(sprites)?),
+        },
+    };
+    let kind = match owner {
+        Some(a) if kind == Kind::Costume && sprites[a].is_stage => Kind::Backdrop,
+        _ => kind,
+    };
+    check(sprites, kind, owner, old, new)?;
+    if old == new {
+        return Ok(0);
+    }
+
+    // which blocks, in which sprite.
+    let owner_name = owner.map(|f| sprites[f].name.clone());
+    let mut ids = vec![BTreeSet::new(); sprites.len()];
+    for reference in &Index::new(project).references {
+        if reference.kind != kind
+            || !same(kind, &reference.name, old)
+            || reference.owner != owner_name
+        {
+            continue;
+        }
+        if let Some(i) = sprites.iter().position(|f| f.name == reference.sprite) {
+            ids[i].insert(reference.block.clone());
+        }
+    }
+    // variables and lists lying around on their own aren't in the index.
+    if let (Kind::Variable | Kind::List, Some(owner)) = (kind, owner) {
+        for (i, sprite) in sprites.iter().enumerate() {
+            for (id, block) in &sprite.blocks {
+                let stray = match (block, sprite.originals.get(id)) {
+                    (BlockType::Stray, Some(a)) => a,
+                    _ => continue,
+                };
+                let list = stray[0] == json!(13);
+                if (list == (kind == Kind::List))
+                    && stray[1] == json!(old)
+                    && project.owner(i, old, list) == owner
+                {
+                    ids[i].insert(id.clone());
+                }
+            }
+        }
+    }
+
+    let stage = sprites.iter().find(|f| f.is_stage);
+    let mut changed = Vec::new();
+    for (sprite, ids) in sprites.iter().zip(&ids) {
+        let mut edited = Vec::new();
+        if !ids.is_empty() {
+            let mut blocks = blocks_json(&Context::new(sprite, stage));
+            for id in ids {
+                let children: Vec<String> = blocks[id]["inputs"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|f| f.1.get(1)?.as_str())
+                    .filter(|f| blocks.get(*f).is_some_and(|f| f["shadow"] == json!(true)))
+                    .map(String::from)
+                    .collect();
+                // dropdowns are shadows in the block's inputs.
+                for id in [id].into_iter().chain(&children) {
+                    if let Some(block) = blocks.get_mut(id) {
+                        if edit(block, kind, old, new) {
+                            edited.push((id.clone(), block.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        changed.push(edited);
+    }
+
+    let mut count = 0;
+    for ((sprite, edited), ids) in project.sprites_mut().iter_mut().zip(changed).zip(&ids) {
+        let mut renamed: BTreeSet<&String> = edited.iter().map(|f| &f.0).collect();
+        for (id, json) in &edited {
+            // blocks that can't be read keep what they were, and what
+            // project.json had says what they are.
+            if let Ok(a) = BlockType::from_json(json) {
+                sprite.blocks.insert(id.clone(), a);
+            }
+            if let Some(a) = sprite.originals.get_mut(id) {
+                *a = json.clone();
+            }
+        }
+        if kind == Kind::Variable {
+            for id in ids {
+                if let Some(BlockType::Of(a)) = sprite.blocks.get_mut(id) {
+                    if a.property.as_deref() == Some(old) {
+                        a.property = Some(new.to_string());
+                        renamed.insert(id);
+                    }
+                }
+            }
+        }
+        count += renamed.len();
+    }
+    rename_itself(project, kind, owner, old, new);
+    Ok(count)
+}
+
+fn stage(sprites: &[Sprite]) -> Result<usize, YaseError> {
+    sprites
+        .iter()
+        .position(|f| f.is_stage)
+        .ok_or_else(|| YaseError::Rename("there's no stage".to_string()))
+}
+
+/// Scratch doesn't care about case in broadcasts.
+fn same(kind: Kind, a: &str, b: &str) -> bool {
+    match kind {
+        Kind::Broadcast => a.to_lowercase() == b.to_lowercase(),
+        _ => a == b,
+    }
+}
+
+/// The names of everything of a kind that belongs to a sprite.
+fn names(sprite: &Sprite, kind: Kind) -> Vec<&str> {
+    match kind {
+        Kind::Variable => sprite.variables.values().map(|f| f.name()).collect(),
+        Kind::List => sprite
+            .lists
+            .keys()
+            .filter_map(|f| Some(sprite.list(f)?.0))
+            .collect(),
+        Kind::Costume | Kind::Backdrop => sprite.costumes.iter().map(|f| f.name()).collect(),
+        Kind::Sound => sprite.sounds.iter().map(|f| f.name()).collect(),
+        Kind::Broadcast => sprite.broadcasts.values().map(String::as_str).collect(),
+        Kind::Sprite => vec![sprite.name.as_str()],
+    }
+}
+
+/// The old name has to be there, and the new one can't be taken.
+fn check(
+    sprites: &[Sprite],
+    kind: Kind,
+    owner: Option<usize>,
+    old: &str,
+    new: &str,
+) -> Result<(), YaseError> {
+    let taken: Vec<&str> = match owner {
+        Some(a) => names(&sprites[a], kind),
+        None => sprites.iter().flat_map(|f| names(f, kind)).collect(),
+    };
+    let has = |name: &str| taken.iter().any(|f| same(kind, f, name));
+    if !has(old) {
+        return Err(YaseError::Rename(format!(
+            "there's no {} called {}",
+            kind.name(),
+            old
+        )));
+    }
+    if has(new) && !same(kind, old, new) {
+        return Err(YaseError::Rename(format!(
+            "there's already a {} called {}",
+            kind.name(),
+            new
+        )));
+    }
+    // a sprite's variable hides the stage's of the same name.
+    if let (Kind::Variable | Kind::List, Some(owner)) = (kind, owner) {
+        let hides = sprites
+            .iter()
+            .enumerate()
+            .filter(|(i, f)| *i != owner && (f.is_stage || sprites[owner].is_stage))
+            .find(|(_, f)| names(f, kind).contains(&new));
+        if let Some((_, a)) = hides {
+            return Err(YaseError::Rename(format!(
+                "{} already has a {} called {}",
+                a.name,
+                kind.name(),
+                new
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Fields and inputs that name things of a kind.
+fn keys(kind: Kind) -> &'static [&'static str] {
+    match kind {
+        Kind::Variable => &["VARIABLE"],
+        Kind::List => &["LIST"],
+        Kind::Broadcast => &["BROADCAST_OPTION", "BROADCAST_INPUT"],
+        Kind::Costume => &["COSTUME"],
+        Kind::Backdrop => &["BACKDROP"],
+        Kind::Sound => &["SOUND_MENU"],
+        Kind::Sprite => &[
+            "TO",
+            "TOWARDS",
+            "CLONE_OPTION",
+            "TOUCHINGOBJECTMENU",
+            "DISTANCETOMENU",
+            "OBJECT",
+        ],
+    }
+}
+
+/// Renames whatever the block names in its fields, its inputs, or on its
+/// own if it's a stray variable. Whether anything changed.
+fn edit(block: &mut SerdeValue, kind: Kind, old: &str, new: &str) -> bool {
+    let keys = keys(kind);
+    // what an input has in it for a variable, list or broadcast.
+    let code = match kind {
+        Kind::Variable => json!(12),
+        Kind::List => json!(13),
+        Kind::Broadcast => json!(11),
+        _ => SerdeValue::Null,
+    };
+    let mut changed = false;
+    let mut rename = |name: &mut SerdeValue| {
+        if name.as_str().is_some_and(|f| same(kind, f, old)) {
+            *name = json!(new);
+            changed = true;
+        }
+    };
+    if block.is_array() && block[0] == code {
+        rename(&mut block[1]);
+    }
+    if let Some(fields) = block["fields"].as_object_mut() {
+        for (_, field) in fields.iter_mut().filter(|f| keys.contains(&f.0.as_str())) {
+            rename(&mut field[0]);
+        }
+    }
+    if let Some(inputs) = block["inputs"].as_object_mut() {
+        for (key, input) in inputs.iter_mut() {
+            for value in input.as_array_mut().into_iter().flatten().skip(1) {
+                // or typed into the input.
+                let literal = value[0] == json!(10) && keys.contains(&key.as_str());
+                if value.is_array() && (value[0] == code || literal) {
+                    rename(&mut value[1]);
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// The thing itself, and its monitor.
+fn rename_itself(project: &mut Project, kind: Kind, owner: Option<usize>, old: &str, new: &str) {
+    let sprites = project.sprites_mut();
+    let sprite_name = owner.map(|f| match sprites[f].is_stage {
+        true => SerdeValue::Null,
+        false => json!(sprites[f].name),
+    });
+    for (i, sprite) in sprites.iter_mut().enumerate() {
+        if owner.is_some_and(|f| f != i) {
+            continue;
+        }
+        match kind {
+            Kind::Variable => {
+                for variable in sprite.variables.values_mut().filter(|f| f.name == old) {
+                    variable.name = new.to_string();
+                }
+            }
+            Kind::List => {
+                for list in sprite
+                    .lists
+                    .values_mut()
+                    .filter(|f| f.first() == Some(&json!(old)))
+                {
+                    list[0] = json!(new);
+                }
+            }
+            Kind::Broadcast => {
+                for name in sprite
+                    .broadcasts
+                    .values_mut()
+                    .filter(|f| same(kind, f, old))
+                {
+                    *name = new.to_string();
+                }
+            }
+            Kind::Costume | Kind::Backdrop => {
+                for costume in sprite.costumes.iter_mut().filter(|f| f.name == old) {
+                    costume.name = new.to_string();
+                }
+            }
+            Kind::Sound => {
+                for sound in sprite.sounds.iter_mut().filter(|f| f.name == old) {
+                    sound.name = new.to_string();
+                }
+            }
+            Kind::Sprite => {
+                if sprite.name == old {
+                    sprite.name = new.to_string();
+                }
+            }
+        }
+    }
+
+    for monitor in &mut project.monitors {
+        let (key, opcode) = match kind {
+            Kind::Variable => ("VARIABLE", "data_variable"),
+            Kind::List => ("LIST", "data_listcontents"),
+            Kind::Sprite => {
+                if monitor["spriteName"] == json!(old) {
+                    monitor["spriteName"] = json!(new);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if monitor["opcode"] == json!(opcode)
+            && monitor["params"][key] == json!(old)
+            && Some(&monitor["spriteName"]) == sprite_name.as_ref()
+        {
+            monitor["params"][key] = json!(new);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decomp::LoadOptions,
+        index::Usage,
+        scratchblocks,
+        test_util::{test_project, transcript},
+    };
+
+    #[test]
+    fn renames_everywhere() {
+        let mut project = test_project();
+        let sprite = &mut project.sprites_mut()[1];
+        let name = sprite.name.clone();
+        let text = "when flag clicked\n\
+            switch costume to (Water v)\n\
+            set [Height v] to (x0)\n\
+            broadcast (display v)\n\
+            say ([Height v] of (Stage v))\n\
+            go to (Generator v)";
+        scratchblocks::add(sprite, text).unwrap();
+        let before = transcript(&project);
+
+        let n = rename(&mut project, Kind::Variable, None, "Height", "tall").unwrap();
+        assert!(n > 1);
+        rename(&mut project, Kind::Variable, Some(&name), "x0", "left").unwrap();
+        rename(&mut project, Kind::Broadcast, None, "Display", "show").unwrap();
+        rename(&mut project, Kind::Costume, Some(&name), "Water", "Sea").unwrap();
+        rename(&mut project, Kind::Sprite, None, &name, "Maker").unwrap();
+        // the same, but by their new names.
+        let before = before
+            .replace("Stage: Height = ", "Stage: tall = ")
+            .replace(&format!("{}: x0 = ", name), &format!("{}: left = ", name))
+            .replace(&format!("{}: ", name), "Maker: ");
+        assert_eq!(transcript(&project), before);
+
+        let json = project.to_json().to_string();
+        for old in [
+            "\"Height\"",
+            "\"x0\"",
+            "\"Display\"",
+            "\"display\"",
+            "\"Water\"",
+        ] {
+            assert!(!json.contains(old), "{}", old);
+        }
+        let name = format!("\"{}\"", name);
+        assert!(!json.contains(&name));
+        let project = Project::from_json_str(&json, LoadOptions::default()).unwrap();
+        let index = Index::new(&project);
+        let of = index.find(Kind::Variable, "tall");
+        assert!(of
+            .iter()
+            .any(|f| f.sprite == "Maker" && f.usage == Usage::Read));
+        assert!(!index.find(Kind::Broadcast, "show").is_empty());
+        assert_eq!(index.find(Kind::Costume, "Sea").len(), 2);
+        assert_eq!(index.find(Kind::Sprite, "Maker").len(), 1);
+        assert_eq!(transcript(&project), before);
+    }
+
+    #[test]
+    fn keeps_names_apart() {
+        let mut project = test_project();
+        let name = project.sprites()[1].name.clone();
+        let err = |f: Result<usize, YaseError>| f.unwrap_err().to_string();
+        assert_eq!(
+            err(rename(&mut project, Kind::Variable, None, "nope", "a")),
+            "can't rename: there's no variable called nope"
+        );
+        assert_eq!(
+            err(rename(&mut project, Kind::Variable, None, "dx", "dy")),
+            "can't rename: there's already a variable called dy"
+        );
+        assert_eq!(
+            err(rename(&mut project, Kind::Variable, None, "dx", "x0")),
+            format!("can't rename: {} already has a variable called x0", name)
+        );
+        assert_eq!(rename(&mut project, Kind::Sound, None, "pop", "pop"), Ok(0));
+    }
+}
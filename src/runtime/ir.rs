@@ -0,0 +1,890 @@
+//! The instruction set scripts get compiled to, and the compiler.
+//!
+//! It's a stack machine: reporters push their value, and whatever uses them
+//! pops it. Control flow turns into jumps, so loops don't need to remember
+//! anything besides where they are and (for repeat) how many times are left,
+//! which lives on the stack too.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    blocks::*,
+    decomp::Project,
+    script::{Input, Linked, Stack},
+};
+
+use super::{
+    ops::{self, BinOp, MathOp, TimeUnit, UnOp},
+    value::Val,
+    world::{RotationStyle as Style, Slot, TargetInit},
+    Hat, HatKind,
+};
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    Const(Val),
+    Pop,
+    Binary(BinOp),
+    Unary(UnOp),
+    Random,
+
+    Var(Slot),
+    SetVar(Slot),
+    ChangeVar(Slot),
+    ListContents(Slot),
+    ListAdd(Slot),
+    /// Index on the stack.
+    ListDelete(Slot),
+    ListDeleteAll(Slot),
+    /// Item, then index on the stack.
+    ListInsert(Slot),
+    /// Index, then item on the stack.
+    ListReplace(Slot),
+    ListItem(Slot),
+    ListLength(Slot),
+    ListContains(Slot),
+    /// An argument of the custom block being run.
+    Arg(usize),
+
+    Jump(usize),
+    JumpIfFalse(usize),
+    JumpIfTrue(usize),
+    /// Turns the value on the stack into a repeat count.
+    RepeatInit,
+    /// Counts the repeat count on the stack down, or pops it and jumps once it runs out.
+    RepeatLoop(usize),
+    /// The end of a loop. Gives other scripts a turn, unless running without screen refresh.
+    Yield,
+    /// Gives other scripts a turn no matter what, for "wait until".
+    Suspend,
+    Call(usize),
+    Return,
+    Stop(StopKind),
+    /// The end of a script.
+    End,
+
+    Wait,
+    Broadcast,
+    BroadcastAndWait,
+    CreateClone,
+    DeleteClone,
+
+    Move,
+    Turn,
+    GotoXY,
+    Goto,
+    GlideXY,
+    GlideTo,
+    PointDirection,
+    PointTowards,
+    ChangeX,
+    SetX,
+    ChangeY,
+    SetY,
+    Bounce,
+    SetRotationStyle(Style),
+    XPosition,
+    YPosition,
+    Direction,
+
+    /// Say or think.
+    Say(bool),
+    SayFor(bool),
+    SwitchCostume,
+    NextCostume,
+    SwitchBackdrop,
+    SwitchBackdropAndWait,
+    NextBackdrop,
+    ChangeEffect(Arc<str>),
+    SetEffect(Arc<str>),
+    ClearEffects,
+    ChangeSize,
+    SetSize,
+    Show,
+    Hide,
+    /// To the front or to the back.
+    GotoLayer(bool),
+    /// Number of layers on the stack, true if forward.
+    ChangeLayer(bool),
+    CostumeNumber,
+    CostumeName,
+    BackdropNumber,
+    BackdropName,
+    Size,
+
+    PlaySoundUntilDone,
+    ChangeVolume,
+    SetVolume,
+    Volume,
+
+    Touching,
+    DistanceTo,
+    Ask,
+    Answer,
+    KeyPressed,
+    MouseDown,
+    MouseX,
+    MouseY,
+    SetDraggable(bool),
+    Timer,
+    ResetTimer,
+    Current(TimeUnit),
+    DaysSince2000,
+    Username,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopKind {
+    All,
+    ThisScript,
+    OtherScripts,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcInfo {
+    pub sprite: usize,
+    pub proccode: String,
+    /// Id of the definition block.
+    pub id: String,
+    pub start: usize,
+    pub args: usize,
+    pub warp: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub code: Vec<Op>,
+    pub hats: Vec<Hat>,
+    /// Where each hat's script starts, in the same order as hats.
+    pub entries: Vec<usize>,
+    pub procedures: Vec<ProcInfo>,
+    pub targets: Vec<TargetInit>,
+    /// Variable names to their index, per sprite.
+    pub variables: Vec<HashMap<String, usize>>,
+    /// Same for lists.
+    pub lists: Vec<HashMap<String, usize>>,
+    pub stage: usize,
+}
+
+impl Program {
+    pub fn compile(project: &Project) -> Program {
+        let sprites = project.sprites();
+        let mut program = Program {
+            stage: sprites.iter().position(|f| f.is_stage).unwrap_or(0),
+            ..Default::default()
+        };
+        for sprite in sprites {
+            let mut init = TargetInit {
+                name: sprite.name.clone(),
+                is_stage: sprite.is_stage,
+                x: sprite.position_x,
+                y: sprite.position_y,
+                direction: sprite.direction,
+                size: sprite.size,
+                visible: sprite.visible,
+                draggable: sprite.draggable,
+                rotation_style: match sprite.rotation_style.as_str() {
+                    "left-right" => Some(Style::LeftRight),
+                    "don't rotate" => Some(Style::DontRotate),
+                    "all around" => Some(Style::AllAround),
+                    _ => None,
+                },
+                costume: sprite.current_costume as usize,
+                costumes: sprite
+                    .costumes
+                    .iter()
+                    .map(|f| f.name().to_string())
+                    .collect(),
+                sounds: sprite
+                    .sounds
+                    .iter()
+                    .map(|f| (f.name().to_string(), f.duration()))
+                    .collect(),
+                volume: sprite.volume,
+                layer: sprite.layer_order as i64,
+                ..Default::default()
+            };
+            // sorted so that slots don't depend on hashmap order.
+            let mut variables: Vec<_> = sprite.variables.iter().collect();
+            variables.sort_by_key(|f| f.0);
+            let mut names = HashMap::new();
+            for (_, var) in variables {
+                names.insert(var.name().to_string(), init.variables.len());
+                init.variable_names.push(var.name().to_string());
+                init.variables.push(json(var.value()));
+            }
+            let mut lists: Vec<_> = sprite.lists.keys().collect();
+            lists.sort();
+            let mut list_names = HashMap::new();
+            for id in lists {
+                if let Some((name, items)) = sprite.list(id) {
+                    list_names.insert(name.to_string(), init.lists.len());
+                    init.list_names.push(name.to_string());
+                    init.lists.push(items.iter().map(json).collect());
+                }
+            }
+            program.targets.push(init);
+            program.variables.push(names);
+            program.lists.push(list_names);
+        }
+
+        for (i, sprite) in sprites.iter().enumerate() {
+            let linked = Linked::new(sprite);
+            let mut compiler = Compiler {
+                program: &mut program,
+                linked: &linked,
+                sprite: i,
+                procs: HashMap::new(),
+                args: Vec::new(),
+                path: HashSet::new(),
+            };
+            compiler.sprite();
+        }
+        program
+    }
+
+    /// Resolves a variable by name for a sprite: its own first, then the stage's.
+    pub fn variable(&self, sprite: usize, name: &str) -> Option<Slot> {
+        resolve(&self.variables, sprite, self.stage, name)
+    }
+
+    pub fn list(&self, sprite: usize, name: &str) -> Option<Slot> {
+        resolve(&self.lists, sprite, self.stage, name)
+    }
+}
+
+fn resolve(
+    names: &[HashMap<String, usize>],
+    sprite: usize,
+    stage: usize,
+    name: &str,
+) -> Option<Slot> {
+    if let Some(a) = names[sprite].get(name) {
+        return Some(Slot::Local(*a));
+    }
+    names[stage].get(name).map(|f| Slot::Global(*f))
+}
+
+pub fn literal(a: &Value) -> Val {
+    match a {
+        Value::Number(a) => Val::Num(*a),
+        Value::String(a) => Val::Str(a.as_str().into()),
+        Value::Variable(a) | Value::List(a) => Val::Str(a.as_str().into()),
+        Value::Null => Val::Str("".into()),
+    }
+}
+
+/// Turns a value from project.json (variable values, list items) into a runtime value.
+pub fn json(a: &serde_json::Value) -> Val {
+    match a {
+        serde_json::Value::Number(a) => Val::Num(a.as_f64().unwrap_or(0.0)),
+        serde_json::Value::String(a) => Val::Str(a.as_str().into()),
+        serde_json::Value::Bool(a) => Val::Bool(*a),
+        serde_json::Value::Null => Val::Str("".into()),
+        a => Val::Str(a.to_string().into()),
+    }
+}
+
+pub fn math_op(op: MathOperator) -> MathOp {
+    match op {
+        MathOperator::Abs => MathOp::Abs,
+        MathOperator::Floor => MathOp::Floor,
+        MathOperator::Ceiling => MathOp::Ceiling,
+        MathOperator::Sqrt => MathOp::Sqrt,
+        MathOperator::Sin => MathOp::Sin,
+        MathOperator::Cos => MathOp::Cos,
+        MathOperator::Tan => MathOp::Tan,
+        MathOperator::Asin => MathOp::Asin,
+        MathOperator::Acos => MathOp::Acos,
+        MathOperator::Atan => MathOp::Atan,
+        MathOperator::Ln => MathOp::Ln,
+        MathOperator::Log => MathOp::Log,
+        MathOperator::EPow => MathOp::EPow,
+        MathOperator::TenPow => MathOp::TenPow,
+    }
+}
+
+pub fn time_unit(option: &CurrentTimeOption) -> TimeUnit {
+    match option {
+        CurrentTimeOption::Year => TimeUnit::Year,
+        CurrentTimeOption::Month => TimeUnit::Month,
+        CurrentTimeOption::Date => TimeUnit::Date,
+        CurrentTimeOption::DayOfWeek => TimeUnit::DayOfWeek,
+        CurrentTimeOption::Hour => TimeUnit::Hour,
+        CurrentTimeOption::Minute => TimeUnit::Minute,
+        CurrentTimeOption::Second => TimeUnit::Second,
+    }
+}
+
+/// Field values are names, which may have been read as numbers.
+pub fn name_of(val: &Value) -> String {
+    match val {
+        Value::Number(a) => a.to_string(),
+        Value::String(a) | Value::Variable(a) | Value::List(a) => a.clone(),
+        Value::Null => String::new(),
+    }
+}
+
+struct Compiler<'a, 'p> {
+    program: &'p mut Program,
+    linked: &'p Linked<'a>,
+    sprite: usize,
+    /// This sprite's custom blocks, by proccode.
+    procs: HashMap<&'a str, usize>,
+    /// Argument names of the custom block being compiled.
+    args: Vec<String>,
+    /// The blocks that the one being compiled is inside of, so that a block
+    /// in its own input or substack isn't compiled forever.
+    path: HashSet<&'a str>,
+}
+
+impl<'a, 'p> Compiler<'a, 'p> {
+    fn sprite(&mut self) {
+        // custom blocks first, so that calls know where they're going.
+        let mut procedures: Vec<_> = self.linked.procedures.values().copied().collect();
+        procedures.sort_by_key(|f| f.id);
+        for procedure in &procedures {
+            self.procs.insert(
+                procedure.signature.proccode(),
+                self.program.procedures.len(),
+            );
+            self.program.procedures.push(ProcInfo {
+                sprite: self.sprite,
+                proccode: procedure.signature.proccode().to_string(),
+                id: procedure.id.to_string(),
+                start: 0,
+                args: procedure.signature.argument_ids().len(),
+                warp: procedure.signature.warp(),
+            });
+        }
+        for procedure in procedures {
+            let index = self.procs[procedure.signature.proccode()];
+            self.program.procedures[index].start = self.program.code.len();
+            self.args = procedure.signature.argument_names().clone();
+            let body = self.linked.stack(procedure.definition.next.as_deref());
+            self.stack(body);
+            self.emit(Op::Return);
+        }
+        self.args.clear();
+
+        for script in self.linked.scripts.clone() {
+            let kind = match script.hat {
+                BlockType::WhenGreenFlagClicked(_) => HatKind::GreenFlag,
+                BlockType::WhenKeyPressed(a) => match &a.key {
+                    Some(a) => HatKind::KeyPressed(a.to_string().to_lowercase()),
+                    None => continue,
+                },
+                BlockType::WhenSpriteClicked(_) => HatKind::SpriteClicked,
+                BlockType::WhenStageClicked(_) => HatKind::StageClicked,
+                BlockType::WhenBackdropSwitchesTo(a) => match &a.backdrop {
+                    Some(a) => HatKind::BackdropSwitchesTo(name_of(a)),
+                    None => continue,
+                },
+                // decimals and negative numbers are saved as text.
+                BlockType::WhenOptionGreaterThen(a) => {
+                    match (&a.option, a.by.as_ref().map(literal)) {
+                        (Some(EventOption::Timer), Some(a)) if a.is_numeric() => {
+                            HatKind::TimerGreaterThan(a.num())
+                        }
+                        _ => continue,
+                    }
+                }
+                BlockType::WhenIRecieveBroadcast(a) => match &a.broadcast {
+                    Some(a) => HatKind::Broadcast(name_of(a)),
+                    None => continue,
+                },
+                BlockType::WhenIStartAsAClone(_) => HatKind::CloneStart,
+                _ => continue,
+            };
+            self.program.hats.push(Hat {
+                sprite: self.sprite,
+                id: script.id.to_string(),
+                kind,
+            });
+            self.program.entries.push(self.program.code.len());
+            let body = self.linked.body(&script);
+            self.stack(body);
+            self.emit(Op::End);
+        }
+    }
+
+    fn emit(&mut self, op: Op) {
+        self.program.code.push(op);
+    }
+
+    fn here(&self) -> usize {
+        self.program.code.len()
+    }
+
+    /// Emits a jump to be filled in later, returning where it is.
+    fn jump(&mut self, op: fn(usize) -> Op) -> usize {
+        self.emit(op(usize::MAX));
+        self.here() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let to = self.here();
+        self.program.code[at] = match self.program.code[at] {
+            Op::Jump(_) => Op::Jump(to),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(to),
+            Op::JumpIfTrue(_) => Op::JumpIfTrue(to),
+            Op::RepeatLoop(_) => Op::RepeatLoop(to),
+            ref a => unreachable!("can't patch {:?}", a),
+        };
+    }
+
+    fn variable(&mut self, name: &Value) -> Slot {
+        let name = name_of(name);
+        if let Some(a) = self.program.variable(self.sprite, &name) {
+            return a;
+        }
+        // scratch makes missing variables on the spot, so do we.
+        let target = &mut self.program.targets[self.sprite];
+        self.program.variables[self.sprite].insert(name.clone(), target.variables.len());
+        target.variables.push(Val::default());
+        target.variable_names.push(name);
+        Slot::Local(target.variables.len() - 1)
+    }
+
+    fn list(&mut self, name: &Value) -> Slot {
+        let name = name_of(name);
+        if let Some(a) = self.program.list(self.sprite, &name) {
+            return a;
+        }
+        let target = &mut self.program.targets[self.sprite];
+        self.program.lists[self.sprite].insert(name.clone(), target.lists.len());
+        target.lists.push(Vec::new());
+        target.list_names.push(name);
+        Slot::Local(target.lists.len() - 1)
+    }
+
+    fn stack(&mut self, stack: Stack<'a>) {
+        let mut entered = Vec::new();
+        for (id, block) in stack {
+            if !self.path.insert(id) {
+                break;
+            }
+            entered.push(id);
+            self.statement(block);
+        }
+        for id in entered {
+            self.path.remove(id);
+        }
+    }
+
+    fn substack(&mut self, val: &'a Option<Value>) {
+        let stack = self.linked.substack(val);
+        self.stack(stack);
+    }
+
+    fn input(&mut self, val: &'a Option<Value>) {
+        let input = self.linked.input(val);
+        self.expr(input);
+    }
+
+    fn value(&mut self, val: &'a Value) {
+        let input = self.linked.value(val);
+        self.expr(input);
+    }
+
+    fn constant(&mut self, val: impl Into<Val>) {
+        self.emit(Op::Const(val.into()));
+    }
+
+    /// Emits a binary operator, folding it away if both sides are constants.
+    fn binary(&mut self, op: BinOp) {
+        let code = &mut self.program.code;
+        if let [.., Op::Const(a), Op::Const(b)] = &code[..] {
+            let val = ops::binary(op, a, b);
+            code.truncate(code.len() - 2);
+            code.push(Op::Const(val));
+            return;
+        }
+        code.push(Op::Binary(op));
+    }
+
+    fn unary(&mut self, op: UnOp) {
+        let code = &mut self.program.code;
+        if let Some(Op::Const(a)) = code.last() {
+            let val = ops::unary(op, a);
+            code.pop();
+            code.push(Op::Const(val));
+            return;
+        }
+        code.push(Op::Unary(op));
+    }
+
+    fn expr(&mut self, input: Input<'a>) {
+        match input {
+            Input::Empty => self.constant(""),
+            Input::Literal(a) => self.emit(Op::Const(literal(a))),
+            Input::Variable(a) => {
+                let slot = self.variable(&Value::String(a.to_string()));
+                self.emit(Op::Var(slot));
+            }
+            Input::List(a) => {
+                let slot = self.list(&Value::String(a.to_string()));
+                self.emit(Op::ListContents(slot));
+            }
+            Input::Block(id, block) => match self.path.insert(id) {
+                true => {
+                    self.reporter(block);
+                    self.path.remove(id);
+                }
+                false => self.constant(""),
+            },
+        }
+    }
+
+    fn reporter(&mut self, block: &'a BlockType) {
+        match block {
+            BlockType::Add(a) => self.operands(&a.a, &a.b, BinOp::Add),
+            BlockType::Sub(a) => self.operands(&a.a, &a.b, BinOp::Sub),
+            BlockType::Mul(a) => self.operands(&a.a, &a.b, BinOp::Mul),
+            BlockType::Divide(a) => self.operands(&a.a, &a.b, BinOp::Div),
+            BlockType::Modulo(a) => self.operands(&a.a, &a.b, BinOp::Mod),
+            BlockType::GreaterThen(a) => self.operands(&a.a, &a.b, BinOp::Gt),
+            BlockType::LesserThen(a) => self.operands(&a.a, &a.b, BinOp::Lt),
+            BlockType::EqualTo(a) => self.operands(&a.a, &a.b, BinOp::Eq),
+            BlockType::And(a) => self.operands(&a.a, &a.b, BinOp::And),
+            BlockType::Or(a) => self.operands(&a.a, &a.b, BinOp::Or),
+            BlockType::Join(a) => self.operands(&a.a, &a.b, BinOp::Join),
+            BlockType::LetterOf(a) => self.operands(&a.index, &a.a, BinOp::LetterOf),
+            BlockType::Contains(a) => self.operands(&a.a, &a.b, BinOp::Contains),
+            BlockType::Not(a) => {
+                self.input(&a.a);
+                self.unary(UnOp::Not);
+            }
+            BlockType::LengthOf(a) => {
+                self.input(&a.a);
+                self.unary(UnOp::Length);
+            }
+            BlockType::Round(a) => {
+                self.input(&a.a);
+                self.unary(UnOp::Round);
+            }
+            BlockType::MathOp(a) => {
+                self.input(&a.a);
+                if let Some(op) = a.operator {
+                    self.unary(UnOp::Math(math_op(op)));
+                }
+            }
+            BlockType::PickRandom(a) => {
+                self.input(&a.min);
+                self.input(&a.max);
+                self.emit(Op::Random);
+            }
+
+            BlockType::XPosition(_) => self.emit(Op::XPosition),
+            BlockType::YPosition(_) => self.emit(Op::YPosition),
+            BlockType::Direction(_) => self.emit(Op::Direction),
+            BlockType::Size(_) => self.emit(Op::Size),
+            BlockType::Volume(_) => self.emit(Op::Volume),
+            BlockType::Costume(Costume::ByNumber(_)) => self.emit(Op::CostumeNumber),
+            BlockType::Costume(Costume::ByName(_)) => self.emit(Op::CostumeName),
+            BlockType::Backdrop(Backdrop::ByNumber(_)) => self.emit(Op::BackdropNumber),
+            BlockType::Backdrop(Backdrop::ByName(_)) => self.emit(Op::BackdropName),
+            BlockType::Answer(_) => self.emit(Op::Answer),
+            BlockType::MouseDown(_) => self.emit(Op::MouseDown),
+            BlockType::MouseX(_) => self.emit(Op::MouseX),
+            BlockType::MouseY(_) => self.emit(Op::MouseY),
+            BlockType::Timer(_) => self.emit(Op::Timer),
+            BlockType::DaysSince2000(_) => self.emit(Op::DaysSince2000),
+            BlockType::Username(_) => self.emit(Op::Username),
+            // there's no microphone.
+            BlockType::Loudness(_) => self.constant(-1.0),
+            BlockType::CurrentTime(a) => match &a.option {
+                Some(a) => self.emit(Op::Current(time_unit(a))),
+                None => self.constant(0.0),
+            },
+            BlockType::KeyPressed(a) => {
+                self.input(&a.key);
+                self.emit(Op::KeyPressed);
+            }
+            BlockType::Touching(a) => {
+                self.input(&a.touching);
+                self.emit(Op::Touching);
+            }
+            BlockType::DistanceTo(a) => {
+                self.input(&a.to);
+                self.emit(Op::DistanceTo);
+            }
+            // there's nothing drawn, so there are no colors to touch.
+            BlockType::TouchingColor(_) | BlockType::ColorTouchingColor(_) => self.constant(false),
+
+            // menus are just their option.
+            BlockType::Costume(Costume::WithName(a))
+            | BlockType::Backdrop(Backdrop::WithName(a)) => match a {
+                Some(a) => self.constant(name_of(a)),
+                None => self.constant(""),
+            },
+            BlockType::Goto(Goto::Menu(a)) | BlockType::Glide(Glide::Menu(a)) => {
+                self.option(&a.option)
+            }
+            BlockType::PointTowardsMenu(a) => self.option(&a.option),
+            BlockType::TouchingMenu(a) => self.option(&a.touching),
+            BlockType::DistanceToMenu(a) => self.option(&a.to),
+            BlockType::KeyOptions(a) => self.option(&a.key),
+            BlockType::CreateCloneOfMenu(a) => self.option(&a.of),
+            BlockType::SoundSoundsMenu(a) => match &a.option {
+                Some(a) => self.constant(name_of(a)),
+                None => self.constant(""),
+            },
+
+            BlockType::DataGetVariable(a) => {
+                let slot = self.variable(&a.variable);
+                self.emit(Op::Var(slot));
+            }
+            BlockType::DataListContents(a) => {
+                let slot = self.list(&a.variable);
+                self.emit(Op::ListContents(slot));
+            }
+            BlockType::DataItemOfList(a) => {
+                self.value(&a.index);
+                let slot = self.list(&a.list);
+                self.emit(Op::ListItem(slot));
+            }
+            BlockType::DataLengthOfList(a) => {
+                let slot = self.list(&a.list);
+                self.emit(Op::ListLength(slot));
+            }
+            BlockType::DataListContainsItem(a) => {
+                self.value(&a.input);
+                let slot = self.list(&a.list);
+                self.emit(Op::ListContains(slot));
+            }
+            BlockType::ArgumentReporter(a) => match self.args.iter().position(|f| *f == a.name) {
+                Some(i) => self.emit(Op::Arg(i)),
+                // used outside of its definition.
+                None => self.constant(if a.boolean {
+                    Val::Bool(false)
+                } else {
+                    Val::Num(0.0)
+                }),
+            },
+            _ => self.constant(""),
+        }
+    }
+
+    fn operands(&mut self, a: &'a Option<Value>, b: &'a Option<Value>, op: BinOp) {
+        self.input(a);
+        self.input(b);
+        self.binary(op);
+    }
+
+    fn option(&mut self, option: &Option<impl std::fmt::Display>) {
+        match option {
+            Some(a) => self.constant(a.to_string()),
+            None => self.constant(""),
+        }
+    }
+
+    fn statement(&mut self, block: &'a BlockType) {
+        match block {
+            BlockType::Move(a) => self.op1(&a.steps, Op::Move),
+            BlockType::RotateRight(a) => self.op1(&a.degrees, Op::Turn),
+            BlockType::RotateLeft(a) => {
+                self.input(&a.degrees);
+                self.constant(-1.0);
+                self.binary(BinOp::Mul);
+                self.emit(Op::Turn);
+            }
+            BlockType::Goto(Goto::Pos(a)) => self.op2(&a.x, &a.y, Op::GotoXY),
+            BlockType::Goto(Goto::Option(a)) => self.op1(&a.option, Op::Goto),
+            BlockType::Glide(Glide::Pos(a)) => {
+                self.input(&a.secs);
+                self.op2(&a.x, &a.y, Op::GlideXY);
+            }
+            BlockType::Glide(Glide::Option(a)) => self.op2(&a.secs, &a.option, Op::GlideTo),
+            BlockType::Point(Point::Direction(a)) => self.op1(&a.direction, Op::PointDirection),
+            BlockType::Point(Point::Towards(a)) => self.op1(&a.option, Op::PointTowards),
+            BlockType::ChangeX(a) => self.op1(&a.x, Op::ChangeX),
+            BlockType::SetX(a) => self.op1(&a.x, Op::SetX),
+            BlockType::ChangeY(a) => self.op1(&a.y, Op::ChangeY),
+            BlockType::SetY(a) => self.op1(&a.y, Op::SetY),
+            BlockType::IfOnEdgeBounce(_) => self.emit(Op::Bounce),
+            BlockType::SetRotationStyle(a) => match &a.style {
+                Some(RotationStyle::AllAround) => self.emit(Op::SetRotationStyle(Style::AllAround)),
+                Some(RotationStyle::LeftRight) => self.emit(Op::SetRotationStyle(Style::LeftRight)),
+                Some(RotationStyle::DontRotate) => {
+                    self.emit(Op::SetRotationStyle(Style::DontRotate))
+                }
+                None => {}
+            },
+
+            BlockType::Say(a) => self.op2(&a.message, &a.secs, Op::SayFor(false)),
+            BlockType::Think(a) => self.op2(&a.message, &a.secs, Op::SayFor(true)),
+            BlockType::SayForever(a) => self.op1(&a.message, Op::Say(false)),
+            BlockType::ThinkForever(a) => self.op1(&a.message, Op::Say(true)),
+            BlockType::SwitchCostume(a) => self.op1(&a.costume, Op::SwitchCostume),
+            BlockType::NextCostume(_) => self.emit(Op::NextCostume),
+            BlockType::SwitchBackdrop(a) => self.op1(&a.backdrop, Op::SwitchBackdrop),
+            BlockType::SwitchBackdropAndWait(a) => self.op1(&a.backdrop, Op::SwitchBackdropAndWait),
+            BlockType::NextBackdrop(_) => self.emit(Op::NextBackdrop),
+            BlockType::ChangeSize(a) => self.op1(&a.units, Op::ChangeSize),
+            BlockType::SetSize(a) => self.op1(&a.percentage, Op::SetSize),
+            BlockType::ClearGraphicEffects(_) => self.emit(Op::ClearEffects),
+            BlockType::ShowSprite(_) => self.emit(Op::Show),
+            BlockType::HideSprite(_) => self.emit(Op::Hide),
+            BlockType::GotoLayer(a) => match &a.option {
+                Some(LayerOption::Front) => self.emit(Op::GotoLayer(true)),
+                Some(LayerOption::Back) => self.emit(Op::GotoLayer(false)),
+                None => {}
+            },
+            BlockType::ChangeLayer(a) => {
+                let forward = !matches!(a.direction, Some(LayerDirection::Backward));
+                self.op1(&a.by, Op::ChangeLayer(forward));
+            }
+            BlockType::ChangeEffectBy(a) => {
+                let effect = a.effect.as_ref().map(name_of).unwrap_or_default();
+                self.op1(&a.units, Op::ChangeEffect(effect.into()));
+            }
+            BlockType::SetEffectTo(a) => {
+                let effect = a.effect.as_ref().map(name_of).unwrap_or_default();
+                self.op1(&a.percentage, Op::SetEffect(effect.into()));
+            }
+
+            // nothing is heard, but the inputs still run.
+            BlockType::PlaySound(a) => self.op1(&a.sound, Op::Pop),
+            BlockType::PlaySoundUntilDone(a) => self.op1(&a.sound, Op::PlaySoundUntilDone),
+            BlockType::ChangeVolumeBy(a) => self.op1(&a.units, Op::ChangeVolume),
+            BlockType::SetVolumeTo(a) => self.op1(&a.percentage, Op::SetVolume),
+
+            BlockType::Broadcast(a) => self.op1(&a.broadcast, Op::Broadcast),
+            BlockType::BroadcastAndWait(a) => self.op1(&a.broadcast, Op::BroadcastAndWait),
+
+            BlockType::WaitSeconds(a) => self.op1(&a.seconds, Op::Wait),
+            BlockType::Repeat(a) => {
+                self.input(&a.units);
+                self.emit(Op::RepeatInit);
+                let head = self.here();
+                let exit = self.jump(Op::RepeatLoop);
+                self.substack(&a.substack);
+                self.emit(Op::Yield);
+                self.emit(Op::Jump(head));
+                self.patch(exit);
+            }
+            BlockType::Forever(a) => {
+                let head = self.here();
+                self.substack(&a.substack);
+                self.emit(Op::Yield);
+                self.emit(Op::Jump(head));
+            }
+            BlockType::RepeatUntil(a) => {
+                let head = self.here();
+                self.input(&a.condition);
+                let exit = self.jump(Op::JumpIfTrue);
+                self.substack(&a.substack);
+                self.emit(Op::Yield);
+                self.emit(Op::Jump(head));
+                self.patch(exit);
+            }
+            BlockType::WaitUntil(a) => {
+                let head = self.here();
+                self.input(&a.condition);
+                let exit = self.jump(Op::JumpIfTrue);
+                self.emit(Op::Suspend);
+                self.emit(Op::Jump(head));
+                self.patch(exit);
+            }
+            BlockType::IfThen(a) => {
+                self.input(&a.condition);
+                let exit = self.jump(Op::JumpIfFalse);
+                self.substack(&a.then);
+                self.patch(exit);
+            }
+            BlockType::IfThenElse(a) => {
+                self.input(&a.condition);
+                let otherwise = self.jump(Op::JumpIfFalse);
+                self.substack(&a.then);
+                let exit = self.jump(Op::Jump);
+                self.patch(otherwise);
+                self.substack(&a.otherwise);
+                self.patch(exit);
+            }
+            BlockType::StopAll(a) => self.emit(Op::Stop(match a.option {
+                Some(StopOption::ThisScript) => StopKind::ThisScript,
+                Some(StopOption::OtherScriptsInSprite) => StopKind::OtherScripts,
+                Some(StopOption::All) | None => StopKind::All,
+            })),
+            BlockType::CreateCloneOf(a) => self.op1(&a.of, Op::CreateClone),
+            BlockType::DeleteClone(_) => self.emit(Op::DeleteClone),
+
+            BlockType::AskAndWait(a) => self.op1(&a.question, Op::Ask),
+            BlockType::SetDragMode(a) => match &a.option {
+                Some(DraggableOption::Draggable) => self.emit(Op::SetDraggable(true)),
+                Some(DraggableOption::NotDraggable) => self.emit(Op::SetDraggable(false)),
+                None => {}
+            },
+            BlockType::ResetTimer(_) => self.emit(Op::ResetTimer),
+
+            BlockType::DataSetVariableTo(a) => {
+                self.value(&a.value);
+                let slot = self.variable(&a.variable);
+                self.emit(Op::SetVar(slot));
+            }
+            BlockType::DataChangeVariableBy(a) => {
+                self.value(&a.value);
+                let slot = self.variable(&a.variable);
+                self.emit(Op::ChangeVar(slot));
+            }
+            BlockType::DataAddToList(a) => {
+                self.value(&a.item);
+                let slot = self.list(&a.list);
+                self.emit(Op::ListAdd(slot));
+            }
+            BlockType::DataDeleteOfList(a) => {
+                self.value(&a.item);
+                let slot = self.list(&a.list);
+                self.emit(Op::ListDelete(slot));
+            }
+            BlockType::DataDeleteAllOfList(a) => {
+                let slot = self.list(&a.list);
+                self.emit(Op::ListDeleteAll(slot));
+            }
+            BlockType::DataInsertAtList(a) => {
+                self.value(&a.item);
+                self.value(&a.index);
+                let slot = self.list(&a.list);
+                self.emit(Op::ListInsert(slot));
+            }
+            BlockType::DataReplaceItemOfList(a) => {
+                self.value(&a.index);
+                self.value(&a.item);
+                let slot = self.list(&a.list);
+                self.emit(Op::ListReplace(slot));
+            }
+
+            BlockType::ProceduresCall(a) => {
+                let index = match self.procs.get(a.signature.proccode()) {
+                    Some(a) => *a,
+                    // calls to blocks that aren't defined do nothing.
+                    None => return,
+                };
+                let args = self.program.procedures[index].args;
+                for i in 0..args {
+                    match a.arguments.get(i) {
+                        Some(arg) => self.input(arg),
+                        None => self.constant(""),
+                    }
+                }
+                self.emit(Op::Call(index));
+            }
+            // everything else either doesn't do anything or isn't a statement.
+            _ => {}
+        }
+    }
+
+    fn op1(&mut self, a: &'a Option<Value>, op: Op) {
+        self.input(a);
+        self.emit(op);
+    }
+
+    fn op2(&mut self, a: &'a Option<Value>, b: &'a Option<Value>, op: Op) {
+        self.input(a);
+        self.input(b);
+        self.emit(op);
+    }
+}
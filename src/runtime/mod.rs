@@ -0,0 +1,17 @@
+//! Running projects.
+//!
+//! Scripts are compiled into a small stack based instruction set (see ir) and
+//! run by the interpreter in vm. The older approach of walking the blocks
+//! themselves is kept in treewalk, mostly to benchmark against. types works
+//! out which variables only ever hold numbers, and parallel which sprites can
+//! run on worker threads.
+//!
+//! The scheduler and everything scripts act on live in yase_rt, so that
+//! transpiled projects can use them too.
+pub use yase_rt::*;
+
+pub mod ir;
+pub mod parallel;
+pub mod treewalk;
+pub mod types;
+pub mod vm;
@@ -0,0 +1,234 @@
+//! Works out which sprites can run on worker threads.
+//!
+//! A sprite can if its scripts never touch anything outside of its own
+//! targets (the stage's variables, other sprites, the timer, the random
+//! number generator), and no other script ever looks at them. Then it
+//! doesn't matter when its scripts run within a tick, which is what
+//! Runtime::set_parallel needs.
+//!
+//! Anything that can't be told for sure from the compiled code counts as
+//! touching everything. The stage never runs in parallel.
+use super::{
+    ir::{Op, Program, StopKind},
+    value::Val,
+    world::Slot,
+    HatKind,
+};
+
+/// The sprites that can run on worker threads, by index.
+pub fn isolated(program: &Program) -> Vec<usize> {
+    // "stop all" would have to stop scripts that already ran.
+    if program
+        .code
+        .iter()
+        .any(|f| matches!(f, Op::Stop(StopKind::All)))
+    {
+        return Vec::new();
+    }
+    let sprites = program.targets.len();
+    let mut isolated = vec![true; sprites];
+    isolated[program.stage] = false;
+
+    for hat in &program.hats {
+        // started in the middle of a tick.
+        if matches!(
+            hat.kind,
+            HatKind::Broadcast(_) | HatKind::BackdropSwitchesTo(_) | HatKind::CloneStart
+        ) {
+            isolated[hat.sprite] = false;
+        }
+    }
+
+    // every sprite's code is in one piece, so where it starts is enough.
+    let mut starts: Vec<(usize, usize)> = program
+        .entries
+        .iter()
+        .zip(&program.hats)
+        .map(|(start, hat)| (*start, hat.sprite))
+        .chain(program.procedures.iter().map(|f| (f.start, f.sprite)))
+        .collect();
+    starts.sort();
+    let mut owner = 0;
+    let mut next = starts.iter().peekable();
+
+    for (i, op) in program.code.iter().enumerate() {
+        while let Some((_, sprite)) = next.next_if(|f| f.0 <= i) {
+            owner = *sprite;
+        }
+        let before = |n: usize| match i.checked_sub(n).map(|f| &program.code[f]) {
+            Some(Op::Const(a)) => Some(a),
+            _ => None,
+        };
+        let touches = match op {
+            Op::Var(slot)
+            | Op::SetVar(slot)
+            | Op::ChangeVar(slot)
+            | Op::ListContents(slot)
+            | Op::ListAdd(slot)
+            | Op::ListDeleteAll(slot)
+            | Op::ListLength(slot)
+            | Op::ListContains(slot) => is_global(*slot),
+            // unless it's a number, the index might be random.
+            Op::ListDelete(slot) | Op::ListInsert(slot) | Op::ListItem(slot) => {
+                is_global(*slot) || !before(1).is_some_and(fixed_index)
+            }
+            Op::ListReplace(slot) => {
+                is_global(*slot) || before(1).is_none() || !before(2).is_some_and(fixed_index)
+            }
+            Op::Touching | Op::DistanceTo | Op::Goto | Op::GlideTo | Op::PointTowards => {
+                sense(program, &mut isolated, before(1))
+            }
+            // clones are new targets, which only the main world can hold.
+            Op::CreateClone => {
+                if before(1).is_none_or(|f| f.to_string() != "_myself_") {
+                    sense(program, &mut isolated, before(1));
+                }
+                true
+            }
+            Op::Random
+            | Op::Broadcast
+            | Op::BroadcastAndWait
+            | Op::DeleteClone
+            | Op::Stop(StopKind::OtherScripts)
+            | Op::SwitchBackdrop
+            | Op::SwitchBackdropAndWait
+            | Op::NextBackdrop
+            | Op::BackdropNumber
+            | Op::BackdropName
+            | Op::Ask
+            | Op::Answer
+            | Op::KeyPressed
+            | Op::MouseDown
+            | Op::MouseX
+            | Op::MouseY
+            | Op::Timer
+            | Op::ResetTimer
+            | Op::GotoLayer(_)
+            | Op::ChangeLayer(_) => true,
+            _ => false,
+        };
+        if touches {
+            isolated[owner] = false;
+        }
+    }
+    (0..sprites).filter(|f| isolated[*f]).collect()
+}
+
+fn is_global(slot: Slot) -> bool {
+    matches!(slot, Slot::Global(_))
+}
+
+fn fixed_index(index: &Val) -> bool {
+    !matches!(index.to_string().as_str(), "random" | "any")
+}
+
+/// Looks at another sprite (or the edge, or the mouse pointer). Marks what's
+/// looked at as not isolated, and returns whether the one looking isn't either.
+/// Only the edge is always where it was.
+fn sense(program: &Program, isolated: &mut [bool], what: Option<&Val>) -> bool {
+    let name = match what {
+        Some(a) => a.to_string(),
+        None => {
+            isolated.iter_mut().for_each(|f| *f = false);
+            return true;
+        }
+    };
+    match name.as_str() {
+        "_edge_" => false,
+        "_mouse_" | "_random_" => true,
+        name => {
+            if let Some(a) = program
+                .targets
+                .iter()
+                .position(|f| !f.is_stage && f.name == name)
+            {
+                isolated[a] = false;
+            }
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        decomp::Project,
+        runtime::{vm::Vm, world::World, Options, Runtime},
+        test_util::test_project,
+    };
+
+    /// A sprite that walks in a circle, counting its steps out loud.
+    fn walker(name: &str, variables: Value, var: &str) -> Value {
+        let id = format!("{}-id", var);
+        json!({
+            "isStage": false,
+            "name": name,
+            "variables": variables,
+            "visible": true,
+            "size": 100,
+            "direction": 90,
+            "blocks": {
+                "flag": {"opcode": "event_whenflagclicked", "next": "repeat", "parent": null,
+                    "inputs": {}, "fields": {}, "shadow": false, "topLevel": true},
+                "repeat": {"opcode": "control_repeat", "next": null, "parent": "flag",
+                    "inputs": {"TIMES": [1, [6, "20"]], "SUBSTACK": [2, "move"]},
+                    "fields": {}, "shadow": false, "topLevel": false},
+                "move": {"opcode": "motion_movesteps", "next": "turn", "parent": "repeat",
+                    "inputs": {"STEPS": [1, [4, "7"]]}, "fields": {}, "shadow": false, "topLevel": false},
+                "turn": {"opcode": "motion_turnright", "next": "count", "parent": "move",
+                    "inputs": {"DEGREES": [1, [4, "13"]]}, "fields": {}, "shadow": false, "topLevel": false},
+                "count": {"opcode": "data_changevariableby", "next": "say", "parent": "turn",
+                    "inputs": {"VALUE": [1, [4, "1"]]}, "fields": {"VARIABLE": [var, id]},
+                    "shadow": false, "topLevel": false},
+                "say": {"opcode": "looks_say", "next": null, "parent": "count",
+                    "inputs": {"MESSAGE": [3, "join", [10, ""]]}, "fields": {}, "shadow": false, "topLevel": false},
+                "join": {"opcode": "operator_join", "next": null, "parent": "say",
+                    "inputs": {"STRING1": [1, [10, "n="]], "STRING2": [3, [12, var, id], [10, ""]]},
+                    "fields": {}, "shadow": false, "topLevel": false},
+            },
+        })
+    }
+
+    fn project() -> Project {
+        let project = json!({
+            "targets": [
+                {"isStage": true, "name": "Stage", "variables": {"total-id": ["total", 0]}},
+                walker("A", json!({"n-id": ["n", 0]}), "n"),
+                walker("B", json!({"n-id": ["n", 5]}), "n"),
+                walker("C", json!({}), "total"),
+            ],
+        });
+        serde_json::from_value(project).unwrap()
+    }
+
+    fn run(project: &Project, frames: u64, parallel: bool) -> String {
+        let program = Program::compile(project);
+        let sprites = isolated(&program);
+        let world = World::new(&program.targets, Options::default().seed);
+        let mut runtime = Runtime::new(Vm::new(program), world, Options::default());
+        if parallel {
+            runtime.set_parallel(&sprites, 4);
+        }
+        runtime.green_flag();
+        runtime.run(frames);
+        runtime.world().transcript()
+    }
+
+    #[test]
+    fn finds_isolated_sprites() {
+        // C writes to a variable of the stage.
+        assert_eq!(isolated(&Program::compile(&project())), vec![1, 2]);
+    }
+
+    #[test]
+    fn parallel_runs_match_serial_ones() {
+        let project = project();
+        assert_eq!(run(&project, 100, false), run(&project, 100, true));
+
+        let project = test_project();
+        assert_eq!(run(&project, 30, false), run(&project, 30, true));
+    }
+}
@@ -0,0 +1,784 @@
+//! Runs scripts by walking their blocks directly, looking every block,
+//! variable and custom block up by its id or name as it goes. This is how
+//! projects were run before the compiler existed, and is kept to check the
+//! compiler against, and to see how much it buys us.
+use crate::{
+    blocks::*,
+    decomp::Project,
+    script::{Input, Linked},
+};
+
+use super::{
+    ir::{literal, math_op, name_of, time_unit, Program},
+    ops::{self, BinOp, UnOp},
+    poll,
+    value::Val,
+    world::{RotationStyle as Style, TargetId},
+    Backend, Ctx, Hat, Status, Thread, WARP_BUDGET,
+};
+
+pub struct TreeWalker<'a> {
+    /// Only used for the hats and the variable names.
+    program: &'a Program,
+    sprites: Vec<Linked<'a>>,
+}
+
+pub struct WalkState<'a> {
+    frames: Vec<Frame<'a>>,
+    warp: usize,
+}
+
+enum Frame<'a> {
+    /// The next block to run in a stack.
+    Stack(Option<&'a str>),
+    /// Loops remember whether they've gone around once, since that's when they yield.
+    Repeat {
+        left: f64,
+        body: &'a Option<Value>,
+        again: bool,
+    },
+    Forever {
+        body: &'a Option<Value>,
+        again: bool,
+    },
+    Until {
+        condition: &'a Option<Value>,
+        body: &'a Option<Value>,
+        again: bool,
+    },
+    Call {
+        args: Vec<Val>,
+        names: &'a [String],
+        warp: bool,
+    },
+}
+
+/// What a block wants to happen after it ran.
+enum Flow<'a> {
+    Next,
+    Push(Frame<'a>),
+    /// Run the block again next time.
+    Again,
+    Wait,
+    Done,
+}
+
+impl<'a> TreeWalker<'a> {
+    pub fn new(project: &'a Project, program: &'a Program) -> TreeWalker<'a> {
+        TreeWalker {
+            program,
+            sprites: project.sprites().iter().map(Linked::new).collect(),
+        }
+    }
+
+    /// The id of the block after this one, with the lifetime of the project.
+    fn next(&self, linked: &Linked<'a>, block: &'a BlockType) -> Option<&'a str> {
+        let next = block.next()?;
+        linked
+            .sprite
+            .blocks
+            .get_key_value(&next)
+            .map(|f| f.0.as_str())
+    }
+
+    fn first(&self, linked: &Linked<'a>, val: &'a Option<Value>) -> Option<&'a str> {
+        match linked.input(val) {
+            Input::Block(id, _) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Backend for TreeWalker<'a> {
+    type State = WalkState<'a>;
+
+    fn hats(&self) -> &[Hat] {
+        &self.program.hats
+    }
+
+    fn start(&self, hat: usize) -> WalkState<'a> {
+        let hat = &self.program.hats[hat];
+        let linked = &self.sprites[hat.sprite];
+        let first = linked.block(&hat.id).and_then(|f| self.next(linked, f));
+        WalkState {
+            frames: vec![Frame::Stack(first)],
+            warp: 0,
+        }
+    }
+
+    fn step(&self, ctx: &mut Ctx, thread: &mut Thread<WalkState<'a>>) -> Status {
+        if !poll(ctx, thread) {
+            return Status::Wait;
+        }
+        let t = thread.target;
+        let linked = &self.sprites[ctx.world.target(t).sprite];
+        let mut budget = WARP_BUDGET;
+        loop {
+            let s = &mut thread.state;
+            let warp = s.warp;
+            // loops yield every time around, unless running without screen refresh.
+            let mut yields = || {
+                if warp == 0 {
+                    return true;
+                }
+                budget -= 1;
+                budget == 0
+            };
+            let frame = match s.frames.last_mut() {
+                Some(a) => a,
+                None => return Status::Done,
+            };
+            let id = match frame {
+                Frame::Stack(cur) => match cur.take() {
+                    Some(id) => id,
+                    None => {
+                        s.frames.pop();
+                        continue;
+                    }
+                },
+                Frame::Repeat { left, body, again } => {
+                    if *again {
+                        *again = false;
+                        if yields() {
+                            return Status::Yield;
+                        }
+                    }
+                    if *left >= 1.0 {
+                        *left -= 1.0;
+                        *again = true;
+                        let first = self.first(linked, body);
+                        s.frames.push(Frame::Stack(first));
+                    } else {
+                        s.frames.pop();
+                    }
+                    continue;
+                }
+                Frame::Forever { body, again } => {
+                    if *again {
+                        *again = false;
+                        if yields() {
+                            return Status::Yield;
+                        }
+                    }
+                    *again = true;
+                    let first = self.first(linked, body);
+                    s.frames.push(Frame::Stack(first));
+                    continue;
+                }
+                Frame::Until {
+                    condition,
+                    body,
+                    again,
+                } => {
+                    if *again {
+                        *again = false;
+                        if yields() {
+                            return Status::Yield;
+                        }
+                    }
+                    let (condition, body) = (*condition, *body);
+                    if self.eval(ctx, t, s, linked, linked.input(condition)).bool() {
+                        s.frames.pop();
+                        continue;
+                    }
+                    if let Some(Frame::Until { again, .. }) = s.frames.last_mut() {
+                        *again = true;
+                    }
+                    let first = self.first(linked, body);
+                    s.frames.push(Frame::Stack(first));
+                    continue;
+                }
+                Frame::Call { .. } => {
+                    return_from(s);
+                    continue;
+                }
+            };
+            let block = match linked.block(id) {
+                Some(a) => a,
+                None => continue,
+            };
+            let next = self.next(linked, block);
+            if let Some(Frame::Stack(cur)) = s.frames.last_mut() {
+                *cur = next;
+            }
+            match self.statement(ctx, thread, linked, block) {
+                Flow::Next => {}
+                Flow::Push(frame) => thread.state.frames.push(frame),
+                Flow::Again => {
+                    if let Some(Frame::Stack(cur)) = thread.state.frames.last_mut() {
+                        *cur = Some(id);
+                    }
+                    return Status::Yield;
+                }
+                Flow::Wait => return Status::Wait,
+                Flow::Done => return Status::Done,
+            }
+        }
+    }
+}
+
+fn return_from(s: &mut WalkState) {
+    while let Some(frame) = s.frames.pop() {
+        if let Frame::Call { warp, .. } = frame {
+            if warp {
+                s.warp -= 1;
+            }
+            return;
+        }
+    }
+}
+
+impl<'a> TreeWalker<'a> {
+    fn input(
+        &self,
+        ctx: &mut Ctx,
+        t: TargetId,
+        s: &WalkState<'a>,
+        linked: &Linked<'a>,
+        val: &'a Option<Value>,
+    ) -> Val {
+        self.eval(ctx, t, s, linked, linked.input(val))
+    }
+
+    fn value(
+        &self,
+        ctx: &mut Ctx,
+        t: TargetId,
+        s: &WalkState<'a>,
+        linked: &Linked<'a>,
+        val: &'a Value,
+    ) -> Val {
+        self.eval(ctx, t, s, linked, linked.value(val))
+    }
+
+    fn variable(&self, ctx: &Ctx, t: TargetId, name: &str) -> Option<(TargetId, usize)> {
+        let sprite = ctx.world.target(t).sprite;
+        let slot = self.program.variable(sprite, name)?;
+        Some(ctx.world.resolve(t, slot))
+    }
+
+    fn list(&self, ctx: &Ctx, t: TargetId, name: &Value) -> Option<(TargetId, usize)> {
+        let sprite = ctx.world.target(t).sprite;
+        let slot = self.program.list(sprite, &name_of(name))?;
+        Some(ctx.world.resolve(t, slot))
+    }
+
+    fn eval(
+        &self,
+        ctx: &mut Ctx,
+        t: TargetId,
+        s: &WalkState<'a>,
+        linked: &Linked<'a>,
+        input: Input<'a>,
+    ) -> Val {
+        let block = match input {
+            Input::Empty => return Val::from(""),
+            Input::Literal(a) => return literal(a),
+            Input::Variable(a) => {
+                return match self.variable(ctx, t, a) {
+                    Some((target, i)) => ctx.world.target(target).variables[i].clone(),
+                    None => Val::default(),
+                }
+            }
+            Input::List(a) => {
+                return match self.list(ctx, t, &Value::String(a.to_string())) {
+                    Some((target, i)) => ctx.world.list_contents(target, i),
+                    None => Val::from(""),
+                }
+            }
+            Input::Block(_, block) => block,
+        };
+        let mut binary = |a: &'a Option<Value>, b: &'a Option<Value>, op: BinOp| {
+            let a = self.input(ctx, t, s, linked, a);
+            let b = self.input(ctx, t, s, linked, b);
+            ops::binary(op, &a, &b)
+        };
+        match block {
+            BlockType::Add(a) => binary(&a.a, &a.b, BinOp::Add),
+            BlockType::Sub(a) => binary(&a.a, &a.b, BinOp::Sub),
+            BlockType::Mul(a) => binary(&a.a, &a.b, BinOp::Mul),
+            BlockType::Divide(a) => binary(&a.a, &a.b, BinOp::Div),
+            BlockType::Modulo(a) => binary(&a.a, &a.b, BinOp::Mod),
+            BlockType::GreaterThen(a) => binary(&a.a, &a.b, BinOp::Gt),
+            BlockType::LesserThen(a) => binary(&a.a, &a.b, BinOp::Lt),
+            BlockType::EqualTo(a) => binary(&a.a, &a.b, BinOp::Eq),
+            BlockType::And(a) => binary(&a.a, &a.b, BinOp::And),
+            BlockType::Or(a) => binary(&a.a, &a.b, BinOp::Or),
+            BlockType::Join(a) => binary(&a.a, &a.b, BinOp::Join),
+            BlockType::LetterOf(a) => binary(&a.index, &a.a, BinOp::LetterOf),
+            BlockType::Contains(a) => binary(&a.a, &a.b, BinOp::Contains),
+            BlockType::Not(a) => ops::unary(UnOp::Not, &self.input(ctx, t, s, linked, &a.a)),
+            BlockType::LengthOf(a) => {
+                ops::unary(UnOp::Length, &self.input(ctx, t, s, linked, &a.a))
+            }
+            BlockType::Round(a) => ops::unary(UnOp::Round, &self.input(ctx, t, s, linked, &a.a)),
+            BlockType::MathOp(a) => {
+                let val = self.input(ctx, t, s, linked, &a.a);
+                match a.operator {
+                    Some(op) => ops::unary(UnOp::Math(math_op(op)), &val),
+                    None => val,
+                }
+            }
+            BlockType::PickRandom(a) => {
+                let min = self.input(ctx, t, s, linked, &a.min);
+                let max = self.input(ctx, t, s, linked, &a.max);
+                Val::Num(ctx.world.rng.pick(&min, &max))
+            }
+
+            BlockType::XPosition(_) => Val::Num(ctx.world.target(t).x),
+            BlockType::YPosition(_) => Val::Num(ctx.world.target(t).y),
+            BlockType::Direction(_) => Val::Num(ctx.world.target(t).direction),
+            BlockType::Size(_) => Val::Num(ctx.world.target(t).size),
+            BlockType::Volume(_) => Val::Num(ctx.world.target(t).volume),
+            BlockType::Costume(Costume::ByNumber(_)) => {
+                Val::Num(ctx.world.target(t).costume as f64 + 1.0)
+            }
+            BlockType::Costume(Costume::ByName(_)) => Val::from(ctx.world.costume_name(t)),
+            BlockType::Backdrop(Backdrop::ByNumber(_)) => {
+                Val::Num(ctx.world.target(ctx.world.stage).costume as f64 + 1.0)
+            }
+            BlockType::Backdrop(Backdrop::ByName(_)) => {
+                Val::from(ctx.world.costume_name(ctx.world.stage))
+            }
+            BlockType::Answer(_) => Val::from(ctx.world.answer.as_str()),
+            BlockType::MouseDown(_) => Val::Bool(ctx.world.mouse_down),
+            BlockType::MouseX(_) => Val::Num(ctx.world.mouse.0),
+            BlockType::MouseY(_) => Val::Num(ctx.world.mouse.1),
+            BlockType::Timer(_) => Val::Num(ctx.world.timer()),
+            BlockType::DaysSince2000(_) => Val::Num(ctx.world.days_since_2000()),
+            BlockType::Username(_) => Val::from(ctx.world.username.as_str()),
+            BlockType::Loudness(_) => Val::Num(-1.0),
+            BlockType::CurrentTime(a) => match &a.option {
+                Some(a) => Val::Num(ops::current(time_unit(a))),
+                None => Val::Num(0.0),
+            },
+            BlockType::KeyPressed(a) => {
+                let key = self.input(ctx, t, s, linked, &a.key);
+                Val::Bool(ctx.world.key_pressed(&key))
+            }
+            BlockType::Touching(a) => {
+                let what = self.input(ctx, t, s, linked, &a.touching);
+                Val::Bool(ctx.world.touching(t, &what))
+            }
+            BlockType::DistanceTo(a) => {
+                let to = self.input(ctx, t, s, linked, &a.to);
+                Val::Num(ctx.world.distance_to(t, &to))
+            }
+            BlockType::TouchingColor(_) | BlockType::ColorTouchingColor(_) => Val::Bool(false),
+
+            BlockType::Costume(Costume::WithName(a))
+            | BlockType::Backdrop(Backdrop::WithName(a)) => {
+                Val::from(a.as_ref().map(name_of).unwrap_or_default())
+            }
+            BlockType::Goto(Goto::Menu(a)) | BlockType::Glide(Glide::Menu(a)) => option(&a.option),
+            BlockType::PointTowardsMenu(a) => option(&a.option),
+            BlockType::TouchingMenu(a) => option(&a.touching),
+            BlockType::DistanceToMenu(a) => option(&a.to),
+            BlockType::KeyOptions(a) => option(&a.key),
+            BlockType::CreateCloneOfMenu(a) => option(&a.of),
+            BlockType::SoundSoundsMenu(a) => {
+                Val::from(a.option.as_ref().map(name_of).unwrap_or_default())
+            }
+
+            BlockType::DataGetVariable(a) => match self.variable(ctx, t, &name_of(&a.variable)) {
+                Some((target, i)) => ctx.world.target(target).variables[i].clone(),
+                None => Val::default(),
+            },
+            BlockType::DataListContents(a) => match self.list(ctx, t, &a.variable) {
+                Some((target, i)) => ctx.world.list_contents(target, i),
+                None => Val::from(""),
+            },
+            BlockType::DataItemOfList(a) => {
+                let index = self.value(ctx, t, s, linked, &a.index);
+                match self.list(ctx, t, &a.list) {
+                    Some((target, i)) => ctx.world.item_of_list(target, i, &index),
+                    None => Val::from(""),
+                }
+            }
+            BlockType::DataLengthOfList(a) => match self.list(ctx, t, &a.list) {
+                Some((target, i)) => Val::Num(ctx.world.list(target, i).len() as f64),
+                None => Val::Num(0.0),
+            },
+            BlockType::DataListContainsItem(a) => {
+                let item = self.value(ctx, t, s, linked, &a.input);
+                match self.list(ctx, t, &a.list) {
+                    Some((target, i)) => Val::Bool(ctx.world.list_contains(target, i, &item)),
+                    None => Val::Bool(false),
+                }
+            }
+            BlockType::ArgumentReporter(a) => {
+                let call = s.frames.iter().rev().find_map(|f| match f {
+                    Frame::Call { args, names, .. } => Some((args, names)),
+                    _ => None,
+                });
+                let arg = call.and_then(|(args, names)| {
+                    let i = names.iter().position(|f| *f == a.name)?;
+                    args.get(i).cloned()
+                });
+                match arg {
+                    Some(a) => a,
+                    None if a.boolean => Val::Bool(false),
+                    None => Val::Num(0.0),
+                }
+            }
+            _ => Val::from(""),
+        }
+    }
+
+    fn statement(
+        &self,
+        ctx: &mut Ctx,
+        thread: &mut Thread<WalkState<'a>>,
+        linked: &Linked<'a>,
+        block: &'a BlockType,
+    ) -> Flow<'a> {
+        let t = thread.target;
+        let s = &thread.state;
+        let mut input = |val: &'a Option<Value>| self.input(ctx, t, s, linked, val);
+        match block {
+            BlockType::Move(a) => {
+                let steps = input(&a.steps).num();
+                ctx.world.move_steps(t, steps);
+            }
+            BlockType::RotateRight(a) => {
+                let degrees = input(&a.degrees).num();
+                ctx.world.turn(t, degrees);
+            }
+            BlockType::RotateLeft(a) => {
+                let degrees = input(&a.degrees).num();
+                ctx.world.turn(t, -degrees);
+            }
+            BlockType::Goto(Goto::Pos(a)) => {
+                let x = input(&a.x).num();
+                let y = input(&a.y).num();
+                ctx.world.set_xy(t, x, y);
+            }
+            BlockType::Goto(Goto::Option(a)) => {
+                let to = input(&a.option);
+                ctx.world.goto(t, &to);
+            }
+            BlockType::Glide(glide) => {
+                let (secs, to) = match glide {
+                    Glide::Pos(a) => {
+                        let secs = input(&a.secs).num();
+                        let x = input(&a.x).num();
+                        let y = input(&a.y).num();
+                        (secs, Some((x, y)))
+                    }
+                    Glide::Option(a) => {
+                        let secs = input(&a.secs).num();
+                        let to = input(&a.option);
+                        (secs, ctx.world.position_of(&to))
+                    }
+                    Glide::Menu(_) => return Flow::Next,
+                };
+                if ctx.glide(t, &mut thread.pending, secs, to) {
+                    return Flow::Wait;
+                }
+            }
+            BlockType::Point(Point::Direction(a)) => {
+                let direction = input(&a.direction).num();
+                ctx.world.set_direction(t, direction);
+            }
+            BlockType::Point(Point::Towards(a)) => {
+                let to = input(&a.option);
+                ctx.world.point_towards(t, &to);
+            }
+            BlockType::ChangeX(a) => {
+                let by = input(&a.x).num();
+                let target = ctx.world.target(t);
+                let (x, y) = (target.x + by, target.y);
+                ctx.world.set_xy(t, x, y);
+            }
+            BlockType::SetX(a) => {
+                let x = input(&a.x).num();
+                let y = ctx.world.target(t).y;
+                ctx.world.set_xy(t, x, y);
+            }
+            BlockType::ChangeY(a) => {
+                let by = input(&a.y).num();
+                let target = ctx.world.target(t);
+                let (x, y) = (target.x, target.y + by);
+                ctx.world.set_xy(t, x, y);
+            }
+            BlockType::SetY(a) => {
+                let y = input(&a.y).num();
+                let x = ctx.world.target(t).x;
+                ctx.world.set_xy(t, x, y);
+            }
+            BlockType::IfOnEdgeBounce(_) => ctx.world.bounce(t),
+            BlockType::SetRotationStyle(a) => {
+                let style = match a.style {
+                    Some(RotationStyle::AllAround) => Style::AllAround,
+                    Some(RotationStyle::LeftRight) => Style::LeftRight,
+                    Some(RotationStyle::DontRotate) => Style::DontRotate,
+                    None => return Flow::Next,
+                };
+                ctx.world.set_rotation_style(t, style);
+            }
+
+            BlockType::Say(a) => {
+                let message = input(&a.message);
+                let secs = input(&a.secs).num();
+                ctx.say_for(t, &mut thread.pending, &message, secs, false);
+                return Flow::Wait;
+            }
+            BlockType::Think(a) => {
+                let message = input(&a.message);
+                let secs = input(&a.secs).num();
+                ctx.say_for(t, &mut thread.pending, &message, secs, true);
+                return Flow::Wait;
+            }
+            BlockType::SayForever(a) => {
+                let message = input(&a.message);
+                ctx.world.say(t, &message, false);
+            }
+            BlockType::ThinkForever(a) => {
+                let message = input(&a.message);
+                ctx.world.say(t, &message, true);
+            }
+            BlockType::SwitchCostume(a) => {
+                let costume = input(&a.costume);
+                ctx.world.switch_costume(t, &costume);
+            }
+            BlockType::NextCostume(_) => ctx.world.next_costume(t),
+            BlockType::SwitchBackdrop(a) => {
+                let backdrop = input(&a.backdrop);
+                ctx.switch_backdrop(&backdrop);
+            }
+            BlockType::SwitchBackdropAndWait(a) => {
+                let backdrop = input(&a.backdrop);
+                if ctx.switch_backdrop_and_wait(&mut thread.pending, &backdrop) {
+                    return Flow::Wait;
+                }
+            }
+            BlockType::NextBackdrop(_) => {
+                ctx.switch_backdrop(&Val::from("next backdrop"));
+            }
+            BlockType::ChangeSize(a) => {
+                let by = input(&a.units).num();
+                let size = ctx.world.target(t).size + by;
+                ctx.world.set_size(t, size);
+            }
+            BlockType::SetSize(a) => {
+                let size = input(&a.percentage).num();
+                ctx.world.set_size(t, size);
+            }
+            BlockType::ClearGraphicEffects(_) => ctx.world.clear_effects(t),
+            BlockType::ShowSprite(_) => ctx.world.set_visible(t, true),
+            BlockType::HideSprite(_) => ctx.world.set_visible(t, false),
+            BlockType::GotoLayer(a) => match a.option {
+                Some(LayerOption::Front) => ctx.world.goto_layer(t, true),
+                Some(LayerOption::Back) => ctx.world.goto_layer(t, false),
+                None => {}
+            },
+            BlockType::ChangeLayer(a) => {
+                let by = input(&a.by).num() as i64;
+                let by = match a.direction {
+                    Some(LayerDirection::Backward) => -by,
+                    _ => by,
+                };
+                ctx.world.change_layer(t, by);
+            }
+            BlockType::ChangeEffectBy(a) => {
+                let by = input(&a.units).num();
+                let effect = a.effect.as_ref().map(name_of).unwrap_or_default();
+                ctx.world.change_effect(t, &effect, by);
+            }
+            BlockType::SetEffectTo(a) => {
+                let to = input(&a.percentage).num();
+                let effect = a.effect.as_ref().map(name_of).unwrap_or_default();
+                ctx.world.set_effect(t, &effect, to);
+            }
+
+            BlockType::PlaySound(a) => {
+                input(&a.sound);
+            }
+            BlockType::PlaySoundUntilDone(a) => {
+                let sound = input(&a.sound);
+                if ctx.play_sound_until_done(t, &mut thread.pending, &sound) {
+                    return Flow::Wait;
+                }
+            }
+            BlockType::ChangeVolumeBy(a) => {
+                let by = input(&a.units).num();
+                let volume = ctx.world.target(t).volume + by;
+                ctx.world.set_volume(t, volume);
+            }
+            BlockType::SetVolumeTo(a) => {
+                let volume = input(&a.percentage).num();
+                ctx.world.set_volume(t, volume);
+            }
+
+            BlockType::Broadcast(a) => {
+                let name = input(&a.broadcast);
+                ctx.broadcast(&name);
+            }
+            BlockType::BroadcastAndWait(a) => {
+                let name = input(&a.broadcast);
+                if ctx.broadcast_and_wait(&mut thread.pending, &name) {
+                    return Flow::Wait;
+                }
+            }
+
+            BlockType::WaitSeconds(a) => {
+                let secs = input(&a.seconds).num();
+                ctx.wait(&mut thread.pending, secs);
+                return Flow::Wait;
+            }
+            BlockType::Repeat(a) => {
+                let left = input(&a.units).num().round();
+                return Flow::Push(Frame::Repeat {
+                    left,
+                    body: &a.substack,
+                    again: false,
+                });
+            }
+            BlockType::Forever(a) => {
+                return Flow::Push(Frame::Forever {
+                    body: &a.substack,
+                    again: false,
+                })
+            }
+            BlockType::RepeatUntil(a) => {
+                return Flow::Push(Frame::Until {
+                    condition: &a.condition,
+                    body: &a.substack,
+                    again: false,
+                })
+            }
+            BlockType::WaitUntil(a) if !input(&a.condition).bool() => return Flow::Again,
+            BlockType::IfThen(a) if input(&a.condition).bool() => {
+                return Flow::Push(Frame::Stack(self.first(linked, &a.then)));
+            }
+            BlockType::IfThenElse(a) => {
+                let branch = match input(&a.condition).bool() {
+                    true => &a.then,
+                    false => &a.otherwise,
+                };
+                return Flow::Push(Frame::Stack(self.first(linked, branch)));
+            }
+            BlockType::StopAll(a) => match a.option {
+                Some(StopOption::ThisScript) => {
+                    let s = &mut thread.state;
+                    if !s.frames.iter().any(|f| matches!(f, Frame::Call { .. })) {
+                        return Flow::Done;
+                    }
+                    return_from(s);
+                }
+                Some(StopOption::OtherScriptsInSprite) => ctx.stop_others(t, thread.id),
+                Some(StopOption::All) | None => {
+                    ctx.stop_all();
+                    return Flow::Done;
+                }
+            },
+            BlockType::CreateCloneOf(a) => {
+                let of = input(&a.of);
+                ctx.create_clone(t, &of);
+            }
+            BlockType::DeleteClone(_) => {
+                // the original can't be deleted, and keeps going.
+                let deleted = ctx.delete_clone(t);
+                if deleted {
+                    return Flow::Done;
+                }
+            }
+
+            BlockType::AskAndWait(a) => {
+                input(&a.question);
+                ctx.world.ask();
+            }
+            BlockType::SetDragMode(a) => match a.option {
+                Some(DraggableOption::Draggable) => ctx.world.target_mut(t).draggable = true,
+                Some(DraggableOption::NotDraggable) => ctx.world.target_mut(t).draggable = false,
+                None => {}
+            },
+            BlockType::ResetTimer(_) => ctx.world.reset_timer(),
+
+            BlockType::DataSetVariableTo(a) => {
+                let val = self.value(ctx, t, s, linked, &a.value);
+                if let Some((target, i)) = self.variable(ctx, t, &name_of(&a.variable)) {
+                    ctx.world.target_mut(target).variables[i] = val;
+                }
+            }
+            BlockType::DataChangeVariableBy(a) => {
+                let by = self.value(ctx, t, s, linked, &a.value).num();
+                if let Some((target, i)) = self.variable(ctx, t, &name_of(&a.variable)) {
+                    let var = &mut ctx.world.target_mut(target).variables[i];
+                    *var = Val::Num(var.num() + by);
+                }
+            }
+            BlockType::DataAddToList(a) => {
+                let item = self.value(ctx, t, s, linked, &a.item);
+                if let Some((target, i)) = self.list(ctx, t, &a.list) {
+                    ctx.world.add_to_list(target, i, item);
+                }
+            }
+            BlockType::DataDeleteOfList(a) => {
+                let index = self.value(ctx, t, s, linked, &a.item);
+                if let Some((target, i)) = self.list(ctx, t, &a.list) {
+                    ctx.world.delete_of_list(target, i, &index);
+                }
+            }
+            BlockType::DataDeleteAllOfList(a) => {
+                if let Some((target, i)) = self.list(ctx, t, &a.list) {
+                    ctx.world.delete_all_of_list(target, i);
+                }
+            }
+            BlockType::DataInsertAtList(a) => {
+                let item = self.value(ctx, t, s, linked, &a.item);
+                let index = self.value(ctx, t, s, linked, &a.index);
+                if let Some((target, i)) = self.list(ctx, t, &a.list) {
+                    ctx.world.insert_at_list(target, i, &index, item);
+                }
+            }
+            BlockType::DataReplaceItemOfList(a) => {
+                let index = self.value(ctx, t, s, linked, &a.index);
+                let item = self.value(ctx, t, s, linked, &a.item);
+                if let Some((target, i)) = self.list(ctx, t, &a.list) {
+                    ctx.world.replace_item_of_list(target, i, &index, item);
+                }
+            }
+
+            BlockType::ProceduresCall(a) => {
+                let procedure = match linked.procedure(a.signature.proccode()) {
+                    Some(a) => *a,
+                    None => return Flow::Next,
+                };
+                let count = procedure.signature.argument_ids().len();
+                let args = (0..count)
+                    .map(|i| match a.arguments.get(i) {
+                        Some(arg) => self.input(ctx, t, s, linked, arg),
+                        None => Val::from(""),
+                    })
+                    .collect();
+                let s = &mut thread.state;
+                let warp = procedure.signature.warp() && s.warp == 0;
+                if warp {
+                    s.warp += 1;
+                }
+                s.frames.push(Frame::Call {
+                    args,
+                    names: procedure.signature.argument_names(),
+                    warp,
+                });
+                let first = procedure
+                    .definition
+                    .next
+                    .as_deref()
+                    .and_then(|f| linked.sprite.blocks.get_key_value(f))
+                    .map(|f| f.0.as_str());
+                return Flow::Push(Frame::Stack(first));
+            }
+            _ => {}
+        }
+        Flow::Next
+    }
+}
+
+fn option(option: &Option<impl std::fmt::Display>) -> Val {
+    match option {
+        Some(a) => Val::from(a.to_string()),
+        None => Val::from(""),
+    }
+}
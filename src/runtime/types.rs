@@ -0,0 +1,442 @@
+//! Works out what kind of values each variable, list and custom block
+//! argument can ever hold.
+//!
+//! Scratch converts between numbers and strings whenever it has to, and that
+//! is a lot of what running a project costs. Most variables only ever hold
+//! numbers though, and a backend that knows which ones could keep them as
+//! plain f64s. None of the backends use this yet, it is only reported by
+//! the types command.
+//!
+//! Everything starts out unused, and every write (initial values, set,
+//! change, list blocks and custom block calls) widens the type of what it
+//! writes to, until nothing changes anymore. Strings that read as numbers
+//! count as numbers, like the "1.5" saved for a decimal; other ones are mixed.
+use std::{collections::HashSet, fmt::Display};
+
+use crate::{
+    blocks::*,
+    decomp::Project,
+    script::{Input, Linked, Stack},
+};
+
+use super::{
+    ir::{literal, name_of, Program},
+    value::Val,
+    world::Slot,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    /// Never written to, at least not by anything that runs.
+    Unused,
+    Number,
+    Boolean,
+    Mixed,
+}
+
+impl Type {
+    pub fn of(val: &Val) -> Type {
+        match val {
+            Val::Num(_) => Type::Number,
+            Val::Bool(_) => Type::Boolean,
+            Val::Str(_) => match val.is_numeric() {
+                true => Type::Number,
+                false => Type::Mixed,
+            },
+        }
+    }
+
+    /// The type of something that can be either.
+    pub fn join(self, other: Type) -> Type {
+        match (self, other) {
+            (Type::Unused, a) | (a, Type::Unused) => a,
+            (a, b) if a == b => a,
+            _ => Type::Mixed,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Type::Unused => "unused",
+            Type::Number => "number",
+            Type::Boolean => "boolean",
+            Type::Mixed => "mixed",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Types {
+    /// Per sprite, in the same order as its variables in Program::targets.
+    pub variables: Vec<Vec<Type>>,
+    /// Same for lists, by the type of their items.
+    pub lists: Vec<Vec<Type>>,
+    /// Per custom block, in the same order as Program::procedures.
+    pub args: Vec<Vec<Type>>,
+    stage: usize,
+}
+
+impl Types {
+    pub fn infer(project: &Project, program: &Program) -> Types {
+        let mut types = Types {
+            variables: program
+                .targets
+                .iter()
+                .map(|f| f.variables.iter().map(Type::of).collect())
+                .collect(),
+            lists: program
+                .targets
+                .iter()
+                .map(|f| {
+                    f.lists
+                        .iter()
+                        .map(|f| f.iter().fold(Type::Unused, |a, b| a.join(Type::of(b))))
+                        .collect()
+                })
+                .collect(),
+            args: program
+                .procedures
+                .iter()
+                .map(|f| vec![Type::Unused; f.args])
+                .collect(),
+            stage: program.stage,
+        };
+        let linked: Vec<_> = project.sprites().iter().map(Linked::new).collect();
+        loop {
+            let before = types.clone();
+            for (sprite, linked) in linked.iter().enumerate() {
+                let mut infer = Infer {
+                    types: &mut types,
+                    program,
+                    linked,
+                    sprite,
+                    procedure: None,
+                    path: HashSet::new(),
+                };
+                for script in &linked.scripts {
+                    infer.stack(linked.body(script));
+                }
+                let mut procedures: Vec<_> = linked.procedures.values().collect();
+                procedures.sort_by_key(|f| f.id);
+                for procedure in procedures {
+                    infer.procedure = program.procedures.iter().position(|f| {
+                        f.sprite == sprite && f.proccode == procedure.signature.proccode()
+                    });
+                    infer.stack(linked.stack(procedure.definition.next.as_deref()));
+                }
+            }
+            if types == before {
+                return types;
+            }
+        }
+    }
+
+    pub fn variable(&self, sprite: usize, slot: Slot) -> Type {
+        match slot {
+            Slot::Local(a) => self.variables[sprite][a],
+            Slot::Global(a) => self.variables[self.stage][a],
+        }
+    }
+
+    pub fn list(&self, sprite: usize, slot: Slot) -> Type {
+        match slot {
+            Slot::Local(a) => self.lists[sprite][a],
+            Slot::Global(a) => self.lists[self.stage][a],
+        }
+    }
+
+    /// Lists what was inferred, sprite by sprite.
+    pub fn report(&self, program: &Program) -> String {
+        let mut out = String::new();
+        for (sprite, target) in program.targets.iter().enumerate() {
+            out += &format!("{}\n", target.name);
+            for (name, ty) in target.variable_names.iter().zip(&self.variables[sprite]) {
+                out += &format!("  variable {}: {}\n", name, ty);
+            }
+            for (name, ty) in target.list_names.iter().zip(&self.lists[sprite]) {
+                out += &format!("  list {}: {}\n", name, ty);
+            }
+            for (info, args) in program.procedures.iter().zip(&self.args) {
+                if info.sprite != sprite {
+                    continue;
+                }
+                for (i, ty) in args.iter().enumerate() {
+                    out += &format!("  argument {} of \"{}\": {}\n", i + 1, info.proccode, ty);
+                }
+            }
+        }
+        out
+    }
+}
+
+struct Infer<'a, 'p> {
+    types: &'p mut Types,
+    program: &'p Program,
+    linked: &'p Linked<'a>,
+    sprite: usize,
+    /// The custom block being looked at, if any.
+    procedure: Option<usize>,
+    /// The blocks that the one being looked at is inside of.
+    path: HashSet<&'a str>,
+}
+
+impl<'a, 'p> Infer<'a, 'p> {
+    fn variable(&mut self, name: &Value) -> Option<&mut Type> {
+        let slot = self.program.variable(self.sprite, &name_of(name))?;
+        Some(match slot {
+            Slot::Local(a) => &mut self.types.variables[self.sprite][a],
+            Slot::Global(a) => &mut self.types.variables[self.types.stage][a],
+        })
+    }
+
+    fn list(&mut self, name: &Value) -> Option<&mut Type> {
+        let slot = self.program.list(self.sprite, &name_of(name))?;
+        Some(match slot {
+            Slot::Local(a) => &mut self.types.lists[self.sprite][a],
+            Slot::Global(a) => &mut self.types.lists[self.types.stage][a],
+        })
+    }
+
+    fn write_variable(&mut self, name: &Value, ty: Type) {
+        if let Some(a) = self.variable(name) {
+            *a = a.join(ty);
+        }
+    }
+
+    fn write_list(&mut self, name: &Value, ty: Type) {
+        if let Some(a) = self.list(name) {
+            *a = a.join(ty);
+        }
+    }
+
+    fn stack(&mut self, stack: Stack<'a>) {
+        let mut entered = Vec::new();
+        for (id, block) in stack {
+            if !self.path.insert(id) {
+                break;
+            }
+            entered.push(id);
+            self.statement(block);
+        }
+        for id in entered {
+            self.path.remove(id);
+        }
+    }
+
+    fn substack(&mut self, val: &'a Option<Value>) {
+        let stack = self.linked.substack(val);
+        self.stack(stack);
+    }
+
+    fn statement(&mut self, block: &'a BlockType) {
+        match block {
+            BlockType::Repeat(a) => self.substack(&a.substack),
+            BlockType::Forever(a) => self.substack(&a.substack),
+            BlockType::RepeatUntil(a) => self.substack(&a.substack),
+            BlockType::IfThen(a) => self.substack(&a.then),
+            BlockType::IfThenElse(a) => {
+                self.substack(&a.then);
+                self.substack(&a.otherwise);
+            }
+            BlockType::DataSetVariableTo(a) => {
+                let ty = self.value(&a.value);
+                self.write_variable(&a.variable, ty);
+            }
+            BlockType::DataChangeVariableBy(a) => self.write_variable(&a.variable, Type::Number),
+            BlockType::DataAddToList(a) => {
+                let ty = self.value(&a.item);
+                self.write_list(&a.list, ty);
+            }
+            BlockType::DataInsertAtList(a) => {
+                let ty = self.value(&a.item);
+                self.write_list(&a.list, ty);
+            }
+            BlockType::DataReplaceItemOfList(a) => {
+                let ty = self.value(&a.item);
+                self.write_list(&a.list, ty);
+            }
+            BlockType::ProceduresCall(a) => {
+                let index =
+                    match self.program.procedures.iter().position(|f| {
+                        f.sprite == self.sprite && f.proccode == a.signature.proccode()
+                    }) {
+                        Some(a) => a,
+                        None => return,
+                    };
+                for i in 0..self.types.args[index].len() {
+                    let ty = match a.arguments.get(i) {
+                        Some(arg) => self.input(arg),
+                        None => Type::Mixed,
+                    };
+                    let arg = &mut self.types.args[index][i];
+                    *arg = arg.join(ty);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, val: &'a Option<Value>) -> Type {
+        let input = self.linked.input(val);
+        self.expr(input)
+    }
+
+    fn value(&mut self, val: &'a Value) -> Type {
+        let input = self.linked.value(val);
+        self.expr(input)
+    }
+
+    fn expr(&mut self, input: Input<'a>) -> Type {
+        match input {
+            Input::Literal(a) => Type::of(&literal(a)),
+            Input::Variable(a) => self
+                .variable(&Value::String(a.to_string()))
+                .map(|f| *f)
+                .unwrap_or(Type::Mixed),
+            Input::Block(id, block) => match self.path.insert(id) {
+                true => {
+                    let ty = self.reporter(block);
+                    self.path.remove(id);
+                    ty
+                }
+                false => Type::Mixed,
+            },
+            Input::Empty | Input::List(_) => Type::Mixed,
+        }
+    }
+
+    /// The type a reporter gives back, as it is run by ir and treewalk.
+    fn reporter(&mut self, block: &'a BlockType) -> Type {
+        match block {
+            BlockType::Add(_)
+            | BlockType::Sub(_)
+            | BlockType::Mul(_)
+            | BlockType::Divide(_)
+            | BlockType::Modulo(_)
+            | BlockType::LengthOf(_)
+            | BlockType::Round(_)
+            | BlockType::MathOp(_)
+            | BlockType::PickRandom(_)
+            | BlockType::XPosition(_)
+            | BlockType::YPosition(_)
+            | BlockType::Direction(_)
+            | BlockType::Size(_)
+            | BlockType::Volume(_)
+            | BlockType::Costume(Costume::ByNumber(_))
+            | BlockType::Backdrop(Backdrop::ByNumber(_))
+            | BlockType::MouseX(_)
+            | BlockType::MouseY(_)
+            | BlockType::Timer(_)
+            | BlockType::DaysSince2000(_)
+            | BlockType::Loudness(_)
+            | BlockType::CurrentTime(_)
+            | BlockType::DistanceTo(_)
+            | BlockType::DataLengthOfList(_) => Type::Number,
+
+            BlockType::GreaterThen(_)
+            | BlockType::LesserThen(_)
+            | BlockType::EqualTo(_)
+            | BlockType::And(_)
+            | BlockType::Or(_)
+            | BlockType::Not(_)
+            | BlockType::Contains(_)
+            | BlockType::KeyPressed(_)
+            | BlockType::Touching(_)
+            | BlockType::TouchingColor(_)
+            | BlockType::ColorTouchingColor(_)
+            | BlockType::MouseDown(_)
+            | BlockType::DataListContainsItem(_) => Type::Boolean,
+
+            BlockType::DataGetVariable(a) => self
+                .variable(&a.variable)
+                .map(|f| *f)
+                .unwrap_or(Type::Mixed),
+            BlockType::ArgumentReporter(a) => {
+                let procedure = self.procedure.and_then(|f| {
+                    let info = &self.program.procedures[f];
+                    let procedure = self.linked.procedure(&info.proccode)?;
+                    let i = procedure
+                        .signature
+                        .argument_names()
+                        .iter()
+                        .position(|g| *g == a.name)?;
+                    Some((f, i))
+                });
+                match procedure {
+                    Some((f, i)) => self.types.args[f][i],
+                    // used outside of its definition.
+                    None if a.boolean => Type::Boolean,
+                    None => Type::Number,
+                }
+            }
+            // item of list gives back "" when out of range.
+            _ => Type::Mixed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decomp::LoadOptions;
+    use serde_json::json;
+
+    #[test]
+    fn widens_what_is_written() {
+        let sb2 = json!({
+            "objName": "Stage",
+            "variables": [
+                {"name": "count", "value": 0},
+                {"name": "copy", "value": 0},
+                {"name": "mixed", "value": 0},
+                {"name": "name", "value": "cat"},
+                {"name": "decimal", "value": "1.5"},
+            ],
+            "lists": [
+                {"listName": "numbers", "contents": [1, 2]},
+                {"listName": "booleans", "contents": []},
+                {"listName": "things", "contents": [1]},
+            ],
+            "scripts": [
+                [0, 0, [
+                    ["whenGreenFlag"],
+                    ["changeVar:by:", "count", 1],
+                    ["setVar:to:", "copy", ["readVariable", "count"]],
+                    ["setVar:to:", "mixed", ["mousePressed"]],
+                    ["setVar:to:", "name", 5],
+                    ["setVar:to:", "decimal", "-3"],
+                    ["append:toList:", 3, "numbers"],
+                    ["append:toList:", ["mousePressed"], "booleans"],
+                    ["append:toList:", "a", "things"],
+                    ["call", "hop %n %b %s %n", 5, ["mousePressed"], "up", ["readVariable", "count"]],
+                    ["call", "hop %n %b %s %n", ["readVariable", "count"], ["mousePressed"], 1, "x"],
+                ]],
+                [0, 200, [
+                    ["procDef", "hop %n %b %s %n", ["a", "b", "c", "d"], [1, false, "", 1], false],
+                    ["setVar:to:", "copy", ["getParam", "d", "r"]],
+                ]],
+            ],
+        });
+        let project = Project::from_sb2(&sb2.to_string(), LoadOptions::default()).unwrap();
+        let program = Program::compile(&project);
+        let types = Types::infer(&project, &program);
+        assert_eq!(
+            types.report(&program),
+            "Stage\n  \
+              variable copy: mixed\n  \
+              variable count: number\n  \
+              variable decimal: number\n  \
+              variable mixed: mixed\n  \
+              variable name: mixed\n  \
+              list booleans: boolean\n  \
+              list numbers: number\n  \
+              list things: mixed\n  \
+              argument 1 of \"hop %n %b %s %n\": number\n  \
+              argument 2 of \"hop %n %b %s %n\": boolean\n  \
+              argument 3 of \"hop %n %b %s %n\": mixed\n  \
+              argument 4 of \"hop %n %b %s %n\": mixed\n"
+        );
+    }
+}
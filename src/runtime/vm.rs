@@ -0,0 +1,579 @@
+//! The interpreter for compiled scripts.
+use super::{
+    ir::{Op, Program, StopKind},
+    ops, poll,
+    value::Val,
+    Backend, Ctx, Hat, Status, Thread, WARP_BUDGET,
+};
+
+pub struct Vm {
+    pub program: Program,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VmState {
+    pc: usize,
+    stack: Vec<Val>,
+    frames: Vec<Frame>,
+    /// How many of the custom blocks being run are "run without screen refresh".
+    warp: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    ret: usize,
+    args: Vec<Val>,
+    /// Stack height when the call started, so that stopping a custom block in
+    /// the middle of a loop doesn't leave its counter behind.
+    base: usize,
+    warp: bool,
+}
+
+impl Vm {
+    pub fn new(program: Program) -> Vm {
+        Vm { program }
+    }
+}
+
+fn pop(stack: &mut Vec<Val>) -> Val {
+    stack.pop().unwrap_or_default()
+}
+
+impl Backend for Vm {
+    type State = VmState;
+
+    fn hats(&self) -> &[Hat] {
+        &self.program.hats
+    }
+
+    fn start(&self, hat: usize) -> VmState {
+        VmState {
+            pc: self.program.entries[hat],
+            ..Default::default()
+        }
+    }
+
+    fn step(&self, ctx: &mut Ctx, thread: &mut Thread<VmState>) -> Status {
+        if !poll(ctx, thread) {
+            return Status::Wait;
+        }
+        let code = &self.program.code;
+        let t = thread.target;
+        let id = thread.id;
+        let s = &mut thread.state;
+        let mut budget = WARP_BUDGET;
+        loop {
+            let op = &code[s.pc];
+            s.pc += 1;
+            match op {
+                Op::Const(a) => s.stack.push(a.clone()),
+                Op::Pop => {
+                    s.stack.pop();
+                }
+                Op::Binary(op) => {
+                    let b = pop(&mut s.stack);
+                    let a = pop(&mut s.stack);
+                    s.stack.push(ops::binary(*op, &a, &b));
+                }
+                Op::Unary(op) => {
+                    let a = pop(&mut s.stack);
+                    s.stack.push(ops::unary(*op, &a));
+                }
+                Op::Random => {
+                    let b = pop(&mut s.stack);
+                    let a = pop(&mut s.stack);
+                    s.stack.push(Val::Num(ctx.world.rng.pick(&a, &b)));
+                }
+
+                Op::Var(slot) => s.stack.push(ctx.world.var(t, *slot).clone()),
+                Op::SetVar(slot) => {
+                    let val = pop(&mut s.stack);
+                    ctx.world.set_var(t, *slot, val);
+                }
+                Op::ChangeVar(slot) => {
+                    let by = pop(&mut s.stack).num();
+                    ctx.world.change_var(t, *slot, by);
+                }
+                Op::ListContents(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    s.stack.push(ctx.world.list_contents(target, i));
+                }
+                Op::ListAdd(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    let item = pop(&mut s.stack);
+                    ctx.world.add_to_list(target, i, item);
+                }
+                Op::ListDelete(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    let index = pop(&mut s.stack);
+                    ctx.world.delete_of_list(target, i, &index);
+                }
+                Op::ListDeleteAll(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    ctx.world.delete_all_of_list(target, i);
+                }
+                Op::ListInsert(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    let index = pop(&mut s.stack);
+                    let item = pop(&mut s.stack);
+                    ctx.world.insert_at_list(target, i, &index, item);
+                }
+                Op::ListReplace(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    let item = pop(&mut s.stack);
+                    let index = pop(&mut s.stack);
+                    ctx.world.replace_item_of_list(target, i, &index, item);
+                }
+                Op::ListItem(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    let index = pop(&mut s.stack);
+                    s.stack.push(ctx.world.item_of_list(target, i, &index));
+                }
+                Op::ListLength(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    s.stack
+                        .push(Val::Num(ctx.world.list(target, i).len() as f64));
+                }
+                Op::ListContains(slot) => {
+                    let (target, i) = ctx.world.resolve(t, *slot);
+                    let item = pop(&mut s.stack);
+                    s.stack
+                        .push(Val::Bool(ctx.world.list_contains(target, i, &item)));
+                }
+                Op::Arg(i) => {
+                    let arg = s
+                        .frames
+                        .last()
+                        .and_then(|f| f.args.get(*i))
+                        .cloned()
+                        .unwrap_or_default();
+                    s.stack.push(arg);
+                }
+
+                Op::Jump(to) => s.pc = *to,
+                Op::JumpIfFalse(to) => {
+                    if !pop(&mut s.stack).bool() {
+                        s.pc = *to;
+                    }
+                }
+                Op::JumpIfTrue(to) => {
+                    if pop(&mut s.stack).bool() {
+                        s.pc = *to;
+                    }
+                }
+                Op::RepeatInit => {
+                    let times = pop(&mut s.stack).num().round();
+                    s.stack.push(Val::Num(times));
+                }
+                Op::RepeatLoop(to) => match s.stack.last_mut() {
+                    Some(Val::Num(a)) if *a >= 1.0 => *a -= 1.0,
+                    _ => {
+                        s.stack.pop();
+                        s.pc = *to;
+                    }
+                },
+                Op::Yield => {
+                    if s.warp == 0 {
+                        return Status::Yield;
+                    }
+                    budget -= 1;
+                    if budget == 0 {
+                        return Status::Yield;
+                    }
+                }
+                Op::Suspend => return Status::Yield,
+                Op::Call(index) => {
+                    let procedure = &self.program.procedures[*index];
+                    let base = s.stack.len() - procedure.args;
+                    let args = s.stack.split_off(base);
+                    let warp = procedure.warp && s.warp == 0;
+                    s.frames.push(Frame {
+                        ret: s.pc,
+                        args,
+                        base,
+                        warp,
+                    });
+                    if warp {
+                        s.warp += 1;
+                    }
+                    s.pc = procedure.start;
+                }
+                Op::Return => return_from(s),
+                Op::Stop(StopKind::ThisScript) => {
+                    if s.frames.is_empty() {
+                        return Status::Done;
+                    }
+                    return_from(s);
+                }
+                Op::Stop(StopKind::All) => {
+                    ctx.stop_all();
+                    return Status::Done;
+                }
+                Op::Stop(StopKind::OtherScripts) => ctx.stop_others(t, id),
+                Op::End => return Status::Done,
+
+                Op::Wait => {
+                    let secs = pop(&mut s.stack).num();
+                    ctx.wait(&mut thread.pending, secs);
+                    return Status::Wait;
+                }
+                Op::Broadcast => {
+                    let name = pop(&mut s.stack);
+                    ctx.broadcast(&name);
+                }
+                Op::BroadcastAndWait => {
+                    let name = pop(&mut s.stack);
+                    if ctx.broadcast_and_wait(&mut thread.pending, &name) {
+                        return Status::Wait;
+                    }
+                }
+                Op::CreateClone => {
+                    let of = pop(&mut s.stack);
+                    ctx.create_clone(t, &of);
+                }
+                Op::DeleteClone => {
+                    if ctx.delete_clone(t) {
+                        return Status::Done;
+                    }
+                }
+
+                Op::Move => {
+                    let steps = pop(&mut s.stack).num();
+                    ctx.world.move_steps(t, steps);
+                }
+                Op::Turn => {
+                    let degrees = pop(&mut s.stack).num();
+                    ctx.world.turn(t, degrees);
+                }
+                Op::GotoXY => {
+                    let y = pop(&mut s.stack).num();
+                    let x = pop(&mut s.stack).num();
+                    ctx.world.set_xy(t, x, y);
+                }
+                Op::Goto => {
+                    let to = pop(&mut s.stack);
+                    ctx.world.goto(t, &to);
+                }
+                Op::GlideXY | Op::GlideTo => {
+                    let to = match op {
+                        Op::GlideXY => {
+                            let y = pop(&mut s.stack).num();
+                            let x = pop(&mut s.stack).num();
+                            Some((x, y))
+                        }
+                        _ => {
+                            let to = pop(&mut s.stack);
+                            ctx.world.position_of(&to)
+                        }
+                    };
+                    let secs = pop(&mut s.stack).num();
+                    if ctx.glide(t, &mut thread.pending, secs, to) {
+                        return Status::Wait;
+                    }
+                }
+                Op::PointDirection => {
+                    let direction = pop(&mut s.stack).num();
+                    ctx.world.set_direction(t, direction);
+                }
+                Op::PointTowards => {
+                    let to = pop(&mut s.stack);
+                    ctx.world.point_towards(t, &to);
+                }
+                Op::ChangeX => {
+                    let by = pop(&mut s.stack).num();
+                    let target = ctx.world.target(t);
+                    let (x, y) = (target.x + by, target.y);
+                    ctx.world.set_xy(t, x, y);
+                }
+                Op::SetX => {
+                    let x = pop(&mut s.stack).num();
+                    let y = ctx.world.target(t).y;
+                    ctx.world.set_xy(t, x, y);
+                }
+                Op::ChangeY => {
+                    let by = pop(&mut s.stack).num();
+                    let target = ctx.world.target(t);
+                    let (x, y) = (target.x, target.y + by);
+                    ctx.world.set_xy(t, x, y);
+                }
+                Op::SetY => {
+                    let y = pop(&mut s.stack).num();
+                    let x = ctx.world.target(t).x;
+                    ctx.world.set_xy(t, x, y);
+                }
+                Op::Bounce => ctx.world.bounce(t),
+                Op::SetRotationStyle(style) => ctx.world.set_rotation_style(t, *style),
+                Op::XPosition => s.stack.push(Val::Num(ctx.world.target(t).x)),
+                Op::YPosition => s.stack.push(Val::Num(ctx.world.target(t).y)),
+                Op::Direction => s.stack.push(Val::Num(ctx.world.target(t).direction)),
+
+                Op::Say(think) => {
+                    let message = pop(&mut s.stack);
+                    ctx.world.say(t, &message, *think);
+                }
+                Op::SayFor(think) => {
+                    let secs = pop(&mut s.stack).num();
+                    let message = pop(&mut s.stack);
+                    ctx.say_for(t, &mut thread.pending, &message, secs, *think);
+                    return Status::Wait;
+                }
+                Op::SwitchCostume => {
+                    let costume = pop(&mut s.stack);
+                    ctx.world.switch_costume(t, &costume);
+                }
+                Op::NextCostume => ctx.world.next_costume(t),
+                Op::SwitchBackdrop => {
+                    let backdrop = pop(&mut s.stack);
+                    ctx.switch_backdrop(&backdrop);
+                }
+                Op::SwitchBackdropAndWait => {
+                    let backdrop = pop(&mut s.stack);
+                    if ctx.switch_backdrop_and_wait(&mut thread.pending, &backdrop) {
+                        return Status::Wait;
+                    }
+                }
+                Op::NextBackdrop => {
+                    ctx.switch_backdrop(&Val::from("next backdrop"));
+                }
+                Op::ChangeEffect(effect) => {
+                    let by = pop(&mut s.stack).num();
+                    ctx.world.change_effect(t, effect, by);
+                }
+                Op::SetEffect(effect) => {
+                    let to = pop(&mut s.stack).num();
+                    ctx.world.set_effect(t, effect, to);
+                }
+                Op::ClearEffects => ctx.world.clear_effects(t),
+                Op::ChangeSize => {
+                    let by = pop(&mut s.stack).num();
+                    let size = ctx.world.target(t).size + by;
+                    ctx.world.set_size(t, size);
+                }
+                Op::SetSize => {
+                    let size = pop(&mut s.stack).num();
+                    ctx.world.set_size(t, size);
+                }
+                Op::Show => ctx.world.set_visible(t, true),
+                Op::Hide => ctx.world.set_visible(t, false),
+                Op::GotoLayer(front) => ctx.world.goto_layer(t, *front),
+                Op::ChangeLayer(forward) => {
+                    let by = pop(&mut s.stack).num() as i64;
+                    ctx.world.change_layer(t, if *forward { by } else { -by });
+                }
+                Op::CostumeNumber => s
+                    .stack
+                    .push(Val::Num(ctx.world.target(t).costume as f64 + 1.0)),
+                Op::CostumeName => s.stack.push(Val::from(ctx.world.costume_name(t))),
+                Op::BackdropNumber => {
+                    let stage = ctx.world.stage;
+                    s.stack
+                        .push(Val::Num(ctx.world.target(stage).costume as f64 + 1.0))
+                }
+                Op::BackdropName => {
+                    let stage = ctx.world.stage;
+                    s.stack.push(Val::from(ctx.world.costume_name(stage)))
+                }
+                Op::Size => s.stack.push(Val::Num(ctx.world.target(t).size)),
+
+                Op::PlaySoundUntilDone => {
+                    let sound = pop(&mut s.stack);
+                    if ctx.play_sound_until_done(t, &mut thread.pending, &sound) {
+                        return Status::Wait;
+                    }
+                }
+                Op::ChangeVolume => {
+                    let by = pop(&mut s.stack).num();
+                    let volume = ctx.world.target(t).volume + by;
+                    ctx.world.set_volume(t, volume);
+                }
+                Op::SetVolume => {
+                    let volume = pop(&mut s.stack).num();
+                    ctx.world.set_volume(t, volume);
+                }
+                Op::Volume => s.stack.push(Val::Num(ctx.world.target(t).volume)),
+
+                Op::Touching => {
+                    let what = pop(&mut s.stack);
+                    s.stack.push(Val::Bool(ctx.world.touching(t, &what)));
+                }
+                Op::DistanceTo => {
+                    let to = pop(&mut s.stack);
+                    s.stack.push(Val::Num(ctx.world.distance_to(t, &to)));
+                }
+                Op::Ask => {
+                    pop(&mut s.stack);
+                    ctx.world.ask();
+                }
+                Op::Answer => s.stack.push(Val::from(ctx.world.answer.as_str())),
+                Op::KeyPressed => {
+                    let key = pop(&mut s.stack);
+                    s.stack.push(Val::Bool(ctx.world.key_pressed(&key)));
+                }
+                Op::MouseDown => s.stack.push(Val::Bool(ctx.world.mouse_down)),
+                Op::MouseX => s.stack.push(Val::Num(ctx.world.mouse.0)),
+                Op::MouseY => s.stack.push(Val::Num(ctx.world.mouse.1)),
+                Op::SetDraggable(a) => ctx.world.target_mut(t).draggable = *a,
+                Op::Timer => s.stack.push(Val::Num(ctx.world.timer())),
+                Op::ResetTimer => ctx.world.reset_timer(),
+                Op::Current(a) => s.stack.push(Val::Num(ops::current(*a))),
+                Op::DaysSince2000 => s.stack.push(Val::Num(ctx.world.days_since_2000())),
+                Op::Username => s.stack.push(Val::from(ctx.world.username.as_str())),
+            }
+        }
+    }
+}
+
+fn return_from(s: &mut VmState) {
+    if let Some(frame) = s.frames.pop() {
+        s.stack.truncate(frame.base);
+        s.pc = frame.ret;
+        if frame.warp {
+            s.warp -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        decomp::{LoadOptions, Project},
+        runtime::{treewalk::TreeWalker, world::World, HatKind, Options, Runtime},
+        scratchblocks::add,
+        test_util::test_project,
+    };
+
+    /// A stage and a sprite, with these scripts.
+    fn project(stage: &str, sprite: &str) -> Project {
+        let json = json!({"targets": [
+            {"isStage": true, "name": "Stage", "variables": {"total-id": ["total", 0]},
+                "lists": {"seen-id": ["seen", []]}},
+            {"isStage": false, "name": "Cat", "variables": {"n-id": ["n", 0]}, "visible": true},
+        ]});
+        let mut project =
+            Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        add(&mut project.sprites_mut()[0], stage).unwrap();
+        add(&mut project.sprites_mut()[1], sprite).unwrap();
+        project
+    }
+
+    fn run_with<B: Backend>(backend: B, program: &Program, frames: u64) -> World {
+        let world = World::new(&program.targets, Options::default().seed);
+        let mut runtime = Runtime::new(backend, world, Options::default());
+        runtime.green_flag();
+        runtime.run(frames);
+        runtime.world().clone()
+    }
+
+    /// Runs the project with both backends, checks they did the same and
+    /// gives back the transcript.
+    fn run(project: &Project, frames: u64) -> String {
+        let program = Program::compile(project);
+        let walked = run_with(TreeWalker::new(project, &program), &program, frames);
+        let compiled = run_with(Vm::new(program.clone()), &program, frames);
+        assert_eq!(walked.transcript(), compiled.transcript());
+        compiled.transcript()
+    }
+
+    #[test]
+    fn runs_loops() {
+        let project = project(
+            "",
+            "when flag clicked\n\
+            set [n v] to (0)\n\
+            repeat (3)\n\
+              change [n v] by (2)\n\
+              add (n) to [seen v]\n\
+            end\n\
+            repeat until <(n) > (9)>\n\
+              change [n v] by (1)\n\
+            end\n\
+            say (join [n=] (n))",
+        );
+        assert_eq!(
+            run(&project, 10),
+            "[0.00] Cat says: n=10\n\
+            Stage: total = 0\n\
+            Stage: seen = [2, 4, 6]\n\
+            Cat: n = 10\n"
+        );
+    }
+
+    #[test]
+    fn runs_procedures() {
+        let project = project(
+            "",
+            "define count down from (x)\n\
+            if <(x) > (0)> then\n\
+              add (x) to [seen v]\n\
+              count down from ((x) - (1))\n\
+            end\n\
+            change [n v] by (1)\n\
+            \n\
+            when flag clicked\n\
+            count down from (3)\n\
+            say (n)",
+        );
+        assert_eq!(
+            run(&project, 10),
+            "[0.00] Cat says: 4\n\
+            Stage: total = 0\n\
+            Stage: seen = [3, 2, 1]\n\
+            Cat: n = 4\n"
+        );
+    }
+
+    #[test]
+    fn runs_broadcasts() {
+        let project = project(
+            "when I receive [Ping v]\n\
+            change [total v] by (1)\n\
+            say (total)",
+            "when flag clicked\n\
+            broadcast (ping v) and wait\n\
+            broadcast (PING v) and wait\n\
+            set [n v] to (total)\n\
+            say (join [after ] (n))",
+        );
+        // broadcasts are matched whatever their case.
+        assert_eq!(
+            run(&project, 10),
+            "[0.00] Stage says: 1\n\
+            [0.03] Stage says: 2\n\
+            [0.07] Cat says: after 2\n\
+            Stage: total = 2\n\
+            Stage: seen = []\n\
+            Cat: n = 2\n"
+        );
+    }
+
+    #[test]
+    fn backends_agree() {
+        run(&test_project(), 30);
+    }
+
+    #[test]
+    fn compiles_timer_hats_given_as_text() {
+        let json = json!({"targets": [{"isStage": true, "name": "Stage", "blocks": {
+            "a": {"opcode": "event_whengreaterthan", "next": null, "parent": null,
+                "inputs": {"VALUE": [1, [4, "1.5"]]}, "fields": {"WHENGREATERTHANMENU": ["TIMER", null]},
+                "topLevel": true, "x": 0, "y": 0},
+            "b": {"opcode": "event_whengreaterthan", "next": null, "parent": null,
+                "inputs": {"VALUE": [1, [4, "-3"]]}, "fields": {"WHENGREATERTHANMENU": ["TIMER", null]},
+                "topLevel": true, "x": 0, "y": 0},
+        }}]});
+        let project = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        let mut hats: Vec<_> = Program::compile(&project)
+            .hats
+            .iter()
+            .map(|f| match f.kind {
+                HatKind::TimerGreaterThan(a) => a,
+                _ => panic!("not a timer hat"),
+            })
+            .collect();
+        hats.sort_by(f64::total_cmp);
+        assert_eq!(hats, vec![-3.0, 1.5]);
+    }
+}
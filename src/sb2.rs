@@ -0,0 +1,1022 @@
+//! Scratch 2 projects.
+//!
+//! An .sb2's project.json is one big stage object, with the sprites in its
+//! `children` and every script an `[x, y, [blocks...]]` where each block is
+//! `[opcode, arguments...]`. That's turned into what Scratch 3 would have
+//! saved for it, so it gets read like any other project. Watchers and
+//! script comments aren't brought over.
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    block_defs::custom::{parse_proccode, ProccodeSegment},
+    block_names::*,
+    error::YaseError,
+    serialize::Slot,
+};
+
+/// What an sb2 block's arguments turn into, in order.
+#[derive(Clone, Copy)]
+enum Arg {
+    Input(&'static str, Slot),
+    /// An input holding a dropdown, with the opcode of the menu block.
+    Menu(&'static str, &'static str),
+    Field(&'static str),
+    /// A field Scratch 3 wants in capitals, `day of week` being `DAYOFWEEK`.
+    Upper(&'static str),
+    Variable,
+    List,
+    Broadcast,
+    /// Always set, without taking an argument.
+    Fixed(&'static str, &'static str),
+}
+
+/// The Scratch 3 version of an sb2 opcode. Custom blocks and the few that
+/// depend on their arguments are dealt with by Target::block.
+fn spec(opcode: &str) -> Option<(&'static str, &'static [Arg])> {
+    use Arg::*;
+    use Slot::{Angle, Color, Empty, Integer, Number, Positive, Text, Whole};
+
+    Some(match opcode {
+        "forward:" => (MOTION_MOVE, &[Input("STEPS", Number)]),
+        "turnRight:" => (MOTION_TURN_RIGHT, &[Input("DEGREES", Number)]),
+        "turnLeft:" => (MOTION_TURN_LEFT, &[Input("DEGREES", Number)]),
+        "heading:" => (MOTION_POINT_DIRECTION, &[Input("DIRECTION", Angle)]),
+        "pointTowards:" => (MOTION_POINT_TOWARDS, &[Menu("TOWARDS", MOTION_POINT_MENU)]),
+        "gotoX:y:" => (MOTION_GOTO_XY, &[Input("X", Number), Input("Y", Number)]),
+        "gotoSpriteOrMouse:" => (MOTION_GOTO, &[Menu("TO", MOTION_GOTO_MENU)]),
+        "glideSecs:toX:y:elapsed:from:" => (
+            MOTION_GLIDE_SECONDS_TO_XY,
+            &[
+                Input("SECS", Number),
+                Input("X", Number),
+                Input("Y", Number),
+            ],
+        ),
+        "changeXposBy:" => (MOTION_CHANGE_X_BY, &[Input("DX", Number)]),
+        "xpos:" => (MOTION_SET_X, &[Input("X", Number)]),
+        "changeYposBy:" => (MOTION_CHANGE_Y_BY, &[Input("DY", Number)]),
+        "ypos:" => (MOTION_SET_Y, &[Input("Y", Number)]),
+        "bounceOffEdge" => (MOTION_IF_ON_EDGE_BOUNCE, &[]),
+        "setRotationStyle" => (MOTION_SET_ROTATION_STYLE, &[Field("STYLE")]),
+        "xpos" => (MOTION_XPOSITION, &[]),
+        "ypos" => (MOTION_YPOSITION, &[]),
+        "heading" => (MOTION_DIRECTION, &[]),
+
+        "say:duration:elapsed:from:" => (
+            LOOKS_SAY_FOR_SECS,
+            &[Input("MESSAGE", Text), Input("SECS", Number)],
+        ),
+        "say:" => (LOOKS_SAY, &[Input("MESSAGE", Text)]),
+        "think:duration:elapsed:from:" => (
+            LOOKS_THINK_FOR_SECS,
+            &[Input("MESSAGE", Text), Input("SECS", Number)],
+        ),
+        "think:" => (LOOKS_THINK, &[Input("MESSAGE", Text)]),
+        "show" => (LOOKS_SHOW, &[]),
+        "hide" => (LOOKS_HIDE, &[]),
+        "lookLike:" => (LOOKS_SWITCH_COSTUME_TO, &[Menu("COSTUME", LOOKS_COSTUME)]),
+        "nextCostume" => (LOOKS_NEXT_COSTUME, &[]),
+        "startScene" => (
+            LOOKS_SWITCH_BACKDROP_TO,
+            &[Menu("BACKDROP", LOOKS_BACKDROP)],
+        ),
+        "startSceneAndWait" => (
+            LOOKS_SWITCH_BACKDROP_TO_AND_WAIT,
+            &[Menu("BACKDROP", LOOKS_BACKDROP)],
+        ),
+        "nextScene" => (LOOKS_NEXT_BACKDROP, &[]),
+        "changeGraphicEffect:by:" => (
+            LOOKS_CHANGE_EFFECT_BY,
+            &[Upper("EFFECT"), Input("CHANGE", Number)],
+        ),
+        "setGraphicEffect:to:" => (
+            LOOKS_SET_EFFECT_TO,
+            &[Upper("EFFECT"), Input("VALUE", Number)],
+        ),
+        "filterReset" => (LOOKS_CLEAR_GRAPHICS_EFFECTS, &[]),
+        "changeSizeBy:" => (LOOKS_CHANGE_SIZE_BY, &[Input("CHANGE", Number)]),
+        "setSizeTo:" => (LOOKS_SET_SIZE_TO, &[Input("SIZE", Number)]),
+        "comeToFront" => (LOOKS_GOTO_FRONT_BACK, &[Fixed("FRONT_BACK", "front")]),
+        "goBackByLayers:" => (
+            LOOKS_GO_FORWARD_BACKWARD_LAYERS,
+            &[Fixed("FORWARD_BACKWARD", "backward"), Input("NUM", Integer)],
+        ),
+        "costumeIndex" => (LOOKS_COSTUME_NUMBER_NAME, &[Fixed("NUMBER_NAME", "number")]),
+        "costumeName" => (LOOKS_COSTUME_NUMBER_NAME, &[Fixed("NUMBER_NAME", "name")]),
+        "backgroundIndex" => (
+            LOOKS_BACKDROP_NUMBER_NAME,
+            &[Fixed("NUMBER_NAME", "number")],
+        ),
+        "sceneName" => (LOOKS_BACKDROP_NUMBER_NAME, &[Fixed("NUMBER_NAME", "name")]),
+        "scale" => (LOOKS_SIZE, &[]),
+
+        "playSound:" => (SOUND_PLAY, &[Menu("SOUND_MENU", SOUND_SOUNDS_MENU)]),
+        "doPlaySoundAndWait" => (
+            SOUND_PLAY_UNTIL_DONE,
+            &[Menu("SOUND_MENU", SOUND_SOUNDS_MENU)],
+        ),
+        "stopAllSounds" => (SOUND_STOP_ALL_SOUNDS, &[]),
+        "changeVolumeBy:" => (SOUND_CHANGE_VOLUME_BY, &[Input("VOLUME", Number)]),
+        "setVolumeTo:" => (SOUND_SET_VOLUME_TO, &[Input("VOLUME", Number)]),
+        "volume" => (SOUND_VOLUME, &[]),
+
+        "whenGreenFlag" => (EVENT_WHEN_FLAG_CLICKED, &[]),
+        "whenKeyPressed" => (EVENT_WHEN_KEY_PRESSED, &[Field("KEY_OPTION")]),
+        "whenClicked" => (EVENT_WHEN_THIS_SPRITECLICKED, &[]),
+        "whenSceneStarts" => (EVENT_WHEN_BACKDROP_SWITCHESTO, &[Field("BACKDROP")]),
+        "whenSensorGreaterThan" => (
+            EVENT_WHEN_GREATER_THAN,
+            &[Upper("WHENGREATERTHANMENU"), Input("VALUE", Number)],
+        ),
+        "whenIReceive" => (EVENT_WHEN_BROADCAST_RECEIVED, &[Broadcast]),
+        "broadcast:" => (
+            EVENT_BROADCAST,
+            &[Input("BROADCAST_INPUT", Slot::Broadcast)],
+        ),
+        "doBroadcastAndWait" => (
+            EVENT_BROADCAST_AND_WAIT,
+            &[Input("BROADCAST_INPUT", Slot::Broadcast)],
+        ),
+
+        "wait:elapsed:from:" => (CONTROL_WAIT, &[Input("DURATION", Positive)]),
+        "doRepeat" => (
+            CONTROL_REPEAT,
+            &[Input("TIMES", Whole), Input("SUBSTACK", Empty)],
+        ),
+        "doForever" => (CONTROL_FOREVER, &[Input("SUBSTACK", Empty)]),
+        "doIf" => (
+            CONTROL_IF,
+            &[Input("CONDITION", Empty), Input("SUBSTACK", Empty)],
+        ),
+        "doIfElse" => (
+            CONTROL_IF_ELSE,
+            &[
+                Input("CONDITION", Empty),
+                Input("SUBSTACK", Empty),
+                Input("SUBSTACK2", Empty),
+            ],
+        ),
+        "doWaitUntil" => (CONTROL_WAIT_UNTIL, &[Input("CONDITION", Empty)]),
+        "doUntil" => (
+            CONTROL_REPEAT_UNTIL,
+            &[Input("CONDITION", Empty), Input("SUBSTACK", Empty)],
+        ),
+        "doWhile" => (
+            CONTROL_WHILE,
+            &[Input("CONDITION", Empty), Input("SUBSTACK", Empty)],
+        ),
+        "whenCloned" => (CONTROL_START_AS_CLONE, &[]),
+        "createCloneOf" => (
+            CONTROL_CREATE_CLONE_OF,
+            &[Menu("CLONE_OPTION", CONTROL_CREATE_CLONE_OF_MENU)],
+        ),
+        "deleteClone" => (CONTROL_DELETE_THIS_CLONE, &[]),
+
+        "touching:" => (
+            SENSING_TOUCHING_OBJECT,
+            &[Menu("TOUCHINGOBJECTMENU", SENSING_TOUCHING_OBJECT_MENU)],
+        ),
+        "touchingColor:" => (SENSING_TOUCHING_COLOR, &[Input("COLOR", Color)]),
+        "color:sees:" => (
+            SENSING_COLOR_IS_TOUCHING_COLOR,
+            &[Input("COLOR", Color), Input("COLOR2", Color)],
+        ),
+        "distanceTo:" => (
+            SENSING_DISTANCE_TO,
+            &[Menu("DISTANCETOMENU", SENSING_DISTANCE_TO_MENU)],
+        ),
+        "doAsk" => (SENSING_ASK_AND_WAIT, &[Input("QUESTION", Text)]),
+        "answer" => (SENSING_ANSWER, &[]),
+        "keyPressed:" => (
+            SENSING_KEY_PRESSED,
+            &[Menu("KEY_OPTION", SENSING_KEY_OPTIONS)],
+        ),
+        "mousePressed" => (SENSING_MOUSE_DOWN, &[]),
+        "mouseX" => (SENSING_MOUSE_X, &[]),
+        "mouseY" => (SENSING_MOUSE_Y, &[]),
+        "soundLevel" => (SENSING_LOUDNESS, &[]),
+        "timer" => (SENSING_TIMER, &[]),
+        "timerReset" => (SENSING_RESET_TIMER, &[]),
+        "getAttribute:of:" => (
+            SENSING_OF,
+            &[Field("PROPERTY"), Menu("OBJECT", SENSING_OF_OBJECT_MENU)],
+        ),
+        "timeAndDate" => (SENSING_CURRENT, &[Upper("CURRENTMENU")]),
+        "timestamp" => (SENSING_DAYS_SINCE_2000, &[]),
+        "getUserName" => (SENSING_USERNAME, &[]),
+
+        "+" => (
+            OPERATOR_ADD,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "-" => (
+            OPERATOR_SUBTRACT,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "*" => (
+            OPERATOR_MULTIPLY,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "/" => (
+            OPERATOR_DIVIDE,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "%" => (
+            OPERATOR_MOD,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "randomFrom:to:" => (
+            OPERATOR_RANDOM,
+            &[Input("FROM", Number), Input("TO", Number)],
+        ),
+        "<" => (
+            OPERATOR_LESSER_THEN,
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        "=" => (
+            OPERATOR_EQUALS,
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        ">" => (
+            OPERATOR_GREATER_THEN,
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        "&" => (
+            OPERATOR_AND,
+            &[Input("OPERAND1", Empty), Input("OPERAND2", Empty)],
+        ),
+        "|" => (
+            OPERATOR_OR,
+            &[Input("OPERAND1", Empty), Input("OPERAND2", Empty)],
+        ),
+        "not" => (OPERATOR_NOT, &[Input("OPERAND", Empty)]),
+        "concatenate:with:" => (
+            OPERATOR_JOIN,
+            &[Input("STRING1", Text), Input("STRING2", Text)],
+        ),
+        "letter:of:" => (
+            OPERATOR_LETTER_OF,
+            &[Input("LETTER", Whole), Input("STRING", Text)],
+        ),
+        "stringLength:" => (OPERATOR_LENGTH, &[Input("STRING", Text)]),
+        "rounded" => (OPERATOR_ROUND, &[Input("NUM", Number)]),
+        "computeFunction:of:" => (OPERATOR_MATHOP, &[Field("OPERATOR"), Input("NUM", Number)]),
+
+        "readVariable" => (DATA_VARIABLE, &[Variable]),
+        "setVar:to:" => (DATA_SET_VARIABLE_TO, &[Variable, Input("VALUE", Text)]),
+        "changeVar:by:" => (DATA_CHANGE_VARIABLE_BY, &[Variable, Input("VALUE", Number)]),
+        "showVariable:" => (DATA_SHOW_VARIABLE, &[Variable]),
+        "hideVariable:" => (DATA_HIDE_VARIABLE, &[Variable]),
+        "contentsOfList:" => (DATA_LIST_COTNENTS, &[List]),
+        "append:toList:" => (DATA_ADD_TO_LIST, &[Input("ITEM", Text), List]),
+        "deleteLine:ofList:" => (DATA_DELETE_OF_LIST, &[Input("INDEX", Integer), List]),
+        "insert:at:ofList:" => (
+            DATA_INSERT_AT_LIST,
+            &[Input("ITEM", Text), Input("INDEX", Integer), List],
+        ),
+        "setLine:ofList:to:" => (
+            DATA_REPLACE_ITEM_OF_LIST,
+            &[Input("INDEX", Integer), List, Input("ITEM", Text)],
+        ),
+        "getLine:ofList:" => (DATA_ITEM_OF_LIST, &[Input("INDEX", Integer), List]),
+        "lineCountOfList:" => (DATA_LENGTH_OF_LIST, &[List]),
+        "list:contains:" => (DATA_LIST_CONTAINS_ITEM, &[List, Input("ITEM", Text)]),
+        "showList:" => (DATA_SHOW_LIST, &[List]),
+        "hideList:" => (DATA_HIDE_LIST, &[List]),
+        _ => return None,
+    })
+}
+
+/// Turns a Scratch 2 project.json into a Scratch 3 one.
+pub fn convert(sb2: &Value) -> Result<Value, YaseError> {
+    let invalid = |path: &str, message: &str| YaseError::Json {
+        path: path.to_string(),
+        message: message.to_string(),
+    };
+    let stage = sb2
+        .as_object()
+        .ok_or_else(|| invalid("", "expected an object"))?;
+    let children = match stage.get("children") {
+        Some(Value::Array(a)) => a.as_slice(),
+        Some(_) => return Err(invalid("children", "expected an array")),
+        None => &[],
+    };
+
+    // watchers are in children too, sprites are what has scripts and costumes.
+    let mut sprites: Vec<(usize, &Map<String, Value>)> = children
+        .iter()
+        .enumerate()
+        .filter_map(|(i, f)| Some((i, f.as_object()?)))
+        .filter(|(_, f)| f.contains_key("objName") && f.contains_key("costumes"))
+        .collect();
+
+    let mut broadcasts = BTreeMap::new();
+    let mut stage_target = Target::new(stage, true, None, "");
+    stage_target.scripts(&mut broadcasts)?;
+
+    // children are in layer order, but listed in the order of the sprite
+    // library.
+    let layers: HashMap<usize, usize> = sprites
+        .iter()
+        .enumerate()
+        .map(|(layer, (i, _))| (*i, layer + 1))
+        .collect();
+    sprites.sort_by_key(|(_, f)| {
+        f.get("indexInLibrary")
+            .and_then(Value::as_u64)
+            .unwrap_or(u64::MAX)
+    });
+    let mut targets = Vec::new();
+    for (i, sprite) in sprites {
+        let path = format!("children[{}]", i);
+        let mut target = Target::new(sprite, false, Some(&stage_target), &path);
+        target.scripts(&mut broadcasts)?;
+        targets.push(target.to_json(layers[&i]));
+    }
+
+    stage_target.broadcasts = broadcasts;
+    targets.insert(0, stage_target.to_json(0));
+    Ok(json!({
+        "targets": targets,
+        "monitors": [],
+        "extensions": [],
+    }))
+}
+
+/// A stage or sprite on its way to Scratch 3.
+struct Target<'a> {
+    json: &'a Map<String, Value>,
+    is_stage: bool,
+    /// What ids start with. Variables are told apart by id alone, so the
+    /// sprite's name is in there.
+    prefix: String,
+    /// Where in the sb2 it is, for errors.
+    path: String,
+    /// Ids by name. The stage's are looked up too, for sprites.
+    variables: BTreeMap<String, String>,
+    lists: BTreeMap<String, String>,
+    stage: Option<(BTreeMap<String, String>, BTreeMap<String, String>)>,
+    broadcasts: BTreeMap<String, String>,
+    blocks: Map<String, Value>,
+}
+
+impl<'a> Target<'a> {
+    fn new(
+        json: &'a Map<String, Value>,
+        is_stage: bool,
+        stage: Option<&Target>,
+        path: &str,
+    ) -> Target<'a> {
+        let name = |key: &str| -> Vec<String> {
+            json.get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|f| text(&f[key_name(key)]))
+                .collect()
+        };
+        let prefix = match is_stage {
+            true => "Stage".to_string(),
+            false => text(&json["objName"]),
+        };
+        let mut target = Target {
+            json,
+            is_stage,
+            prefix,
+            path: path.to_string(),
+            variables: BTreeMap::new(),
+            lists: BTreeMap::new(),
+            stage: stage.map(|f| (f.variables.clone(), f.lists.clone())),
+            broadcasts: BTreeMap::new(),
+            blocks: Map::new(),
+        };
+        for name in name("variables") {
+            let id = target.id("variable", &name);
+            target.variables.insert(name, id);
+        }
+        for name in name("lists") {
+            let id = target.id("list", &name);
+            target.lists.insert(name, id);
+        }
+        target
+    }
+
+    fn id(&self, kind: &str, name: &str) -> String {
+        format!("{}-{}-{}", self.prefix, kind, name)
+    }
+
+    fn path(&self, rest: String) -> String {
+        match self.path.is_empty() {
+            true => rest,
+            false => format!("{}.{}", self.path, rest),
+        }
+    }
+
+    fn scripts(&mut self, broadcasts: &mut BTreeMap<String, String>) -> Result<(), YaseError> {
+        let scripts = match self.json.get("scripts") {
+            Some(Value::Array(a)) => a,
+            Some(_) => {
+                return Err(YaseError::Json {
+                    path: self.path("scripts".to_string()),
+                    message: "expected an array".to_string(),
+                })
+            }
+            None => return Ok(()),
+        };
+        for (i, script) in scripts.iter().enumerate() {
+            let path = self.path(format!("scripts[{}]", i));
+            let (x, y, stack) = match script.as_array().map(Vec::as_slice) {
+                Some([x, y, Value::Array(stack)]) => (x, y, stack),
+                _ => {
+                    return Err(YaseError::Json {
+                        path,
+                        message: "expected [x, y, [blocks...]]".to_string(),
+                    })
+                }
+            };
+            let mut ctx = Script {
+                target: self,
+                broadcasts,
+                path,
+            };
+            if let Some(id) = ctx.stack(stack, None)? {
+                let top = &mut self.blocks[&id];
+                top["topLevel"] = json!(true);
+                top["x"] = x.clone();
+                top["y"] = y.clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn to_json(&self, layer: usize) -> Value {
+        let json = self.json;
+        let get = |key: &str| json.get(key).cloned().unwrap_or(Value::Null);
+        let number =
+            |key: &str, default: f64| json.get(key).and_then(Value::as_f64).unwrap_or(default);
+
+        let mut variables = Map::new();
+        for var in json
+            .get("variables")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = text(&var["name"]);
+            let mut value = vec![json!(name), var["value"].clone()];
+            if var["isPersistent"] == json!(true) {
+                value.push(json!(true));
+            }
+            variables.insert(self.variables[&name].clone(), json!(value));
+        }
+        // ones that were used without being there.
+        for (name, id) in &self.variables {
+            variables.entry(id.clone()).or_insert(json!([name, 0]));
+        }
+        let mut lists = Map::new();
+        for list in json
+            .get("lists")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = text(&list["listName"]);
+            let items = match &list["contents"] {
+                Value::Array(a) => a.clone(),
+                _ => Vec::new(),
+            };
+            lists.insert(self.lists[&name].clone(), json!([name, items]));
+        }
+        for (name, id) in &self.lists {
+            lists.entry(id.clone()).or_insert(json!([name, []]));
+        }
+        let broadcasts: Map<String, Value> = self
+            .broadcasts
+            .iter()
+            .map(|(name, id)| (id.clone(), json!(name)))
+            .collect();
+
+        let costumes: Vec<Value> = json
+            .get("costumes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|f| {
+                let (id, format) = asset(&f["baseLayerMD5"]);
+                let mut costume = json!({
+                    "assetId": id,
+                    "name": text(&f["costumeName"]),
+                    "md5ext": text(&f["baseLayerMD5"]),
+                    "dataFormat": format,
+                    "rotationCenterX": f["rotationCenterX"].as_f64().unwrap_or(0.0),
+                    "rotationCenterY": f["rotationCenterY"].as_f64().unwrap_or(0.0),
+                });
+                if format != "svg" {
+                    costume["bitmapResolution"] =
+                        json!(f["bitmapResolution"].as_f64().unwrap_or(1.0));
+                }
+                costume
+            })
+            .collect();
+        let sounds: Vec<Value> = json
+            .get("sounds")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|f| {
+                let (id, format) = asset(&f["md5"]);
+                json!({
+                    "assetId": id,
+                    "name": text(&f["soundName"]),
+                    "dataFormat": format,
+                    "rate": f["rate"].as_f64().unwrap_or(0.0),
+                    "sampleCount": f["sampleCount"].as_f64().unwrap_or(0.0),
+                    "md5ext": text(&f["md5"]),
+                })
+            })
+            .collect();
+
+        let mut target = json!({
+            "isStage": self.is_stage,
+            "name": if self.is_stage { "Stage".to_string() } else { text(&get("objName")) },
+            "variables": variables,
+            "lists": lists,
+            "broadcasts": broadcasts,
+            "blocks": self.blocks,
+            "comments": {},
+            "currentCostume": number("currentCostumeIndex", 0.0),
+            "costumes": costumes,
+            "sounds": sounds,
+            "volume": number("volume", 100.0),
+            "layerOrder": layer,
+        });
+        let rest = match self.is_stage {
+            true => json!({
+                "tempo": number("tempoBPM", 60.0),
+                "videoTransparency": (1.0 - number("videoAlpha", 0.5)) * 100.0,
+                "videoState": "off",
+                "textToSpeechLanguage": null,
+            }),
+            false => json!({
+                "visible": json.get("visible").and_then(Value::as_bool).unwrap_or(true),
+                "x": number("scratchX", 0.0),
+                "y": number("scratchY", 0.0),
+                "size": number("scale", 1.0) * 100.0,
+                "direction": number("direction", 90.0),
+                "draggable": json.get("isDraggable").and_then(Value::as_bool).unwrap_or(false),
+                "rotationStyle": match json.get("rotationStyle").and_then(Value::as_str) {
+                    Some("leftRight") => "left-right",
+                    Some("none") => "don't rotate",
+                    _ => "all around",
+                },
+            }),
+        };
+        for (key, value) in rest.as_object().unwrap() {
+            target[key] = value.clone();
+        }
+        target
+    }
+}
+
+/// What the name of each entry is under.
+fn key_name(key: &str) -> &'static str {
+    match key {
+        "lists" => "listName",
+        _ => "name",
+    }
+}
+
+/// `0a1b.png` is asset `0a1b` in png format.
+fn asset(md5: &Value) -> (String, String) {
+    let md5 = text(md5);
+    match md5.rsplit_once('.') {
+        Some((id, format)) => (id.to_string(), format.to_string()),
+        None => (md5.clone(), String::new()),
+    }
+}
+
+/// Literals as Scratch 3 stores them, which is always as text.
+fn text(val: &Value) -> String {
+    match val {
+        Value::String(a) => a.clone(),
+        Value::Null => String::new(),
+        a => a.to_string(),
+    }
+}
+
+/// Scratch 2 colors are numbers, with the alpha in the top byte.
+fn color(val: &Value) -> String {
+    match val.as_f64() {
+        Some(a) => format!("#{:06x}", (a as i64) & 0xffffff),
+        None => text(val),
+    }
+}
+
+/// One script of a target, being turned into blocks.
+struct Script<'t, 'a> {
+    target: &'t mut Target<'a>,
+    broadcasts: &'t mut BTreeMap<String, String>,
+    path: String,
+}
+
+impl Script<'_, '_> {
+    fn invalid(&self, message: String) -> YaseError {
+        YaseError::Json {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    fn add(&mut self, block: Value) -> String {
+        let id = format!("{}-{}", self.target.prefix, self.target.blocks.len());
+        self.target.blocks.insert(id.clone(), block);
+        id
+    }
+
+    /// Blocks one under another. Gives the id of the first one.
+    fn stack(
+        &mut self,
+        stack: &[Value],
+        parent: Option<&str>,
+    ) -> Result<Option<String>, YaseError> {
+        let mut first = None;
+        let mut prev: Option<String> = parent.map(str::to_string);
+        for (i, block) in stack.iter().enumerate() {
+            let id = self.block(block, prev.as_deref())?;
+            if i == 0 {
+                first = Some(id.clone());
+            } else if let Some(a) = &prev {
+                self.target.blocks[a]["next"] = json!(id);
+            }
+            prev = Some(id);
+        }
+        Ok(first)
+    }
+
+    fn block(&mut self, block: &Value, parent: Option<&str>) -> Result<String, YaseError> {
+        let (opcode, args) = match block.as_array().map(Vec::as_slice) {
+            Some([Value::String(opcode), args @ ..]) => (opcode.as_str(), args),
+            _ => return Err(self.invalid(format!("not a block: {}", block))),
+        };
+        let id = self.add(json!({
+            "opcode": opcode,
+            "next": null,
+            "parent": parent,
+            "inputs": {},
+            "fields": {},
+            "shadow": false,
+            "topLevel": false,
+        }));
+
+        let arg = |i: usize| args.get(i).unwrap_or(&Value::Null);
+        let mut args = args.iter();
+        let (opcode, specs): (&str, &[Arg]) = match opcode {
+            "procDef" => {
+                self.definition(&id, args.as_slice())?;
+                return Ok(id);
+            }
+            "call" => {
+                self.call(&id, args.as_slice())?;
+                return Ok(id);
+            }
+            "getParam" => {
+                let opcode = match arg(1).as_str() {
+                    Some("b") => ARGUMENT_REPORTER_BOOLEAN,
+                    _ => ARGUMENT_REPORTER_STRING_NUMBER,
+                };
+                let block = &mut self.target.blocks[&id];
+                block["opcode"] = json!(opcode);
+                block["fields"]["VALUE"] = json!([text(arg(0)), null]);
+                return Ok(id);
+            }
+            "whenClicked" if self.target.is_stage => (EVENT_WHEN_STAGE_CLICKED, &[]),
+            "deleteLine:ofList:" if arg(0) == "all" => {
+                args.next();
+                (DATA_DELETE_ALL_OF_LIST, &[Arg::List])
+            }
+            "stopScripts" => {
+                let option = text(arg(0));
+                let block = &mut self.target.blocks[&id];
+                block["mutation"] = json!({
+                    "tagName": "mutation",
+                    "children": [],
+                    "hasnext": option.starts_with("other scripts").to_string(),
+                });
+                (CONTROL_STOP, &[Arg::Field("STOP_OPTION")])
+            }
+            a => match spec(a) {
+                Some(a) => a,
+                // left for loading to complain about.
+                None => return Ok(id),
+            },
+        };
+        self.target.blocks[&id]["opcode"] = json!(opcode);
+
+        for spec in specs {
+            let val = match spec {
+                Arg::Fixed(name, value) => {
+                    self.field(&id, name, json!([value, null]));
+                    continue;
+                }
+                _ => args.next().unwrap_or(&Value::Null),
+            };
+            match *spec {
+                Arg::Input(name, slot) => self.input(&id, name, val, slot)?,
+                Arg::Menu(name, menu) => match val {
+                    Value::Array(_) => {
+                        let child = self.block(val, Some(&id))?;
+                        self.target.blocks[&id]["inputs"][name] = json!([2, child]);
+                    }
+                    a => {
+                        let shadow = self.add(json!({
+                            "opcode": menu,
+                            "next": null,
+                            "parent": id,
+                            "inputs": {},
+                            "fields": { name: [text(a), null] },
+                            "shadow": true,
+                            "topLevel": false,
+                        }));
+                        self.target.blocks[&id]["inputs"][name] = json!([1, shadow]);
+                    }
+                },
+                Arg::Field(name) => self.field(&id, name, json!([text(val), null])),
+                Arg::Upper(name) => {
+                    let value = text(val).to_uppercase().replace(' ', "");
+                    self.field(&id, name, json!([value, null]))
+                }
+                Arg::Variable => {
+                    let name = text(val);
+                    let id_of = self.target.variable(&name);
+                    self.field(&id, "VARIABLE", json!([name, id_of]))
+                }
+                Arg::List => {
+                    let name = text(val);
+                    let id_of = self.target.list(&name);
+                    self.field(&id, "LIST", json!([name, id_of]))
+                }
+                Arg::Broadcast => {
+                    let name = text(val);
+                    let id_of = self.broadcast(&name);
+                    self.field(&id, "BROADCAST_OPTION", json!([name, id_of]))
+                }
+                Arg::Fixed(..) => unreachable!(),
+            }
+        }
+        Ok(id)
+    }
+
+    fn field(&mut self, id: &str, name: &str, value: Value) {
+        self.target.blocks[id]["fields"][name] = value;
+    }
+
+    fn input(&mut self, id: &str, name: &str, val: &Value, slot: Slot) -> Result<(), YaseError> {
+        let input = match val {
+            // a stack of blocks, for a substack.
+            Value::Array(a) if a.first().is_some_and(Value::is_array) => {
+                match self.stack(a, Some(id))? {
+                    Some(child) => json!([2, child]),
+                    None => return Ok(()),
+                }
+            }
+            Value::Array(_) => {
+                let child = self.block(val, Some(id))?;
+                match slot {
+                    Slot::Empty => json!([2, child]),
+                    a => json!([3, child, a.shadow()]),
+                }
+            }
+            Value::Null | Value::Bool(false) if matches!(slot, Slot::Empty) => return Ok(()),
+            a => match slot {
+                Slot::Broadcast => {
+                    let name = text(a);
+                    json!([1, [slot.code(), name, self.broadcast(&name)]])
+                }
+                Slot::Color => json!([1, [slot.code(), color(a)]]),
+                _ => json!([1, [slot.code(), text(a)]]),
+            },
+        };
+        self.target.blocks[id]["inputs"][name] = input;
+        Ok(())
+    }
+
+    fn broadcast(&mut self, name: &str) -> String {
+        self.broadcasts
+            .entry(name.to_string())
+            .or_insert_with(|| format!("broadcast-{}", name))
+            .clone()
+    }
+
+    /// Argument ids only have to be the same between a custom block's
+    /// definition and its calls.
+    fn argument_ids(proccode: &str) -> Vec<String> {
+        (0..arguments(proccode).len())
+            .map(|f| format!("input{}", f))
+            .collect()
+    }
+
+    /// `["procDef", proccode, names, defaults, warp]`, which is a definition
+    /// holding a prototype holding a reporter for each argument.
+    fn definition(&mut self, id: &str, args: &[Value]) -> Result<(), YaseError> {
+        let proccode = text(args.first().unwrap_or(&Value::Null));
+        let list = |i: usize| match args.get(i) {
+            Some(Value::Array(a)) => a.clone(),
+            _ => Vec::new(),
+        };
+        let names: Vec<String> = list(1).iter().map(text).collect();
+        let defaults = list(2);
+        let warp = args
+            .get(3)
+            .is_some_and(|f| *f == json!(true) || *f == "true");
+        let ids = Script::argument_ids(&proccode);
+
+        let prototype = self.add(json!({
+            "opcode": PROCEDURES_PROTOTYPE,
+            "next": null,
+            "parent": id,
+            "inputs": {},
+            "fields": {},
+            "shadow": true,
+            "topLevel": false,
+            "mutation": {
+                "tagName": "mutation",
+                "children": [],
+                "proccode": proccode,
+                "argumentids": json!(ids).to_string(),
+                "argumentnames": json!(names).to_string(),
+                "argumentdefaults": json!(defaults).to_string(),
+                "warp": warp.to_string(),
+            },
+        }));
+        for ((arg, name), kind) in ids.iter().zip(&names).zip(arguments(&proccode)) {
+            let opcode = match kind {
+                ProccodeSegment::Boolean => ARGUMENT_REPORTER_BOOLEAN,
+                _ => ARGUMENT_REPORTER_STRING_NUMBER,
+            };
+            let reporter = self.add(json!({
+                "opcode": opcode,
+                "next": null,
+                "parent": prototype,
+                "inputs": {},
+                "fields": { "VALUE": [name, null] },
+                "shadow": true,
+                "topLevel": false,
+            }));
+            self.target.blocks[&prototype]["inputs"][arg] = json!([1, reporter]);
+        }
+
+        let block = &mut self.target.blocks[id];
+        block["opcode"] = json!(PROCEDURES_DEFINITION);
+        block["inputs"]["custom_block"] = json!([1, prototype]);
+        Ok(())
+    }
+
+    /// `["call", proccode, arguments...]`.
+    fn call(&mut self, id: &str, args: &[Value]) -> Result<(), YaseError> {
+        let proccode = text(args.first().unwrap_or(&Value::Null));
+        let ids = Script::argument_ids(&proccode);
+        for ((arg, val), kind) in ids
+            .iter()
+            .zip(args.iter().skip(1).chain(std::iter::repeat(&Value::Null)))
+            .zip(arguments(&proccode))
+        {
+            let slot = match kind {
+                ProccodeSegment::Boolean => Slot::Empty,
+                _ => Slot::Text,
+            };
+            self.input(id, arg, val, slot)?;
+        }
+        let block = &mut self.target.blocks[id];
+        block["opcode"] = json!(PROCEDURES_CALL);
+        block["mutation"] = json!({
+            "tagName": "mutation",
+            "children": [],
+            "proccode": proccode,
+            "argumentids": json!(ids).to_string(),
+            "warp": "false",
+        });
+        Ok(())
+    }
+}
+
+/// The arguments of a custom block, in order.
+fn arguments(proccode: &str) -> Vec<ProccodeSegment> {
+    parse_proccode(proccode)
+        .into_iter()
+        .filter(|f| !matches!(f, ProccodeSegment::Label(_)))
+        .collect()
+}
+
+impl Target<'_> {
+    /// Sprites can use their own variables or the stage's. Any that's nowhere
+    /// is made on the spot, since Scratch 2 would've shown it as 0 too.
+    fn variable(&mut self, name: &str) -> String {
+        if let Some(a) = self.variables.get(name) {
+            return a.clone();
+        }
+        if let Some(a) = self.stage.as_ref().and_then(|f| f.0.get(name)) {
+            return a.clone();
+        }
+        let id = self.id("variable", name);
+        self.variables.insert(name.to_string(), id.clone());
+        id
+    }
+
+    fn list(&mut self, name: &str) -> String {
+        if let Some(a) = self.lists.get(name) {
+            return a.clone();
+        }
+        if let Some(a) = self.stage.as_ref().and_then(|f| f.1.get(name)) {
+            return a.clone();
+        }
+        let id = self.id("list", name);
+        self.lists.insert(name.to_string(), id.clone());
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decomp::{LoadOptions, Project},
+        scratchblocks,
+        test_util::{check_blocks, transcript},
+    };
+    use serde_json::json;
+
+    #[test]
+    fn converts_projects() {
+        let sb2 = json!({
+            "objName": "Stage",
+            "variables": [{"name": "score", "value": 0, "isPersistent": false}],
+            "costumes": [{"costumeName": "backdrop1", "baseLayerMD5": "b.png",
+                "bitmapResolution": 2, "rotationCenterX": 480, "rotationCenterY": 360}],
+            "children": [
+                {"objName": "Cat", "scratchX": 10, "scratchY": -20, "direction": 90,
+                    "scale": 0.5, "indexInLibrary": 1,
+                    "costumes": [{"costumeName": "cat", "baseLayerMD5": "c.svg",
+                        "rotationCenterX": 47, "rotationCenterY": 55}],
+                    "scripts": [
+                        [0, 0, [
+                            ["whenGreenFlag"],
+                            ["forward:", 10],
+                            ["doRepeat", 2, [
+                                ["turnRight:", 15],
+                                ["changeVar:by:", "score", 1],
+                            ]],
+                            ["call", "hop %n", 5],
+                            ["broadcast:", "go"],
+                        ]],
+                        [0, 200, [
+                            ["procDef", "hop %n", ["height"], [1], false],
+                            ["changeYposBy:", ["getParam", "height", "r"]],
+                            ["say:", ["getParam", "height", "r"]],
+                        ]],
+                        [0, 400, [
+                            ["whenIReceive", "go"],
+                            ["say:", ["getAttribute:of:", "x position", "Cat"]],
+                            ["say:", ["readVariable", "score"]],
+                        ]],
+                    ]},
+                {"target": "Cat", "cmd": "getVar:", "param": "score"},
+            ],
+        });
+        let project = Project::from_sb2(&sb2.to_string(), LoadOptions::default()).unwrap();
+        let names: Vec<&str> = project.sprites().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["Stage", "Cat"]);
+        let cat = &project.sprites()[1];
+        assert_eq!(
+            (cat.position_x, cat.position_y, cat.size),
+            (10.0, -20.0, 50.0)
+        );
+        assert_eq!(
+            scratchblocks::sprite(cat).trim_end(),
+            "when flag clicked\n\
+            move (10) steps\n\
+            repeat (2)\n  \
+              turn right (15) degrees\n  \
+              change [score v] by (1)\n\
+            end\n\
+            hop (5)\n\
+            broadcast (go v)\n\
+            \n\
+            when I receive [go v]\n\
+            say ([x position v] of (Cat v))\n\
+            say (score)\n\
+            \n\
+            define hop (height)\n\
+            change y by (height)\n\
+            say (height)"
+        );
+        let json = project.to_json();
+        for target in json["targets"].as_array().unwrap() {
+            check_blocks(target);
+        }
+        assert_eq!(
+            transcript(&project),
+            "[0.07] Cat says: 5\n[0.07] Cat says: 2\nStage: score = 2\n"
+        );
+    }
+}
@@ -0,0 +1,2016 @@
+//! Scripts as scratchblocks text, the syntax the Scratch forums and wiki
+//! write blocks in (`move (10) steps`, `if <...> then`).
+//!
+//! Numbers go in `()`, text in `[]`, booleans in `<>`, and dropdowns end
+//! with ` v`. C blocks have their insides indented and close with `end`.
+//!
+//! Text can be read back in too, so scripts for tests and fixtures don't
+//! have to be written as project.json.
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use lazy_static::lazy_static;
+use serde_json::{json, Map, Value as SerdeValue};
+
+use crate::{
+    block_defs::custom::{parse_proccode, ProccodeSegment},
+    block_names::*,
+    blocks::*,
+    decomp::Sprite,
+    error::YaseError,
+    script::{Input, Linked},
+    serialize::{self, text},
+    visit::Walker,
+};
+
+/// Every script in the sprite, with a blank line between each.
+pub fn sprite(sprite: &Sprite) -> String {
+    let printer = Printer::new(sprite);
+    let scripts: Vec<String> = Walker::new(&sprite.blocks)
+        .tops()
+        .into_iter()
+        .map(|f| printer.script(f))
+        .collect();
+    scripts.join("\n")
+}
+
+/// The stack starting at the given block, down to its last block.
+pub fn script(sprite: &Sprite, id: &str) -> String {
+    Printer::new(sprite).script(id)
+}
+
+/// Every block of the stack starting at the given block, including the ones
+/// inside C blocks, as its id and its own line, in the order they're printed.
+pub fn blocks<'a>(sprite: &'a Sprite, id: &str) -> Vec<(&'a str, String)> {
+    let printer = Printer::new(sprite);
+    let mut out = Vec::new();
+    printer.lines(Some(id), &mut out);
+    out
+}
+
+/// What kind of hole an input is, which is how its literal gets written.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Number,
+    Text,
+    Boolean,
+    /// Broadcasts, and menus that were given a literal.
+    Menu,
+}
+
+pub(crate) struct Printer<'a> {
+    pub(crate) linked: Linked<'a>,
+    /// The blocks that the one being printed is inside of, so that a block
+    /// in its own input or substack isn't printed forever.
+    path: RefCell<HashSet<&'a str>>,
+}
+
+impl<'a> Printer<'a> {
+    pub(crate) fn new(sprite: &'a Sprite) -> Printer<'a> {
+        Printer {
+            linked: Linked::new(sprite),
+            path: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn script(&self, id: &str) -> String {
+        let mut out = String::new();
+        self.stack(Some(id), 0, &mut out);
+        out
+    }
+
+    fn stack(&self, first: Option<&str>, depth: usize, out: &mut String) {
+        let mut entered = Vec::new();
+        for (id, block) in self.linked.stack(first) {
+            if !self.path.borrow_mut().insert(id) {
+                break;
+            }
+            entered.push(id);
+            self.statement(block, depth, out);
+        }
+        for id in entered {
+            self.path.borrow_mut().remove(id);
+        }
+    }
+
+    /// Like stack, a line per block, but without indenting or closing C blocks.
+    fn lines(&self, first: Option<&str>, out: &mut Vec<(&'a str, String)>) {
+        let mut entered = Vec::new();
+        for (id, block) in self.linked.stack(first) {
+            if !self.path.borrow_mut().insert(id) {
+                break;
+            }
+            entered.push(id);
+            out.push((id, self.block(block)));
+            let substacks = match block {
+                BlockType::Repeat(a) => vec![&a.substack],
+                BlockType::Forever(a) => vec![&a.substack],
+                BlockType::RepeatUntil(a) => vec![&a.substack],
+                BlockType::IfThen(a) => vec![&a.then],
+                BlockType::IfThenElse(a) => vec![&a.then, &a.otherwise],
+                _ => vec![],
+            };
+            for val in substacks {
+                if let Input::Block(id, _) = self.linked.input(val) {
+                    self.lines(Some(id), out);
+                }
+            }
+        }
+        for id in entered {
+            self.path.borrow_mut().remove(id);
+        }
+    }
+
+    fn substack(&self, val: &'a Option<Value>, depth: usize, out: &mut String) {
+        if let Input::Block(id, _) = self.linked.input(val) {
+            self.stack(Some(id), depth, out);
+        }
+    }
+
+    /// One block of a stack, and everything inside it if it's a C block.
+    fn statement(&self, block: &'a BlockType, depth: usize, out: &mut String) {
+        let line = |out: &mut String, text: &str| {
+            out.push_str(&"  ".repeat(depth));
+            out.push_str(text);
+            out.push('\n');
+        };
+        line(out, &self.block(block));
+        match block {
+            BlockType::Repeat(a) => self.substack(&a.substack, depth + 1, out),
+            BlockType::Forever(a) => self.substack(&a.substack, depth + 1, out),
+            BlockType::RepeatUntil(a) => self.substack(&a.substack, depth + 1, out),
+            BlockType::IfThen(a) => self.substack(&a.then, depth + 1, out),
+            BlockType::IfThenElse(a) => {
+                self.substack(&a.then, depth + 1, out);
+                line(out, "else");
+                self.substack(&a.otherwise, depth + 1, out);
+            }
+            _ => return,
+        }
+        line(out, "end");
+    }
+
+    /// An input, resolved to whatever's in it.
+    fn input(&self, val: &'a Option<Value>, slot: Slot) -> String {
+        self.resolved(self.linked.input(val), slot)
+    }
+
+    /// Same as input, for the blocks that always have a value.
+    fn value(&self, val: &'a Value, slot: Slot) -> String {
+        self.resolved(self.linked.value(val), slot)
+    }
+
+    fn resolved(&self, input: Input<'a>, slot: Slot) -> String {
+        match (input, slot) {
+            (Input::Block(id, b), _) => {
+                let inserted = self.path.borrow_mut().insert(id);
+                match inserted {
+                    true => {
+                        let text = self.block(b);
+                        self.path.borrow_mut().remove(id);
+                        text
+                    }
+                    // in its own input, which only a broken project has.
+                    false => self.resolved(Input::Empty, slot),
+                }
+            }
+            (Input::Variable(a), _) => format!("({})", escape(a)),
+            (Input::List(a), _) => format!("({} :: list)", escape(a)),
+            (Input::Empty, Slot::Number) => "()".to_string(),
+            (Input::Empty, Slot::Text) => "[]".to_string(),
+            (Input::Empty, Slot::Boolean) => "<>".to_string(),
+            (Input::Empty, Slot::Menu) => "[ v]".to_string(),
+            (Input::Literal(a), Slot::Menu) => menu(&text(a)),
+            (Input::Literal(a), Slot::Number) => {
+                let a = text(a);
+                // anything that isn't a number would be read as a variable.
+                match a.is_empty() || a.trim().parse::<f64>().is_ok() {
+                    true => format!("({})", a),
+                    false => format!("[{}]", escape(&a)),
+                }
+            }
+            (Input::Literal(a), _) => format!("[{}]", escape(&text(a))),
+        }
+    }
+
+    /// A block as it's written on its own line, or inside another block's
+    /// input if it's a reporter. C blocks are only their first line.
+    pub(crate) fn block(&self, block: &'a BlockType) -> String {
+        use Slot::*;
+
+        match block {
+            BlockType::Move(a) => format!("move {} steps", self.input(&a.steps, Number)),
+            BlockType::RotateLeft(a) => {
+                format!("turn left {} degrees", self.input(&a.degrees, Number))
+            }
+            BlockType::RotateRight(a) => {
+                format!("turn right {} degrees", self.input(&a.degrees, Number))
+            }
+            BlockType::Goto(Goto::Pos(a)) => format!(
+                "go to x: {} y: {}",
+                self.input(&a.x, Number),
+                self.input(&a.y, Number)
+            ),
+            BlockType::Goto(Goto::Option(a)) => format!("go to {}", self.input(&a.option, Menu)),
+            BlockType::Goto(Goto::Menu(a)) | BlockType::Glide(Glide::Menu(a)) => {
+                menu(&a.option.as_ref().map(movement).unwrap_or_default())
+            }
+            BlockType::Glide(Glide::Pos(a)) => format!(
+                "glide {} secs to x: {} y: {}",
+                self.input(&a.secs, Number),
+                self.input(&a.x, Number),
+                self.input(&a.y, Number)
+            ),
+            BlockType::Glide(Glide::Option(a)) => format!(
+                "glide {} secs to {}",
+                self.input(&a.secs, Number),
+                self.input(&a.option, Menu)
+            ),
+            BlockType::Point(Point::Direction(a)) => {
+                format!("point in direction {}", self.input(&a.direction, Number))
+            }
+            BlockType::Point(Point::Towards(a)) => {
+                format!("point towards {}", self.input(&a.option, Menu))
+            }
+            BlockType::PointTowardsMenu(a) => {
+                menu(&a.option.as_ref().map(movement).unwrap_or_default())
+            }
+            BlockType::ChangeX(a) => format!("change x by {}", self.input(&a.x, Number)),
+            BlockType::SetX(a) => format!("set x to {}", self.input(&a.x, Number)),
+            BlockType::ChangeY(a) => format!("change y by {}", self.input(&a.y, Number)),
+            BlockType::SetY(a) => format!("set y to {}", self.input(&a.y, Number)),
+            BlockType::IfOnEdgeBounce(_) => "if on edge, bounce".to_string(),
+            BlockType::SetRotationStyle(a) => {
+                format!("set rotation style {}", field(&a.style))
+            }
+            BlockType::XPosition(_) => "(x position)".to_string(),
+            BlockType::YPosition(_) => "(y position)".to_string(),
+            BlockType::Direction(_) => "(direction)".to_string(),
+
+            BlockType::Say(a) => format!(
+                "say {} for {} seconds",
+                self.input(&a.message, Text),
+                self.input(&a.secs, Number)
+            ),
+            BlockType::SayForever(a) => format!("say {}", self.input(&a.message, Text)),
+            BlockType::Think(a) => format!(
+                "think {} for {} seconds",
+                self.input(&a.message, Text),
+                self.input(&a.secs, Number)
+            ),
+            BlockType::ThinkForever(a) => format!("think {}", self.input(&a.message, Text)),
+            BlockType::SwitchCostume(a) => {
+                format!("switch costume to {}", self.input(&a.costume, Menu))
+            }
+            BlockType::NextCostume(_) => "next costume".to_string(),
+            BlockType::SwitchBackdrop(a) => {
+                format!("switch backdrop to {}", self.input(&a.backdrop, Menu))
+            }
+            BlockType::SwitchBackdropAndWait(a) => format!(
+                "switch backdrop to {} and wait",
+                self.input(&a.backdrop, Menu)
+            ),
+            BlockType::NextBackdrop(_) => "next backdrop".to_string(),
+            BlockType::ChangeSize(a) => format!("change size by {}", self.input(&a.units, Number)),
+            BlockType::SetSize(a) => format!("set size to {} %", self.input(&a.percentage, Number)),
+            BlockType::ClearGraphicEffects(_) => "clear graphic effects".to_string(),
+            BlockType::ShowSprite(_) => "show".to_string(),
+            BlockType::HideSprite(_) => "hide".to_string(),
+            BlockType::HideAllSprites(_) => "hide all sprites :: looks".to_string(),
+            BlockType::GotoLayer(a) => format!("go to {} layer", field(&a.option)),
+            BlockType::ChangeLayer(a) => {
+                let direction = match &a.direction {
+                    Some(LayerDirection::Value(a)) => text(a),
+                    Some(a) => a.to_string(),
+                    None => String::new(),
+                };
+                format!(
+                    "go {} {} layers",
+                    menu_field(&direction),
+                    self.input(&a.by, Number)
+                )
+            }
+            BlockType::Costume(Costume::ByNumber(_)) => "(costume [number v])".to_string(),
+            BlockType::Costume(Costume::ByName(_)) => "(costume [name v])".to_string(),
+            BlockType::Costume(Costume::WithName(a))
+            | BlockType::Backdrop(Backdrop::WithName(a)) => {
+                menu(&a.as_ref().map(text).unwrap_or_default())
+            }
+            BlockType::Backdrop(Backdrop::ByNumber(_)) => "(backdrop [number v])".to_string(),
+            BlockType::Backdrop(Backdrop::ByName(_)) => "(backdrop [name v])".to_string(),
+            BlockType::Size(_) => "(size)".to_string(),
+
+            BlockType::PlaySound(a) => format!("start sound {}", self.input(&a.sound, Menu)),
+            BlockType::PlaySoundUntilDone(a) => {
+                format!("play sound {} until done", self.input(&a.sound, Menu))
+            }
+            BlockType::StartSound(a) => {
+                let sound = match self.linked.block(&a.sound) {
+                    Some(b) => self.block(b),
+                    None => menu(&a.sound),
+                };
+                format!("start sound {}", sound)
+            }
+            BlockType::StopAllSounds(_) => "stop all sounds".to_string(),
+            BlockType::ChangeEffectBy(a) => format!(
+                "change {} effect by {}",
+                effect(&a.effect),
+                self.input(&a.units, Number)
+            ),
+            BlockType::SetEffectTo(a) => format!(
+                "set {} effect to {}",
+                effect(&a.effect),
+                self.input(&a.percentage, Number)
+            ),
+            BlockType::ClearSoundEffects(_) => "clear sound effects".to_string(),
+            BlockType::ChangeVolumeBy(a) => {
+                format!("change volume by {}", self.input(&a.units, Number))
+            }
+            BlockType::SetVolumeTo(a) => {
+                format!("set volume to {} %", self.input(&a.percentage, Number))
+            }
+            BlockType::Volume(_) => "(volume)".to_string(),
+            BlockType::SoundSoundsMenu(a) => menu(&a.option.as_ref().map(text).unwrap_or_default()),
+            BlockType::SoundEffectsMenu(a) => menu(&lower(&a.option)),
+
+            BlockType::WhenGreenFlagClicked(_) => "when flag clicked".to_string(),
+            BlockType::WhenKeyPressed(a) => format!("when {} key pressed", field(&a.key)),
+            BlockType::WhenSpriteClicked(_) => "when this sprite clicked".to_string(),
+            BlockType::WhenStageClicked(_) => "when stage clicked".to_string(),
+            BlockType::WhenBackdropSwitchesTo(a) => format!(
+                "when backdrop switches to {}",
+                menu_field(&a.backdrop.as_ref().map(text).unwrap_or_default())
+            ),
+            BlockType::WhenOptionGreaterThen(a) => format!(
+                "when {} > {}",
+                menu_field(&lower(&a.option)),
+                self.input(&a.by, Number)
+            ),
+            BlockType::WhenIRecieveBroadcast(a) => format!(
+                "when I receive {}",
+                menu_field(&a.broadcast.as_ref().map(text).unwrap_or_default())
+            ),
+            BlockType::Broadcast(a) => format!("broadcast {}", self.input(&a.broadcast, Menu)),
+            BlockType::BroadcastAndWait(a) => {
+                format!("broadcast {} and wait", self.input(&a.broadcast, Menu))
+            }
+
+            BlockType::WaitSeconds(a) => format!("wait {} seconds", self.input(&a.seconds, Number)),
+            BlockType::Repeat(a) => format!("repeat {}", self.input(&a.units, Number)),
+            BlockType::Forever(_) => "forever".to_string(),
+            BlockType::IfThen(a) => format!("if {} then", self.input(&a.condition, Boolean)),
+            BlockType::IfThenElse(a) => format!("if {} then", self.input(&a.condition, Boolean)),
+            BlockType::WaitUntil(a) => format!("wait until {}", self.input(&a.condition, Boolean)),
+            BlockType::RepeatUntil(a) => {
+                format!("repeat until {}", self.input(&a.condition, Boolean))
+            }
+            BlockType::StopAll(a) => {
+                let option = match (&a.option, self.linked.sprite.is_stage) {
+                    (Some(StopOption::OtherScriptsInSprite), true) => {
+                        "other scripts in stage".to_string()
+                    }
+                    (Some(a), _) => a.to_string(),
+                    (None, _) => String::new(),
+                };
+                format!("stop {}", menu_field(&option))
+            }
+            BlockType::WhenIStartAsAClone(_) => "when I start as a clone".to_string(),
+            BlockType::CreateCloneOf(a) => format!("create clone of {}", self.input(&a.of, Menu)),
+            BlockType::CreateCloneOfMenu(a) => menu(&match &a.of {
+                Some(SpriteOption::Myself) => "myself".to_string(),
+                Some(a) => a.to_string(),
+                None => String::new(),
+            }),
+            BlockType::DeleteClone(_) => "delete this clone".to_string(),
+
+            BlockType::Touching(a) => format!("<touching {} ?>", self.input(&a.touching, Menu)),
+            BlockType::TouchingMenu(a) => {
+                menu(&a.touching.as_ref().map(sensing).unwrap_or_default())
+            }
+            BlockType::TouchingColor(a) => {
+                format!("<touching color {} ?>", self.input(&a.color, Text))
+            }
+            BlockType::ColorTouchingColor(a) => format!(
+                "<color {} is touching {} ?>",
+                self.input(&a.color1, Text),
+                self.input(&a.color2, Text)
+            ),
+            BlockType::DistanceTo(a) => format!("(distance to {})", self.input(&a.to, Menu)),
+            BlockType::DistanceToMenu(a) => menu(&a.to.as_ref().map(sensing).unwrap_or_default()),
+            BlockType::Of(a) => format!(
+                "({} of {})",
+                menu_field(a.property.as_deref().unwrap_or_default()),
+                self.input(&a.object, Menu)
+            ),
+            BlockType::OfObjectMenu(a) => menu(&match &a.object {
+                Some(OfObject::Stage) => "Stage".to_string(),
+                Some(OfObject::Sprite(a)) => a.clone(),
+                None => String::new(),
+            }),
+            BlockType::AskAndWait(a) => format!("ask {} and wait", self.input(&a.question, Text)),
+            BlockType::Answer(_) => "(answer)".to_string(),
+            BlockType::KeyPressed(a) => format!("<key {} pressed?>", self.input(&a.key, Menu)),
+            BlockType::KeyOptions(a) => {
+                menu(&a.key.as_ref().map(|f| f.to_string()).unwrap_or_default())
+            }
+            BlockType::MouseDown(_) => "<mouse down?>".to_string(),
+            BlockType::MouseX(_) => "(mouse x)".to_string(),
+            BlockType::MouseY(_) => "(mouse y)".to_string(),
+            BlockType::DraggableOption(a) => menu(&a.to_string()),
+            BlockType::SetDragMode(a) => format!("set drag mode {}", field(&a.option)),
+            BlockType::Loudness(_) => "(loudness)".to_string(),
+            BlockType::Timer(_) => "(timer)".to_string(),
+            BlockType::ResetTimer(_) => "reset timer".to_string(),
+            BlockType::BackdropOf(a) => {
+                let property = match a.backdrop {
+                    BackdropOfOption::BackdropNumber => "backdrop #",
+                    BackdropOfOption::BackdropName => "backdrop name",
+                    BackdropOfOption::Volume => "volume",
+                    BackdropOfOption::MyVariable => "my variable",
+                };
+                format!("({} of (Stage v))", menu_field(property))
+            }
+            BlockType::CurrentTime(a) => {
+                let option = match &a.option {
+                    Some(CurrentTimeOption::DayOfWeek) => "day of week".to_string(),
+                    a => lower(a),
+                };
+                format!("(current {})", menu_field(&option))
+            }
+            BlockType::DaysSince2000(_) => "(days since 2000)".to_string(),
+            BlockType::Username(_) => "(username)".to_string(),
+
+            BlockType::Add(a) => self.operator("+", &a.a, &a.b),
+            BlockType::Sub(a) => self.operator("-", &a.a, &a.b),
+            BlockType::Mul(a) => self.operator("*", &a.a, &a.b),
+            BlockType::Divide(a) => self.operator("/", &a.a, &a.b),
+            BlockType::Modulo(a) => self.operator("mod", &a.a, &a.b),
+            BlockType::PickRandom(a) => format!(
+                "(pick random {} to {})",
+                self.input(&a.min, Number),
+                self.input(&a.max, Number)
+            ),
+            BlockType::GreaterThen(a) => self.comparison(">", &a.a, &a.b),
+            BlockType::LesserThen(a) => self.comparison("<", &a.a, &a.b),
+            BlockType::EqualTo(a) => self.comparison("=", &a.a, &a.b),
+            BlockType::And(a) => format!(
+                "<{} and {}>",
+                self.input(&a.a, Boolean),
+                self.input(&a.b, Boolean)
+            ),
+            BlockType::Or(a) => format!(
+                "<{} or {}>",
+                self.input(&a.a, Boolean),
+                self.input(&a.b, Boolean)
+            ),
+            BlockType::Not(a) => format!("<not {}>", self.input(&a.a, Boolean)),
+            BlockType::Join(a) => {
+                format!(
+                    "(join {} {})",
+                    self.input(&a.a, Text),
+                    self.input(&a.b, Text)
+                )
+            }
+            BlockType::LetterOf(a) => format!(
+                "(letter {} of {})",
+                self.input(&a.index, Number),
+                self.input(&a.a, Text)
+            ),
+            BlockType::LengthOf(a) => format!("(length of {})", self.input(&a.a, Text)),
+            BlockType::Contains(a) => format!(
+                "<{} contains {}?>",
+                self.input(&a.a, Text),
+                self.input(&a.b, Text)
+            ),
+            BlockType::Round(a) => format!("(round {})", self.input(&a.a, Number)),
+            BlockType::MathOp(a) => {
+                format!("({} of {})", field(&a.operator), self.input(&a.a, Number))
+            }
+
+            BlockType::DataGetVariable(a) => format!("({})", escape(&text(&a.variable))),
+            BlockType::DataSetVariableTo(a) => format!(
+                "set {} to {}",
+                menu_field(&text(&a.variable)),
+                self.value(&a.value, Text)
+            ),
+            BlockType::DataChangeVariableBy(a) => format!(
+                "change {} by {}",
+                menu_field(&text(&a.variable)),
+                self.value(&a.value, Number)
+            ),
+            BlockType::DataShowVariable(a) => {
+                format!("show variable {}", menu_field(&text(&a.variable)))
+            }
+            BlockType::DataHideVariable(a) => {
+                format!("hide variable {}", menu_field(&text(&a.variable)))
+            }
+            BlockType::DataListContents(a) => format!("({} :: list)", escape(&text(&a.variable))),
+            BlockType::DataListIndexAll(_) => menu("all"),
+            BlockType::DataListIndexAllRandom(_) => menu("random"),
+            BlockType::DataAddToList(a) => format!(
+                "add {} to {}",
+                self.value(&a.item, Text),
+                menu_field(&text(&a.list))
+            ),
+            BlockType::DataDeleteOfList(a) => format!(
+                "delete {} of {}",
+                self.value(&a.item, Number),
+                menu_field(&text(&a.list))
+            ),
+            BlockType::DataDeleteAllOfList(a) => {
+                format!("delete all of {}", menu_field(&text(&a.list)))
+            }
+            BlockType::DataInsertAtList(a) => format!(
+                "insert {} at {} of {}",
+                self.value(&a.item, Text),
+                self.value(&a.index, Number),
+                menu_field(&text(&a.list))
+            ),
+            BlockType::DataReplaceItemOfList(a) => format!(
+                "replace item {} of {} with {}",
+                self.value(&a.index, Number),
+                menu_field(&text(&a.list)),
+                self.value(&a.item, Text)
+            ),
+            BlockType::DataItemOfList(a) => format!(
+                "(item {} of {})",
+                self.value(&a.index, Number),
+                menu_field(&text(&a.list))
+            ),
+            BlockType::DataLengthOfList(a) => {
+                format!("(length of {})", menu_field(&text(&a.list)))
+            }
+            BlockType::DataListContainsItem(a) => format!(
+                "<{} contains {} ?>",
+                menu_field(&text(&a.list)),
+                self.value(&a.input, Text)
+            ),
+            BlockType::ShowList(a) => format!("show list {}", menu_field(&text(&a.list))),
+            BlockType::HideList(a) => format!("hide list {}", menu_field(&text(&a.list))),
+
+            BlockType::ProceduresCall(a) => {
+                let mut args = a.arguments.iter();
+                let parts: Vec<String> = a
+                    .signature
+                    .segments()
+                    .iter()
+                    .map(|f| {
+                        let slot = match f {
+                            ProccodeSegment::Label(a) => return escape(a),
+                            ProccodeSegment::Number => Number,
+                            ProccodeSegment::String => Text,
+                            ProccodeSegment::Boolean => Boolean,
+                        };
+                        match args.next() {
+                            Some(a) => self.input(a, slot),
+                            None => self.resolved(Input::Empty, slot),
+                        }
+                    })
+                    .collect();
+                parts.join(" ")
+            }
+            BlockType::ProceduresDefinition(a) => match self.linked.block(&a.block) {
+                Some(BlockType::ProceduresPrototype(b)) => format!("define {}", prototype(b)),
+                _ => "define".to_string(),
+            },
+            BlockType::ProceduresPrototype(a) => prototype(a),
+            BlockType::ArgumentReporter(a) => match a.boolean {
+                true => format!("<{}>", escape(&a.name)),
+                false => format!("({})", escape(&a.name)),
+            },
+
+            // nothing in scratchblocks for these, so they at least say what
+            // they were.
+            BlockType::ProceduresDeclaration(_) => {
+                format!("{} :: grey", crate::block_names::PROCEDURES_DECLARATION)
+            }
+            BlockType::UnusedOpcode(a) => format!("{} :: grey", escape(&a.name)),
+            BlockType::InvalidOpcode(a) => format!("{} :: grey", escape(&a.name)),
+            BlockType::Stray => "... :: grey".to_string(),
+        }
+    }
+
+    fn operator(&self, op: &str, a: &'a Option<Value>, b: &'a Option<Value>) -> String {
+        format!(
+            "({} {} {})",
+            self.input(a, Slot::Number),
+            op,
+            self.input(b, Slot::Number)
+        )
+    }
+
+    fn comparison(&self, op: &str, a: &'a Option<Value>, b: &'a Option<Value>) -> String {
+        format!(
+            "<{} {} {}>",
+            self.input(a, Slot::Text),
+            op,
+            self.input(b, Slot::Text)
+        )
+    }
+}
+
+/// A custom block's name and arguments, as its definition has them.
+fn prototype(prototype: &ProceduresPrototype) -> String {
+    let signature = prototype.signature();
+    let mut names = signature.argument_names().iter();
+    let mut parts: Vec<String> = signature
+        .segments()
+        .iter()
+        .map(|f| {
+            let name = match f {
+                ProccodeSegment::Label(a) => return escape(a),
+                _ => escape(names.next().map(|f| f.as_str()).unwrap_or_default()),
+            };
+            match f {
+                ProccodeSegment::Boolean => format!("<{}>", name),
+                _ => format!("({})", name),
+            }
+        })
+        .collect();
+    // scratchblocks has no way to say it, so it goes in a comment.
+    if signature.warp() {
+        parts.push("// run without screen refresh".to_string());
+    }
+    parts.join(" ")
+}
+
+/// A dropdown that can have a block dropped in it instead.
+fn menu(name: &str) -> String {
+    format!("({} v)", escape(name))
+}
+
+/// A dropdown that's only ever a dropdown.
+fn menu_field(name: &str) -> String {
+    format!("[{} v]", escape(name))
+}
+
+fn field<T: std::fmt::Display>(val: &Option<T>) -> String {
+    menu_field(&val.as_ref().map(|f| f.to_string()).unwrap_or_default())
+}
+
+fn lower<T: std::fmt::Display>(val: &Option<T>) -> String {
+    val.as_ref()
+        .map(|f| f.to_string().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn effect(val: &Option<Value>) -> String {
+    menu_field(
+        &val.as_ref()
+            .map(|f| text(f).to_lowercase())
+            .unwrap_or_default(),
+    )
+}
+
+fn movement(option: &MovementOption) -> String {
+    match option {
+        MovementOption::RandomPosition => "random position".to_string(),
+        MovementOption::MousePointer => "mouse-pointer".to_string(),
+        MovementOption::Sprite(a) => a.clone(),
+    }
+}
+
+fn sensing(option: &SensingOption) -> String {
+    match option {
+        SensingOption::MousePointer => "mouse-pointer".to_string(),
+        SensingOption::Edge => "edge".to_string(),
+        a => a.to_string(),
+    }
+}
+
+/// Backslashes the characters that would otherwise end the text early.
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if matches!(c, '\\' | '[' | ']' | '(' | ')' | '<' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Adds the scripts in some scratchblocks text to a sprite, and gives the id
+/// of the first block of each. Scripts are separated by blank lines. Custom
+/// blocks can be used anywhere, as long as they're defined somewhere in the
+/// text or already in the sprite. Nothing is added if anything is wrong.
+pub fn add(sprite: &mut Sprite, text: &str) -> Result<Vec<String>, YaseError> {
+    let mut reader = Reader::new(sprite, text)?;
+    let tops = reader.scripts()?;
+    let mut blocks = Vec::new();
+    for (id, json) in reader.blocks {
+        let block = BlockType::from_json(&json).map_err(|err| YaseError::Block {
+            sprite: sprite.name.clone(),
+            opcode: json["opcode"].as_str().unwrap_or_default().to_string(),
+            path: err.path,
+            message: err.message,
+            id: id.clone(),
+        })?;
+        blocks.push((id, block));
+    }
+    sprite.blocks.extend(blocks);
+    Ok(tops)
+}
+
+/// What goes into each hole of a block, in the order they're written.
+#[derive(Clone, Copy)]
+enum Arg {
+    Input(&'static str, serialize::Slot),
+    /// An input holding a dropdown, with the opcode of the menu block.
+    Menu(&'static str, &'static str),
+    Field(&'static str),
+    /// A field Scratch 3 wants in capitals, `day of week` being `DAYOFWEEK`.
+    Upper(&'static str),
+    /// A graphic effect, or a sound effect if true.
+    Effect(bool),
+    /// What `([sqrt v] of (9))` does.
+    Operator,
+    Variable,
+    List,
+    Broadcast,
+    /// The inside of a C block, which is the lines under it.
+    Substack(&'static str),
+}
+
+/// Every block there's text for. Brackets around the whole thing mean a
+/// reporter or boolean, and `_` is where an argument goes. Blocks that read
+/// the same are told apart by their arguments, see fits.
+const BLOCKS: &[(&str, &str, &[Arg])] = {
+    use serialize::Slot::{Angle, Color, Empty, Integer, Number, Positive, Text, Whole};
+    use Arg::*;
+
+    &[
+        ("move _ steps", MOTION_MOVE, &[Input("STEPS", Number)]),
+        (
+            "turn right _ degrees",
+            MOTION_TURN_RIGHT,
+            &[Input("DEGREES", Number)],
+        ),
+        (
+            "turn cw _ degrees",
+            MOTION_TURN_RIGHT,
+            &[Input("DEGREES", Number)],
+        ),
+        (
+            "turn left _ degrees",
+            MOTION_TURN_LEFT,
+            &[Input("DEGREES", Number)],
+        ),
+        (
+            "turn ccw _ degrees",
+            MOTION_TURN_LEFT,
+            &[Input("DEGREES", Number)],
+        ),
+        (
+            "go to x: _ y: _",
+            MOTION_GOTO_XY,
+            &[Input("X", Number), Input("Y", Number)],
+        ),
+        ("go to _", MOTION_GOTO, &[Menu("TO", MOTION_GOTO_MENU)]),
+        (
+            "glide _ secs to x: _ y: _",
+            MOTION_GLIDE_SECONDS_TO_XY,
+            &[
+                Input("SECS", Number),
+                Input("X", Number),
+                Input("Y", Number),
+            ],
+        ),
+        (
+            "glide _ secs to _",
+            MOTION_GLIDE_TO,
+            &[Input("SECS", Number), Menu("TO", MOTION_GLIDE_TO_MENU)],
+        ),
+        (
+            "point in direction _",
+            MOTION_POINT_DIRECTION,
+            &[Input("DIRECTION", Angle)],
+        ),
+        (
+            "point towards _",
+            MOTION_POINT_TOWARDS,
+            &[Menu("TOWARDS", MOTION_POINT_MENU)],
+        ),
+        ("change x by _", MOTION_CHANGE_X_BY, &[Input("DX", Number)]),
+        ("set x to _", MOTION_SET_X, &[Input("X", Number)]),
+        ("change y by _", MOTION_CHANGE_Y_BY, &[Input("DY", Number)]),
+        ("set y to _", MOTION_SET_Y, &[Input("Y", Number)]),
+        ("if on edge, bounce", MOTION_IF_ON_EDGE_BOUNCE, &[]),
+        (
+            "set rotation style _",
+            MOTION_SET_ROTATION_STYLE,
+            &[Field("STYLE")],
+        ),
+        ("(x position)", MOTION_XPOSITION, &[]),
+        ("(y position)", MOTION_YPOSITION, &[]),
+        ("(direction)", MOTION_DIRECTION, &[]),
+        (
+            "say _ for _ seconds",
+            LOOKS_SAY_FOR_SECS,
+            &[Input("MESSAGE", Text), Input("SECS", Number)],
+        ),
+        ("say _", LOOKS_SAY, &[Input("MESSAGE", Text)]),
+        (
+            "think _ for _ seconds",
+            LOOKS_THINK_FOR_SECS,
+            &[Input("MESSAGE", Text), Input("SECS", Number)],
+        ),
+        ("think _", LOOKS_THINK, &[Input("MESSAGE", Text)]),
+        (
+            "switch costume to _",
+            LOOKS_SWITCH_COSTUME_TO,
+            &[Menu("COSTUME", LOOKS_COSTUME)],
+        ),
+        ("next costume", LOOKS_NEXT_COSTUME, &[]),
+        (
+            "switch backdrop to _",
+            LOOKS_SWITCH_BACKDROP_TO,
+            &[Menu("BACKDROP", LOOKS_BACKDROP)],
+        ),
+        (
+            "switch backdrop to _ and wait",
+            LOOKS_SWITCH_BACKDROP_TO_AND_WAIT,
+            &[Menu("BACKDROP", LOOKS_BACKDROP)],
+        ),
+        ("next backdrop", LOOKS_NEXT_BACKDROP, &[]),
+        (
+            "change size by _",
+            LOOKS_CHANGE_SIZE_BY,
+            &[Input("CHANGE", Number)],
+        ),
+        (
+            "set size to _ %",
+            LOOKS_SET_SIZE_TO,
+            &[Input("SIZE", Number)],
+        ),
+        (
+            "change _ effect by _",
+            LOOKS_CHANGE_EFFECT_BY,
+            &[Effect(false), Input("CHANGE", Number)],
+        ),
+        (
+            "change _ effect by _",
+            SOUND_CHANGE_EFFECT_BY,
+            &[Effect(true), Input("VALUE", Number)],
+        ),
+        (
+            "set _ effect to _",
+            LOOKS_SET_EFFECT_TO,
+            &[Effect(false), Input("VALUE", Number)],
+        ),
+        (
+            "set _ effect to _",
+            SOUND_SET_EFFECT_TO,
+            &[Effect(true), Input("VALUE", Number)],
+        ),
+        ("clear graphic effects", LOOKS_CLEAR_GRAPHICS_EFFECTS, &[]),
+        ("show", LOOKS_SHOW, &[]),
+        ("hide", LOOKS_HIDE, &[]),
+        ("hide all sprites", LOOKS_HIDE_ALL_SPRITES, &[]),
+        (
+            "go to _ layer",
+            LOOKS_GOTO_FRONT_BACK,
+            &[Field("FRONT_BACK")],
+        ),
+        (
+            "go _ _ layers",
+            LOOKS_GO_FORWARD_BACKWARD_LAYERS,
+            &[Field("FORWARD_BACKWARD"), Input("NUM", Integer)],
+        ),
+        (
+            "(costume _)",
+            LOOKS_COSTUME_NUMBER_NAME,
+            &[Field("NUMBER_NAME")],
+        ),
+        (
+            "(backdrop _)",
+            LOOKS_BACKDROP_NUMBER_NAME,
+            &[Field("NUMBER_NAME")],
+        ),
+        ("(size)", LOOKS_SIZE, &[]),
+        (
+            "start sound _",
+            SOUND_PLAY,
+            &[Menu("SOUND_MENU", SOUND_SOUNDS_MENU)],
+        ),
+        (
+            "play sound _ until done",
+            SOUND_PLAY_UNTIL_DONE,
+            &[Menu("SOUND_MENU", SOUND_SOUNDS_MENU)],
+        ),
+        ("stop all sounds", SOUND_STOP_ALL_SOUNDS, &[]),
+        ("clear sound effects", SOUND_CLEAR_EFFECTS, &[]),
+        (
+            "change volume by _",
+            SOUND_CHANGE_VOLUME_BY,
+            &[Input("VOLUME", Number)],
+        ),
+        (
+            "set volume to _ %",
+            SOUND_SET_VOLUME_TO,
+            &[Input("VOLUME", Number)],
+        ),
+        ("(volume)", SOUND_VOLUME, &[]),
+        ("when flag clicked", EVENT_WHEN_FLAG_CLICKED, &[]),
+        ("when green flag clicked", EVENT_WHEN_FLAG_CLICKED, &[]),
+        (
+            "when _ key pressed",
+            EVENT_WHEN_KEY_PRESSED,
+            &[Field("KEY_OPTION")],
+        ),
+        (
+            "when this sprite clicked",
+            EVENT_WHEN_THIS_SPRITECLICKED,
+            &[],
+        ),
+        ("when stage clicked", EVENT_WHEN_STAGE_CLICKED, &[]),
+        (
+            "when backdrop switches to _",
+            EVENT_WHEN_BACKDROP_SWITCHESTO,
+            &[Field("BACKDROP")],
+        ),
+        (
+            "when _ > _",
+            EVENT_WHEN_GREATER_THAN,
+            &[Upper("WHENGREATERTHANMENU"), Input("VALUE", Number)],
+        ),
+        (
+            "when I receive _",
+            EVENT_WHEN_BROADCAST_RECEIVED,
+            &[Broadcast],
+        ),
+        (
+            "broadcast _",
+            EVENT_BROADCAST,
+            &[Input("BROADCAST_INPUT", serialize::Slot::Broadcast)],
+        ),
+        (
+            "broadcast _ and wait",
+            EVENT_BROADCAST_AND_WAIT,
+            &[Input("BROADCAST_INPUT", serialize::Slot::Broadcast)],
+        ),
+        (
+            "wait _ seconds",
+            CONTROL_WAIT,
+            &[Input("DURATION", Positive)],
+        ),
+        (
+            "repeat _",
+            CONTROL_REPEAT,
+            &[Input("TIMES", Whole), Substack("SUBSTACK")],
+        ),
+        ("forever", CONTROL_FOREVER, &[Substack("SUBSTACK")]),
+        (
+            "if _ then",
+            CONTROL_IF,
+            &[Input("CONDITION", Empty), Substack("SUBSTACK")],
+        ),
+        (
+            "wait until _",
+            CONTROL_WAIT_UNTIL,
+            &[Input("CONDITION", Empty)],
+        ),
+        (
+            "repeat until _",
+            CONTROL_REPEAT_UNTIL,
+            &[Input("CONDITION", Empty), Substack("SUBSTACK")],
+        ),
+        ("stop _", CONTROL_STOP, &[Field("STOP_OPTION")]),
+        ("when I start as a clone", CONTROL_START_AS_CLONE, &[]),
+        (
+            "create clone of _",
+            CONTROL_CREATE_CLONE_OF,
+            &[Menu("CLONE_OPTION", CONTROL_CREATE_CLONE_OF_MENU)],
+        ),
+        ("delete this clone", CONTROL_DELETE_THIS_CLONE, &[]),
+        (
+            "<touching _ ?>",
+            SENSING_TOUCHING_OBJECT,
+            &[Menu("TOUCHINGOBJECTMENU", SENSING_TOUCHING_OBJECT_MENU)],
+        ),
+        (
+            "<touching color _ ?>",
+            SENSING_TOUCHING_COLOR,
+            &[Input("COLOR", Color)],
+        ),
+        (
+            "<color _ is touching _ ?>",
+            SENSING_COLOR_IS_TOUCHING_COLOR,
+            &[Input("COLOR", Color), Input("COLOR2", Color)],
+        ),
+        (
+            "(distance to _)",
+            SENSING_DISTANCE_TO,
+            &[Menu("DISTANCETOMENU", SENSING_DISTANCE_TO_MENU)],
+        ),
+        (
+            "ask _ and wait",
+            SENSING_ASK_AND_WAIT,
+            &[Input("QUESTION", Text)],
+        ),
+        ("(answer)", SENSING_ANSWER, &[]),
+        (
+            "<key _ pressed?>",
+            SENSING_KEY_PRESSED,
+            &[Menu("KEY_OPTION", SENSING_KEY_OPTIONS)],
+        ),
+        ("<mouse down?>", SENSING_MOUSE_DOWN, &[]),
+        ("(mouse x)", SENSING_MOUSE_X, &[]),
+        ("(mouse y)", SENSING_MOUSE_Y, &[]),
+        (
+            "set drag mode _",
+            SENSING_SET_DRAG_MODE,
+            &[Field("DRAG_MODE")],
+        ),
+        ("(loudness)", SENSING_LOUDNESS, &[]),
+        ("(timer)", SENSING_TIMER, &[]),
+        ("reset timer", SENSING_RESET_TIMER, &[]),
+        ("(current _)", SENSING_CURRENT, &[Upper("CURRENTMENU")]),
+        ("(days since 2000)", SENSING_DAYS_SINCE_2000, &[]),
+        ("(username)", SENSING_USERNAME, &[]),
+        (
+            "(_ + _)",
+            OPERATOR_ADD,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        (
+            "(_ - _)",
+            OPERATOR_SUBTRACT,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        (
+            "(_ * _)",
+            OPERATOR_MULTIPLY,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        (
+            "(_ / _)",
+            OPERATOR_DIVIDE,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        (
+            "(_ mod _)",
+            OPERATOR_MOD,
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        (
+            "(pick random _ to _)",
+            OPERATOR_RANDOM,
+            &[Input("FROM", Number), Input("TO", Number)],
+        ),
+        (
+            "<_ > _>",
+            OPERATOR_GREATER_THEN,
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        (
+            "<_ < _>",
+            OPERATOR_LESSER_THEN,
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        (
+            "<_ = _>",
+            OPERATOR_EQUALS,
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        (
+            "<_ and _>",
+            OPERATOR_AND,
+            &[Input("OPERAND1", Empty), Input("OPERAND2", Empty)],
+        ),
+        (
+            "<_ or _>",
+            OPERATOR_OR,
+            &[Input("OPERAND1", Empty), Input("OPERAND2", Empty)],
+        ),
+        ("<not _>", OPERATOR_NOT, &[Input("OPERAND", Empty)]),
+        (
+            "(join _ _)",
+            OPERATOR_JOIN,
+            &[Input("STRING1", Text), Input("STRING2", Text)],
+        ),
+        (
+            "(letter _ of _)",
+            OPERATOR_LETTER_OF,
+            &[Input("LETTER", Whole), Input("STRING", Text)],
+        ),
+        ("(length of _)", DATA_LENGTH_OF_LIST, &[List]),
+        ("(length of _)", OPERATOR_LENGTH, &[Input("STRING", Text)]),
+        (
+            "<_ contains _ ?>",
+            DATA_LIST_CONTAINS_ITEM,
+            &[List, Input("ITEM", Text)],
+        ),
+        (
+            "<_ contains _ ?>",
+            OPERATOR_CONTAINS,
+            &[Input("STRING1", Text), Input("STRING2", Text)],
+        ),
+        ("(round _)", OPERATOR_ROUND, &[Input("NUM", Number)]),
+        (
+            "(_ of _)",
+            OPERATOR_MATHOP,
+            &[Operator, Input("NUM", Number)],
+        ),
+        (
+            "(_ of _)",
+            SENSING_OF,
+            &[Field("PROPERTY"), Menu("OBJECT", SENSING_OF_OBJECT_MENU)],
+        ),
+        (
+            "set _ to _",
+            DATA_SET_VARIABLE_TO,
+            &[Variable, Input("VALUE", Text)],
+        ),
+        (
+            "change _ by _",
+            DATA_CHANGE_VARIABLE_BY,
+            &[Variable, Input("VALUE", Number)],
+        ),
+        ("show variable _", DATA_SHOW_VARIABLE, &[Variable]),
+        ("hide variable _", DATA_HIDE_VARIABLE, &[Variable]),
+        ("add _ to _", DATA_ADD_TO_LIST, &[Input("ITEM", Text), List]),
+        (
+            "delete _ of _",
+            DATA_DELETE_OF_LIST,
+            &[Input("INDEX", Integer), List],
+        ),
+        ("delete all of _", DATA_DELETE_ALL_OF_LIST, &[List]),
+        (
+            "insert _ at _ of _",
+            DATA_INSERT_AT_LIST,
+            &[Input("ITEM", Text), Input("INDEX", Integer), List],
+        ),
+        (
+            "replace item _ of _ with _",
+            DATA_REPLACE_ITEM_OF_LIST,
+            &[Input("INDEX", Integer), List, Input("ITEM", Text)],
+        ),
+        (
+            "(item _ of _)",
+            DATA_ITEM_OF_LIST,
+            &[Input("INDEX", Integer), List],
+        ),
+        ("show list _", DATA_SHOW_LIST, &[List]),
+        ("hide list _", DATA_HIDE_LIST, &[List]),
+    ]
+};
+
+lazy_static! {
+    /// What each of BLOCKS is matched by, see key.
+    static ref KEYS: Vec<(Shape, String)> = BLOCKS
+        .iter()
+        .map(|(template, _, _)| {
+            let (shape, inner) = match template.chars().next() {
+                Some('(') => (Shape::Reporter, &template[1..template.len() - 1]),
+                Some('<') => (Shape::Boolean, &template[1..template.len() - 1]),
+                _ => (Shape::Stack, *template),
+            };
+            let (parts, _) = tokenize(inner).unwrap_or_default();
+            (shape, key(&parts))
+        })
+        .collect();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Stack,
+    Reporter,
+    Boolean,
+}
+
+/// A piece of a line, as it was written.
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Word(String),
+    /// `(10)`, or `()`.
+    Number(String),
+    /// `[hello]`.
+    Text(String),
+    /// `(Sprite1 v)`, which can have a reporter dropped in it instead.
+    Menu(String),
+    /// `[x v]`.
+    Field(String),
+    Reporter(Vec<Part>),
+    Boolean(Vec<Part>),
+}
+
+/// The block a line (or what's inside a reporter) is, if it's one of BLOCKS.
+fn find(shape: Shape, parts: &[Part]) -> Option<(&'static str, &'static [Arg])> {
+    let key = key(parts);
+    let args = arguments(parts);
+    BLOCKS
+        .iter()
+        .zip(KEYS.iter())
+        .find(|(block, f)| f.0 == shape && f.1 == key && fits(block.2, &args))
+        .map(|(block, _)| (block.1, block.2))
+}
+
+/// Whether the arguments can go in the holes, for blocks that read the same.
+fn fits(specs: &[Arg], args: &[&Part]) -> bool {
+    let specs: Vec<&Arg> = specs
+        .iter()
+        .filter(|f| !matches!(f, Arg::Substack(_)))
+        .collect();
+    specs.len() == args.len()
+        && specs
+            .iter()
+            .zip(args)
+            .all(|(spec, part)| match (spec, part) {
+                (Arg::Input(_, serialize::Slot::Empty), a) => matches!(a, Part::Boolean(_)),
+                (Arg::Input(..) | Arg::Menu(..), a) => !matches!(a, Part::Field(_)),
+                (Arg::Effect(sound), Part::Field(a)) => {
+                    let a = a.to_lowercase();
+                    *sound == matches!(a.as_str(), "pitch" | "pan" | "pan left/right")
+                }
+                (Arg::Operator, Part::Field(a)) => {
+                    MathOperator::from(Some(Value::String(a.to_lowercase())))
+                        .is_ok_and(|f| f.is_some())
+                }
+                (_, a) => matches!(a, Part::Field(_)),
+            })
+}
+
+/// A line without its arguments, so `move (10) steps` is `move _ steps`.
+/// Case doesn't matter, and a `?` on the end of a word is a word of its own.
+fn key(parts: &[Part]) -> String {
+    let mut words = Vec::new();
+    for part in parts {
+        match part {
+            Part::Word(a) if a == "::" => break,
+            Part::Word(a) => match a.strip_suffix('?') {
+                Some(b) if !b.is_empty() => {
+                    words.push(b.to_lowercase());
+                    words.push("?".to_string());
+                }
+                _ => words.push(a.to_lowercase()),
+            },
+            _ => words.push("_".to_string()),
+        }
+    }
+    words.join(" ")
+}
+
+/// Everything but the words, up to the `::`.
+fn arguments(parts: &[Part]) -> Vec<&Part> {
+    parts
+        .iter()
+        .take_while(|f| !matches!(f, Part::Word(a) if a == "::"))
+        .filter(|f| !matches!(f, Part::Word(_)))
+        .collect()
+}
+
+/// What comes after `::`, like `list` in `(things :: list)`.
+fn category(parts: &[Part]) -> String {
+    let words: Vec<&str> = parts
+        .iter()
+        .skip_while(|f| !matches!(f, Part::Word(a) if a == "::"))
+        .skip(1)
+        .filter_map(|f| match f {
+            Part::Word(a) => Some(a.as_str()),
+            _ => None,
+        })
+        .collect();
+    words.join(" ").to_lowercase()
+}
+
+/// The name a variable, list or argument is written with, if that's all
+/// there is.
+fn name(parts: &[Part]) -> Option<String> {
+    let mut words = Vec::new();
+    for part in parts {
+        match part {
+            Part::Word(a) if a == "::" => break,
+            Part::Word(a) => words.push(a.as_str()),
+            _ => return None,
+        }
+    }
+    match words.is_empty() {
+        true => None,
+        false => Some(words.join(" ")),
+    }
+}
+
+/// A custom block's proccode and argument names, from what follows `define`.
+fn signature(parts: &[Part]) -> Result<(String, Vec<String>), String> {
+    let mut proccode = Vec::new();
+    let mut names = Vec::new();
+    for part in parts {
+        let (kind, inner) = match part {
+            Part::Word(a) => {
+                proccode.push(a.clone());
+                continue;
+            }
+            Part::Reporter(a) => ("%s", a),
+            Part::Boolean(a) => ("%b", a),
+            _ => return Err("custom block arguments go in () or <>".to_string()),
+        };
+        names.push(name(inner).ok_or("an argument needs a name")?);
+        proccode.push(kind.to_string());
+    }
+    match proccode.is_empty() {
+        true => Err("define needs a name".to_string()),
+        false => Ok((proccode.join(" "), names)),
+    }
+}
+
+/// Splits a line into words and arguments, and gives whatever comment came
+/// after `//`.
+fn tokenize(line: &str) -> Result<(Vec<Part>, Option<String>), String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    let parts = group(&chars, &mut i, None)?;
+    let comment = chars
+        .get(i + 2..)
+        .map(|f| f.iter().collect::<String>().trim().to_string());
+    Ok((parts, comment))
+}
+
+/// Parts up to the given closing bracket, or to the end of the line.
+fn group(chars: &[char], i: &mut usize, close: Option<char>) -> Result<Vec<Part>, String> {
+    let spaced = |j: usize| !matches!(chars.get(j), Some(c) if !c.is_whitespace());
+    let mut parts = Vec::new();
+    loop {
+        while chars.get(*i).is_some_and(|f| f.is_whitespace()) {
+            *i += 1;
+        }
+        let c = match chars.get(*i) {
+            Some(a) => *a,
+            None => {
+                return match close {
+                    Some(a) => Err(format!("missing {}", a)),
+                    None => Ok(parts),
+                }
+            }
+        };
+        match c {
+            ')' if close == Some(')') => {
+                *i += 1;
+                return Ok(parts);
+            }
+            // `>` with spaces on both sides is greater than.
+            '>' if close == Some('>') && !(spaced(*i - 1) && spaced(*i + 1)) => {
+                *i += 1;
+                return Ok(parts);
+            }
+            '/' if close.is_none() && chars.get(*i + 1) == Some(&'/') => return Ok(parts),
+            '(' if chars[*i + 1..].starts_with(&[' ', 'v', ')']) => {
+                *i += 4;
+                parts.push(Part::Menu(String::new()));
+            }
+            '(' => {
+                *i += 1;
+                parts.push(round(group(chars, i, Some(')'))?));
+            }
+            '<' if !spaced(*i + 1) => {
+                *i += 1;
+                parts.push(Part::Boolean(group(chars, i, Some('>'))?));
+            }
+            '<' | '>' => {
+                *i += 1;
+                parts.push(Part::Word(c.to_string()));
+            }
+            '[' => {
+                *i += 1;
+                parts.push(square(chars, i)?);
+            }
+            ')' | ']' => return Err(format!("unexpected {}", c)),
+            _ => parts.push(Part::Word(word(chars, i))),
+        }
+    }
+}
+
+/// What was in `()`: a number, a dropdown, or a reporter.
+fn round(inner: Vec<Part>) -> Part {
+    let number = |a: &str| {
+        a.starts_with(|f: char| f.is_ascii_digit() || matches!(f, '-' | '+' | '.'))
+            && a.parse::<f64>().is_ok()
+    };
+    match inner.as_slice() {
+        [] => return Part::Number(String::new()),
+        [Part::Word(a)] if number(a) => return Part::Number(a.clone()),
+        [words @ .., Part::Word(v)] if v == "v" && !words.is_empty() => {
+            if let Some(a) = name(words) {
+                return Part::Menu(a);
+            }
+        }
+        _ => {}
+    }
+    Part::Reporter(inner)
+}
+
+/// What was in `[]`, which is text unless it ends with ` v`.
+fn square(chars: &[char], i: &mut usize) -> Result<Part, String> {
+    let mut text = String::new();
+    loop {
+        match chars.get(*i) {
+            Some('\\') => {
+                text.extend(chars.get(*i + 1));
+                *i += 2;
+            }
+            Some(']') => break,
+            Some(c) => {
+                text.push(*c);
+                *i += 1;
+            }
+            None => return Err("missing ]".to_string()),
+        }
+    }
+    *i += 1;
+    Ok(match text.strip_suffix(" v") {
+        Some(a) => Part::Field(a.to_string()),
+        None => Part::Text(text),
+    })
+}
+
+fn word(chars: &[char], i: &mut usize) -> String {
+    let mut word = String::new();
+    while let Some(c) = chars.get(*i) {
+        match c {
+            '\\' => {
+                word.extend(chars.get(*i + 1));
+                *i += 2;
+                continue;
+            }
+            '(' | ')' | '[' | ']' | '<' | '>' => break,
+            c if c.is_whitespace() => break,
+            c => word.push(*c),
+        }
+        *i += 1;
+    }
+    word
+}
+
+/// A custom block as a call needs it.
+#[derive(Debug, Clone)]
+struct Procedure {
+    proccode: String,
+    argument_ids: Vec<String>,
+    warp: bool,
+}
+
+/// Turns the lines into what project.json would have for them.
+struct Reader {
+    lines: Vec<String>,
+    /// The next line to read.
+    line: usize,
+    /// The line errors are about.
+    current: usize,
+    blocks: Map<String, SerdeValue>,
+    /// Ids the sprite already uses.
+    taken: HashSet<String>,
+    count: usize,
+    /// Custom blocks, by key.
+    procedures: HashMap<String, Procedure>,
+    /// Argument names of the custom block being defined.
+    arguments: HashSet<String>,
+}
+
+impl Reader {
+    fn new(sprite: &Sprite, text: &str) -> Result<Reader, YaseError> {
+        let mut procedures = HashMap::new();
+        for block in sprite.blocks.values() {
+            if let BlockType::ProceduresPrototype(a) = block {
+                let signature = a.signature();
+                let mut parts = Vec::new();
+                for segment in signature.segments() {
+                    match segment {
+                        ProccodeSegment::Label(a) => {
+                            parts.extend(a.split_whitespace().map(|f| Part::Word(f.to_string())))
+                        }
+                        _ => parts.push(Part::Number(String::new())),
+                    }
+                }
+                let procedure = Procedure {
+                    proccode: signature.proccode().to_string(),
+                    argument_ids: signature.argument_ids().clone(),
+                    warp: signature.warp(),
+                };
+                procedures.insert(key(&parts), procedure);
+            }
+        }
+
+        // custom blocks can be used before they're defined.
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        for (i, line) in lines.iter().enumerate() {
+            let invalid = |message: String| YaseError::Scratchblocks {
+                line: i + 1,
+                message,
+            };
+            let (parts, comment) = tokenize(line).map_err(invalid)?;
+            if !matches!(parts.first(), Some(Part::Word(a)) if a.eq_ignore_ascii_case("define")) {
+                continue;
+            }
+            let (proccode, names) = signature(&parts[1..]).map_err(invalid)?;
+            let procedure = Procedure {
+                proccode,
+                argument_ids: (0..names.len()).map(|f| format!("input{}", f)).collect(),
+                warp: comment.is_some_and(|f| f.contains("run without screen refresh")),
+            };
+            procedures.insert(key(&parts[1..]), procedure);
+        }
+
+        Ok(Reader {
+            lines,
+            line: 0,
+            current: 0,
+            blocks: Map::new(),
+            taken: sprite.blocks.keys().cloned().collect(),
+            count: 0,
+            procedures,
+            arguments: HashSet::new(),
+        })
+    }
+
+    fn invalid(&self, line: usize, message: impl Into<String>) -> YaseError {
+        YaseError::Scratchblocks {
+            line: line + 1,
+            message: message.into(),
+        }
+    }
+
+    fn add(&mut self, block: SerdeValue) -> String {
+        loop {
+            let id = format!("sb-{}", self.count);
+            self.count += 1;
+            if !self.taken.contains(&id) {
+                self.blocks.insert(id.clone(), block);
+                return id;
+            }
+        }
+    }
+
+    fn block(&mut self, opcode: &str, parent: Option<&str>) -> String {
+        self.add(json!({
+            "opcode": opcode,
+            "next": null,
+            "parent": parent,
+            "inputs": {},
+            "fields": {},
+            "shadow": false,
+            "topLevel": parent.is_none(),
+        }))
+    }
+
+    fn field(&mut self, id: &str, name: &str, value: &str) {
+        self.blocks[id]["fields"][name] = json!([value, null]);
+    }
+
+    /// `end` or `else`, if that's what the next line is.
+    fn closer(&self) -> Option<String> {
+        let line = self.lines.get(self.line)?;
+        let line = line.split("//").next().unwrap_or_default().trim();
+        let line = line.to_lowercase();
+        matches!(line.as_str(), "end" | "else").then_some(line)
+    }
+
+    fn scripts(&mut self) -> Result<Vec<String>, YaseError> {
+        let mut tops = Vec::new();
+        while self.line < self.lines.len() {
+            if self.lines[self.line].trim().is_empty() {
+                self.line += 1;
+                continue;
+            }
+            if let Some(a) = self.closer() {
+                return Err(self.invalid(self.line, format!("{} without a C block", a)));
+            }
+            self.arguments.clear();
+            tops.extend(self.stack(None)?);
+        }
+        Ok(tops)
+    }
+
+    /// Lines one under another, up to a blank line at the top, or what
+    /// closes the C block they're in. Gives the id of the first one.
+    fn stack(&mut self, parent: Option<&str>) -> Result<Option<String>, YaseError> {
+        let mut first = None;
+        let mut prev: Option<String> = parent.map(str::to_string);
+        while let Some(line) = self.lines.get(self.line) {
+            if line.trim().is_empty() {
+                match parent {
+                    Some(_) => {
+                        self.line += 1;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            if self.closer().is_some() {
+                break;
+            }
+            let id = self.statement(prev.as_deref())?;
+            match (&first, &prev) {
+                (Some(_), Some(prev)) => self.blocks[prev]["next"] = json!(id),
+                _ => first = Some(id.clone()),
+            }
+            prev = Some(id);
+        }
+        Ok(first)
+    }
+
+    /// One line, and the lines inside it if it's a C block.
+    fn statement(&mut self, parent: Option<&str>) -> Result<String, YaseError> {
+        self.current = self.line;
+        let (parts, _) =
+            tokenize(&self.lines[self.line]).map_err(|f| self.invalid(self.current, f))?;
+        self.line += 1;
+
+        if let Some(Part::Word(a)) = parts.first() {
+            if a.eq_ignore_ascii_case("define") {
+                return self.definition(&parts[1..], parent);
+            }
+        }
+        if let [part @ (Part::Reporter(_) | Part::Boolean(_))] = parts.as_slice() {
+            return self.reporter(part, parent);
+        }
+        if let Some(procedure) = self.procedures.get(&key(&parts)).cloned() {
+            return self.call(&procedure, &parts, parent);
+        }
+        let (opcode, specs) = match find(Shape::Stack, &parts) {
+            Some(a) => a,
+            None => {
+                let line = self.lines[self.current].trim();
+                return Err(self.invalid(self.current, format!("unknown block: {}", line)));
+            }
+        };
+        let id = self.block(opcode, parent);
+        self.fill(&id, specs, &parts)?;
+
+        let substack = specs.iter().find_map(|f| match f {
+            Arg::Substack(a) => Some(*a),
+            _ => None,
+        });
+        if let Some(name) = substack {
+            self.substack(&id, name)?;
+            if opcode == CONTROL_IF && self.closer().as_deref() == Some("else") {
+                self.line += 1;
+                self.blocks[&id]["opcode"] = json!(CONTROL_IF_ELSE);
+                self.substack(&id, "SUBSTACK2")?;
+            }
+            match self.closer() {
+                Some(a) if a == "end" => self.line += 1,
+                Some(a) => return Err(self.invalid(self.line, format!("{} without an if", a))),
+                // running out of lines closes whatever's still open.
+                None => {}
+            }
+        }
+        Ok(id)
+    }
+
+    fn substack(&mut self, id: &str, name: &str) -> Result<(), YaseError> {
+        if let Some(first) = self.stack(Some(id))? {
+            self.blocks[id]["inputs"][name] = json!([2, first]);
+        }
+        Ok(())
+    }
+
+    /// A reporter or boolean. Ones that aren't blocks are variables, lists
+    /// (`:: list`) and the arguments of custom blocks.
+    fn reporter(&mut self, part: &Part, parent: Option<&str>) -> Result<String, YaseError> {
+        let (shape, parts) = match part {
+            Part::Reporter(a) => (Shape::Reporter, a),
+            Part::Boolean(a) => (Shape::Boolean, a),
+            _ => return Err(self.invalid(self.current, "expected a reporter")),
+        };
+        if let Some((opcode, specs)) = find(shape, parts) {
+            let id = self.block(opcode, parent);
+            self.fill(&id, specs, parts)?;
+            return Ok(id);
+        }
+        let name = match name(parts) {
+            Some(a) => a,
+            None => {
+                let message = format!("unknown reporter: {}", key(parts));
+                return Err(self.invalid(self.current, message));
+            }
+        };
+        let category = category(parts);
+        let (opcode, field) = match (shape, category.as_str()) {
+            (_, "list") => (DATA_LIST_COTNENTS, "LIST"),
+            (Shape::Boolean, _) => (ARGUMENT_REPORTER_BOOLEAN, "VALUE"),
+            (_, "custom-arg") => (ARGUMENT_REPORTER_STRING_NUMBER, "VALUE"),
+            _ if self.arguments.contains(&name) => (ARGUMENT_REPORTER_STRING_NUMBER, "VALUE"),
+            _ => (DATA_VARIABLE, "VARIABLE"),
+        };
+        let id = self.block(opcode, parent);
+        self.field(&id, field, &name);
+        Ok(id)
+    }
+
+    /// Puts the arguments of a line into the block's inputs and fields.
+    fn fill(&mut self, id: &str, specs: &[Arg], parts: &[Part]) -> Result<(), YaseError> {
+        let mut args = arguments(parts).into_iter();
+        let empty = Part::Boolean(Vec::new());
+        for spec in specs {
+            let part = match spec {
+                Arg::Substack(_) => continue,
+                // fits made sure there's one for everything else.
+                _ => args.next().unwrap_or(&empty),
+            };
+            let label = match part {
+                Part::Field(a) => a.as_str(),
+                _ => "",
+            };
+            match *spec {
+                Arg::Input(name, slot) => self.input(id, name, part, slot)?,
+                Arg::Menu(name, menu) => self.menu(id, name, menu, part)?,
+                Arg::Field(name) => self.field(id, name, label),
+                Arg::Upper(name) => self.field(id, name, &label.to_uppercase().replace(' ', "")),
+                Arg::Effect(_) => {
+                    let effect = match label.to_lowercase().as_str() {
+                        "pan left/right" => "PAN".to_string(),
+                        _ => label.to_uppercase(),
+                    };
+                    self.field(id, "EFFECT", &effect);
+                }
+                Arg::Operator => self.field(id, "OPERATOR", &label.to_lowercase()),
+                Arg::Variable => self.field(id, "VARIABLE", label),
+                Arg::List => self.field(id, "LIST", label),
+                Arg::Broadcast => self.field(id, "BROADCAST_OPTION", label),
+                Arg::Substack(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn input(
+        &mut self,
+        id: &str,
+        name: &str,
+        part: &Part,
+        slot: serialize::Slot,
+    ) -> Result<(), YaseError> {
+        let input = match part {
+            Part::Boolean(a) if a.is_empty() => return Ok(()),
+            Part::Reporter(_) | Part::Boolean(_) => {
+                let child = self.reporter(part, Some(id))?;
+                match slot {
+                    serialize::Slot::Empty => json!([2, child]),
+                    a => json!([3, child, a.shadow()]),
+                }
+            }
+            Part::Number(a) | Part::Text(a) | Part::Menu(a) => match slot {
+                serialize::Slot::Empty => {
+                    let message = format!("expected a boolean, got {}", a);
+                    return Err(self.invalid(self.current, message));
+                }
+                serialize::Slot::Broadcast => json!([1, [11, a, a]]),
+                b => json!([1, [b.code(), a]]),
+            },
+            Part::Word(a) | Part::Field(a) => {
+                let message = format!("expected an input, got {}", a);
+                return Err(self.invalid(self.current, message));
+            }
+        };
+        self.blocks[id]["inputs"][name] = input;
+        Ok(())
+    }
+
+    /// A dropdown, which is a menu block of its own unless there's a
+    /// reporter in it.
+    fn menu(&mut self, id: &str, name: &str, menu: &str, part: &Part) -> Result<(), YaseError> {
+        let option = match part {
+            Part::Reporter(_) | Part::Boolean(_) => {
+                let child = self.reporter(part, Some(id))?;
+                self.blocks[id]["inputs"][name] = json!([2, child]);
+                return Ok(());
+            }
+            Part::Number(a) | Part::Text(a) | Part::Menu(a) => a,
+            Part::Word(a) | Part::Field(a) => {
+                let message = format!("expected a dropdown, got {}", a);
+                return Err(self.invalid(self.current, message));
+            }
+        };
+        // costumes, sounds and keys can have any name, sprites can't be
+        // called what the special options are saved as.
+        let option = match (menu, option.as_str()) {
+            (LOOKS_COSTUME | LOOKS_BACKDROP | SOUND_SOUNDS_MENU | SENSING_KEY_OPTIONS, a) => a,
+            (_, "random position") => "_random_",
+            (_, "mouse-pointer") => "_mouse_",
+            (_, "edge") => "_edge_",
+            (_, "myself") => "_myself_",
+            (_, "Stage") => "_stage_",
+            (_, a) => a,
+        };
+        let shadow = self.add(json!({
+            "opcode": menu,
+            "next": null,
+            "parent": id,
+            "inputs": {},
+            "fields": { name: [option, null] },
+            "shadow": true,
+            "topLevel": false,
+        }));
+        self.blocks[id]["inputs"][name] = json!([1, shadow]);
+        Ok(())
+    }
+
+    /// A definition holding a prototype holding a reporter for each
+    /// argument, the same as the editor makes.
+    fn definition(&mut self, parts: &[Part], parent: Option<&str>) -> Result<String, YaseError> {
+        if parent.is_some() {
+            return Err(self.invalid(self.current, "define has to start a script"));
+        }
+        let (proccode, names) = signature(parts).map_err(|f| self.invalid(self.current, f))?;
+        let procedure = self.procedures[&key(parts)].clone();
+        let kinds: Vec<bool> = parse_proccode(&proccode)
+            .iter()
+            .filter(|f| !matches!(f, ProccodeSegment::Label(_)))
+            .map(|f| *f == ProccodeSegment::Boolean)
+            .collect();
+        let defaults: Vec<&str> = kinds
+            .iter()
+            .map(|f| match f {
+                true => "false",
+                false => "",
+            })
+            .collect();
+
+        let id = self.block(PROCEDURES_DEFINITION, None);
+        let prototype = self.add(json!({
+            "opcode": PROCEDURES_PROTOTYPE,
+            "next": null,
+            "parent": id,
+            "inputs": {},
+            "fields": {},
+            "shadow": true,
+            "topLevel": false,
+            "mutation": {
+                "tagName": "mutation",
+                "children": [],
+                "proccode": proccode,
+                "argumentids": json!(procedure.argument_ids).to_string(),
+                "argumentnames": json!(names).to_string(),
+                "argumentdefaults": json!(defaults).to_string(),
+                "warp": procedure.warp.to_string(),
+            },
+        }));
+        for ((arg, name), boolean) in procedure.argument_ids.iter().zip(&names).zip(kinds) {
+            let opcode = match boolean {
+                true => ARGUMENT_REPORTER_BOOLEAN,
+                false => ARGUMENT_REPORTER_STRING_NUMBER,
+            };
+            let reporter = self.add(json!({
+                "opcode": opcode,
+                "next": null,
+                "parent": prototype,
+                "inputs": {},
+                "fields": { "VALUE": [name, null] },
+                "shadow": true,
+                "topLevel": false,
+            }));
+            self.blocks[&prototype]["inputs"][arg] = json!([1, reporter]);
+        }
+        self.blocks[&id]["inputs"]["custom_block"] = json!([1, prototype]);
+        self.arguments = names.into_iter().collect();
+        Ok(id)
+    }
+
+    fn call(
+        &mut self,
+        procedure: &Procedure,
+        parts: &[Part],
+        parent: Option<&str>,
+    ) -> Result<String, YaseError> {
+        let id = self.block(PROCEDURES_CALL, parent);
+        let kinds = parse_proccode(&procedure.proccode)
+            .into_iter()
+            .filter(|f| !matches!(f, ProccodeSegment::Label(_)));
+        for ((arg, kind), part) in procedure
+            .argument_ids
+            .iter()
+            .zip(kinds)
+            .zip(arguments(parts))
+        {
+            let slot = match kind {
+                ProccodeSegment::Boolean => serialize::Slot::Empty,
+                _ => serialize::Slot::Text,
+            };
+            self.input(&id, arg, part, slot)?;
+        }
+        self.blocks[&id]["mutation"] = json!({
+            "tagName": "mutation",
+            "children": [],
+            "proccode": procedure.proccode,
+            "argumentids": json!(procedure.argument_ids).to_string(),
+            "warp": procedure.warp.to_string(),
+        });
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_project;
+
+    #[test]
+    fn prints_test_project() {
+        let project = test_project();
+        let stage = sprite(&project.sprites()[0]);
+        assert!(stage.starts_with("when flag clicked\nforever\n  set [direction v] to [none]\n"));
+        assert!(stage.contains("    if <key (right arrow v) pressed?> then\n"));
+        assert!(stage.contains("      change [dy v] by ((5.5) * (brickSize))\n"));
+        assert!(stage.contains("  broadcast (Display v) and wait\n"));
+
+        for sprite in project.sprites().iter().map(sprite) {
+            let lines: Vec<&str> = sprite.lines().map(|f| f.trim()).collect();
+            let opened = lines
+                .iter()
+                .filter(|f| f.starts_with("if ") || f.starts_with("repeat") || **f == "forever")
+                .count();
+            let closed = lines.iter().filter(|f| **f == "end").count();
+            assert_eq!(opened, closed);
+            // nothing left over from Debug.
+            assert!(!sprite.contains('{'), "{}", sprite);
+        }
+    }
+
+    #[test]
+    fn stops_at_loops() {
+        let json = json!({"targets": [{"isStage": true, "name": "Stage", "blocks": {
+            "a": {"opcode": "event_whenflagclicked", "next": "b", "parent": null,
+                "inputs": {}, "fields": {}, "topLevel": true, "x": 0, "y": 0},
+            "b": {"opcode": "control_forever", "next": null, "parent": "a",
+                "inputs": {"SUBSTACK": [2, "c"]}, "fields": {}},
+            "c": {"opcode": "looks_say", "next": "b", "parent": "b",
+                "inputs": {"MESSAGE": [3, "d", [10, "hi"]]}, "fields": {}},
+            "d": {"opcode": "operator_add", "next": null, "parent": "c",
+                "inputs": {"NUM1": [3, "d", [4, "1"]], "NUM2": [3, "d", [4, "2"]]}, "fields": {}},
+        }}]});
+        let options = crate::decomp::LoadOptions { strict: false };
+        let project = crate::decomp::Project::from_json_str(&json.to_string(), options).unwrap();
+        assert_eq!(
+            sprite(&project.sprites()[0]),
+            "when flag clicked\nforever\n  say (() + ())\nend\n"
+        );
+    }
+
+    #[test]
+    fn reads_back_what_it_prints() {
+        let mut project = test_project();
+        // blocks that test.json doesn't have.
+        let text = "when flag clicked\nshow list [things v]\nhide list [things v]";
+        add(&mut project.sprites_mut()[0], text).unwrap();
+        assert!(sprite(&project.sprites()[0]).contains(text));
+        for original in project.sprites() {
+            let text = sprite(original);
+            let mut copy = Sprite {
+                name: original.name.clone(),
+                is_stage: original.is_stage,
+                ..Sprite::default()
+            };
+            add(&mut copy, &text).unwrap();
+            // ids are different, so scripts can come out in another order.
+            let scripts = |f: &str| {
+                let mut scripts: Vec<String> =
+                    f.trim_end().split("\n\n").map(str::to_string).collect();
+                scripts.sort();
+                scripts
+            };
+            assert_eq!(scripts(&sprite(&copy)), scripts(&text));
+        }
+    }
+
+    #[test]
+    fn reads_scripts() {
+        let mut sprite = Sprite::default();
+        let text = "when flag clicked\n\
+            repeat (10)\n\
+            \x20 jump (3) <mouse down?> :: custom\n\
+            end\n\
+            say (join [a \\] b] (things :: list))\n\
+            \n\
+            define jump (height) <high>\n\
+            if <<high> and <(height) > [2]>> then\n\
+            \x20 change y by (height)\n\
+            else\n\
+            \x20 go to (random position v)\n\
+            end";
+        let tops = add(&mut sprite, text).unwrap();
+        assert_eq!(tops.len(), 2);
+        let first = "when flag clicked\n\
+            repeat (10)\n\
+            \x20 jump [3] <mouse down?>\n\
+            end\n\
+            say (join [a \\] b] (things :: list))\n";
+        assert_eq!(script(&sprite, &tops[0]), first);
+        assert!(script(&sprite, &tops[1]).contains("  go to (random position v)\n"));
+
+        let err = add(&mut sprite, "move (10) steps\nfly").unwrap_err();
+        assert_eq!(
+            err,
+            YaseError::Scratchblocks {
+                line: 2,
+                message: "unknown block: fly".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape("a [b] (c) <d> \\"), "a \\[b\\] \\(c\\) \\<d\\> \\\\");
+    }
+}
@@ -0,0 +1,164 @@
+//! A linked view over a sprite's blocks.
+//!
+//! Blocks only know each other by their string ids, so everything that wants to
+//! follow a script around would otherwise have to go back to the sprite's hashmap
+//! every step. This does that once and hands out references instead.
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    blocks::{BlockType, ProcedureSignature, ProceduresDefinition, Value},
+    decomp::Sprite,
+};
+
+/// A hat block and everything under it.
+#[derive(Debug, Clone, Copy)]
+pub struct Script<'a> {
+    pub id: &'a str,
+    pub hat: &'a BlockType,
+}
+
+/// A custom block's definition, keyed by its proccode.
+#[derive(Debug, Clone, Copy)]
+pub struct Procedure<'a> {
+    pub id: &'a str,
+    pub definition: &'a ProceduresDefinition,
+    pub signature: &'a ProcedureSignature,
+}
+
+/// What an input actually points to.
+#[derive(Debug, Clone, Copy)]
+pub enum Input<'a> {
+    Empty,
+    Literal(&'a Value),
+    Variable(&'a str),
+    List(&'a str),
+    Block(&'a str, &'a BlockType),
+}
+
+#[derive(Debug, Clone)]
+pub struct Linked<'a> {
+    pub sprite: &'a Sprite,
+    /// Every hat in the sprite, ordered by id so that runs are reproducible.
+    pub scripts: Vec<Script<'a>>,
+    pub procedures: HashMap<&'a str, Procedure<'a>>,
+    /// Top level blocks that aren't under a hat and will never run.
+    pub strays: Vec<&'a str>,
+}
+
+impl<'a> Linked<'a> {
+    pub fn new(sprite: &'a Sprite) -> Linked<'a> {
+        let mut scripts = Vec::new();
+        let mut strays = Vec::new();
+        let mut procedures = HashMap::new();
+        for (id, block) in &sprite.blocks {
+            if block.as_block().prev().is_some() {
+                continue;
+            }
+            if block.is_hat() {
+                scripts.push(Script { id, hat: block });
+            } else if !matches!(block, BlockType::ProceduresPrototype(_)) {
+                // old projects don't give prototypes a parent.
+                strays.push(id.as_str());
+            }
+            if let BlockType::ProceduresDefinition(a) = block {
+                if let Some(BlockType::ProceduresPrototype(b)) = sprite.blocks.get(&a.block) {
+                    procedures.insert(
+                        b.signature.proccode(),
+                        Procedure {
+                            id,
+                            definition: a,
+                            signature: &b.signature,
+                        },
+                    );
+                }
+            }
+        }
+        scripts.sort_by_key(|f| f.id);
+        strays.sort();
+
+        Linked {
+            sprite,
+            scripts,
+            procedures,
+            strays,
+        }
+    }
+
+    pub fn block(&self, id: &str) -> Option<&'a BlockType> {
+        self.sprite.blocks.get(id)
+    }
+
+    /// Resolves an input to the block it points to, if it points to one.
+    pub fn input(&self, val: &'a Option<Value>) -> Input<'a> {
+        match val {
+            Some(a) => self.value(a),
+            None => Input::Empty,
+        }
+    }
+
+    /// Same as input, for the few blocks that always have a value.
+    pub fn value(&self, val: &'a Value) -> Input<'a> {
+        match val {
+            Value::Null => Input::Empty,
+            Value::Variable(a) => Input::Variable(a),
+            Value::List(a) => Input::List(a),
+            Value::String(a) => match self.sprite.blocks.get_key_value(a) {
+                Some((id, block)) => Input::Block(id, block),
+                None => Input::Literal(val),
+            },
+            Value::Number(_) => Input::Literal(val),
+        }
+    }
+
+    /// The blocks in a stack, starting from (and including) the given block.
+    pub fn stack(&self, first: Option<&str>) -> Stack<'a> {
+        Stack {
+            blocks: &self.sprite.blocks,
+            cur: first.and_then(|f| self.sprite.blocks.get_key_value(f)),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// The blocks inside a C block's substack input.
+    pub fn substack(&self, val: &'a Option<Value>) -> Stack<'a> {
+        match self.input(val) {
+            Input::Block(id, _) => self.stack(Some(id)),
+            _ => self.stack(None),
+        }
+    }
+
+    /// The blocks under a hat, not including the hat itself.
+    pub fn body(&self, script: &Script<'a>) -> Stack<'a> {
+        let mut stack = self.stack(Some(script.id));
+        stack.next();
+        stack
+    }
+
+    pub fn procedure(&self, proccode: &str) -> Option<&Procedure<'a>> {
+        self.procedures.get(proccode)
+    }
+}
+
+/// Iterator over a stack of blocks, following each block's next. It stops
+/// if the stack loops back on itself, which only a broken project does.
+pub struct Stack<'a> {
+    blocks: &'a HashMap<String, BlockType>,
+    cur: Option<(&'a String, &'a BlockType)>,
+    seen: HashSet<&'a str>,
+}
+
+impl<'a> Iterator for Stack<'a> {
+    type Item = (&'a str, &'a BlockType);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, block) = self.cur.take()?;
+        if !self.seen.insert(id) {
+            return None;
+        }
+        self.cur = block
+            .as_block()
+            .next()
+            .and_then(|f| self.blocks.get_key_value(&f));
+        Some((id, block))
+    }
+}
@@ -0,0 +1,1185 @@
+//! Turns blocks back into what project.json has.
+//!
+//! Reading a block throws away everything only the editor cares about: which
+//! inputs are shadows, what kind of literal an input held, where scripts are.
+//! So that's put back the way the editor would have made it. Inputs that name
+//! a block of the same sprite are taken to point at it, like everywhere else.
+//! Blocks that were loaded keep what they had, as far as they haven't changed.
+//! New scripts are lined up one under another in the top left corner.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Serialize, Serializer};
+use serde_json::{json, Map, Value as SerdeValue};
+
+use crate::{block_names::*, blocks::*, decomp::Sprite};
+
+/// What a block needs to know about the sprite it's in.
+pub struct Context<'a> {
+    blocks: &'a HashMap<String, BlockType>,
+    /// What project.json had for the blocks, when they were loaded from one.
+    originals: Option<&'a HashMap<String, SerdeValue>>,
+    /// Ids of variables, lists and broadcasts, by name.
+    variables: HashMap<String, String>,
+    lists: HashMap<String, String>,
+    broadcasts: HashMap<String, String>,
+    is_stage: bool,
+    /// The argument reporters that belong to each prototype, by name.
+    arguments: HashMap<String, HashMap<String, String>>,
+}
+
+impl<'a> Context<'a> {
+    /// Variables, lists and broadcasts of the stage are visible to every
+    /// sprite, so their ids come from there too.
+    pub fn new(sprite: &'a Sprite, stage: Option<&Sprite>) -> Context<'a> {
+        let mut ctx = Context::lone(&sprite.blocks);
+        ctx.originals = Some(&sprite.originals);
+        ctx.is_stage = sprite.is_stage;
+        for target in stage.into_iter().chain([sprite]) {
+            for (id, variable) in &target.variables {
+                ctx.variables
+                    .insert(variable.name().to_string(), id.clone());
+            }
+            for id in target.lists.keys() {
+                if let Some((name, _)) = target.list(id) {
+                    ctx.lists.insert(name.to_string(), id.clone());
+                }
+            }
+            for (id, name) in &target.broadcasts {
+                ctx.broadcasts.insert(name.clone(), id.clone());
+            }
+        }
+        for (id, block) in ctx.blocks {
+            if let BlockType::ArgumentReporter(a) = block {
+                let prototype = match &a.prev {
+                    Some(a) => a,
+                    None => continue,
+                };
+                if let Some(BlockType::ProceduresPrototype(_)) = ctx.blocks.get(prototype) {
+                    ctx.arguments
+                        .entry(prototype.clone())
+                        .or_default()
+                        .insert(a.name.clone(), id.clone());
+                }
+            }
+        }
+        ctx
+    }
+
+    /// For blocks on their own. Nothing has an id but its name, and no input
+    /// points at a block.
+    pub fn lone(blocks: &'a HashMap<String, BlockType>) -> Context<'a> {
+        Context {
+            blocks,
+            originals: None,
+            variables: HashMap::new(),
+            lists: HashMap::new(),
+            broadcasts: HashMap::new(),
+            is_stage: false,
+            arguments: HashMap::new(),
+        }
+    }
+
+    fn is_block(&self, id: &str) -> bool {
+        self.blocks.contains_key(id)
+    }
+
+    /// Menus and the insides of a custom block's definition.
+    fn is_shadow(&self, block: &BlockType) -> bool {
+        match block {
+            BlockType::Goto(Goto::Menu(_))
+            | BlockType::Glide(Glide::Menu(_))
+            | BlockType::PointTowardsMenu(_)
+            | BlockType::TouchingMenu(_)
+            | BlockType::DistanceToMenu(_)
+            | BlockType::OfObjectMenu(_)
+            | BlockType::KeyOptions(_)
+            | BlockType::CreateCloneOfMenu(_)
+            | BlockType::SoundSoundsMenu(_)
+            | BlockType::SoundEffectsMenu(_)
+            | BlockType::Costume(Costume::WithName(_))
+            | BlockType::Backdrop(Backdrop::WithName(_))
+            | BlockType::DataListIndexAll(_)
+            | BlockType::DataListIndexAllRandom(_)
+            | BlockType::ProceduresPrototype(_) => true,
+            BlockType::ArgumentReporter(a) => matches!(
+                a.prev.as_ref().and_then(|f| self.blocks.get(f)),
+                Some(BlockType::ProceduresPrototype(_))
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// What kind of input something is, which decides what goes under it when
+/// nothing (or a reporter) is in there.
+#[derive(Clone, Copy)]
+pub(crate) enum Slot {
+    Number,
+    Positive,
+    Whole,
+    Integer,
+    Angle,
+    Color,
+    Text,
+    Broadcast,
+    /// Holds a menu block.
+    Menu,
+    /// Boolean inputs and substacks, which are empty when there's nothing.
+    Empty,
+}
+
+impl Slot {
+    pub(crate) fn code(self) -> u64 {
+        match self {
+            Slot::Number => 4,
+            Slot::Positive => 5,
+            Slot::Whole => 6,
+            Slot::Integer => 7,
+            Slot::Angle => 8,
+            Slot::Color => 9,
+            Slot::Text | Slot::Menu | Slot::Empty => 10,
+            Slot::Broadcast => 11,
+        }
+    }
+
+    /// The literal a reporter covers up.
+    pub(crate) fn shadow(self) -> SerdeValue {
+        match self {
+            Slot::Menu | Slot::Empty | Slot::Broadcast => SerdeValue::Null,
+            Slot::Color => json!([self.code(), "#000000"]),
+            a => json!([a.code(), ""]),
+        }
+    }
+}
+
+/// Text as it's shown in the editor.
+pub(crate) fn text(val: &Value) -> String {
+    match val {
+        Value::Number(a) => a.to_string(),
+        Value::String(a) | Value::Variable(a) | Value::List(a) => a.clone(),
+        Value::Null => String::new(),
+    }
+}
+
+struct Encoder<'c, 'a> {
+    ctx: &'c Context<'a>,
+    inputs: Map<String, SerdeValue>,
+    fields: Map<String, SerdeValue>,
+    mutation: Option<SerdeValue>,
+}
+
+impl Encoder<'_, '_> {
+    fn input(&mut self, name: &str, val: Option<&Value>, slot: Slot) {
+        let ctx = self.ctx;
+        let input = match val {
+            Some(Value::String(a)) if ctx.is_block(a) => {
+                match (ctx.blocks.get(a).is_some_and(|f| ctx.is_shadow(f)), slot) {
+                    (true, _) => json!([1, a]),
+                    (false, Slot::Empty) => json!([2, a]),
+                    (false, slot) => json!([3, a, slot.shadow()]),
+                }
+            }
+            Some(Value::Variable(a)) => self.reporter(12, a, &ctx.variables, slot),
+            Some(Value::List(a)) => self.reporter(13, a, &ctx.lists, slot),
+            Some(Value::Null) | None => match slot {
+                Slot::Empty | Slot::Menu => return,
+                Slot::Broadcast => json!([1, [11, "", ""]]),
+                slot => json!([1, slot.shadow()]),
+            },
+            Some(a) => match slot {
+                Slot::Broadcast => {
+                    let name = text(a);
+                    let id = ctx.broadcasts.get(&name).unwrap_or(&name);
+                    json!([1, [11, name, id]])
+                }
+                slot => json!([1, [slot.code(), text(a)]]),
+            },
+        };
+        self.inputs.insert(name.to_string(), input);
+    }
+
+    /// A variable or list dropped straight into an input.
+    fn reporter(
+        &self,
+        code: u64,
+        name: &str,
+        ids: &HashMap<String, String>,
+        slot: Slot,
+    ) -> SerdeValue {
+        let id = ids.get(name).map(String::as_str).unwrap_or(name);
+        match slot {
+            Slot::Empty => json!([2, [code, name, id]]),
+            slot => json!([3, [code, name, id], slot.shadow()]),
+        }
+    }
+
+    fn field(&mut self, name: &str, val: Option<String>) {
+        if let Some(a) = val {
+            self.fields.insert(name.to_string(), json!([a, null]));
+        }
+    }
+
+    /// A field naming a variable, list or broadcast, which also has its id.
+    fn named(&mut self, name: &str, val: &Value, ids: &HashMap<String, String>) {
+        let val = text(val);
+        let id = ids.get(&val).cloned().unwrap_or_else(|| val.clone());
+        self.fields.insert(name.to_string(), json!([val, id]));
+    }
+
+    fn variable(&mut self, val: &Value) {
+        let ctx = self.ctx;
+        self.named("VARIABLE", val, &ctx.variables);
+    }
+
+    fn list(&mut self, val: &Value) {
+        let ctx = self.ctx;
+        self.named("LIST", val, &ctx.lists);
+    }
+}
+
+/// The mutation of a custom block. Calls leave out the names and defaults.
+fn mutation(signature: &ProcedureSignature, prototype: bool) -> SerdeValue {
+    let list = |f: SerdeValue| SerdeValue::String(f.to_string());
+    let mut mutation = json!({
+        "tagName": "mutation",
+        "children": [],
+        "proccode": signature.proccode(),
+        "argumentids": list(json!(signature.argument_ids())),
+        "warp": signature.warp().to_string(),
+    });
+    if prototype {
+        let defaults: Vec<SerdeValue> = signature
+            .argument_defaults()
+            .iter()
+            .map(|f| match f {
+                Value::Number(a) => json!(a),
+                a => json!(text(a)),
+            })
+            .collect();
+        mutation["argumentnames"] = list(json!(signature.argument_names()));
+        mutation["argumentdefaults"] = list(json!(defaults));
+    }
+    mutation
+}
+
+pub(crate) fn is_sound_effect(effect: &Option<Value>) -> bool {
+    matches!(effect, Some(Value::String(a)) if a == "PITCH" || a == "PAN")
+}
+
+/// A block as it's stored in project.json, or None for what isn't a block of
+/// its own. The id is only needed for custom block definitions.
+pub fn block_json(id: &str, block: &BlockType, ctx: &Context) -> Option<SerdeValue> {
+    use Slot::*;
+
+    let mut e = Encoder {
+        ctx,
+        inputs: Map::new(),
+        fields: Map::new(),
+        mutation: None,
+    };
+    let opcode = match block {
+        BlockType::Move(a) => {
+            e.input("STEPS", a.steps.as_ref(), Number);
+            MOTION_MOVE
+        }
+        BlockType::RotateLeft(a) => {
+            e.input("DEGREES", a.degrees.as_ref(), Number);
+            MOTION_TURN_LEFT
+        }
+        BlockType::RotateRight(a) => {
+            e.input("DEGREES", a.degrees.as_ref(), Number);
+            MOTION_TURN_RIGHT
+        }
+        BlockType::Goto(Goto::Pos(a)) => {
+            e.input("X", a.x.as_ref(), Number);
+            e.input("Y", a.y.as_ref(), Number);
+            MOTION_GOTO_XY
+        }
+        BlockType::Goto(Goto::Option(a)) => {
+            e.input("TO", a.option.as_ref(), Menu);
+            MOTION_GOTO
+        }
+        BlockType::Goto(Goto::Menu(a)) => {
+            e.field("TO", a.option.as_ref().map(|f| f.to_string()));
+            MOTION_GOTO_MENU
+        }
+        BlockType::Glide(Glide::Pos(a)) => {
+            e.input("SECS", a.secs.as_ref(), Number);
+            e.input("X", a.x.as_ref(), Number);
+            e.input("Y", a.y.as_ref(), Number);
+            MOTION_GLIDE_SECONDS_TO_XY
+        }
+        BlockType::Glide(Glide::Option(a)) => {
+            e.input("SECS", a.secs.as_ref(), Number);
+            e.input("TO", a.option.as_ref(), Menu);
+            MOTION_GLIDE_TO
+        }
+        BlockType::Glide(Glide::Menu(a)) => {
+            e.field("TO", a.option.as_ref().map(|f| f.to_string()));
+            MOTION_GLIDE_TO_MENU
+        }
+        BlockType::Point(Point::Direction(a)) => {
+            e.input("DIRECTION", a.direction.as_ref(), Angle);
+            MOTION_POINT_DIRECTION
+        }
+        BlockType::Point(Point::Towards(a)) => {
+            e.input("TOWARDS", a.option.as_ref(), Menu);
+            MOTION_POINT_TOWARDS
+        }
+        BlockType::PointTowardsMenu(a) => {
+            e.field("TOWARDS", a.option.as_ref().map(|f| f.to_string()));
+            MOTION_POINT_MENU
+        }
+        BlockType::ChangeX(a) => {
+            e.input("DX", a.x.as_ref(), Number);
+            MOTION_CHANGE_X_BY
+        }
+        BlockType::SetX(a) => {
+            e.input("X", a.x.as_ref(), Number);
+            MOTION_SET_X
+        }
+        BlockType::ChangeY(a) => {
+            e.input("DY", a.y.as_ref(), Number);
+            MOTION_CHANGE_Y_BY
+        }
+        BlockType::SetY(a) => {
+            e.input("Y", a.y.as_ref(), Number);
+            MOTION_SET_Y
+        }
+        BlockType::IfOnEdgeBounce(_) => MOTION_IF_ON_EDGE_BOUNCE,
+        BlockType::SetRotationStyle(a) => {
+            e.field("STYLE", a.style.as_ref().map(|f| f.to_string()));
+            MOTION_SET_ROTATION_STYLE
+        }
+        BlockType::XPosition(_) => MOTION_XPOSITION,
+        BlockType::YPosition(_) => MOTION_YPOSITION,
+        BlockType::Direction(_) => MOTION_DIRECTION,
+
+        BlockType::Say(a) => {
+            e.input("MESSAGE", a.message.as_ref(), Text);
+            e.input("SECS", a.secs.as_ref(), Number);
+            LOOKS_SAY_FOR_SECS
+        }
+        BlockType::SayForever(a) => {
+            e.input("MESSAGE", a.message.as_ref(), Text);
+            LOOKS_SAY
+        }
+        BlockType::Think(a) => {
+            e.input("MESSAGE", a.message.as_ref(), Text);
+            e.input("SECS", a.secs.as_ref(), Number);
+            LOOKS_THINK_FOR_SECS
+        }
+        BlockType::ThinkForever(a) => {
+            e.input("MESSAGE", a.message.as_ref(), Text);
+            LOOKS_THINK
+        }
+        BlockType::SwitchCostume(a) => {
+            e.input("COSTUME", a.costume.as_ref(), Menu);
+            LOOKS_SWITCH_COSTUME_TO
+        }
+        BlockType::NextCostume(_) => LOOKS_NEXT_COSTUME,
+        BlockType::SwitchBackdrop(a) => {
+            e.input("BACKDROP", a.backdrop.as_ref(), Menu);
+            LOOKS_SWITCH_BACKDROP_TO
+        }
+        BlockType::SwitchBackdropAndWait(a) => {
+            e.input("BACKDROP", a.backdrop.as_ref(), Menu);
+            LOOKS_SWITCH_BACKDROP_TO_AND_WAIT
+        }
+        BlockType::NextBackdrop(_) => LOOKS_NEXT_BACKDROP,
+        BlockType::ChangeSize(a) => {
+            e.input("CHANGE", a.units.as_ref(), Number);
+            LOOKS_CHANGE_SIZE_BY
+        }
+        BlockType::SetSize(a) => {
+            e.input("SIZE", a.percentage.as_ref(), Number);
+            LOOKS_SET_SIZE_TO
+        }
+        BlockType::ClearGraphicEffects(_) => LOOKS_CLEAR_GRAPHICS_EFFECTS,
+        BlockType::ShowSprite(_) => LOOKS_SHOW,
+        BlockType::HideSprite(_) => LOOKS_HIDE,
+        BlockType::HideAllSprites(_) => LOOKS_HIDE_ALL_SPRITES,
+        BlockType::GotoLayer(a) => {
+            e.field("FRONT_BACK", a.option.as_ref().map(|f| f.to_string()));
+            LOOKS_GOTO_FRONT_BACK
+        }
+        BlockType::ChangeLayer(a) => {
+            e.field(
+                "FORWARD_BACKWARD",
+                a.direction.as_ref().map(|f| match f {
+                    LayerDirection::Value(a) => text(a),
+                    a => a.to_string(),
+                }),
+            );
+            e.input("NUM", a.by.as_ref(), Integer);
+            LOOKS_GO_FORWARD_BACKWARD_LAYERS
+        }
+        BlockType::Costume(Costume::ByNumber(_)) => {
+            e.field("NUMBER_NAME", Some("number".to_string()));
+            LOOKS_COSTUME_NUMBER_NAME
+        }
+        BlockType::Costume(Costume::ByName(_)) => {
+            e.field("NUMBER_NAME", Some("name".to_string()));
+            LOOKS_COSTUME_NUMBER_NAME
+        }
+        BlockType::Costume(Costume::WithName(a)) => {
+            e.field("COSTUME", a.as_ref().map(text));
+            LOOKS_COSTUME
+        }
+        BlockType::Backdrop(Backdrop::ByNumber(_)) => {
+            e.field("NUMBER_NAME", Some("number".to_string()));
+            LOOKS_BACKDROP_NUMBER_NAME
+        }
+        BlockType::Backdrop(Backdrop::ByName(_)) => {
+            e.field("NUMBER_NAME", Some("name".to_string()));
+            LOOKS_BACKDROP_NUMBER_NAME
+        }
+        BlockType::Backdrop(Backdrop::WithName(a)) => {
+            e.field("BACKDROP", a.as_ref().map(text));
+            LOOKS_BACKDROP
+        }
+        BlockType::Size(_) => LOOKS_SIZE,
+
+        BlockType::PlaySound(a) => {
+            e.input("SOUND_MENU", a.sound.as_ref(), Menu);
+            SOUND_PLAY
+        }
+        BlockType::PlaySoundUntilDone(a) => {
+            e.input("SOUND_MENU", a.sound.as_ref(), Menu);
+            SOUND_PLAY_UNTIL_DONE
+        }
+        BlockType::StartSound(a) => {
+            e.input("SOUND_MENU", Some(&Value::String(a.sound.clone())), Menu);
+            SOUND_PLAY
+        }
+        BlockType::StopAllSounds(_) => SOUND_STOP_ALL_SOUNDS,
+        // looks and sound effects are read into the same blocks, and only
+        // sounds have pitch and pan.
+        BlockType::ChangeEffectBy(a) => {
+            e.field("EFFECT", a.effect.as_ref().map(text));
+            match is_sound_effect(&a.effect) {
+                true => {
+                    e.input("VALUE", a.units.as_ref(), Number);
+                    SOUND_CHANGE_EFFECT_BY
+                }
+                false => {
+                    e.input("CHANGE", a.units.as_ref(), Number);
+                    LOOKS_CHANGE_EFFECT_BY
+                }
+            }
+        }
+        BlockType::SetEffectTo(a) => {
+            e.field("EFFECT", a.effect.as_ref().map(text));
+            e.input("VALUE", a.percentage.as_ref(), Number);
+            match is_sound_effect(&a.effect) {
+                true => SOUND_SET_EFFECT_TO,
+                false => LOOKS_SET_EFFECT_TO,
+            }
+        }
+        BlockType::ClearSoundEffects(_) => SOUND_CLEAR_EFFECTS,
+        BlockType::ChangeVolumeBy(a) => {
+            e.input("VOLUME", a.units.as_ref(), Number);
+            SOUND_CHANGE_VOLUME_BY
+        }
+        BlockType::SetVolumeTo(a) => {
+            e.input("VOLUME", a.percentage.as_ref(), Number);
+            SOUND_SET_VOLUME_TO
+        }
+        BlockType::Volume(_) => SOUND_VOLUME,
+        BlockType::SoundSoundsMenu(a) => {
+            e.field("SOUND_MENU", a.option.as_ref().map(text));
+            SOUND_SOUNDS_MENU
+        }
+        BlockType::SoundEffectsMenu(a) => {
+            e.field("EFFECT", a.option.as_ref().map(|f| f.to_string()));
+            SOUND_EFFECTS_MENU
+        }
+
+        BlockType::WhenGreenFlagClicked(_) => EVENT_WHEN_FLAG_CLICKED,
+        BlockType::WhenKeyPressed(a) => {
+            e.field("KEY_OPTION", a.key.as_ref().map(|f| f.to_string()));
+            EVENT_WHEN_KEY_PRESSED
+        }
+        BlockType::WhenSpriteClicked(_) => EVENT_WHEN_THIS_SPRITECLICKED,
+        BlockType::WhenStageClicked(_) => EVENT_WHEN_STAGE_CLICKED,
+        BlockType::WhenBackdropSwitchesTo(a) => {
+            e.field("BACKDROP", a.backdrop.as_ref().map(text));
+            EVENT_WHEN_BACKDROP_SWITCHESTO
+        }
+        BlockType::WhenOptionGreaterThen(a) => {
+            e.field(
+                "WHENGREATERTHANMENU",
+                a.option.as_ref().map(|f| f.to_string()),
+            );
+            e.input("VALUE", a.by.as_ref(), Number);
+            EVENT_WHEN_GREATER_THAN
+        }
+        BlockType::WhenIRecieveBroadcast(a) => {
+            let name = a.broadcast.clone().unwrap_or(Value::Null);
+            e.named("BROADCAST_OPTION", &name, &ctx.broadcasts);
+            EVENT_WHEN_BROADCAST_RECEIVED
+        }
+        BlockType::Broadcast(a) => {
+            e.input("BROADCAST_INPUT", a.broadcast.as_ref(), Broadcast);
+            EVENT_BROADCAST
+        }
+        BlockType::BroadcastAndWait(a) => {
+            e.input("BROADCAST_INPUT", a.broadcast.as_ref(), Broadcast);
+            EVENT_BROADCAST_AND_WAIT
+        }
+
+        BlockType::WaitSeconds(a) => {
+            e.input("DURATION", a.seconds.as_ref(), Positive);
+            CONTROL_WAIT
+        }
+        BlockType::Repeat(a) => {
+            e.input("TIMES", a.units.as_ref(), Whole);
+            e.input("SUBSTACK", a.substack.as_ref(), Empty);
+            CONTROL_REPEAT
+        }
+        BlockType::Forever(a) => {
+            e.input("SUBSTACK", a.substack.as_ref(), Empty);
+            CONTROL_FOREVER
+        }
+        BlockType::IfThen(a) => {
+            e.input("CONDITION", a.condition.as_ref(), Empty);
+            e.input("SUBSTACK", a.then.as_ref(), Empty);
+            CONTROL_IF
+        }
+        BlockType::IfThenElse(a) => {
+            e.input("CONDITION", a.condition.as_ref(), Empty);
+            e.input("SUBSTACK", a.then.as_ref(), Empty);
+            e.input("SUBSTACK2", a.otherwise.as_ref(), Empty);
+            CONTROL_IF_ELSE
+        }
+        BlockType::WaitUntil(a) => {
+            e.input("CONDITION", a.condition.as_ref(), Empty);
+            CONTROL_WAIT_UNTIL
+        }
+        BlockType::RepeatUntil(a) => {
+            e.input("CONDITION", a.condition.as_ref(), Empty);
+            e.input("SUBSTACK", a.substack.as_ref(), Empty);
+            CONTROL_REPEAT_UNTIL
+        }
+        BlockType::StopAll(a) => {
+            let others = matches!(a.option, Some(StopOption::OtherScriptsInSprite));
+            e.field(
+                "STOP_OPTION",
+                a.option.as_ref().map(|f| match (f, ctx.is_stage) {
+                    (StopOption::OtherScriptsInSprite, true) => {
+                        "other scripts in stage".to_string()
+                    }
+                    (a, _) => a.to_string(),
+                }),
+            );
+            // only "other scripts" can have blocks after it.
+            e.mutation = Some(json!({
+                "tagName": "mutation",
+                "children": [],
+                "hasnext": others.to_string(),
+            }));
+            CONTROL_STOP
+        }
+        BlockType::WhenIStartAsAClone(_) => CONTROL_START_AS_CLONE,
+        BlockType::CreateCloneOf(a) => {
+            e.input("CLONE_OPTION", a.of.as_ref(), Menu);
+            CONTROL_CREATE_CLONE_OF
+        }
+        BlockType::CreateCloneOfMenu(a) => {
+            e.field("CLONE_OPTION", a.of.as_ref().map(|f| f.to_string()));
+            CONTROL_CREATE_CLONE_OF_MENU
+        }
+        BlockType::DeleteClone(_) => CONTROL_DELETE_THIS_CLONE,
+
+        BlockType::Touching(a) => {
+            e.input("TOUCHINGOBJECTMENU", a.touching.as_ref(), Menu);
+            SENSING_TOUCHING_OBJECT
+        }
+        BlockType::TouchingMenu(a) => {
+            e.field(
+                "TOUCHINGOBJECTMENU",
+                a.touching.as_ref().map(|f| f.to_string()),
+            );
+            SENSING_TOUCHING_OBJECT_MENU
+        }
+        BlockType::TouchingColor(a) => {
+            e.input("COLOR", a.color.as_ref(), Color);
+            SENSING_TOUCHING_COLOR
+        }
+        BlockType::ColorTouchingColor(a) => {
+            e.input("COLOR", a.color1.as_ref(), Color);
+            e.input("COLOR2", a.color2.as_ref(), Color);
+            SENSING_COLOR_IS_TOUCHING_COLOR
+        }
+        BlockType::DistanceTo(a) => {
+            e.input("DISTANCETOMENU", a.to.as_ref(), Menu);
+            SENSING_DISTANCE_TO
+        }
+        BlockType::DistanceToMenu(a) => {
+            e.field("DISTANCETOMENU", a.to.as_ref().map(|f| f.to_string()));
+            SENSING_DISTANCE_TO_MENU
+        }
+        BlockType::Of(a) => {
+            e.field("PROPERTY", a.property.clone());
+            e.input("OBJECT", a.object.as_ref(), Menu);
+            SENSING_OF
+        }
+        BlockType::OfObjectMenu(a) => {
+            e.field("OBJECT", a.object.as_ref().map(|f| f.to_string()));
+            SENSING_OF_OBJECT_MENU
+        }
+        BlockType::AskAndWait(a) => {
+            e.input("QUESTION", a.question.as_ref(), Text);
+            SENSING_ASK_AND_WAIT
+        }
+        BlockType::Answer(_) => SENSING_ANSWER,
+        BlockType::KeyPressed(a) => {
+            e.input("KEY_OPTION", a.key.as_ref(), Menu);
+            SENSING_KEY_PRESSED
+        }
+        BlockType::KeyOptions(a) => {
+            e.field("KEY_OPTION", a.key.as_ref().map(|f| f.to_string()));
+            SENSING_KEY_OPTIONS
+        }
+        BlockType::MouseDown(_) => SENSING_MOUSE_DOWN,
+        BlockType::MouseX(_) => SENSING_MOUSE_X,
+        BlockType::MouseY(_) => SENSING_MOUSE_Y,
+        // only ever a part of set drag mode.
+        BlockType::DraggableOption(_) => return None,
+        BlockType::SetDragMode(a) => {
+            e.field("DRAG_MODE", a.option.as_ref().map(|f| f.to_string()));
+            SENSING_SET_DRAG_MODE
+        }
+        BlockType::Loudness(_) => SENSING_LOUDNESS,
+        BlockType::Timer(_) => SENSING_TIMER,
+        BlockType::ResetTimer(_) => SENSING_RESET_TIMER,
+        BlockType::BackdropOf(a) => {
+            let property = match a.backdrop {
+                BackdropOfOption::BackdropNumber => "backdrop #",
+                BackdropOfOption::BackdropName => "backdrop name",
+                BackdropOfOption::Volume => "volume",
+                BackdropOfOption::MyVariable => "my variable",
+            };
+            e.field("PROPERTY", Some(property.to_string()));
+            SENSING_OF
+        }
+        BlockType::CurrentTime(a) => {
+            e.field("CURRENTMENU", a.option.as_ref().map(|f| f.to_string()));
+            SENSING_CURRENT
+        }
+        BlockType::DaysSince2000(_) => SENSING_DAYS_SINCE_2000,
+        BlockType::Username(_) => SENSING_USERNAME,
+
+        BlockType::Add(a) => {
+            e.input("NUM1", a.a.as_ref(), Number);
+            e.input("NUM2", a.b.as_ref(), Number);
+            OPERATOR_ADD
+        }
+        BlockType::Sub(a) => {
+            e.input("NUM1", a.a.as_ref(), Number);
+            e.input("NUM2", a.b.as_ref(), Number);
+            OPERATOR_SUBTRACT
+        }
+        BlockType::Mul(a) => {
+            e.input("NUM1", a.a.as_ref(), Number);
+            e.input("NUM2", a.b.as_ref(), Number);
+            OPERATOR_MULTIPLY
+        }
+        BlockType::Divide(a) => {
+            e.input("NUM1", a.a.as_ref(), Number);
+            e.input("NUM2", a.b.as_ref(), Number);
+            OPERATOR_DIVIDE
+        }
+        BlockType::PickRandom(a) => {
+            e.input("FROM", a.min.as_ref(), Number);
+            e.input("TO", a.max.as_ref(), Number);
+            OPERATOR_RANDOM
+        }
+        BlockType::GreaterThen(a) => {
+            e.input("OPERAND1", a.a.as_ref(), Text);
+            e.input("OPERAND2", a.b.as_ref(), Text);
+            OPERATOR_GREATER_THEN
+        }
+        BlockType::LesserThen(a) => {
+            e.input("OPERAND1", a.a.as_ref(), Text);
+            e.input("OPERAND2", a.b.as_ref(), Text);
+            OPERATOR_LESSER_THEN
+        }
+        BlockType::EqualTo(a) => {
+            e.input("OPERAND1", a.a.as_ref(), Text);
+            e.input("OPERAND2", a.b.as_ref(), Text);
+            OPERATOR_EQUALS
+        }
+        BlockType::And(a) => {
+            e.input("OPERAND1", a.a.as_ref(), Empty);
+            e.input("OPERAND2", a.b.as_ref(), Empty);
+            OPERATOR_AND
+        }
+        BlockType::Or(a) => {
+            e.input("OPERAND1", a.a.as_ref(), Empty);
+            e.input("OPERAND2", a.b.as_ref(), Empty);
+            OPERATOR_OR
+        }
+        BlockType::Not(a) => {
+            e.input("OPERAND", a.a.as_ref(), Empty);
+            OPERATOR_NOT
+        }
+        BlockType::Join(a) => {
+            e.input("STRING1", a.a.as_ref(), Text);
+            e.input("STRING2", a.b.as_ref(), Text);
+            OPERATOR_JOIN
+        }
+        BlockType::LetterOf(a) => {
+            e.input("LETTER", a.index.as_ref(), Whole);
+            e.input("STRING", a.a.as_ref(), Text);
+            OPERATOR_LETTER_OF
+        }
+        BlockType::LengthOf(a) => {
+            e.input("STRING", a.a.as_ref(), Text);
+            OPERATOR_LENGTH
+        }
+        BlockType::Contains(a) => {
+            e.input("STRING1", a.a.as_ref(), Text);
+            e.input("STRING2", a.b.as_ref(), Text);
+            OPERATOR_CONTAINS
+        }
+        BlockType::Modulo(a) => {
+            e.input("NUM1", a.a.as_ref(), Number);
+            e.input("NUM2", a.b.as_ref(), Number);
+            OPERATOR_MOD
+        }
+        BlockType::Round(a) => {
+            e.input("NUM", a.a.as_ref(), Number);
+            OPERATOR_ROUND
+        }
+        BlockType::MathOp(a) => {
+            e.field("OPERATOR", a.operator.as_ref().map(|f| f.to_string()));
+            e.input("NUM", a.a.as_ref(), Number);
+            OPERATOR_MATHOP
+        }
+
+        BlockType::DataGetVariable(a) => {
+            e.variable(&a.variable);
+            DATA_VARIABLE
+        }
+        BlockType::DataSetVariableTo(a) => {
+            e.variable(&a.variable);
+            e.input("VALUE", Some(&a.value), Text);
+            DATA_SET_VARIABLE_TO
+        }
+        BlockType::DataChangeVariableBy(a) => {
+            e.variable(&a.variable);
+            e.input("VALUE", Some(&a.value), Number);
+            DATA_CHANGE_VARIABLE_BY
+        }
+        BlockType::DataShowVariable(a) => {
+            e.variable(&a.variable);
+            DATA_SHOW_VARIABLE
+        }
+        BlockType::DataHideVariable(a) => {
+            e.variable(&a.variable);
+            DATA_HIDE_VARIABLE
+        }
+        BlockType::DataListContents(a) => {
+            e.list(&a.variable);
+            DATA_LIST_COTNENTS
+        }
+        BlockType::DataListIndexAll(_) => DATA_LIST_INDEX_ALL,
+        BlockType::DataListIndexAllRandom(_) => DATA_LIST_INDEX_RANDOM,
+        BlockType::DataAddToList(a) => {
+            e.list(&a.list);
+            e.input("ITEM", Some(&a.item), Text);
+            DATA_ADD_TO_LIST
+        }
+        BlockType::DataDeleteOfList(a) => {
+            e.list(&a.list);
+            e.input("INDEX", Some(&a.item), Integer);
+            DATA_DELETE_OF_LIST
+        }
+        BlockType::DataDeleteAllOfList(a) => {
+            e.list(&a.list);
+            DATA_DELETE_ALL_OF_LIST
+        }
+        BlockType::DataInsertAtList(a) => {
+            e.list(&a.list);
+            e.input("ITEM", Some(&a.item), Text);
+            e.input("INDEX", Some(&a.index), Integer);
+            DATA_INSERT_AT_LIST
+        }
+        BlockType::DataReplaceItemOfList(a) => {
+            e.list(&a.list);
+            e.input("INDEX", Some(&a.index), Integer);
+            e.input("ITEM", Some(&a.item), Text);
+            DATA_REPLACE_ITEM_OF_LIST
+        }
+        BlockType::DataItemOfList(a) => {
+            e.list(&a.list);
+            e.input("INDEX", Some(&a.index), Integer);
+            DATA_ITEM_OF_LIST
+        }
+        BlockType::DataLengthOfList(a) => {
+            e.list(&a.list);
+            DATA_LENGTH_OF_LIST
+        }
+        BlockType::DataListContainsItem(a) => {
+            e.list(&a.list);
+            e.input("ITEM", Some(&a.input), Text);
+            DATA_LIST_CONTAINS_ITEM
+        }
+        BlockType::ShowList(a) => {
+            e.list(&a.list);
+            DATA_SHOW_LIST
+        }
+        BlockType::HideList(a) => {
+            e.list(&a.list);
+            DATA_HIDE_LIST
+        }
+
+        BlockType::ProceduresCall(a) => {
+            let types = a.signature.argument_types();
+            for (i, id) in a.signature.argument_ids().iter().enumerate() {
+                let slot = match types.get(i) {
+                    Some(ProccodeSegment::Boolean) => Empty,
+                    _ => Text,
+                };
+                e.input(id, a.arguments.get(i).and_then(|f| f.as_ref()), slot);
+            }
+            e.mutation = Some(mutation(&a.signature, false));
+            PROCEDURES_CALL
+        }
+        BlockType::ProceduresDeclaration(_) => PROCEDURES_DECLARATION,
+        BlockType::ProceduresDefinition(a) => {
+            e.inputs
+                .insert("custom_block".to_string(), json!([1, a.block]));
+            PROCEDURES_DEFINITION
+        }
+        BlockType::ProceduresPrototype(a) => {
+            let signature = &a.signature;
+            if let Some(reporters) = ctx.arguments.get(id) {
+                for (arg, name) in signature
+                    .argument_ids()
+                    .iter()
+                    .zip(signature.argument_names())
+                {
+                    if let Some(reporter) = reporters.get(name) {
+                        e.inputs.insert(arg.clone(), json!([1, reporter]));
+                    }
+                }
+            }
+            e.mutation = Some(mutation(signature, true));
+            PROCEDURES_PROTOTYPE
+        }
+        BlockType::ArgumentReporter(a) => {
+            e.field("VALUE", Some(a.name.clone()));
+            match a.boolean {
+                true => ARGUMENT_REPORTER_BOOLEAN,
+                false => ARGUMENT_REPORTER_STRING_NUMBER,
+            }
+        }
+
+        BlockType::UnusedOpcode(a) => a.name.as_str(),
+        BlockType::InvalidOpcode(a) => a.name.as_str(),
+        BlockType::Stray => return None,
+    };
+
+    let parent = block.as_block().prev();
+    let mut json = json!({
+        "opcode": opcode,
+        "next": block.next(),
+        "parent": parent,
+        "inputs": e.inputs,
+        "fields": e.fields,
+        "shadow": ctx.is_shadow(block),
+        "topLevel": parent.is_none(),
+    });
+    if parent.is_none() {
+        json["x"] = json!(0);
+        json["y"] = json!(0);
+    }
+    if let Some(a) = e.mutation {
+        json["mutation"] = a;
+    }
+    Some(json)
+}
+
+/// Takes what the editor had for a block that was loaded, and has changed
+/// since, over to what it is now: where it was, what was in its inputs
+/// underneath any reporters, its comment. Blocks that haven't changed are
+/// written back exactly as they were.
+fn merge(id: &str, now: SerdeValue, original: &SerdeValue, ctx: &Context) -> SerdeValue {
+    let then = block_json(id, &BlockType::from_json_lenient(original).0, ctx);
+    let (mut now, then) = match (now, then) {
+        (a, Some(b)) if a == b => return original.clone(),
+        (SerdeValue::Object(a), Some(SerdeValue::Object(b))) => (a, b),
+        (a, _) => return a,
+    };
+    let original = match original.as_object() {
+        Some(a) => a,
+        None => return SerdeValue::Object(now),
+    };
+    // inputs and fields that are still the same.
+    for key in ["inputs", "fields"] {
+        let (now, then, original) = match (now.get_mut(key), then.get(key), original.get(key)) {
+            (Some(SerdeValue::Object(a)), Some(b), Some(c)) => (a, b, c),
+            _ => continue,
+        };
+        for (name, value) in now.iter_mut() {
+            if let Some(a) = original.get(name) {
+                if then.get(name) == Some(value) {
+                    *value = a.clone();
+                }
+            }
+        }
+    }
+    if now.get("mutation") == then.get("mutation") {
+        if let Some(a) = original.get("mutation") {
+            now.insert("mutation".to_string(), a.clone());
+        }
+    }
+    // only the editor knows which blocks are shadows.
+    if let Some(a) = original.get("shadow") {
+        now.insert("shadow".to_string(), a.clone());
+    }
+    if now.get("topLevel") == Some(&json!(true)) {
+        for key in ["x", "y"] {
+            if let Some(a) = original.get(key) {
+                now.insert(key.to_string(), a.clone());
+            }
+        }
+    }
+    for (key, value) in original {
+        now.entry(key).or_insert_with(|| value.clone());
+    }
+    SerdeValue::Object(now)
+}
+
+/// The blocks of a sprite, as they are in project.json.
+pub fn blocks_json(ctx: &Context) -> Map<String, SerdeValue> {
+    let mut blocks = Map::new();
+    // blocks that are the same as they were, and scripts that were already
+    // somewhere.
+    let mut kept = HashSet::new();
+    let mut placed = HashSet::new();
+    for (id, block) in ctx.blocks {
+        let original = ctx.originals.and_then(|f| f.get(id));
+        let json = match (block_json(id, block, ctx), original) {
+            (Some(a), Some(b)) => merge(id, a, b, ctx),
+            (Some(a), None) => a,
+            // variables and lists lying around on their own.
+            (None, Some(b)) => b.clone(),
+            (None, None) => continue,
+        };
+        if original == Some(&json) {
+            kept.insert(id.clone());
+        }
+        if original.is_some_and(|f| f.get("x").is_some()) && json.get("x").is_some() {
+            placed.insert(id.clone());
+        }
+        blocks.insert(id.clone(), json);
+    }
+
+    // menus that don't remember which block they're in.
+    let mut parents = Vec::new();
+    for (id, block) in &blocks {
+        let inputs = block["inputs"]
+            .as_object()
+            .into_iter()
+            .flat_map(|f| f.values());
+        for child in inputs.filter_map(|f| f.get(1)?.as_str()) {
+            match blocks.get(child) {
+                Some(a) if a["parent"].is_null() && !kept.contains(child) => {
+                    parents.push((child.to_string(), id.clone()))
+                }
+                _ => {}
+            }
+        }
+    }
+    for (child, parent) in parents {
+        if let Some(SerdeValue::Object(a)) = blocks.get_mut(&child) {
+            a.insert("parent".to_string(), json!(parent));
+            a.insert("topLevel".to_string(), json!(false));
+            a.remove("x");
+            a.remove("y");
+        }
+    }
+
+    // new scripts go under the ones that are already there.
+    let bottom = placed
+        .iter()
+        .filter_map(|f| blocks[f]["y"].as_f64())
+        .fold(None, |a: Option<f64>, b| Some(a.map_or(b, |a| a.max(b))));
+    let mut scripts: Vec<String> = blocks
+        .iter()
+        .filter(|f| f.1["topLevel"] == json!(true) && !placed.contains(f.0))
+        .map(|f| f.0.clone())
+        .collect();
+    scripts.sort();
+    for (i, id) in scripts.iter().enumerate() {
+        let y = bottom.map_or(0.0, |f| f + 400.0) + i as f64 * 400.0;
+        blocks[id]["x"] = json!(0);
+        blocks[id]["y"] = json!(y);
+    }
+    blocks
+}
+
+/// On its own, a block can't tell which of its inputs point at other blocks,
+/// or what the ids of the variables it uses are. Serialize its sprite for that.
+impl Serialize for BlockType {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let blocks = HashMap::new();
+        block_json("", self, &Context::lone(&blocks)).serialize(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, io::Read};
+
+    use super::*;
+    use crate::assets::DirStore;
+    use crate::decomp::{LoadOptions, Project};
+    use crate::test_util::{check_blocks, test_project, transcript};
+
+    #[test]
+    fn round_trips_test_project() {
+        let original: SerdeValue =
+            serde_json::from_str(&std::fs::read_to_string("./test.json").unwrap()).unwrap();
+        let project = test_project();
+        let json = project.to_json();
+        for key in ["monitors", "meta", "extensions"] {
+            assert_eq!(json[key], original[key]);
+        }
+        for (a, b) in json["targets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .zip(original["targets"].as_array().unwrap())
+        {
+            let blocks = a["blocks"].as_object().unwrap();
+            assert_eq!(blocks.len(), b["blocks"].as_object().unwrap().len());
+            for (id, block) in b["blocks"].as_object().unwrap() {
+                assert_eq!(&blocks[id], block, "{}", id);
+            }
+            for (key, value) in b.as_object().unwrap() {
+                assert_eq!(&a[key], value, "{}", key);
+            }
+            assert_eq!(a, b);
+        }
+
+        // numbers f32 can't hold.
+        let mut sprite = project.sprites()[1].clone();
+        sprite.position_x = 0.1;
+        sprite.direction = 90.000001;
+        let moved = sprite.to_json(None);
+        assert_eq!(
+            (moved["x"].as_f64(), moved["direction"].as_f64()),
+            (Some(0.1), Some(90.000001))
+        );
+
+        let reloaded: Project = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(transcript(&project), transcript(&reloaded));
+        // and nothing changes the second time around.
+        assert_eq!(json, reloaded.to_json());
+    }
+
+    #[test]
+    fn writes_valid_blocks() {
+        let project = test_project();
+        let stage = project.sprites().iter().find(|f| f.is_stage);
+        let mut sprites = Vec::new();
+        for sprite in project.sprites() {
+            // as if they were made here.
+            let mut sprite = sprite.clone();
+            sprite.originals.clear();
+            let json = sprite.to_json(stage);
+            check_blocks(&json);
+            sprites.push(json);
+        }
+
+        let reloaded: Project =
+            serde_json::from_value(json!({"targets": sprites, "meta": project.to_json()["meta"]}))
+                .unwrap();
+        for (a, b) in project.sprites().iter().zip(reloaded.sprites()) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.blocks.len(), b.blocks.len());
+        }
+        assert_eq!(transcript(&project), transcript(&reloaded));
+    }
+
+    #[test]
+    fn changed_scripts_stay_where_they_were() {
+        let project = test_project();
+        let mut sprite = project.sprites()[1].clone();
+        let (id, original) = sprite
+            .originals
+            .iter()
+            .find(|f| f.1["opcode"] == json!(EVENT_WHEN_FLAG_CLICKED))
+            .map(|(a, b)| (a.clone(), b.clone()))
+            .unwrap();
+        match sprite.blocks.get_mut(&id) {
+            Some(BlockType::WhenGreenFlagClicked(a)) => a.next = None,
+            _ => unreachable!(),
+        }
+
+        let json = sprite.to_json(project.sprites().iter().find(|f| f.is_stage));
+        let block = &json["blocks"][&id];
+        assert_eq!(block["next"], SerdeValue::Null);
+        assert_eq!(block["x"], original["x"]);
+        assert_eq!(block["y"], original["y"]);
+    }
+
+    #[test]
+    fn saves_sb3() {
+        // costumes and sounds whose md5 is right, with their names in them.
+        let mut json = test_project().to_json();
+        let mut files = BTreeMap::new();
+        for target in json["targets"].as_array_mut().unwrap() {
+            for kind in ["costumes", "sounds"] {
+                for asset in target[kind].as_array_mut().unwrap() {
+                    let data = asset["name"].as_str().unwrap().to_string();
+                    let md5 = format!("{:x}", md5::compute(&data));
+                    let md5ext = format!("{}.{}", md5, asset["dataFormat"].as_str().unwrap());
+                    asset["assetId"] = json!(md5);
+                    asset["md5ext"] = json!(md5ext);
+                    files.insert(md5ext, data);
+                }
+            }
+        }
+        let project = Project::from_json_str(&json.to_string(), LoadOptions::default()).unwrap();
+        let dir = std::env::temp_dir().join(format!("yase-save-{}", std::process::id()));
+        let assets = dir.join("assets");
+        std::fs::create_dir_all(&assets).unwrap();
+        let sb3 = dir.join("test.sb3");
+
+        // nothing to take the costumes from yet.
+        assert!(project.save_sb3(&sb3, &DirStore::new(&assets)).is_err());
+        assert!(!sb3.exists());
+
+        for (name, data) in &files {
+            std::fs::write(assets.join(name), data).unwrap();
+        }
+        project.save_sb3(&sb3, &DirStore::new(&assets)).unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&sb3).unwrap()).unwrap();
+        for (name, expected) in &files {
+            let mut data = String::new();
+            zip.by_name(name)
+                .unwrap()
+                .read_to_string(&mut data)
+                .unwrap();
+            assert_eq!(&data, expected);
+        }
+        let mut json = String::new();
+        zip.by_name("project.json")
+            .unwrap()
+            .read_to_string(&mut json)
+            .unwrap();
+        let saved: SerdeValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(saved, project.to_json());
+
+        let data = std::fs::read(&sb3).unwrap();
+        let loaded = Project::from_sb3_bytes(&data, LoadOptions::default()).unwrap();
+        assert_eq!(loaded.to_json(), project.to_json());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+//! How big a project is and what it's made of.
+//!
+//! Blocks are counted by palette category, along with how deep control
+//! blocks go inside each other and how much custom blocks, clones and
+//! broadcasts get used. Everything that's in a sprite counts, strays too.
+use std::collections::BTreeMap;
+
+use serde_derive::Serialize;
+
+use crate::{
+    blocks::{BlockType, Category},
+    decomp::{Project, Sprite},
+    visit::{BlockVisitor, Walker},
+};
+
+/// Counts for each sprite, and all of them added up.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub sprites: Vec<SpriteStats>,
+    /// Named `total`, with the deepest nesting of any sprite.
+    pub total: SpriteStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpriteStats {
+    pub name: String,
+    /// Blocks in each category, by its name. Menus aren't counted.
+    pub blocks: BTreeMap<&'static str, usize>,
+    /// Every top level stack, strays included.
+    pub scripts: usize,
+    /// The most control blocks that are inside each other, 0 without any.
+    pub max_depth: usize,
+    pub custom_blocks: usize,
+    pub custom_block_calls: usize,
+    pub clones_created: usize,
+    pub clone_hats: usize,
+    pub clones_deleted: usize,
+    /// Broadcast blocks, waiting or not.
+    pub broadcasts: usize,
+    pub broadcast_hats: usize,
+}
+
+impl Stats {
+    pub fn new(project: &Project) -> Stats {
+        let sprites: Vec<SpriteStats> = project.sprites().iter().map(SpriteStats::new).collect();
+        let mut total = SpriteStats::empty("total");
+        for sprite in &sprites {
+            for (category, count) in &sprite.blocks {
+                *total.blocks.get_mut(category).unwrap() += count;
+            }
+            total.scripts += sprite.scripts;
+            total.max_depth = total.max_depth.max(sprite.max_depth);
+            total.custom_blocks += sprite.custom_blocks;
+            total.custom_block_calls += sprite.custom_block_calls;
+            total.clones_created += sprite.clones_created;
+            total.clone_hats += sprite.clone_hats;
+            total.clones_deleted += sprite.clones_deleted;
+            total.broadcasts += sprite.broadcasts;
+            total.broadcast_hats += sprite.broadcast_hats;
+        }
+        Stats { sprites, total }
+    }
+}
+
+impl SpriteStats {
+    fn empty(name: &str) -> SpriteStats {
+        SpriteStats {
+            name: name.to_string(),
+            blocks: Category::ALL.iter().map(|f| (f.name(), 0)).collect(),
+            scripts: 0,
+            max_depth: 0,
+            custom_blocks: 0,
+            custom_block_calls: 0,
+            clones_created: 0,
+            clone_hats: 0,
+            clones_deleted: 0,
+            broadcasts: 0,
+            broadcast_hats: 0,
+        }
+    }
+
+    pub fn new(sprite: &Sprite) -> SpriteStats {
+        let walker = Walker::new(&sprite.blocks);
+        let mut counter = Counter {
+            stats: SpriteStats::empty(&sprite.name),
+            depth: 0,
+        };
+        counter.stats.scripts = walker.tops().len();
+        walker.walk(&mut counter);
+        counter.stats
+    }
+
+    /// The whole row of the table, blocks first.
+    fn row(&self) -> Vec<String> {
+        let mut row = vec![self.name.clone()];
+        row.extend(
+            Category::ALL
+                .iter()
+                .map(|f| self.blocks[f.name()].to_string()),
+        );
+        row.push(self.blocks.values().sum::<usize>().to_string());
+        for count in [
+            self.scripts,
+            self.max_depth,
+            self.custom_blocks,
+            self.custom_block_calls,
+            self.clones_created,
+            self.clone_hats,
+            self.clones_deleted,
+            self.broadcasts,
+            self.broadcast_hats,
+        ] {
+            row.push(count.to_string());
+        }
+        row
+    }
+}
+
+struct Counter {
+    stats: SpriteStats,
+    /// Control blocks the current one is inside of.
+    depth: usize,
+}
+
+fn is_control(block: &BlockType) -> bool {
+    matches!(
+        block,
+        BlockType::Repeat(_)
+            | BlockType::Forever(_)
+            | BlockType::RepeatUntil(_)
+            | BlockType::IfThen(_)
+            | BlockType::IfThenElse(_)
+    )
+}
+
+impl<'a> BlockVisitor<'a> for Counter {
+    fn enter(&mut self, _: &'a str, block: &'a BlockType, _: usize) -> bool {
+        if let Some(category) = block.category() {
+            *self.stats.blocks.get_mut(category.name()).unwrap() += 1;
+        }
+        let stats = &mut self.stats;
+        match block {
+            BlockType::ProceduresDefinition(_) => stats.custom_blocks += 1,
+            BlockType::ProceduresCall(_) => stats.custom_block_calls += 1,
+            BlockType::CreateCloneOf(_) => stats.clones_created += 1,
+            BlockType::WhenIStartAsAClone(_) => stats.clone_hats += 1,
+            BlockType::DeleteClone(_) => stats.clones_deleted += 1,
+            BlockType::Broadcast(_) | BlockType::BroadcastAndWait(_) => stats.broadcasts += 1,
+            BlockType::WhenIRecieveBroadcast(_) => stats.broadcast_hats += 1,
+            _ => {}
+        }
+        if is_control(block) {
+            self.depth += 1;
+            stats.max_depth = stats.max_depth.max(self.depth);
+        }
+        true
+    }
+
+    fn leave(&mut self, _: &'a str, block: &'a BlockType, _: usize) {
+        if is_control(block) {
+            self.depth -= 1;
+        }
+    }
+}
+
+const HEADERS: [&str; 20] = [
+    "sprite",
+    "motion",
+    "looks",
+    "sound",
+    "events",
+    "control",
+    "sensing",
+    "operators",
+    "data",
+    "procedures",
+    "blocks",
+    "scripts",
+    "depth",
+    "custom",
+    "calls",
+    "clones",
+    "clone hats",
+    "deletes",
+    "broadcasts",
+    "receivers",
+];
+
+/// Two tables of a row per sprite and one for the total: the blocks in each
+/// category, then everything else.
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rows = vec![HEADERS.iter().map(|f| f.to_string()).collect::<Vec<_>>()];
+        rows.extend(self.sprites.iter().map(SpriteStats::row));
+        rows.push(self.total.row());
+        let widths: Vec<usize> = (0..HEADERS.len())
+            .map(|i| rows.iter().map(|f| f[i].chars().count()).max().unwrap_or(0))
+            .collect();
+        // the blocks table ends with their total, the other starts after it.
+        for (i, columns) in [1..11, 11..20].into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for row in &rows {
+                write!(f, "{:<1$}", row[0], widths[0])?;
+                for j in columns.clone() {
+                    write!(f, "  {:>1$}", row[j], widths[j])?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scratchblocks, test_util::test_project};
+
+    #[test]
+    fn counts_blocks() {
+        let mut project = test_project();
+        let before = Stats::new(&project);
+        let sprite = &mut project.sprites_mut()[1];
+        let text = "when flag clicked\n\
+            forever\n\
+            if <mouse down?> then\n\
+            create clone of (myself v)\n\
+            broadcast (hello v)\n\
+            end\n\
+            end";
+        scratchblocks::add(sprite, text).unwrap();
+        let after = Stats::new(&project);
+
+        let (old, new) = (&before.sprites[1], &after.sprites[1]);
+        let added: Vec<(&str, usize)> = new
+            .blocks
+            .iter()
+            .map(|(name, count)| (*name, count - old.blocks[name]))
+            .filter(|f| f.1 > 0)
+            .collect();
+        assert_eq!(added, [("control", 3), ("events", 2), ("sensing", 1)]);
+        assert_eq!(new.scripts, old.scripts + 1);
+        assert_eq!(new.max_depth, old.max_depth.max(2));
+        assert_eq!(new.clones_created, old.clones_created + 1);
+        assert_eq!(new.broadcasts, old.broadcasts + 1);
+
+        let scripts: usize = after.sprites.iter().map(|f| f.scripts).sum();
+        assert_eq!(after.total.scripts, scripts);
+        let table = after.to_string();
+        assert!(table.starts_with("sprite"));
+        assert!(table.contains("\n\nsprite"));
+    }
+}
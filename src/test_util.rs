@@ -0,0 +1,59 @@
+//! What the tests share: the project they run against, and ways to check
+//! what comes out.
+use serde_json::{json, Value};
+
+use crate::{
+    decomp::{LoadOptions, Project},
+    runtime::{ir::Program, vm::Vm, world::World, Options, Runtime},
+};
+
+/// test.json, which the tests are run against.
+pub(crate) fn test_project() -> Project {
+    let json = std::fs::read_to_string("./test.json").unwrap();
+    Project::from_json_str(&json, LoadOptions::default()).unwrap()
+}
+
+/// Everything the project says in its first second, run on the VM.
+pub(crate) fn transcript(project: &Project) -> String {
+    let program = Program::compile(project);
+    let world = World::new(&program.targets, Options::default().seed);
+    let mut runtime = Runtime::new(Vm::new(program), world, Options::default());
+    runtime.green_flag();
+    runtime.run(30);
+    runtime.world().transcript()
+}
+
+/// What the editor checks for when it loads blocks: everything that's
+/// pointed at is there, and points back.
+pub(crate) fn check_blocks(target: &Value) {
+    let blocks = target["blocks"].as_object().unwrap();
+    for (id, block) in blocks {
+        match block["parent"].as_str() {
+            Some(a) => {
+                assert!(blocks.contains_key(a), "{} has a missing parent", id);
+                assert_eq!(block["topLevel"], json!(false), "{}", id);
+            }
+            None => {
+                assert_eq!(block["topLevel"], json!(true), "{}", id);
+                assert!(block["x"].is_number() && block["y"].is_number(), "{}", id);
+            }
+        }
+        if let Some(next) = block["next"].as_str() {
+            assert_eq!(blocks[next]["parent"], json!(id), "{}", id);
+        }
+        for (name, input) in block["inputs"].as_object().unwrap() {
+            let input = input.as_array().unwrap();
+            match input[0].as_u64() {
+                Some(1) | Some(2) | Some(3) => {}
+                _ => panic!("{}.{} has no shadow code", id, name),
+            }
+            if let Some(child) = input[1].as_str() {
+                assert_eq!(blocks[child]["parent"], json!(id), "{}.{}", id, name);
+                assert_eq!(input[0] == json!(1), blocks[child]["shadow"] == json!(true));
+            }
+        }
+        for field in block["fields"].as_object().unwrap().values() {
+            assert_eq!(field.as_array().unwrap().len(), 2);
+        }
+    }
+}
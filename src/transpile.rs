@@ -0,0 +1,1370 @@
+//! Turns a project into a standalone Rust crate that runs it natively.
+//!
+//! Every script and custom block becomes a function, linked against yase_rt
+//! for the scheduler and everything scripts act on. Scripts can't just be
+//! loops, because they have to give the other scripts a turn at the end of
+//! every iteration, so they are state machines that pick up where they left
+//! off (see yase_rt::compiled).
+//!
+//! Custom blocks that run without screen refresh don't give anyone a turn,
+//! so the ones that never wait for anything are also compiled to plain
+//! loops, which is where most of the speed comes from. Unlike the
+//! interpreter, those don't stop after WARP_BUDGET iterations.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    blocks::*,
+    decomp::Project,
+    runtime::{
+        ir::{literal, math_op, name_of, time_unit, Program},
+        ops::{self, BinOp, UnOp},
+        value::Val,
+        world::{RotationStyle as Style, Slot, TargetInit},
+        HatKind,
+    },
+    script::{Input, Linked, Stack},
+};
+
+/// Where the written crate gets yase_rt from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rt {
+    /// A checkout of it. Relative paths are from the written crate.
+    Path(PathBuf),
+    /// A published version of it.
+    Version(String),
+}
+
+impl Rt {
+    /// The dependency line for Cargo.toml.
+    fn dependency(&self) -> String {
+        match self {
+            Rt::Path(a) => format!("yase_rt = {{ path = {:?} }}", a),
+            Rt::Version(a) => format!("yase_rt = {:?}", a),
+        }
+    }
+}
+
+impl Default for Rt {
+    /// The rt next to the yase this was built from.
+    fn default() -> Self {
+        Rt::Path(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/rt")))
+    }
+}
+
+/// Writes the crate for a project to a directory.
+pub fn transpile(project: &Project, out: &Path, rt: &Rt) -> io::Result<()> {
+    let name: String = out
+        .file_name()
+        .map(|f| f.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .map(|f| if f.is_ascii_alphanumeric() { f } else { '_' })
+        .collect();
+    let name = match name.chars().next() {
+        Some(a) if a.is_ascii_alphabetic() => name,
+        _ => format!("project{}", name),
+    };
+    fs::create_dir_all(out.join("src"))?;
+    fs::write(
+        out.join("Cargo.toml"),
+        format!(
+            "[package]\nname = {:?}\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+             [dependencies]\n{}\n\n\
+             # not part of whatever workspace it was written into.\n[workspace]\n",
+            name,
+            rt.dependency(),
+        ),
+    )?;
+    fs::write(out.join("src/main.rs"), generate(project))
+}
+
+/// The source of the crate's main.rs.
+pub fn generate(project: &Project) -> String {
+    let program = Program::compile(project);
+    let sprites = project.sprites();
+    let linked: Vec<_> = sprites.iter().map(Linked::new).collect();
+    let native = native(&program, &linked);
+
+    let mut out = String::new();
+    out += "//! Generated by yase, edits will be lost.\n";
+    out += "#![allow(unused, unreachable_code, clippy::all)]\n\n";
+    out += "use yase_rt::{\n";
+    out += "    compiled::{Compiled, Exit, Frame, Func, Task},\n";
+    out += "    ops::{self, BinOp, MathOp, TimeUnit, UnOp},\n";
+    out += "    value::Val,\n";
+    out += "    world::{RotationStyle, Slot, TargetInit, World},\n";
+    out += "    Ctx, Hat, HatKind, Options, Runtime,\n";
+    out += "};\n\n";
+
+    out += "fn main() {\n";
+    out += "    // how many frames to run for, 30 of them make a second.\n";
+    out +=
+        "    let frames = std::env::args().nth(1).and_then(|f| f.parse().ok()).unwrap_or(300);\n";
+    out += "    let options = Options::default();\n";
+    out += "    let world = World::new(&targets(), options.seed);\n";
+    out += "    let backend = Compiled {\n";
+    out += "        hats: hats(),\n";
+    out += &format!(
+        "        entries: ({}..{}).collect(),\n",
+        program.procedures.len(),
+        program.procedures.len() + program.hats.len()
+    );
+    out += "        funcs: FUNCS.to_vec(),\n";
+    out += "    };\n";
+    out += "    let mut runtime = Runtime::new(backend, world, options);\n";
+    out += "    runtime.green_flag();\n";
+    out += "    let ran = runtime.run(frames);\n";
+    out += "    print!(\"{}\", runtime.world().transcript());\n";
+    out += "    println!(\"ran for {} frames\", ran);\n";
+    out += "}\n\n";
+
+    out += "fn targets() -> Vec<TargetInit> {\n    vec![\n";
+    for target in &program.targets {
+        out += &target_init(target);
+    }
+    out += "    ]\n}\n\n";
+
+    out += "fn hats() -> Vec<Hat> {\n    vec![\n";
+    for hat in &program.hats {
+        out += &format!(
+            "        Hat {{ sprite: {}, id: {:?}.into(), kind: {} }},\n",
+            hat.sprite,
+            hat.id,
+            hat_kind(&hat.kind)
+        );
+    }
+    out += "    ]\n}\n\n";
+
+    let count = program.procedures.len() + program.hats.len();
+    out += &format!("const FUNCS: [Func; {}] = [", count);
+    out += &(0..count)
+        .map(|f| format!("f{}", f))
+        .collect::<Vec<_>>()
+        .join(", ");
+    out += "];\n";
+
+    for (i, info) in program.procedures.iter().enumerate() {
+        let linked = &linked[info.sprite];
+        let procedure = match linked.procedure(&info.proccode) {
+            Some(a) => *a,
+            None => continue,
+        };
+        let first = procedure.definition.next.as_deref();
+        let mut gen = Gen::new(&program, linked, info.sprite, &native);
+        gen.args = procedure.signature.argument_names().clone();
+        out += &format!("\n/// {}\n", info.proccode);
+        out += &gen.machine(&format!("f{}", i), linked.stack(first));
+        if native[i] {
+            out += &format!("\n/// {}, without screen refresh.\n", info.proccode);
+            out += &gen.native(&format!("w{}", i), linked.stack(first));
+        }
+    }
+    for (i, hat) in program.hats.iter().enumerate() {
+        let linked = &linked[hat.sprite];
+        let script = match linked.scripts.iter().find(|f| f.id == hat.id) {
+            Some(a) => a,
+            None => continue,
+        };
+        let mut gen = Gen::new(&program, linked, hat.sprite, &native);
+        out += &format!(
+            "\n/// {}: {}\n",
+            program.targets[hat.sprite].name,
+            hat_kind(&hat.kind)
+        );
+        out += &gen.machine(
+            &format!("f{}", program.procedures.len() + i),
+            linked.body(script),
+        );
+    }
+    out
+}
+
+/// Which custom blocks get a plain version: the ones that never wait, yield
+/// or loop forever, and only call others like them.
+fn native(program: &Program, linked: &[Linked]) -> Vec<bool> {
+    let mut native = Vec::new();
+    let mut calls = Vec::new();
+    for info in &program.procedures {
+        let linked = &linked[info.sprite];
+        let mut scan = Scan::default();
+        if let Some(procedure) = linked.procedure(&info.proccode) {
+            scan.stack(linked, linked.stack(procedure.definition.next.as_deref()));
+        }
+        native.push(!scan.waits);
+        calls.push(
+            scan.calls
+                .iter()
+                .filter_map(|f| {
+                    program
+                        .procedures
+                        .iter()
+                        .position(|g| g.sprite == info.sprite && g.proccode == *f)
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+    loop {
+        let mut changed = false;
+        for i in 0..native.len() {
+            if native[i] && calls[i].iter().any(|f| !native[*f]) {
+                native[i] = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            return native;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scan<'a> {
+    waits: bool,
+    calls: Vec<&'a str>,
+}
+
+impl<'a> Scan<'a> {
+    fn stack(&mut self, linked: &Linked<'a>, stack: Stack<'a>) {
+        for (_, block) in stack {
+            match block {
+                BlockType::Forever(_)
+                | BlockType::WaitUntil(_)
+                | BlockType::WaitSeconds(_)
+                | BlockType::Say(_)
+                | BlockType::Think(_)
+                | BlockType::Glide(_)
+                | BlockType::SwitchBackdropAndWait(_)
+                | BlockType::PlaySoundUntilDone(_)
+                | BlockType::BroadcastAndWait(_) => self.waits = true,
+                BlockType::Repeat(a) => self.stack(linked, linked.substack(&a.substack)),
+                BlockType::RepeatUntil(a) => self.stack(linked, linked.substack(&a.substack)),
+                BlockType::IfThen(a) => self.stack(linked, linked.substack(&a.then)),
+                BlockType::IfThenElse(a) => {
+                    self.stack(linked, linked.substack(&a.then));
+                    self.stack(linked, linked.substack(&a.otherwise));
+                }
+                BlockType::ProceduresCall(a) => self.calls.push(a.signature.proccode()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether a stack could make a state machine stop and come back later.
+fn suspends<'a>(linked: &Linked<'a>, stack: Stack<'a>, native: &dyn Fn(&str) -> bool) -> bool {
+    for (_, block) in stack {
+        let suspends = match block {
+            BlockType::Forever(_)
+            | BlockType::Repeat(_)
+            | BlockType::RepeatUntil(_)
+            | BlockType::WaitUntil(_)
+            | BlockType::WaitSeconds(_)
+            | BlockType::Say(_)
+            | BlockType::Think(_)
+            | BlockType::Glide(_)
+            | BlockType::SwitchBackdropAndWait(_)
+            | BlockType::PlaySoundUntilDone(_)
+            | BlockType::BroadcastAndWait(_) => true,
+            BlockType::IfThen(a) => suspends(linked, linked.substack(&a.then), native),
+            BlockType::IfThenElse(a) => {
+                suspends(linked, linked.substack(&a.then), native)
+                    || suspends(linked, linked.substack(&a.otherwise), native)
+            }
+            BlockType::ProceduresCall(a) => !native(a.signature.proccode()),
+            _ => false,
+        };
+        if suspends {
+            return true;
+        }
+    }
+    false
+}
+
+/// A reporter, either known while transpiling or computed at runtime.
+enum Code {
+    Const(Val),
+    Expr(String),
+}
+
+impl Code {
+    fn rust(&self) -> String {
+        match self {
+            Code::Const(a) => val(a),
+            Code::Expr(a) => a.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// A state machine, which returns an Exit.
+    Machine,
+    /// A plain function, which returns whether the script goes on.
+    Native,
+}
+
+struct Gen<'a, 'p> {
+    program: &'p Program,
+    linked: &'p Linked<'a>,
+    sprite: usize,
+    native: &'p [bool],
+    /// Argument names of the custom block being transpiled.
+    args: Vec<String>,
+    mode: Mode,
+    out: String,
+    indent: usize,
+    states: usize,
+    /// How many repeat loops we're in, and the most there ever were.
+    depth: usize,
+    slots: usize,
+    temps: usize,
+}
+
+impl<'a, 'p> Gen<'a, 'p> {
+    fn new(
+        program: &'p Program,
+        linked: &'p Linked<'a>,
+        sprite: usize,
+        native: &'p [bool],
+    ) -> Gen<'a, 'p> {
+        Gen {
+            program,
+            linked,
+            sprite,
+            native,
+            args: Vec::new(),
+            mode: Mode::Machine,
+            out: String::new(),
+            indent: 0,
+            states: 0,
+            depth: 0,
+            slots: 0,
+            temps: 0,
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out += "    ";
+        }
+        self.out += line;
+        self.out += "\n";
+    }
+
+    fn machine(&mut self, name: &str, body: Stack<'a>) -> String {
+        self.mode = Mode::Machine;
+        self.out.clear();
+        self.states = 1;
+        self.slots = 0;
+        self.temps = 0;
+        self.indent = 4;
+        self.stack(body);
+        self.line("return Exit::Return;");
+        let body = std::mem::take(&mut self.out);
+
+        let mut out = format!(
+            "fn {}(ctx: &mut Ctx, task: &mut Task, fr: &mut Frame) -> Exit {{\n",
+            name
+        );
+        out += "    let t = task.target;\n";
+        out += "    loop {\n";
+        out += "        match fr.pc {\n";
+        out += "            0 => {\n";
+        if self.slots > 0 {
+            out += &format!("                fr.slots = vec![0.0; {}];\n", self.slots);
+        }
+        out += &body;
+        out += "            }\n";
+        out += "            _ => unreachable!(),\n";
+        out += "        }\n";
+        out += "    }\n";
+        out += "}\n";
+        out
+    }
+
+    fn native(&mut self, name: &str, body: Stack<'a>) -> String {
+        self.mode = Mode::Native;
+        self.out.clear();
+        self.temps = 0;
+        self.indent = 1;
+        self.line("let t = task.target;");
+        self.stack(body);
+        self.line("true");
+        format!(
+            "fn {}(ctx: &mut Ctx, task: &mut Task, args: &[Val]) -> bool {{\n{}}}\n",
+            name,
+            std::mem::take(&mut self.out)
+        )
+    }
+
+    // State machines
+
+    fn state(&mut self) -> usize {
+        self.states += 1;
+        self.states - 1
+    }
+
+    fn goto(&mut self, to: usize) {
+        self.line(&format!("fr.pc = {};", to));
+        self.line("continue;");
+    }
+
+    /// Ends the current state and starts another.
+    fn open(&mut self, state: usize) {
+        self.indent -= 1;
+        self.line("}");
+        self.line(&format!("{} => {{", state));
+        self.indent += 1;
+    }
+
+    /// Goes back to the top of a loop, giving everyone else a turn first.
+    fn again(&mut self, head: usize) {
+        self.line(&format!("fr.pc = {};", head));
+        self.line("if task.tick() {");
+        self.line("    return Exit::Yield;");
+        self.line("}");
+        self.line("continue;");
+    }
+
+    /// Emits a block that might have to wait, given the call that says so.
+    fn wait(&mut self, call: &str) {
+        let next = self.state();
+        self.line(&format!("fr.pc = {};", next));
+        self.line(&format!("if {} {{", call));
+        self.line("    return Exit::Wait;");
+        self.line("}");
+        self.line("continue;");
+        self.open(next);
+    }
+
+    /// Ends the script: "stop all", or a deleted clone.
+    fn done(&self) -> &'static str {
+        match self.mode {
+            Mode::Machine => "return Exit::Done;",
+            Mode::Native => "return false;",
+        }
+    }
+
+    fn is_native(&self, proccode: &str) -> bool {
+        self.procedure(proccode)
+            .map(|f| self.native[f] && self.program.procedures[f].warp)
+            .unwrap_or(false)
+    }
+
+    fn procedure(&self, proccode: &str) -> Option<usize> {
+        self.program
+            .procedures
+            .iter()
+            .position(|f| f.sprite == self.sprite && f.proccode == proccode)
+    }
+
+    fn slot(&self, name: &Value, list: bool) -> Option<Slot> {
+        let name = name_of(name);
+        match list {
+            false => self.program.variable(self.sprite, &name),
+            true => self.program.list(self.sprite, &name),
+        }
+    }
+
+    /// Evaluates a reporter into a local, returning its name.
+    fn temp(&mut self, code: Code) -> String {
+        self.temps += 1;
+        let name = format!("v{}", self.temps);
+        let line = format!("let {} = {};", name, code.rust());
+        self.line(&line);
+        name
+    }
+
+    fn input_temp(&mut self, val: &'a Option<Value>) -> String {
+        let code = self.input(val);
+        self.temp(code)
+    }
+
+    fn value_temp(&mut self, val: &'a Value) -> String {
+        let code = self.value(val);
+        self.temp(code)
+    }
+
+    // Statements
+
+    fn stack(&mut self, stack: Stack<'a>) {
+        for (_, block) in stack {
+            self.statement(block);
+        }
+    }
+
+    fn substack(&mut self, val: &'a Option<Value>) {
+        let stack = self.linked.substack(val);
+        self.stack(stack);
+    }
+
+    /// The body of an if, which doesn't need states of its own if nothing in
+    /// it can suspend.
+    fn inline(&self, val: &'a Option<Value>) -> bool {
+        self.mode == Mode::Native
+            || !suspends(self.linked, self.linked.substack(val), &|f| {
+                self.is_native(f)
+            })
+    }
+
+    fn statement(&mut self, block: &'a BlockType) {
+        match block {
+            BlockType::Move(a) => {
+                let steps = self.input_temp(&a.steps);
+                self.line(&format!("ctx.world.move_steps(t, {}.num());", steps));
+            }
+            BlockType::RotateRight(a) => {
+                let degrees = self.input_temp(&a.degrees);
+                self.line(&format!("ctx.world.turn(t, {}.num());", degrees));
+            }
+            BlockType::RotateLeft(a) => {
+                let degrees = self.input_temp(&a.degrees);
+                self.line(&format!("ctx.world.turn(t, {}.num() * -1.0);", degrees));
+            }
+            BlockType::Goto(Goto::Pos(a)) => {
+                let x = self.input_temp(&a.x);
+                let y = self.input_temp(&a.y);
+                self.line(&format!("ctx.world.set_xy(t, {}.num(), {}.num());", x, y));
+            }
+            BlockType::Goto(Goto::Option(a)) => {
+                let to = self.input_temp(&a.option);
+                self.line(&format!("ctx.world.goto(t, &{});", to));
+            }
+            BlockType::Glide(Glide::Pos(a)) => {
+                let secs = self.input_temp(&a.secs);
+                let x = self.input_temp(&a.x);
+                let y = self.input_temp(&a.y);
+                self.wait(&format!(
+                    "ctx.glide(t, task.pending, {}.num(), Some(({}.num(), {}.num())))",
+                    secs, x, y
+                ));
+            }
+            BlockType::Glide(Glide::Option(a)) => {
+                let secs = self.input_temp(&a.secs);
+                let to = self.input_temp(&a.option);
+                let to = self.temp(Code::Expr(format!("ctx.world.position_of(&{})", to)));
+                self.wait(&format!(
+                    "ctx.glide(t, task.pending, {}.num(), {})",
+                    secs, to
+                ));
+            }
+            BlockType::Point(Point::Direction(a)) => {
+                let direction = self.input_temp(&a.direction);
+                self.line(&format!("ctx.world.set_direction(t, {}.num());", direction));
+            }
+            BlockType::Point(Point::Towards(a)) => {
+                let to = self.input_temp(&a.option);
+                self.line(&format!("ctx.world.point_towards(t, &{});", to));
+            }
+            BlockType::ChangeX(a) => {
+                let by = self.input_temp(&a.x);
+                self.line(&format!("let x = ctx.world.target(t).x + {}.num();", by));
+                self.line("let y = ctx.world.target(t).y;");
+                self.line("ctx.world.set_xy(t, x, y);");
+            }
+            BlockType::SetX(a) => {
+                let x = self.input_temp(&a.x);
+                self.line("let y = ctx.world.target(t).y;");
+                self.line(&format!("ctx.world.set_xy(t, {}.num(), y);", x));
+            }
+            BlockType::ChangeY(a) => {
+                let by = self.input_temp(&a.y);
+                self.line("let x = ctx.world.target(t).x;");
+                self.line(&format!("let y = ctx.world.target(t).y + {}.num();", by));
+                self.line("ctx.world.set_xy(t, x, y);");
+            }
+            BlockType::SetY(a) => {
+                let y = self.input_temp(&a.y);
+                self.line("let x = ctx.world.target(t).x;");
+                self.line(&format!("ctx.world.set_xy(t, x, {}.num());", y));
+            }
+            BlockType::IfOnEdgeBounce(_) => self.line("ctx.world.bounce(t);"),
+            BlockType::SetRotationStyle(a) => {
+                let style = match &a.style {
+                    Some(RotationStyle::AllAround) => Style::AllAround,
+                    Some(RotationStyle::LeftRight) => Style::LeftRight,
+                    Some(RotationStyle::DontRotate) => Style::DontRotate,
+                    None => return,
+                };
+                self.line(&format!(
+                    "ctx.world.set_rotation_style(t, RotationStyle::{:?});",
+                    style
+                ));
+            }
+
+            BlockType::Say(a) => self.say_for(&a.message, &a.secs, false),
+            BlockType::Think(a) => self.say_for(&a.message, &a.secs, true),
+            BlockType::SayForever(a) => {
+                let message = self.input_temp(&a.message);
+                self.line(&format!("ctx.world.say(t, &{}, false);", message));
+            }
+            BlockType::ThinkForever(a) => {
+                let message = self.input_temp(&a.message);
+                self.line(&format!("ctx.world.say(t, &{}, true);", message));
+            }
+            BlockType::SwitchCostume(a) => {
+                let costume = self.input_temp(&a.costume);
+                self.line(&format!("ctx.world.switch_costume(t, &{});", costume));
+            }
+            BlockType::NextCostume(_) => self.line("ctx.world.next_costume(t);"),
+            BlockType::SwitchBackdrop(a) => {
+                let backdrop = self.input_temp(&a.backdrop);
+                self.line(&format!("ctx.switch_backdrop(&{});", backdrop));
+            }
+            BlockType::SwitchBackdropAndWait(a) => {
+                let backdrop = self.input_temp(&a.backdrop);
+                self.wait(&format!(
+                    "ctx.switch_backdrop_and_wait(task.pending, &{})",
+                    backdrop
+                ));
+            }
+            BlockType::NextBackdrop(_) => {
+                self.line("ctx.switch_backdrop(&Val::from(\"next backdrop\"));")
+            }
+            BlockType::ChangeSize(a) => {
+                let by = self.input_temp(&a.units);
+                self.line(&format!(
+                    "let size = ctx.world.target(t).size + {}.num();",
+                    by
+                ));
+                self.line("ctx.world.set_size(t, size);");
+            }
+            BlockType::SetSize(a) => {
+                let size = self.input_temp(&a.percentage);
+                self.line(&format!("ctx.world.set_size(t, {}.num());", size));
+            }
+            BlockType::ClearGraphicEffects(_) => self.line("ctx.world.clear_effects(t);"),
+            BlockType::ShowSprite(_) => self.line("ctx.world.set_visible(t, true);"),
+            BlockType::HideSprite(_) => self.line("ctx.world.set_visible(t, false);"),
+            BlockType::GotoLayer(a) => match &a.option {
+                Some(LayerOption::Front) => self.line("ctx.world.goto_layer(t, true);"),
+                Some(LayerOption::Back) => self.line("ctx.world.goto_layer(t, false);"),
+                None => {}
+            },
+            BlockType::ChangeLayer(a) => {
+                let by = self.input_temp(&a.by);
+                let sign = match a.direction {
+                    Some(LayerDirection::Backward) => "-",
+                    _ => "",
+                };
+                self.line(&format!(
+                    "ctx.world.change_layer(t, {}({}.num() as i64));",
+                    sign, by
+                ));
+            }
+            BlockType::ChangeEffectBy(a) => {
+                let effect = a.effect.as_ref().map(name_of).unwrap_or_default();
+                let by = self.input_temp(&a.units);
+                self.line(&format!(
+                    "ctx.world.change_effect(t, {:?}, {}.num());",
+                    effect, by
+                ));
+            }
+            BlockType::SetEffectTo(a) => {
+                let effect = a.effect.as_ref().map(name_of).unwrap_or_default();
+                let to = self.input_temp(&a.percentage);
+                self.line(&format!(
+                    "ctx.world.set_effect(t, {:?}, {}.num());",
+                    effect, to
+                ));
+            }
+
+            // nothing is heard, but the inputs still run.
+            BlockType::PlaySound(a) => {
+                self.input_temp(&a.sound);
+            }
+            BlockType::PlaySoundUntilDone(a) => {
+                let sound = self.input_temp(&a.sound);
+                self.wait(&format!(
+                    "ctx.play_sound_until_done(t, task.pending, &{})",
+                    sound
+                ));
+            }
+            BlockType::ChangeVolumeBy(a) => {
+                let by = self.input_temp(&a.units);
+                self.line(&format!(
+                    "let volume = ctx.world.target(t).volume + {}.num();",
+                    by
+                ));
+                self.line("ctx.world.set_volume(t, volume);");
+            }
+            BlockType::SetVolumeTo(a) => {
+                let volume = self.input_temp(&a.percentage);
+                self.line(&format!("ctx.world.set_volume(t, {}.num());", volume));
+            }
+
+            BlockType::Broadcast(a) => {
+                let name = self.input_temp(&a.broadcast);
+                self.line(&format!("ctx.broadcast(&{});", name));
+            }
+            BlockType::BroadcastAndWait(a) => {
+                let name = self.input_temp(&a.broadcast);
+                self.wait(&format!("ctx.broadcast_and_wait(task.pending, &{})", name));
+            }
+
+            BlockType::WaitSeconds(a) => {
+                let secs = self.input_temp(&a.seconds);
+                self.wait(&format!("ctx.wait(task.pending, {}.num())", secs));
+            }
+            BlockType::Repeat(a) => self.repeat(a),
+            BlockType::Forever(a) => {
+                // never native, see Scan.
+                let head = self.state();
+                self.goto(head);
+                self.open(head);
+                self.substack(&a.substack);
+                self.again(head);
+                let after = self.state();
+                self.open(after);
+            }
+            BlockType::RepeatUntil(a) => {
+                if self.mode == Mode::Native {
+                    let condition = self.input(&a.condition).rust();
+                    self.line(&format!("while !{}.bool() {{", condition));
+                    self.indent += 1;
+                    self.substack(&a.substack);
+                    self.indent -= 1;
+                    self.line("}");
+                    return;
+                }
+                let head = self.state();
+                let exit = self.state();
+                self.goto(head);
+                self.open(head);
+                let condition = self.input(&a.condition).rust();
+                self.line(&format!("if {}.bool() {{", condition));
+                self.indent += 1;
+                self.goto(exit);
+                self.indent -= 1;
+                self.line("}");
+                self.substack(&a.substack);
+                self.again(head);
+                self.open(exit);
+            }
+            BlockType::WaitUntil(a) => {
+                // never native either.
+                let head = self.state();
+                self.goto(head);
+                self.open(head);
+                let condition = self.input(&a.condition).rust();
+                self.line(&format!("if !{}.bool() {{", condition));
+                self.line("    return Exit::Yield;");
+                self.line("}");
+            }
+            BlockType::IfThen(a) => {
+                let condition = self.input(&a.condition).rust();
+                if self.inline(&a.then) {
+                    self.line(&format!("if {}.bool() {{", condition));
+                    self.indent += 1;
+                    self.substack(&a.then);
+                    self.indent -= 1;
+                    self.line("}");
+                    return;
+                }
+                let then = self.state();
+                let exit = self.state();
+                self.line(&format!(
+                    "fr.pc = if {}.bool() {{ {} }} else {{ {} }};",
+                    condition, then, exit
+                ));
+                self.line("continue;");
+                self.open(then);
+                self.substack(&a.then);
+                self.goto(exit);
+                self.open(exit);
+            }
+            BlockType::IfThenElse(a) => {
+                let condition = self.input(&a.condition).rust();
+                if self.inline(&a.then) && self.inline(&a.otherwise) {
+                    self.line(&format!("if {}.bool() {{", condition));
+                    self.indent += 1;
+                    self.substack(&a.then);
+                    self.indent -= 1;
+                    self.line("} else {");
+                    self.indent += 1;
+                    self.substack(&a.otherwise);
+                    self.indent -= 1;
+                    self.line("}");
+                    return;
+                }
+                let then = self.state();
+                let otherwise = self.state();
+                let exit = self.state();
+                self.line(&format!(
+                    "fr.pc = if {}.bool() {{ {} }} else {{ {} }};",
+                    condition, then, otherwise
+                ));
+                self.line("continue;");
+                self.open(then);
+                self.substack(&a.then);
+                self.goto(exit);
+                self.open(otherwise);
+                self.substack(&a.otherwise);
+                self.goto(exit);
+                self.open(exit);
+            }
+            BlockType::StopAll(a) => match a.option {
+                Some(StopOption::ThisScript) => match self.mode {
+                    Mode::Machine => self.line("return Exit::Return;"),
+                    Mode::Native => self.line("return true;"),
+                },
+                Some(StopOption::OtherScriptsInSprite) => self.line("ctx.stop_others(t, task.id);"),
+                Some(StopOption::All) | None => {
+                    self.line("ctx.stop_all();");
+                    let done = self.done();
+                    self.line(done);
+                }
+            },
+            BlockType::CreateCloneOf(a) => {
+                let of = self.input_temp(&a.of);
+                self.line(&format!("ctx.create_clone(t, &{});", of));
+            }
+            BlockType::DeleteClone(_) => {
+                self.line("if ctx.delete_clone(t) {");
+                let done = self.done();
+                self.line(&format!("    {}", done));
+                self.line("}");
+            }
+
+            BlockType::AskAndWait(a) => {
+                self.input_temp(&a.question);
+                self.line("ctx.world.ask();");
+            }
+            BlockType::SetDragMode(a) => match &a.option {
+                Some(DraggableOption::Draggable) => {
+                    self.line("ctx.world.target_mut(t).draggable = true;")
+                }
+                Some(DraggableOption::NotDraggable) => {
+                    self.line("ctx.world.target_mut(t).draggable = false;")
+                }
+                None => {}
+            },
+            BlockType::ResetTimer(_) => self.line("ctx.world.reset_timer();"),
+
+            BlockType::DataSetVariableTo(a) => {
+                let value = self.value_temp(&a.value);
+                if let Some(slot) = self.slot(&a.variable, false) {
+                    self.line(&format!(
+                        "ctx.world.set_var(t, {}, {});",
+                        slot_code(slot),
+                        value
+                    ));
+                }
+            }
+            BlockType::DataChangeVariableBy(a) => {
+                let by = self.value_temp(&a.value);
+                if let Some(slot) = self.slot(&a.variable, false) {
+                    self.line(&format!(
+                        "ctx.world.change_var(t, {}, {}.num());",
+                        slot_code(slot),
+                        by
+                    ));
+                }
+            }
+            BlockType::DataAddToList(a) => {
+                let item = self.value_temp(&a.item);
+                self.list_op(&a.list, &format!("add_to_list(l, i, {})", item));
+            }
+            BlockType::DataDeleteOfList(a) => {
+                let index = self.value_temp(&a.item);
+                self.list_op(&a.list, &format!("delete_of_list(l, i, &{})", index));
+            }
+            BlockType::DataDeleteAllOfList(a) => self.list_op(&a.list, "delete_all_of_list(l, i)"),
+            BlockType::DataInsertAtList(a) => {
+                let item = self.value_temp(&a.item);
+                let index = self.value_temp(&a.index);
+                self.list_op(
+                    &a.list,
+                    &format!("insert_at_list(l, i, &{}, {})", index, item),
+                );
+            }
+            BlockType::DataReplaceItemOfList(a) => {
+                let index = self.value_temp(&a.index);
+                let item = self.value_temp(&a.item);
+                self.list_op(
+                    &a.list,
+                    &format!("replace_item_of_list(l, i, &{}, {})", index, item),
+                );
+            }
+
+            BlockType::ProceduresCall(a) => {
+                let index = match self.procedure(a.signature.proccode()) {
+                    Some(a) => a,
+                    // calls to blocks that aren't defined do nothing.
+                    None => return,
+                };
+                let info = &self.program.procedures[index];
+                let (count, warp) = (info.args, info.warp);
+                let args: Vec<_> = (0..count)
+                    .map(|i| match a.arguments.get(i) {
+                        Some(arg) => self.input_temp(arg),
+                        None => self.temp(Code::Const(Val::from(""))),
+                    })
+                    .collect();
+                let args = args.join(", ");
+                if self.mode == Mode::Native || self.is_native(a.signature.proccode()) {
+                    self.line(&format!("if !w{}(ctx, task, &[{}]) {{", index, args));
+                    let done = self.done();
+                    self.line(&format!("    {}", done));
+                    self.line("}");
+                    return;
+                }
+                let next = self.state();
+                self.line(&format!("fr.pc = {};", next));
+                self.line(&format!(
+                    "return Exit::Call({}, vec![{}], {});",
+                    index, args, warp
+                ));
+                self.open(next);
+            }
+            // everything else either doesn't do anything or isn't a statement.
+            _ => {}
+        }
+    }
+
+    fn say_for(&mut self, message: &'a Option<Value>, secs: &'a Option<Value>, think: bool) {
+        let message = self.input_temp(message);
+        let secs = self.input_temp(secs);
+        self.wait(&format!(
+            "ctx.say_for(t, task.pending, &{}, {}.num(), {})",
+            message, secs, think
+        ));
+    }
+
+    fn repeat(&mut self, a: &'a Repeat) {
+        let times = self.input(&a.units).rust();
+        if self.mode == Mode::Native {
+            self.line("{");
+            self.indent += 1;
+            self.line(&format!("let mut left = {}.num().round();", times));
+            self.line("while left >= 1.0 {");
+            self.indent += 1;
+            self.line("left -= 1.0;");
+            self.substack(&a.substack);
+            self.indent -= 1;
+            self.line("}");
+            self.indent -= 1;
+            self.line("}");
+            return;
+        }
+        let slot = self.depth;
+        self.depth += 1;
+        self.slots = self.slots.max(self.depth);
+        let head = self.state();
+        let exit = self.state();
+        self.line(&format!("fr.slots[{}] = {}.num().round();", slot, times));
+        self.goto(head);
+        self.open(head);
+        self.line(&format!("if fr.slots[{}] >= 1.0 {{", slot));
+        self.line(&format!("    fr.slots[{}] -= 1.0;", slot));
+        self.line("} else {");
+        self.indent += 1;
+        self.goto(exit);
+        self.indent -= 1;
+        self.line("}");
+        self.substack(&a.substack);
+        self.again(head);
+        self.open(exit);
+        self.depth -= 1;
+    }
+
+    fn list_op(&mut self, list: &Value, call: &str) {
+        if let Some(slot) = self.slot(list, true) {
+            self.line(&format!(
+                "let (l, i) = ctx.world.resolve(t, {});",
+                slot_code(slot)
+            ));
+            self.line(&format!("ctx.world.{};", call));
+        }
+    }
+
+    // Reporters
+
+    fn input(&mut self, val: &'a Option<Value>) -> Code {
+        let input = self.linked.input(val);
+        self.expr(input)
+    }
+
+    fn value(&mut self, val: &'a Value) -> Code {
+        let input = self.linked.value(val);
+        self.expr(input)
+    }
+
+    fn expr(&mut self, input: Input<'a>) -> Code {
+        match input {
+            Input::Empty => Code::Const(Val::from("")),
+            Input::Literal(a) => Code::Const(literal(a)),
+            Input::Variable(a) => self.variable(&Value::String(a.to_string())),
+            Input::List(a) => self.list_contents(&Value::String(a.to_string())),
+            Input::Block(_, block) => self.reporter(block),
+        }
+    }
+
+    fn variable(&self, name: &Value) -> Code {
+        match self.slot(name, false) {
+            Some(slot) => Code::Expr(format!("ctx.world.var(t, {}).clone()", slot_code(slot))),
+            None => Code::Const(Val::default()),
+        }
+    }
+
+    fn list_contents(&self, name: &Value) -> Code {
+        self.list_reporter(name, "ctx.world.list_contents(l, i)", "")
+    }
+
+    /// A reporter about a list, given the index of the list as l, i.
+    fn list_reporter(&self, name: &Value, expr: &str, before: &str) -> Code {
+        match self.slot(name, true) {
+            Some(slot) => Code::Expr(format!(
+                "{{ {}let (l, i) = ctx.world.resolve(t, {}); {} }}",
+                before,
+                slot_code(slot),
+                expr
+            )),
+            None => Code::Const(Val::from("")),
+        }
+    }
+
+    fn world(&self, expr: &str) -> Code {
+        Code::Expr(expr.to_string())
+    }
+
+    fn reporter(&mut self, block: &'a BlockType) -> Code {
+        match block {
+            BlockType::Add(a) => self.binary(&a.a, &a.b, BinOp::Add),
+            BlockType::Sub(a) => self.binary(&a.a, &a.b, BinOp::Sub),
+            BlockType::Mul(a) => self.binary(&a.a, &a.b, BinOp::Mul),
+            BlockType::Divide(a) => self.binary(&a.a, &a.b, BinOp::Div),
+            BlockType::Modulo(a) => self.binary(&a.a, &a.b, BinOp::Mod),
+            BlockType::GreaterThen(a) => self.binary(&a.a, &a.b, BinOp::Gt),
+            BlockType::LesserThen(a) => self.binary(&a.a, &a.b, BinOp::Lt),
+            BlockType::EqualTo(a) => self.binary(&a.a, &a.b, BinOp::Eq),
+            BlockType::And(a) => self.binary(&a.a, &a.b, BinOp::And),
+            BlockType::Or(a) => self.binary(&a.a, &a.b, BinOp::Or),
+            BlockType::Join(a) => self.binary(&a.a, &a.b, BinOp::Join),
+            BlockType::LetterOf(a) => self.binary(&a.index, &a.a, BinOp::LetterOf),
+            BlockType::Contains(a) => self.binary(&a.a, &a.b, BinOp::Contains),
+            BlockType::Not(a) => self.unary(&a.a, UnOp::Not),
+            BlockType::LengthOf(a) => self.unary(&a.a, UnOp::Length),
+            BlockType::Round(a) => self.unary(&a.a, UnOp::Round),
+            BlockType::MathOp(a) => match a.operator {
+                Some(op) => self.unary(&a.a, UnOp::Math(math_op(op))),
+                None => self.input(&a.a),
+            },
+            BlockType::PickRandom(a) => {
+                let min = self.input(&a.min).rust();
+                let max = self.input(&a.max).rust();
+                Code::Expr(format!(
+                    "{{ let a = {}; let b = {}; Val::Num(ctx.world.rng.pick(&a, &b)) }}",
+                    min, max
+                ))
+            }
+
+            BlockType::XPosition(_) => self.world("Val::Num(ctx.world.target(t).x)"),
+            BlockType::YPosition(_) => self.world("Val::Num(ctx.world.target(t).y)"),
+            BlockType::Direction(_) => self.world("Val::Num(ctx.world.target(t).direction)"),
+            BlockType::Size(_) => self.world("Val::Num(ctx.world.target(t).size)"),
+            BlockType::Volume(_) => self.world("Val::Num(ctx.world.target(t).volume)"),
+            BlockType::Costume(Costume::ByNumber(_)) => {
+                self.world("Val::Num(ctx.world.target(t).costume as f64 + 1.0)")
+            }
+            BlockType::Costume(Costume::ByName(_)) => {
+                self.world("Val::from(ctx.world.costume_name(t))")
+            }
+            BlockType::Backdrop(Backdrop::ByNumber(_)) => {
+                self.world("Val::Num(ctx.world.target(ctx.world.stage).costume as f64 + 1.0)")
+            }
+            BlockType::Backdrop(Backdrop::ByName(_)) => {
+                self.world("Val::from(ctx.world.costume_name(ctx.world.stage))")
+            }
+            BlockType::Answer(_) => self.world("Val::from(ctx.world.answer.as_str())"),
+            BlockType::MouseDown(_) => self.world("Val::Bool(ctx.world.mouse_down)"),
+            BlockType::MouseX(_) => self.world("Val::Num(ctx.world.mouse.0)"),
+            BlockType::MouseY(_) => self.world("Val::Num(ctx.world.mouse.1)"),
+            BlockType::Timer(_) => self.world("Val::Num(ctx.world.timer())"),
+            BlockType::DaysSince2000(_) => self.world("Val::Num(ctx.world.days_since_2000())"),
+            BlockType::Username(_) => self.world("Val::from(ctx.world.username.as_str())"),
+            // there's no microphone.
+            BlockType::Loudness(_) => Code::Const(Val::Num(-1.0)),
+            BlockType::CurrentTime(a) => match &a.option {
+                Some(a) => Code::Expr(format!(
+                    "Val::Num(ops::current(TimeUnit::{:?}))",
+                    time_unit(a)
+                )),
+                None => Code::Const(Val::Num(0.0)),
+            },
+            BlockType::KeyPressed(a) => {
+                let key = self.input(&a.key).rust();
+                Code::Expr(format!(
+                    "{{ let a = {}; Val::Bool(ctx.world.key_pressed(&a)) }}",
+                    key
+                ))
+            }
+            BlockType::Touching(a) => {
+                let what = self.input(&a.touching).rust();
+                Code::Expr(format!(
+                    "{{ let a = {}; Val::Bool(ctx.world.touching(t, &a)) }}",
+                    what
+                ))
+            }
+            BlockType::DistanceTo(a) => {
+                let to = self.input(&a.to).rust();
+                Code::Expr(format!(
+                    "{{ let a = {}; Val::Num(ctx.world.distance_to(t, &a)) }}",
+                    to
+                ))
+            }
+            // there's nothing drawn, so there are no colors to touch.
+            BlockType::TouchingColor(_) | BlockType::ColorTouchingColor(_) => {
+                Code::Const(Val::Bool(false))
+            }
+
+            // menus are just their option.
+            BlockType::Costume(Costume::WithName(a))
+            | BlockType::Backdrop(Backdrop::WithName(a)) => {
+                Code::Const(Val::from(a.as_ref().map(name_of).unwrap_or_default()))
+            }
+            BlockType::Goto(Goto::Menu(a)) | BlockType::Glide(Glide::Menu(a)) => option(&a.option),
+            BlockType::PointTowardsMenu(a) => option(&a.option),
+            BlockType::TouchingMenu(a) => option(&a.touching),
+            BlockType::DistanceToMenu(a) => option(&a.to),
+            BlockType::KeyOptions(a) => option(&a.key),
+            BlockType::CreateCloneOfMenu(a) => option(&a.of),
+            BlockType::SoundSoundsMenu(a) => Code::Const(Val::from(
+                a.option.as_ref().map(name_of).unwrap_or_default(),
+            )),
+
+            BlockType::DataGetVariable(a) => self.variable(&a.variable),
+            BlockType::DataListContents(a) => self.list_contents(&a.variable),
+            BlockType::DataItemOfList(a) => {
+                let index = self.value(&a.index).rust();
+                self.list_reporter(
+                    &a.list,
+                    "ctx.world.item_of_list(l, i, &index)",
+                    &format!("let index = {}; ", index),
+                )
+            }
+            BlockType::DataLengthOfList(a) => {
+                self.list_reporter(&a.list, "Val::Num(ctx.world.list(l, i).len() as f64)", "")
+            }
+            BlockType::DataListContainsItem(a) => {
+                let item = self.value(&a.input).rust();
+                self.list_reporter(
+                    &a.list,
+                    "Val::Bool(ctx.world.list_contains(l, i, &item))",
+                    &format!("let item = {}; ", item),
+                )
+            }
+            BlockType::ArgumentReporter(a) => match self.args.iter().position(|f| *f == a.name) {
+                Some(i) => Code::Expr(match self.mode {
+                    Mode::Machine => format!("fr.args[{}].clone()", i),
+                    Mode::Native => format!("args[{}].clone()", i),
+                }),
+                // used outside of its definition.
+                None => Code::Const(if a.boolean {
+                    Val::Bool(false)
+                } else {
+                    Val::Num(0.0)
+                }),
+            },
+            _ => Code::Const(Val::from("")),
+        }
+    }
+
+    /// A binary operator, worked out now if both sides are constants.
+    fn binary(&mut self, a: &'a Option<Value>, b: &'a Option<Value>, op: BinOp) -> Code {
+        let a = self.input(a);
+        let b = self.input(b);
+        if let (Code::Const(a), Code::Const(b)) = (&a, &b) {
+            return Code::Const(ops::binary(op, a, b));
+        }
+        let arithmetic = match op {
+            BinOp::Add => Some("+"),
+            BinOp::Sub => Some("-"),
+            BinOp::Mul => Some("*"),
+            BinOp::Div => Some("/"),
+            _ => None,
+        };
+        match arithmetic {
+            Some(sign) => Code::Expr(format!(
+                "Val::Num({}.num() {} {}.num())",
+                a.rust(),
+                sign,
+                b.rust()
+            )),
+            None => Code::Expr(format!(
+                "ops::binary(BinOp::{:?}, &{}, &{})",
+                op,
+                a.rust(),
+                b.rust()
+            )),
+        }
+    }
+
+    fn unary(&mut self, a: &'a Option<Value>, op: UnOp) -> Code {
+        let a = self.input(a);
+        if let Code::Const(a) = &a {
+            return Code::Const(ops::unary(op, a));
+        }
+        let op = match op {
+            UnOp::Math(op) => format!("UnOp::Math(MathOp::{:?})", op),
+            op => format!("UnOp::{:?}", op),
+        };
+        Code::Expr(format!("ops::unary({}, &{})", op, a.rust()))
+    }
+}
+
+fn option(option: &Option<impl std::fmt::Display>) -> Code {
+    match option {
+        Some(a) => Code::Const(Val::from(a.to_string())),
+        None => Code::Const(Val::from("")),
+    }
+}
+
+fn float(a: f64) -> String {
+    if a.is_nan() {
+        "f64::NAN".to_string()
+    } else if a.is_infinite() {
+        if a > 0.0 {
+            "f64::INFINITY"
+        } else {
+            "f64::NEG_INFINITY"
+        }
+        .to_string()
+    } else {
+        format!("{:?}", a)
+    }
+}
+
+fn val(a: &Val) -> String {
+    match a {
+        Val::Num(a) => format!("Val::Num({})", float(*a)),
+        Val::Str(a) => format!("Val::from({:?})", a.as_ref()),
+        Val::Bool(a) => format!("Val::Bool({})", a),
+    }
+}
+
+fn slot_code(slot: Slot) -> String {
+    match slot {
+        Slot::Local(a) => format!("Slot::Local({})", a),
+        Slot::Global(a) => format!("Slot::Global({})", a),
+    }
+}
+
+fn hat_kind(kind: &HatKind) -> String {
+    match kind {
+        HatKind::GreenFlag => "HatKind::GreenFlag".to_string(),
+        HatKind::KeyPressed(a) => format!("HatKind::KeyPressed({:?}.into())", a),
+        HatKind::SpriteClicked => "HatKind::SpriteClicked".to_string(),
+        HatKind::StageClicked => "HatKind::StageClicked".to_string(),
+        HatKind::BackdropSwitchesTo(a) => format!("HatKind::BackdropSwitchesTo({:?}.into())", a),
+        HatKind::TimerGreaterThan(a) => format!("HatKind::TimerGreaterThan({})", float(*a)),
+        HatKind::Broadcast(a) => format!("HatKind::Broadcast({:?}.into())", a),
+        HatKind::CloneStart => "HatKind::CloneStart".to_string(),
+    }
+}
+
+fn target_init(target: &TargetInit) -> String {
+    let strings = |f: &[String]| {
+        f.iter()
+            .map(|f| format!("{:?}.into()", f))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let vals = |f: &[Val]| f.iter().map(val).collect::<Vec<_>>().join(", ");
+    let mut out = String::from("        TargetInit {\n");
+    out += &format!("            name: {:?}.into(),\n", target.name);
+    out += &format!("            is_stage: {},\n", target.is_stage);
+    out += &format!("            x: {},\n", float(target.x));
+    out += &format!("            y: {},\n", float(target.y));
+    out += &format!("            direction: {},\n", float(target.direction));
+    out += &format!("            size: {},\n", float(target.size));
+    out += &format!("            visible: {},\n", target.visible);
+    out += &format!("            draggable: {},\n", target.draggable);
+    out += &format!(
+        "            rotation_style: {},\n",
+        match target.rotation_style {
+            Some(a) => format!("Some(RotationStyle::{:?})", a),
+            None => "None".to_string(),
+        }
+    );
+    out += &format!("            costume: {},\n", target.costume);
+    out += &format!(
+        "            costumes: vec![{}],\n",
+        strings(&target.costumes)
+    );
+    out += &format!(
+        "            sounds: vec![{}],\n",
+        target
+            .sounds
+            .iter()
+            .map(|f| format!("({:?}.into(), {})", f.0, float(f.1)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    out += &format!("            volume: {},\n", float(target.volume));
+    out += &format!("            layer: {},\n", target.layer);
+    out += &format!(
+        "            variables: vec![{}],\n",
+        vals(&target.variables)
+    );
+    out += &format!(
+        "            lists: vec![{}],\n",
+        target
+            .lists
+            .iter()
+            .map(|f| format!("vec![{}]", vals(f)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    out += &format!(
+        "            variable_names: vec![{}],\n",
+        strings(&target.variable_names)
+    );
+    out += &format!(
+        "            list_names: vec![{}],\n",
+        strings(&target.list_names)
+    );
+    out += "        },\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_project, transcript};
+    use std::process::Command;
+
+    #[test]
+    fn generates_the_same_crate() {
+        let project = test_project();
+        assert_eq!(generate(&project), generate(&project));
+        let dependency = Rt::Version("0.1.0".into()).dependency();
+        assert_eq!(dependency, "yase_rt = \"0.1.0\"");
+    }
+
+    #[test]
+    fn runs_like_the_vm() {
+        let project = test_project();
+        let out = std::env::temp_dir().join(format!("yase_transpiled_{}", std::process::id()));
+        transpile(&project, &out, &Rt::default()).unwrap();
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+        let output = Command::new(cargo)
+            .args(["run", "-q", "--", "30"])
+            .current_dir(&out)
+            .env("CARGO_TARGET_DIR", out.join("target"))
+            .output()
+            .unwrap();
+        fs::remove_dir_all(&out).unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let ran = stdout.rfind("ran for ").unwrap();
+        assert_eq!(&stdout[..ran], transcript(&project));
+    }
+}
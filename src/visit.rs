@@ -0,0 +1,207 @@
+//! Walking a sprite's blocks without matching on every one of them.
+//!
+//! A [`Walker`] follows each stack from block to block, and goes into every
+//! input that's a block on the way, so substacks and reporters get visited
+//! too. What to do with each block is up to the [`BlockVisitor`].
+use std::collections::{HashMap, HashSet};
+
+use crate::blocks::{BlockType, Value};
+
+pub trait BlockVisitor<'a> {
+    /// Called on the way down, before anything inside the block. Returning
+    /// false skips its inputs, the rest of the stack is still walked.
+    fn enter(&mut self, id: &'a str, block: &'a BlockType, depth: usize) -> bool {
+        let _ = (id, block, depth);
+        true
+    }
+
+    /// Called on the way back up, after everything inside the block.
+    fn leave(&mut self, id: &'a str, block: &'a BlockType, depth: usize) {
+        let _ = (id, block, depth);
+    }
+}
+
+/// Depth is 0 for the blocks in a top level stack, and one more for each
+/// substack or input a block is in.
+#[derive(Debug, Clone, Copy)]
+pub struct Walker<'a> {
+    blocks: &'a HashMap<String, BlockType>,
+}
+
+impl<'a> Walker<'a> {
+    pub fn new(blocks: &'a HashMap<String, BlockType>) -> Walker<'a> {
+        Walker { blocks }
+    }
+
+    /// Every top level stack, hats and strays alike, ordered by id.
+    pub fn walk(&self, visitor: &mut impl BlockVisitor<'a>) {
+        for id in self.tops() {
+            self.walk_stack(id, visitor, 0);
+        }
+    }
+
+    /// The first block of every top level stack, ordered by id.
+    pub fn tops(&self) -> Vec<&'a str> {
+        // menus that are only a field don't know their parent, so what's on
+        // top has no parent and nothing pointing at it either.
+        let mut below = HashSet::new();
+        for block in self.blocks.values() {
+            below.extend(block.as_block().next());
+            for (_, input) in block.inputs() {
+                if let Value::String(a) = input {
+                    below.insert(a.clone());
+                }
+            }
+        }
+        let mut tops: Vec<&'a str> = self
+            .blocks
+            .iter()
+            .filter(|(id, f)| f.as_block().prev().is_none() && !below.contains(*id))
+            // old projects don't give prototypes a parent.
+            .filter(|(_, f)| !matches!(f, BlockType::ProceduresPrototype(_)))
+            .map(|(id, _)| id.as_str())
+            .collect();
+        tops.sort();
+        tops
+    }
+
+    /// A block and everything after it, up to where the stack loops back on
+    /// itself if a broken project has it do that.
+    pub fn walk_stack(&self, first: &str, visitor: &mut impl BlockVisitor<'a>, depth: usize) {
+        self.stack(first, visitor, depth, &mut HashSet::new());
+    }
+
+    /// Just the one block, and whatever's in its inputs.
+    pub fn walk_block(&self, id: &str, visitor: &mut impl BlockVisitor<'a>, depth: usize) {
+        let mut path = HashSet::new();
+        if let Some((id, _)) = self.blocks.get_key_value(id) {
+            path.insert(id.as_str());
+        }
+        self.block(id, visitor, depth, &mut path);
+    }
+
+    /// The path has every block the stack is inside of, and the ones before
+    /// it in its own stack, so that no block is walked inside itself.
+    fn stack(
+        &self,
+        first: &str,
+        visitor: &mut impl BlockVisitor<'a>,
+        depth: usize,
+        path: &mut HashSet<&'a str>,
+    ) {
+        let mut entered = Vec::new();
+        let mut cur = self.blocks.get_key_value(first);
+        while let Some((id, block)) = cur.filter(|f| path.insert(f.0)) {
+            entered.push(id.as_str());
+            self.block(id, visitor, depth, path);
+            cur = block
+                .as_block()
+                .next()
+                .and_then(|f| self.blocks.get_key_value(&f));
+        }
+        for id in entered {
+            path.remove(id);
+        }
+    }
+
+    fn block(
+        &self,
+        id: &str,
+        visitor: &mut impl BlockVisitor<'a>,
+        depth: usize,
+        path: &mut HashSet<&'a str>,
+    ) {
+        let (id, block) = match self.blocks.get_key_value(id) {
+            Some(a) => a,
+            None => return,
+        };
+        if visitor.enter(id, block, depth) {
+            for (_, input) in block.inputs() {
+                if let Value::String(a) = input {
+                    self.stack(a, visitor, depth + 1, path);
+                }
+            }
+        }
+        visitor.leave(id, block, depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decomp::{LoadOptions, Project},
+        stats::Stats,
+        test_util::test_project,
+    };
+
+    /// Checks that every block is left in the reverse order it was entered
+    /// in, and counts how many times each one was.
+    #[derive(Default)]
+    struct Nesting<'a> {
+        open: Vec<(&'a str, usize)>,
+        seen: HashMap<&'a str, usize>,
+    }
+
+    impl<'a> BlockVisitor<'a> for Nesting<'a> {
+        fn enter(&mut self, id: &'a str, _: &'a BlockType, depth: usize) -> bool {
+            if let Some((_, parent)) = self.open.last() {
+                assert_eq!(depth, parent + 1);
+            }
+            self.open.push((id, depth));
+            *self.seen.entry(id).or_default() += 1;
+            true
+        }
+
+        fn leave(&mut self, id: &'a str, _: &'a BlockType, depth: usize) {
+            assert_eq!(self.open.pop(), Some((id, depth)));
+        }
+    }
+
+    #[test]
+    fn visits_every_block_once() {
+        let project = test_project();
+        for sprite in project.sprites() {
+            let mut nesting = Nesting::default();
+            Walker::new(&sprite.blocks).walk(&mut nesting);
+            assert!(nesting.open.is_empty());
+            for (id, block) in &sprite.blocks {
+                let parent = block.as_block().prev();
+                let parent = parent.and_then(|f| sprite.blocks.get(&f));
+                // a prototype and its arguments are the definition's, not
+                // something in it.
+                let prototype = matches!(block, BlockType::ProceduresPrototype(_))
+                    || matches!(parent, Some(BlockType::ProceduresPrototype(_)));
+                let expected = match prototype {
+                    true => None,
+                    false => Some(&1),
+                };
+                assert_eq!(nesting.seen.get(id.as_str()), expected, "{}", id);
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_loops() {
+        let json = serde_json::json!({"targets": [{"isStage": true, "name": "Stage", "blocks": {
+            "a": {"opcode": "event_whenflagclicked", "next": "b", "parent": null,
+                "inputs": {}, "fields": {}, "topLevel": true, "x": 0, "y": 0},
+            "b": {"opcode": "control_forever", "next": "c", "parent": "a",
+                "inputs": {"SUBSTACK": [2, "b"]}, "fields": {}},
+            "c": {"opcode": "looks_say", "next": "b", "parent": "b",
+                "inputs": {"MESSAGE": [3, "d", [10, "hi"]]}, "fields": {}},
+            // adds itself to itself, which used to take 2^n steps.
+            "d": {"opcode": "operator_add", "next": null, "parent": "c",
+                "inputs": {"NUM1": [3, "d", [4, "1"]], "NUM2": [3, "d", [4, "2"]]}, "fields": {}},
+        }}]});
+        let options = LoadOptions { strict: false };
+        let project = Project::from_json_str(&json.to_string(), options).unwrap();
+        let mut nesting = Nesting::default();
+        Walker::new(&project.sprites()[0].blocks).walk(&mut nesting);
+        assert!(nesting.open.is_empty());
+        for id in ["a", "b", "c", "d"] {
+            assert_eq!(nesting.seen[id], 1, "{}", id);
+        }
+        Stats::new(&project);
+    }
+}